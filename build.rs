@@ -0,0 +1,8 @@
+/*
+    Generates the gRPC service and messages of proto/opportunities.proto with the vendored protoc, so no system installation is needed
+*/
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::compile_protos("proto/opportunities.proto")?;
+    Ok(())
+}
@@ -1,26 +1,737 @@
-use clap::Parser;
+use std::{ffi::OsString, fmt, net::SocketAddr, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+use clap::{
+    builder::RangedU64ValueParser, error::ErrorKind, ArgAction, CommandFactory, FromArgMatches,
+    Parser, Subcommand, ValueEnum,
+};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde_json::{Map, Value};
 use tokio::sync::OnceCell;
 
+use crate::structs::cex::aggregator::Weighting;
+
 pub static CONFIG: OnceCell<Config> = OnceCell::const_new();
 
+const DEFAULT_BINANCE_TICKER: &str = "solusdt";
+const DEFAULT_PYTH_PRICE_ID: &str = "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG"; // SOL/USD
+const REDACTED: &str = "<redacted>";
+const DISABLED_PAIR_SUFFIX: &str = ":disabled";
+// Upper bound of the configurable buffer capacities, so a misconfigured one cannot exhaust memory
+const MAX_BUFFER_CAPACITY: u64 = 100_000;
+
+// Symbols of known pairs along with their Binance tickers and Pyth price ids
+const KNOWN_SYMBOLS: [(&str, &str, &str); 4] = [
+    (
+        "SOLUSD",
+        "solusdt",
+        "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG",
+    ),
+    (
+        "BTCUSD",
+        "btcusdt",
+        "GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU",
+    ),
+    (
+        "ETHUSD",
+        "ethusdt",
+        "JBu1AL4obBcCMqKBBxhpWCNUt136ijcuMZLFvTP7iWdB",
+    ),
+    (
+        "BNBUSD",
+        "bnbusdt",
+        "4CkQJBxhU8EZ2UjhigbtdaPbpTe6mqf811fipYBFbSYN",
+    ),
+];
+
 /*
     Struct holding CLI optional arguments
 */
 #[derive(Parser)]
 pub struct Config {
-    // Pair from Binance spot market
-    #[arg(long, short, default_value = "solusdt")]
-    pub binance_ticker: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // Pair from Binance market, defaults to the one of symbol argument or solusdt
+    #[arg(long, short)]
+    pub binance_ticker: Option<String>,
 
-    // Price id pubkey from Pyth
+    // Price id pubkey from Pyth, defaults to the one of symbol argument or SOL/USD price id
     // List of available ids (Solana) can be found here:
     // https://pyth.network/price-feeds?cluster=solana-mainnet-beta
-    #[arg(
-        long,
-        short,
-        default_value = "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG"
-    )]
-    pub pyth_price_id: String,
+    #[arg(long, short)]
+    pub pyth_price_id: Option<String>,
+
+    // Symbol of a known pair resolved to its Binance ticker and Pyth price id, e.g. BTCUSD
+    // binance_ticker and pyth_price_id arguments override the resolved ones
+    #[arg(long)]
+    pub symbol: Option<String>,
+
+    // Path of a Unix domain socket to which found opportunities are written as newline-delimited JSON
+    #[arg(long)]
+    pub uds_path: Option<PathBuf>,
+
+    // Format of opportunities written to the Unix domain socket, binary writes frames of the compact layout of sinks::binary
+    #[arg(long, value_enum, default_value = "json", requires = "uds_path")]
+    pub uds_format: StreamFormat,
+
+    // Path of a Unix domain socket accepting commands which add and remove monitored pairs at runtime
+    #[arg(long)]
+    pub control_socket: Option<PathBuf>,
+
+    // Path of a SQLite database into which found opportunities are inserted
+    #[arg(long)]
+    pub sqlite: Option<PathBuf>,
+
+    // URL of a Discord webhook to which found opportunities are posted
+    #[arg(long)]
+    pub discord_webhook: Option<String>,
+
+    // Comma-separated host:port addresses of Kafka brokers to which found opportunities are produced
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_delimiter = ',', requires = "kafka_topic")]
+    pub kafka_brokers: Vec<String>,
+
+    // Kafka topic to which found opportunities are produced, keyed by pair
+    #[cfg(feature = "kafka")]
+    #[arg(long, requires = "kafka_brokers")]
+    pub kafka_topic: Option<String>,
+
+    // URL of Redis to which found opportunities are published, redis://[[user]:password@]host[:port]
+    #[cfg(feature = "redis")]
+    #[arg(long)]
+    pub redis_url: Option<String>,
+
+    // Redis channel to which found opportunities are published
+    #[cfg(feature = "redis")]
+    #[arg(long, default_value = "opportunities")]
+    pub redis_channel: String,
+
+    // Number of opportunities buffered while Redis is unavailable, the oldest one is dropped beyond it
+    #[cfg(feature = "redis")]
+    #[arg(long, default_value_t = 1024, value_parser = RangedU64ValueParser::<usize>::new().range(1..=MAX_BUFFER_CAPACITY))]
+    pub redis_buffer_capacity: usize,
+
+    // Address on which found opportunities are streamed to gRPC subscribers, see proto/opportunities.proto
+    #[arg(long)]
+    pub grpc_addr: Option<SocketAddr>,
+
+    // Output receiving every found opportunity in form json:<stdout|path>, csv:<stdout|path> or webhook:<url>, can be repeated
+    #[arg(long = "sink")]
+    pub sinks: Vec<SinkConfig>,
+
+    // Pair to monitor in form <binance_ticker>:<pyth_price_id>, can be repeated
+    // Pairs quoted in other currency than Pyth prices can be given as <binance_ticker>:<base_pyth_price_id>/<quote_pyth_price_id>
+    // Pairs suffixed with :disabled are monitored without emitting their opportunities until enabled at runtime
+    // When provided, binance_ticker and pyth_price_id arguments are ignored
+    #[arg(long)]
+    pub pair: Vec<PairConfig>,
+
+    // Binance market compared with Pyth prices
+    #[arg(long, value_enum, default_value = "spot")]
+    pub market: Market,
+
+    // Binance WS endpoint, combined wraps every message in an envelope naming its stream while raw sends bare payloads
+    #[arg(long, value_enum, default_value = "combined")]
+    pub binance_endpoint: StreamEndpoint,
+
+    // Comma-separated CEX sources whose tops of the book are combined with the Binance one into the reference compared with Pyth prices
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub combine_with: Vec<CexSource>,
+
+    // Weighting of the combined tops of the book, volume or comma-separated <source>=<weight> pairs, e.g. binance=3,coinbase=1
+    #[arg(long, default_value = "volume", requires = "combine_with")]
+    pub cex_weighting: Weighting,
+
+    // Number of worker tasks evaluating the pairs
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub workers: u16,
+
+    // Probability of the Pyth price staying within the range used for comparison with Binance prices
+    #[arg(long, value_enum, default_value = "95")]
+    pub confidence_interval: ConfidenceInterval,
+
+    // Multiplier of Pyth confidence used verbatim instead of the one of confidence_interval, e.g. 2.12 for about 95%
+    #[arg(long, value_parser = parse_positive_decimal, conflicts_with = "confidence_interval")]
+    pub confidence_factor: Option<Decimal>,
+
+    // Age in seconds after which the Binance WS connection is replaced with a new one
+    // Binance drops connections after 24 hours
+    #[arg(long, default_value_t = 23 * 60 * 60)]
+    pub binance_max_connection_age: u64,
+
+    // Time in seconds within which a Binance WS connection, along with the subscription, has to be established
+    #[arg(long, default_value_t = 10)]
+    pub connect_timeout: u64,
+
+    // Time in seconds without any Binance WS message after which the connection is treated as lost and replaced
+    #[arg(long, default_value_t = 60)]
+    pub idle_timeout: u64,
+
+    // Time in seconds within which a Pyth price fetch, including connecting, has to complete before it is retried
+    #[arg(long, default_value_t = 10)]
+    pub pyth_timeout: u64,
+
+    // Number of the lowest and the highest Pyth publisher prices dropped before averaging the remaining ones
+    // 0 uses the aggregate price computed by Pyth
+    #[arg(long, default_value_t = 0)]
+    pub pyth_publisher_trim: usize,
+
+    // Pyth price and confidence pair the probable price range is computed from, the aggregate or its exponential moving average
+    #[arg(long, value_enum, default_value = "spot")]
+    pub pyth_field: PythField,
+
+    // Seconds over which Pyth price and confidence are averaged, each weighted by the time it was the latest one
+    // The probable price range is computed from the averages, so instantaneous oracle moves are not chased
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub pyth_twap_window: Option<u64>,
+
+    // Signal used to detect Solana congestion, during which opportunities are suppressed
+    #[arg(long, value_enum, requires = "congestion_threshold")]
+    pub congestion_signal: Option<CongestionSignalKind>,
+
+    // Level of the congestion signal above which opportunities are suppressed
+    // In micro-lamports per compute unit for priority-fee signal and in milliseconds for slot-time signal
+    #[arg(long)]
+    pub congestion_threshold: Option<u64>,
+
+    // Path of a JSON file to which deduplication state of the pairs is saved on shutdown and from which it is restored on startup
+    #[arg(long)]
+    pub dedup_state_file: Option<PathBuf>,
+
+    // Age in seconds after which a saved deduplication state no longer suppresses opportunities
+    #[arg(long, default_value_t = 300)]
+    pub dedup_state_ttl: u64,
+
+    // Minimum time in milliseconds between evaluations of a pair, updates received in between are coalesced
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub eval_interval_ms: Option<u64>,
+
+    // Length in seconds of bars aggregating the signed edge of each pair, printed as JSON when closed
+    #[arg(long)]
+    pub profit_bars: Option<u64>,
+
+    // Whether evaluated ticks without an opportunity are printed as JSON no-op events with the spread
+    // At most one per pair is printed per evaluation interval, or per second if it is not provided
+    #[arg(long)]
+    pub emit_noops: bool,
+
+    // Whether closes of open opportunities are printed as JSON events with their lifetime and the reason they closed for
+    #[arg(long)]
+    pub emit_closes: bool,
+
+    // Whether the probable price range of every new Pyth price is printed as JSON with its confidence, independent of Binance data
+    #[arg(long)]
+    pub emit_bounds: bool,
+
+    // Interval in seconds between heartbeat lines reporting the state of the monitored pairs
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub heartbeat: Option<u64>,
+
+    // Interval in seconds between refreshes of Binance exchange info, pairs of symbols no longer traded are paused
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub exchange_info_refresh: Option<u64>,
+
+    // Interval in seconds between snapshots of the Binance mid, Pyth range and signed edge of all the pairs, printed as a single JSON document
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub spread_snapshot: Option<u64>,
+
+    // Interval in seconds between samples of the congestion signal
+    #[arg(long, default_value_t = 10)]
+    pub congestion_check_interval: u64,
+
+    // Solana RPC queried for the congestion signal
+    #[arg(long, default_value = "https://api.mainnet-beta.solana.com")]
+    pub solana_rpc_url: String,
+
+    // Orca Whirlpool of a pair as <binance_ticker>:<pool_address>, whose price is blended into the reference DEX price, can be repeated
+    // The pool has to quote the pair like its Pyth feed, i.e. its token A in units of its token B
+    #[arg(long = "dex-pool")]
+    pub dex_pools: Vec<DexPoolConfig>,
+
+    // Weight of the pool price in the reference DEX price, the rest being the Pyth price, 1 replaces the Pyth price
+    #[arg(long, default_value = "1", value_parser = parse_probability)]
+    pub dex_price_weight: Decimal,
+
+    // Interval in milliseconds between reads of the pool prices from solana_rpc_url
+    #[arg(long, default_value_t = 1000)]
+    pub dex_pool_interval_ms: u64,
+
+    // Price compared with Binance prices of every pair instead of the one fetched from Pyth, for testing and demos
+    // It has no confidence, so pyth_band_bps argument sets width of the probable price range around it
+    #[arg(long, value_parser = parse_positive_decimal)]
+    pub fixed_dex_price: Option<Decimal>,
+
+    // Width in basis points of a fixed band around Pyth price compared with Binance prices
+    // When provided, the Pyth confidence and confidence_interval argument are ignored
+    #[arg(long)]
+    pub pyth_band_bps: Option<u32>,
+
+    // Minimum Pyth confidence in units of the price, used instead of smaller reported ones so the probable price range does not collapse
+    #[arg(long, conflicts_with_all = ["pyth_band_bps", "min_confidence_bps"])]
+    pub min_confidence: Option<Decimal>,
+
+    // Minimum Pyth confidence in basis points of the price, used instead of smaller reported ones
+    #[arg(long, conflicts_with = "pyth_band_bps")]
+    pub min_confidence_bps: Option<u32>,
+
+    // Fraction of the confidence factor added per second since the Pyth price was published, e.g. 0.05
+    // Widens the probable price range of older prices, so they need a larger edge to yield an opportunity
+    #[arg(long, value_parser = parse_positive_decimal, conflicts_with = "pyth_band_bps")]
+    pub confidence_age_growth: Option<Decimal>,
+
+    // Whether the reciprocal of the Pyth price is compared, for feeds quoting the inverse of the Binance pair, e.g. USD/BTC for btcusdt
+    // The probable price range is computed from the feed as published and then inverted bound by bound
+    #[arg(long)]
+    pub invert_pyth: bool,
+
+    // Maximum time in milliseconds between Binance and Pyth prices an opportunity is found in, more skewed ones are suppressed
+    #[arg(long)]
+    pub max_feed_skew_ms: Option<u64>,
+
+    // Milliseconds added to Pyth publish time before the skew is measured, positive if Pyth is known to trail Binance
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    pub pyth_time_offset_ms: i64,
+
+    // Size of Binance price buckets used for deduplication of opportunities
+    // When provided, opportunities in the same direction and bucket as one found within dedup_window are suppressed
+    // Otherwise only an opportunity equal to the previous one is suppressed
+    #[arg(long, value_parser = parse_positive_decimal)]
+    pub dedup_bucket: Option<Decimal>,
+
+    // Time in seconds for which an opportunity suppresses the following ones in its bucket
+    #[arg(long, default_value_t = 60)]
+    pub dedup_window: u64,
+
+    // Maximum number of recent buckets remembered per pair for deduplication, the oldest one is forgotten beyond it
+    #[arg(long, default_value_t = 1024, value_parser = RangedU64ValueParser::<usize>::new().range(1..=MAX_BUFFER_CAPACITY))]
+    pub max_dedup_buckets: usize,
+
+    // Condition of finding an opportunity, Binance price beyond the probable price range or positive expected profit
+    #[arg(long, value_enum, default_value = "bound")]
+    pub strategy: StrategyKind,
+
+    // Expected profit an opportunity has to exceed if ev strategy is used
+    #[arg(long, default_value_t = Decimal::ZERO)]
+    pub min_expected_profit: Decimal,
+
+    // Minimum difference between Binance price and the crossed bound in ticks of the symbol, from Binance exchange info
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub min_edge_ticks: Option<u32>,
+
+    // Multiple of Pyth confidence by which Binance price has to be beyond the crossed bound to open an opportunity, e.g. 0.5
+    #[arg(long, value_parser = parse_positive_decimal)]
+    pub hysteresis_open_sigma: Option<Decimal>,
+
+    // Multiple of Pyth confidence by which Binance price has to stay beyond the crossed bound to keep an open opportunity,
+    // 0 closes it once the price falls back within the probable price range
+    #[arg(long, default_value = "0", requires = "hysteresis_open_sigma", value_parser = parse_non_negative_decimal)]
+    pub hysteresis_close_sigma: Decimal,
+
+    // Source of quantity of opportunities found at the best bid and ask
+    #[arg(long, value_enum, default_value = "book-side")]
+    pub quantity_strategy: QuantityStrategyKind,
+
+    // Quantity of every opportunity if fixed quantity strategy is used
+    #[arg(long, required_if_eq("quantity_strategy", "fixed"))]
+    pub fixed_quantity: Option<Decimal>,
+
+    // Binance price of opportunities found at the best bid and ask
+    #[arg(long, value_enum, default_value = "best")]
+    pub execution_price_model: ExecutionPriceModelKind,
+
+    // Basis points of the best price by which execution price is moved into the spread if offset model is used
+    #[arg(long, required_if_eq("execution_price_model", "offset"))]
+    pub execution_offset_bps: Option<u32>,
+
+    // Whether Binance price equal to a bound of the probable price range is an opportunity, which is break-even at best
+    #[arg(long)]
+    pub inclusive_bounds: bool,
+
+    // Direction reported when a tick qualifies in both of them, sell-first keeps the order of the search
+    #[arg(long, value_enum, default_value = "sell-first")]
+    pub tie_break: TieBreak,
+
+    // Binance price compared to the bounds of the probable price range, crossing compares bid to the upper and ask to the lower one
+    #[arg(long, value_enum, default_value = "crossing")]
+    pub comparison_basis: ComparisonBasis,
+
+    // Handling of opportunities whose estimated profit is positive but rounds to zero
+    #[arg(long, value_enum, default_value = "emit")]
+    pub zero_profit: ZeroProfitPolicy,
+
+    // Increase of estimated profit over the last report required to report an open opportunity again
+    // Without it, every change of an open opportunity is reported
+    #[arg(long, value_parser = parse_non_negative_decimal)]
+    pub reemit_improvement: Option<Decimal>,
+
+    // Milliseconds after a reconnection of Binance WS during which found opportunities are suppressed, as the first ticks may reflect the gap
+    #[arg(long, default_value_t = 0)]
+    pub post_reconnect_grace_ms: u64,
+
+    // Probability of filling a limit order resting on Binance, e.g. 0.6, by which profits are scaled to their expected values
+    // Without it, every opportunity is assumed to be filled
+    #[arg(long, value_parser = parse_probability)]
+    pub fill_probability: Option<Decimal>,
+
+    // Quantity available on the DEX leg, e.g. depth of the pool within the probable price range
+    // Without it, the DEX leg is assumed to fill any quantity
+    #[arg(long, value_parser = parse_positive_decimal)]
+    pub dex_quantity: Option<Decimal>,
+
+    // Model of filling both legs when the DEX quantity differs from the Binance one
+    #[arg(long, value_enum, default_value = "full-min")]
+    pub fill_model: FillModel,
+
+    // Opportunities reported, direction-change reports only the ones in another direction than the last reported one
+    #[arg(long, value_enum, default_value = "every-tick")]
+    pub alert_on: AlertOn,
+
+    // Leg of the round trip executed as taker, paying the taker fee, while the other one rests as maker paying the maker fee
+    // Without it, the Binance taker fee of the ticker is paid on the Binance leg only
+    #[arg(long, value_enum, requires_all = ["maker_fee", "taker_fee"])]
+    pub taker_leg: Option<Leg>,
+
+    // Maker fee as a fraction of the notional of the leg, e.g. 0.0002 for 2 bps
+    #[arg(long, requires = "taker_leg")]
+    pub maker_fee: Option<Decimal>,
+
+    // Taker fee as a fraction of the notional of the leg, e.g. 0.001 for 10 bps
+    #[arg(long, requires = "taker_leg")]
+    pub taker_fee: Option<Decimal>,
+
+    // Base fee in lamports of the Solana transaction executing the DEX leg, subtracted from profits in the quote currency
+    #[arg(long)]
+    pub solana_base_fee_lamports: Option<u64>,
+
+    // Priority fee in lamports of the Solana transaction executing the DEX leg, subtracted from profits in the quote currency
+    #[arg(long)]
+    pub priority_fee_lamports: Option<u64>,
+
+    // Price of SOL in the quote currency converting the Solana transaction fees
+    // Without it, the Pyth price of the pair is used, which has to quote SOL then
+    #[arg(long, value_parser = parse_positive_decimal)]
+    pub sol_quote_price: Option<Decimal>,
+
+    // Whether the first opportunity found after startup is reported, it may reflect a gap existing before
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    pub emit_initial: bool,
+
+    // Time in milliseconds for which an opportunity has to be present continuously before it is reported once
+    #[arg(long, default_value_t = 0)]
+    pub min_persistence_ms: u64,
+
+    // Time in milliseconds for which a reported opportunity suppresses the following ones in the same direction
+    // An opportunity in the other direction is reported immediately and ends the cooldown
+    #[arg(long, default_value_t = 0)]
+    pub cooldown: u64,
+
+    // Width in basis points of Binance notional above zero estimated profit within which emitted opportunities are flagged marginal
+    #[arg(long)]
+    pub marginal_band_bps: Option<u32>,
+
+    // Percent of Binance notional above which estimated profit is implausible, such opportunities are suppressed as anomalies
+    #[arg(long, value_parser = parse_positive_decimal)]
+    pub max_plausible_profit_pct: Option<Decimal>,
+
+    // Number of anomalous opportunities after which emission is halted until resumed
+    #[arg(long, requires = "max_plausible_profit_pct")]
+    pub anomaly_halt_after: Option<u64>,
+
+    // Ratio of the rate of opportunities emitted in the last tenth of the baseline window to the rate in the rest of it
+    // above which emission is halted until resumed, as such a jump more likely comes from bad data or a bug
+    #[arg(long, value_parser = parse_positive_decimal)]
+    pub halt_rate_multiplier: Option<Decimal>,
+
+    // Ratio of the average estimated profit of opportunities emitted in the last tenth of the baseline window to the average
+    // in the rest of it above which emission is halted until resumed
+    #[arg(long, value_parser = parse_positive_decimal)]
+    pub halt_profit_multiplier: Option<Decimal>,
+
+    // Seconds of emitted opportunities the halting multipliers are measured over, nothing is halted during the first window
+    #[arg(long, default_value_t = 3600)]
+    pub halt_baseline_window: u64,
+
+    // Whether emitted opportunities carry snapshots of the Pyth price and Binance ticker data they were found in
+    #[arg(long)]
+    pub include_raw: bool,
+
+    // Whether emitted opportunities carry the latest funding rate of their pair, which only perp pairs have
+    #[arg(long)]
+    pub include_funding_rate: bool,
+
+    // Whether trailing zeros of the decimal values of emitted opportunities are trimmed, so every sink and log shows them alike
+    #[arg(long)]
+    pub normalize_decimals: bool,
+
+    // Whether opportunities with estimated profit not positive after all the costs are suppressed
+    #[arg(long)]
+    pub net_positive_only: bool,
+
+    // Edges of estimated profit buckets of the histogram printed in the summary, separated by commas
+    #[arg(long, value_delimiter = ',', default_value = "0.01,0.1,1,10")]
+    pub profit_histogram_edges: Vec<Decimal>,
+
+    // Edges in milliseconds of buckets of the histogram of opportunity lifetimes printed in the summary, separated by commas
+    #[arg(long, value_delimiter = ',', default_value = "100,1000,10000")]
+    pub lifetime_histogram_edges: Vec<u64>,
+
+    // Number of the most recent updates of each feed kept per pair and printed on SIGUSR1, for debugging
+    #[arg(long, default_value_t = 100, value_parser = RangedU64ValueParser::<usize>::new().range(0..=MAX_BUFFER_CAPACITY))]
+    pub tick_buffer_capacity: usize,
+
+    // Number of the most recent emitted opportunities buffered for sinks which lag behind, older ones are skipped by them
+    #[arg(long, default_value_t = 1024, value_parser = RangedU64ValueParser::<usize>::new().range(1..=MAX_BUFFER_CAPACITY))]
+    pub opportunity_buffer_capacity: usize,
+
+    // Strategy of rounding estimated profits
+    #[arg(long, value_enum, default_value = "half-even")]
+    pub rounding_mode: RoundingMode,
+}
+
+/*
+    Commands run instead of monitoring the markets
+*/
+#[derive(Subcommand)]
+pub enum Command {
+    // Replays recorded ticks and prints found opportunities
+    Backtest {
+        // Files with recorded ticks, one JSON object per line, merged in timestamp order
+        // Can be repeated or separated by commas, * and ? in file names match any files of their directory
+        #[arg(long, required = true, value_delimiter = ',')]
+        data: Vec<PathBuf>,
+
+        // Pace of the replay relative to the recorded one, 0 replays as fast as possible
+        #[arg(long, default_value_t = 0.0)]
+        replay_speed: f64,
+    },
+
+    // Sweeps confidence factors over recorded ticks and reports how each of them performs
+    Calibrate {
+        // File with recorded ticks, one JSON object per line
+        #[arg(long)]
+        data: PathBuf,
+
+        #[arg(long, default_value = "1.0")]
+        min_factor: Decimal,
+
+        #[arg(long, default_value = "4.0")]
+        max_factor: Decimal,
+
+        #[arg(long, default_value = "0.25", value_parser = parse_positive_decimal)]
+        step: Decimal,
+    },
+
+    // Measures throughput and latency of evaluating synthetic ticks with the arbitrage finder
+    Bench {
+        // Number of evaluations
+        #[arg(long, default_value_t = 100_000)]
+        iterations: u64,
+    },
+
+    // Prints Binance tickers of the market which can be traded, to be used as binance_ticker
+    ListPairs {
+        // Substring the printed tickers contain, ignoring case, e.g. sol
+        #[arg(long)]
+        filter: Option<String>,
+    },
+
+    // Checks documented test vectors of the probable price range and opportunities against this binary, printing pass or fail
+    VerifyMath,
+
+    // Prints JSON Schema of the found opportunities, as serialized by the sinks
+    Schema,
+
+    // Prints the effective arguments, including defaults and the resolved pairs, as JSON with secrets redacted
+    Config,
+}
+
+/*
+    Supported Binance WS endpoints
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StreamEndpoint {
+    Combined, // /stream, messages wrapped in {stream, data}
+    Raw,      // /ws, bare payloads
+}
+
+/*
+    Supported formats of opportunities written to a stream
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StreamFormat {
+    Json,   // a line of JSON per opportunity
+    Binary, // a length-prefixed frame per opportunity
+}
+
+/*
+    Supported CEX sources combined with Binance
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CexSource {
+    Coinbase, // Coinbase Exchange, by products matching Binance tickers, e.g. SOL-USDT for solusdt
+}
+
+/*
+    Supported Binance markets
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Market {
+    Spot,
+    Perp, // USD-M futures
+}
+
+/*
+    Supported conditions of finding an opportunity
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StrategyKind {
+    Bound, // Binance price beyond the crossed bound of the probable price range
+    Ev,    // expected profit above min_expected_profit argument
+}
+
+/*
+    Supported sources of quantity of an opportunity
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum QuantityStrategyKind {
+    BookSide, // quantity of the traded side of the book
+    MinBoth,  // the lower of bid and ask quantities
+    Fixed,    // fixed_quantity argument
+}
+
+/*
+    Supported assumptions about execution price of an opportunity
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExecutionPriceModelKind {
+    Best,   // the best price of the traded side
+    Mid,    // the middle of the spread
+    Offset, // execution_offset_bps argument into the spread
+}
+
+/*
+    Supported models of filling both legs of a round trip whose available quantities differ
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FillModel {
+    FullMin,      // both legs fill the smaller of the requested and DEX quantities in full
+    Proportional, // both legs fill the fraction of the requested quantity the scarcer leg covers
+}
+
+/*
+    Supported conditions of reporting a found opportunity
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AlertOn {
+    EveryTick,       // every opportunity passing the other suppressions
+    DirectionChange, // only the first one after the prevailing direction changes
+}
+
+/*
+    Supported policies of choosing the direction of a tick qualifying in both of them, possible with a very narrow probable price range
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TieBreak {
+    Reject,    // neither direction is reported
+    MaxProfit, // the direction with the larger estimated profit, sell side if equal
+    SellFirst, // SellBinanceBuyDex
+    BuyFirst,  // BuyBinanceSellDex
+}
+
+/*
+    Supported Binance prices compared to the bounds of the probable price range
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ComparisonBasis {
+    Crossing, // bid to the upper bound and ask to the lower one, the Binance leg trades at its best price
+    Mid,      // the middle of the spread to both bounds, the Binance leg trades at the mid
+}
+
+/*
+    Supported handling of opportunities whose estimated profit rounds to zero
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ZeroProfitPolicy {
+    Emit,     // reported with zero estimated profit, for visibility
+    Suppress, // counted as suppressed
+}
+
+/*
+    Legs of the round trip
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Leg {
+    Binance,
+    Dex,
+}
+
+/*
+    Supported Pyth price and confidence pairs
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PythField {
+    Spot, // aggregate price and confidence
+    Ema,  // exponential moving average of the aggregate price and confidence
+}
+
+/*
+    Supported sources of Solana congestion signal
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CongestionSignalKind {
+    PriorityFee, // median of recent prioritization fees
+    SlotTime,    // average time of recent slots
+}
+
+/*
+    Supported confidence intervals
+    Factors are multipliers of Pyth confidence, treated as the standard deviation of Laplace distribution
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConfidenceInterval {
+    #[value(name = "90")]
+    P90,
+    #[value(name = "95")]
+    P95,
+    #[value(name = "99")]
+    P99,
+}
+
+impl ConfidenceInterval {
+    /*
+        Returns ln(1 / (1 - p)) / sqrt(2) rounded to 2 decimal places
+    */
+    pub fn factor(&self) -> Decimal {
+        match self {
+            Self::P90 => Decimal::new(163, 2),
+            Self::P95 => Decimal::new(212, 2),
+            Self::P99 => Decimal::new(326, 2),
+        }
+    }
+}
+
+/*
+    Supported strategies of rounding Decimal values
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RoundingMode {
+    HalfUp,     // midpoints are rounded away from zero
+    HalfEven,   // midpoints are rounded to the even neighbour (banker's rounding)
+    TowardZero, // values are truncated
+}
+
+impl RoundingMode {
+    /*
+        Returns the matching rust_decimal rounding strategy
+    */
+    pub fn strategy(&self) -> RoundingStrategy {
+        match self {
+            Self::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Self::HalfEven => RoundingStrategy::MidpointNearestEven,
+            Self::TowardZero => RoundingStrategy::ToZero,
+        }
+    }
 }
 
 impl Config {
@@ -28,6 +739,607 @@ impl Config {
         Parses CLI arguments into Config instance
     */
     pub fn new() -> Self {
-        Self::parse()
+        let config = Self::parse();
+        if let (true, Err(err)) = (config.pair.is_empty(), config.default_pair()) {
+            Self::command()
+                .error(ErrorKind::ValueValidation, err)
+                .exit();
+        }
+
+        config
+    }
+
+    /*
+        Parses the CLI arguments into a JSON object of the effective value of every argument, keyed by its name
+        Values are given as passed or defaulted, along with the pairs resolved from them, secrets such as webhook URLs are redacted
+    */
+    pub fn effective_arguments<I, T>(args: I) -> Result<Value>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let command = Self::command();
+        let matches = command.clone().try_get_matches_from(args)?;
+        let config = Self::from_arg_matches(&matches)?;
+
+        let mut arguments = Map::new();
+        for argument in command.get_arguments() {
+            let id = argument.get_id().as_str();
+            let value = match argument.get_action() {
+                ArgAction::Help
+                | ArgAction::HelpShort
+                | ArgAction::HelpLong
+                | ArgAction::Version => continue,
+                ArgAction::SetTrue | ArgAction::SetFalse => Value::from(matches.get_flag(id)),
+                ArgAction::Count => Value::from(matches.get_count(id)),
+                action => {
+                    let mut values = matches
+                        .get_raw(id)
+                        .into_iter()
+                        .flatten()
+                        .map(|value| Value::from(redact(id, &value.to_string_lossy())));
+                    if matches!(action, ArgAction::Append)
+                        || argument.get_value_delimiter().is_some()
+                    {
+                        Value::from(values.collect::<Vec<_>>())
+                    } else {
+                        values.next_back().unwrap_or(Value::Null)
+                    }
+                }
+            };
+            arguments.insert(id.to_string(), value);
+        }
+        let resolved_pairs = match config.pair.is_empty() {
+            true => config.default_pair().into_iter().collect(),
+            false => config.pair,
+        };
+        arguments.insert(
+            "resolved_pairs".to_string(),
+            Value::from(
+                resolved_pairs
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            ),
+        );
+
+        Ok(Value::Object(arguments))
+    }
+
+    /*
+        Returns multiplier of Pyth confidence, either the provided one or the one of the confidence interval
+    */
+    pub fn confidence_factor(&self) -> Decimal {
+        self.confidence_factor
+            .unwrap_or_else(|| self.confidence_interval.factor())
+    }
+
+    /*
+        Returns pairs to monitor, falling back to binance_ticker and pyth_price_id if none were provided
+    */
+    pub fn pairs(&self) -> Vec<PairConfig> {
+        if self.pair.is_empty() {
+            return vec![self.default_pair().expect("Pair validated when parsing")];
+        }
+
+        self.pair.clone()
+    }
+
+    /*
+        Returns the pair given by symbol, binance_ticker and pyth_price_id arguments
+        Fails if the symbol is unknown and either of the ids is not provided
+    */
+    pub fn default_pair(&self) -> Result<PairConfig> {
+        let (known_binance_ticker, known_pyth_price_id) = match &self.symbol {
+            Some(symbol) => match resolve_symbol(symbol) {
+                Some((binance_ticker, pyth_price_id)) => {
+                    (Some(binance_ticker), Some(pyth_price_id))
+                }
+                None => (None, None),
+            },
+            None => (Some(DEFAULT_BINANCE_TICKER), Some(DEFAULT_PYTH_PRICE_ID)),
+        };
+
+        match (
+            self.binance_ticker.as_deref().or(known_binance_ticker),
+            self.pyth_price_id.as_deref().or(known_pyth_price_id),
+        ) {
+            (Some(binance_ticker), Some(pyth_price_id)) => Ok(PairConfig {
+                binance_ticker: binance_ticker.to_lowercase(),
+                pyth_price_id: pyth_price_id.to_string(),
+                pyth_quote_price_id: None,
+                enabled: true,
+            }),
+            _ => Err(anyhow!(format!(
+                "Symbol {} is unknown, both binance_ticker and pyth_price_id have to be provided",
+                self.symbol.as_deref().unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+impl Default for Config {
+    /*
+        Creates Config instance holding the default values of all arguments
+    */
+    fn default() -> Self {
+        Self::parse_from(["keyrock-task"])
+    }
+}
+
+/*
+    Returns Binance ticker and Pyth price id of a known symbol, ignoring case and a slash between the assets
+*/
+pub fn resolve_symbol(symbol: &str) -> Option<(&'static str, &'static str)> {
+    let symbol = symbol.replace('/', "").to_uppercase();
+    KNOWN_SYMBOLS
+        .iter()
+        .find(|(known_symbol, _, _)| *known_symbol == symbol)
+        .map(|(_, binance_ticker, pyth_price_id)| (*binance_ticker, *pyth_price_id))
+}
+
+/*
+    Parses a Decimal argument, rejecting values which are not positive
+*/
+fn parse_positive_decimal(s: &str) -> Result<Decimal> {
+    let value = Decimal::from_str(s)?;
+    if value <= Decimal::ZERO {
+        return Err(anyhow!(format!("{} is not positive", s)));
+    }
+
+    Ok(value)
+}
+
+/*
+    Parses a Decimal argument, rejecting negative values
+*/
+fn parse_non_negative_decimal(s: &str) -> Result<Decimal> {
+    let value = Decimal::from_str(s)?;
+    if value < Decimal::ZERO {
+        return Err(anyhow!(format!("{} is negative", s)));
+    }
+
+    Ok(value)
+}
+
+/*
+    Parses a Decimal argument, rejecting values which are not in (0, 1]
+*/
+fn parse_probability(s: &str) -> Result<Decimal> {
+    let value = parse_positive_decimal(s)?;
+    if value > Decimal::ONE {
+        return Err(anyhow!(format!("{} is greater than 1", s)));
+    }
+
+    Ok(value)
+}
+
+/*
+    Replaces secrets in the value of the argument, i.e. the Discord webhook and URLs of webhook sinks, which carry tokens,
+    and the Redis URL if it carries a password
+*/
+fn redact(id: &str, value: &str) -> String {
+    match id {
+        "discord_webhook" => REDACTED.to_string(),
+        "redis_url" if value.contains('@') => REDACTED.to_string(),
+        "sinks" if value.starts_with("webhook:") => format!("webhook:{}", REDACTED),
+        _ => value.to_string(),
+    }
+}
+
+/*
+    Struct holding identifiers of a single pair on both markets
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairConfig {
+    pub binance_ticker: String,
+    pub pyth_price_id: String,
+    // Price id used to derive price of pyth_price_id asset in terms of this one
+    pub pyth_quote_price_id: Option<String>,
+    // Whether opportunities of the pair are emitted, a disabled pair stays monitored and can be enabled at runtime
+    pub enabled: bool,
+}
+
+impl FromStr for PairConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ids, enabled) = match s.strip_suffix(DISABLED_PAIR_SUFFIX) {
+            Some(ids) => (ids, false),
+            None => (s, true),
+        };
+        let maybe_ids = ids.split_once(':').map(|(binance_ticker, pyth_price_ids)| {
+            match pyth_price_ids.split_once('/') {
+                Some((base, quote)) => (binance_ticker, base, Some(quote)),
+                None => (binance_ticker, pyth_price_ids, None),
+            }
+        });
+
+        match maybe_ids {
+            Some((binance_ticker, pyth_price_id, pyth_quote_price_id))
+                if !binance_ticker.is_empty()
+                    && !pyth_price_id.is_empty()
+                    && pyth_quote_price_id != Some("") =>
+            {
+                Ok(Self {
+                    binance_ticker: binance_ticker.to_lowercase(),
+                    pyth_price_id: pyth_price_id.to_string(),
+                    pyth_quote_price_id: pyth_quote_price_id.map(str::to_string),
+                    enabled,
+                })
+            }
+            _ => Err(anyhow!(format!(
+                "Pair {} is not in form <binance_ticker>:<pyth_price_id>[/<pyth_quote_price_id>][:disabled]",
+                s
+            ))),
+        }
+    }
+}
+
+/*
+    Struct holding the on-chain pool of a single pair
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DexPoolConfig {
+    pub binance_ticker: String,
+    pub pool: String, // address of the Orca Whirlpool account
+}
+
+impl FromStr for DexPoolConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((binance_ticker, pool)) if !binance_ticker.is_empty() && !pool.is_empty() => {
+                Ok(Self {
+                    binance_ticker: binance_ticker.to_lowercase(),
+                    pool: pool.to_string(),
+                })
+            }
+            _ => Err(anyhow!(format!(
+                "DEX pool {} is not in form <binance_ticker>:<pool_address>",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for PairConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.binance_ticker, self.pyth_price_id)?;
+        if let Some(pyth_quote_price_id) = &self.pyth_quote_price_id {
+            write!(f, "/{}", pyth_quote_price_id)?;
+        }
+        if !self.enabled {
+            write!(f, "{}", DISABLED_PAIR_SUFFIX)?;
+        }
+        Ok(())
+    }
+}
+
+/*
+    Supported outputs of found opportunities
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkConfig {
+    Json(SinkTarget),   // a line of JSON per opportunity
+    Csv(SinkTarget),    // a CSV row per opportunity, after a header
+    Binary(SinkTarget), // a length-prefixed frame of the compact binary layout per opportunity
+    Webhook(String),    // URL to which every opportunity is posted as JSON
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkTarget {
+    Stdout,
+    File(PathBuf),
+}
+
+impl FromStr for SinkConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let target = |target: &str| match target {
+            "stdout" => SinkTarget::Stdout,
+            path => SinkTarget::File(PathBuf::from(path)),
+        };
+
+        match s.split_once(':') {
+            Some((_, "")) => Err(anyhow!(format!("Sink {} has no target", s))),
+            Some(("json", json_target)) => Ok(Self::Json(target(json_target))),
+            Some(("csv", csv_target)) => Ok(Self::Csv(target(csv_target))),
+            Some(("binary", binary_target)) => Ok(Self::Binary(target(binary_target))),
+            Some(("webhook", url)) => Ok(Self::Webhook(url.to_string())),
+            _ => Err(anyhow!(format!(
+                "Sink {} is not in form json:<stdout|path>, csv:<stdout|path>, binary:<stdout|path> or webhook:<url>",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for SinkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, target) = match self {
+            Self::Json(target) => ("json", target),
+            Self::Csv(target) => ("csv", target),
+            Self::Binary(target) => ("binary", target),
+            Self::Webhook(url) => return write!(f, "webhook:{}", url),
+        };
+        match target {
+            SinkTarget::Stdout => write!(f, "{}:stdout", kind),
+            SinkTarget::File(path) => write!(f, "{}:{}", kind, path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use clap::Parser;
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+
+    use crate::structs::{
+        arbitrage_finder::{calculate_pyth_confidence_bounds, FinderSettings},
+        cex::aggregator::Weighting,
+    };
+
+    use serde_json::Value;
+
+    use super::{resolve_symbol, CexSource, Config, PairConfig};
+
+    #[test]
+    fn test_pairs_default() {
+        let config = Config::default();
+        assert_eq!(
+            config.pairs(),
+            vec![PairConfig {
+                binance_ticker: "solusdt".to_string(),
+                pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+                pyth_quote_price_id: None,
+                enabled: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_confidence_factor() {
+        assert_eq!(Config::default().confidence_factor(), Decimal::new(212, 2));
+
+        let config = Config::parse_from(["keyrock-task", "--confidence-factor", "3.0"]);
+        let settings = FinderSettings::from_config(&config);
+        assert_eq!(settings.confidence_factor, Decimal::new(3, 0));
+        // 69.852445 +- 0.669724 * 3
+        let price = Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        };
+        assert_eq!(
+            calculate_pyth_confidence_bounds(price, settings.confidence_factor).unwrap(),
+            (Decimal::new(67843273, 6), Decimal::new(71861617, 6))
+        );
+
+        assert!(Config::try_parse_from(["keyrock-task", "--confidence-factor", "0"]).is_err());
+        assert!(Config::try_parse_from(["keyrock-task", "--confidence-factor", "-2.12"]).is_err());
+        assert!(Config::try_parse_from([
+            "keyrock-task",
+            "--confidence-factor",
+            "2.12",
+            "--confidence-interval",
+            "99"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_calibrate_step() {
+        let calibrate = |step| {
+            Config::try_parse_from([
+                "keyrock-task",
+                "calibrate",
+                "--data",
+                "ticks.jsonl",
+                "--step",
+                step,
+            ])
+        };
+        assert!(calibrate("0.5").is_ok());
+        assert!(calibrate("0").is_err());
+        assert!(calibrate("-0.25").is_err());
+    }
+
+    #[test]
+    fn test_max_plausible_profit_pct() {
+        let parse =
+            |pct| Config::try_parse_from(["keyrock-task", "--max-plausible-profit-pct", pct]);
+        assert!(parse("5").is_ok());
+        assert!(parse("0").is_err());
+        assert!(parse("-5").is_err());
+    }
+
+    #[test]
+    fn test_fixed_dex_price() {
+        let parse = |price| Config::try_parse_from(["keyrock-task", "--fixed-dex-price", price]);
+        assert_eq!(
+            parse("71.5").unwrap().fixed_dex_price,
+            Some(Decimal::new(715, 1))
+        );
+        assert!(parse("0").is_err());
+        assert!(parse("-71.5").is_err());
+    }
+
+    #[test]
+    fn test_combine_with() {
+        let config = Config::try_parse_from([
+            "keyrock-task",
+            "--combine-with",
+            "coinbase",
+            "--cex-weighting",
+            "binance=3,coinbase=1",
+        ])
+        .unwrap();
+        assert_eq!(config.combine_with, vec![CexSource::Coinbase]);
+        assert_eq!(
+            config.cex_weighting,
+            Weighting::Fixed(HashMap::from([
+                ("binance".to_string(), Decimal::from(3)),
+                ("coinbase".to_string(), Decimal::ONE),
+            ]))
+        );
+
+        let config = Config::default();
+        assert!(config.combine_with.is_empty());
+        assert_eq!(config.cex_weighting, Weighting::Volume);
+        // Weighting is meaningless without another source
+        assert!(Config::try_parse_from(["keyrock-task", "--cex-weighting", "volume"]).is_err());
+        assert!(Config::try_parse_from(["keyrock-task", "--combine-with", "kraken"]).is_err());
+    }
+
+    #[test]
+    fn test_reemit_improvement() {
+        let parse = |improvement| {
+            Config::try_parse_from(["keyrock-task", "--reemit-improvement", improvement])
+        };
+        assert!(parse("0.01").is_ok());
+        assert!(parse("0").is_ok());
+        assert!(parse("-0.01").is_err());
+    }
+
+    #[test]
+    fn test_symbol() {
+        let config = Config::parse_from(["keyrock-task", "--symbol", "BTCUSD"]);
+        assert_eq!(
+            config.pairs(),
+            vec![PairConfig {
+                binance_ticker: "btcusdt".to_string(),
+                pyth_price_id: "GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU".to_string(),
+                pyth_quote_price_id: None,
+                enabled: true,
+            }]
+        );
+        assert_eq!(
+            resolve_symbol("eth/usd"),
+            Some(("ethusdt", "JBu1AL4obBcCMqKBBxhpWCNUt136ijcuMZLFvTP7iWdB"))
+        );
+
+        // Either side can be overridden
+        let config = Config::parse_from(["keyrock-task", "--symbol", "btcusd", "-b", "BTCFDUSD"]);
+        assert_eq!(config.pairs()[0].binance_ticker, "btcfdusd");
+        assert_eq!(
+            config.pairs()[0].pyth_price_id,
+            "GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU"
+        );
+
+        // Unknown symbol requires both ids
+        assert_eq!(resolve_symbol("DOGEUSD"), None);
+        let config = Config::parse_from(["keyrock-task", "--symbol", "DOGEUSD", "-b", "dogeusdt"]);
+        assert!(config.default_pair().is_err());
+        let config = Config::parse_from([
+            "keyrock-task",
+            "--symbol",
+            "DOGEUSD",
+            "-b",
+            "dogeusdt",
+            "-p",
+            "FsSM3s38PX9K7Dn6eGzuE29S2Dsk1Sss1baytTQdCaQj",
+        ]);
+        assert_eq!(
+            config.pairs()[0].pyth_price_id,
+            "FsSM3s38PX9K7Dn6eGzuE29S2Dsk1Sss1baytTQdCaQj"
+        );
+    }
+
+    #[test]
+    fn test_pairs_provided() {
+        let config = Config::parse_from([
+            "keyrock-task",
+            "--pair",
+            "SOLUSDT:H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG",
+            "--pair",
+            "bnbusdt:4CkQJBxhU8EZ2UjhigbtdaPbpTe6mqf811fipYBFbSYN:disabled",
+        ]);
+        let pairs = config.pairs();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].binance_ticker, "solusdt");
+        assert!(pairs[0].enabled);
+        assert_eq!(
+            pairs[1].pyth_price_id,
+            "4CkQJBxhU8EZ2UjhigbtdaPbpTe6mqf811fipYBFbSYN"
+        );
+        assert!(!pairs[1].enabled);
+        assert_eq!(
+            pairs[1].to_string(),
+            "bnbusdt:4CkQJBxhU8EZ2UjhigbtdaPbpTe6mqf811fipYBFbSYN:disabled"
+        );
+    }
+
+    #[test]
+    fn test_pair_cross() {
+        let pair = "solbtc:H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG/GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU"
+            .parse::<PairConfig>()
+            .unwrap();
+        assert_eq!(pair.binance_ticker, "solbtc");
+        assert_eq!(
+            pair.pyth_price_id,
+            "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG"
+        );
+        assert_eq!(
+            pair.pyth_quote_price_id,
+            Some("GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU".to_string())
+        );
+    }
+
+    #[test]
+    fn test_pair_invalid() {
+        assert!("solusdt".parse::<PairConfig>().is_err());
+        assert!("solbtc:H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG/"
+            .parse::<PairConfig>()
+            .is_err());
+        assert!(":H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG"
+            .parse::<PairConfig>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_effective_arguments() {
+        let arguments = Config::effective_arguments([
+            "keyrock-task",
+            "--symbol",
+            "BTCUSD",
+            "--cooldown",
+            "500",
+            "--emit-noops",
+            "--emit-initial",
+            "false",
+            "--sink",
+            "csv:stdout",
+            "--sink",
+            "webhook:https://example.com/hook?token=secret",
+            "--discord-webhook",
+            "https://discord.com/api/webhooks/1/secret",
+            "config",
+        ])
+        .unwrap();
+
+        assert_eq!(arguments["cooldown"], "500");
+        assert_eq!(arguments["emit_noops"], true);
+        assert_eq!(arguments["emit_initial"], "false");
+        assert_eq!(arguments["market"], "spot"); // default
+        assert_eq!(arguments["binance_ticker"], Value::Null);
+        assert_eq!(
+            arguments["resolved_pairs"],
+            Value::from(vec!["btcusdt:GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU"])
+        );
+        assert_eq!(
+            arguments["sinks"],
+            Value::from(vec!["csv:stdout", "webhook:<redacted>"])
+        );
+        assert_eq!(arguments["discord_webhook"], "<redacted>");
+        assert!(!arguments.to_string().contains("secret"));
+
+        assert!(Config::effective_arguments(["keyrock-task", "--cooldown", "soon"]).is_err());
     }
 }
@@ -0,0 +1,71 @@
+/*
+    Library entry point, so the arbitrage logic can be embedded without the CLI
+    Config is built programmatically with Config::default() or a struct literal, which never reads the process arguments,
+    and passed to FinderSettings::from_config, PairState::new and State::from_config, none of which touch CONFIG or STATE
+    Only the binary and State::new parse the arguments into CONFIG
+*/
+pub mod config;
+pub mod structs;
+
+pub use config::{Config, PairConfig};
+pub use structs::{
+    arbitrage_finder::{ArbitrageFinder, ArbitrageOpportunity, EvalOutcome, FinderSettings},
+    pair_supervisor::PairSupervisor,
+    state::{PairState, State},
+    worker_pool::WorkerPool,
+};
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+
+    use crate::{
+        config::CONFIG,
+        structs::{arbitrage_finder::ArbitrageDirection, cex::binance::BookTickerData},
+    };
+
+    use super::{ArbitrageFinder, Config, FinderSettings, PairState};
+
+    #[tokio::test]
+    async fn test_library_use() {
+        let config = Config {
+            symbol: Some("SOLUSD".to_string()),
+            ..Default::default()
+        };
+        let pair = PairState::new(&config.pairs()[0]);
+        // l: 68.43263012 h: 71.27225988
+        pair.update_latest_pyth_price(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        }))
+        .await;
+        *pair.get_latest_binance_ticker_data().write().await = Some(BookTickerData {
+            b: "71.3833".to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        });
+
+        let opportunity = ArbitrageFinder::with_settings(FinderSettings::from_config(&config))
+            .find_opportunity(
+                pair.get_latest_pyth_price(),
+                pair.get_latest_binance_ticker_data(),
+                pair.binance_taker_fee,
+            )
+            .await
+            .unwrap();
+        assert_eq!(opportunity.direction, ArbitrageDirection::SellBinanceBuyDex);
+        assert_eq!(
+            opportunity.estimated_profit,
+            Decimal::from_str("0.03400176").unwrap()
+        );
+        // The arguments of the test binary were never parsed
+        assert!(CONFIG.get().is_none());
+    }
+}
@@ -1,32 +1,373 @@
-use structs::{
-    arbitrage_finder::ArbitrageFinder,
-    state::{State, STATE},
+use std::{net::SocketAddr, path::Path, sync::Arc, time::Duration};
+
+use keyrock_task::{
+    config::{Command, Config, CongestionSignalKind, Market, StreamFormat, CONFIG},
+    structs::{
+        arbitrage_finder::FinderSettings,
+        backoff::Backoff,
+        backtest::Backtest,
+        bench,
+        bounds::BoundsReporter,
+        calibration,
+        cex::{
+            binance::Binance,
+            exchange_info::{
+                self, ExchangeInfoPoller, RateLimited, SymbolDecimalsCache, SYMBOL_DECIMALS,
+            },
+        },
+        clock::{Clock, SystemClock},
+        control::{ControlCommand, ControlRequest, ControlSocket},
+        dead_mans_switch::DeadMansSwitch,
+        dedup_state::DedupState,
+        emitter::{EmittedOpportunity, OpportunityEmitter},
+        heartbeat::Heartbeat,
+        noops::DEFAULT_NOOP_INTERVAL,
+        numeric,
+        on_chain::congestion::{
+            CongestionMonitor, CongestionSignal, PrioritizationFeeSignal, SlotTimeSignal,
+        },
+        pair_supervisor::{ListingChanges, PairSupervisor},
+        schema,
+        sinks::{discord::DiscordSink, grpc::GrpcSink, handler, sqlite::SqliteSink, uds::UdsSink},
+        state::{State, STATE},
+        stats::{LifetimeHistogram, ProfitHistogram, Stats},
+        verify,
+        worker_pool::WorkerPool,
+    },
+};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
 };
-use tokio::task::JoinHandle;
 
-mod config;
-mod structs;
+const CONTROL_CHANNEL_CAPACITY: usize = 16;
+const PYTH_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(500);
+const PYTH_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+const COINBASE_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(500);
+const COINBASE_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
 
 #[tokio::main]
 async fn main() {
-    let tasks = vec![
-        handle_pyth_price_update().await,
-        handle_binance_ticker_data_update().await,
-        handle_finding_arbitrage_opportunities().await,
-    ];
+    let config = CONFIG.get_or_init(|| async { Config::new() }).await;
+    if let Some(command) = &config.command {
+        return handle_command(config, command).await;
+    }
+
+    SYMBOL_DECIMALS
+        .get_or_try_init(|| async {
+            let pairs = config.pairs();
+            let tickers = pairs
+                .iter()
+                .map(|pair| pair.binance_ticker.as_str())
+                .collect::<Vec<_>>();
+            SymbolDecimalsCache::fetch(config.market, &tickers).await
+        })
+        .await
+        .expect("Could not fetch decimals of the symbols");
+
+    let (opportunity_sender, _) = broadcast::channel(config.opportunity_buffer_capacity);
+    let stats = Arc::new(std::sync::Mutex::new(Stats {
+        profit_histogram: ProfitHistogram::new(config.profit_histogram_edges.clone()),
+        lifetime_histogram: LifetimeHistogram::new(
+            config
+                .lifetime_histogram_edges
+                .iter()
+                .map(|edge| Duration::from_millis(*edge))
+                .collect(),
+        ),
+        window_start: Some(SystemClock.unix_millis()),
+        ..Default::default()
+    }));
+    let mut emitter = OpportunityEmitter::new(opportunity_sender.clone(), stats.clone());
+    if config.net_positive_only {
+        emitter = emitter.with_net_positive_only();
+    }
+    if let Some(marginal_band_bps) = config.marginal_band_bps {
+        emitter = emitter.with_marginal_band_bps(marginal_band_bps);
+    }
+    if let Some(max_plausible_profit_pct) = config.max_plausible_profit_pct {
+        emitter = emitter
+            .with_max_plausible_profit_pct(max_plausible_profit_pct, config.anomaly_halt_after);
+    }
+    if config.include_raw {
+        emitter = emitter.with_raw_inputs();
+    }
+    if config.include_funding_rate {
+        let state = STATE.get_or_init(|| async { State::new().await }).await;
+        emitter = emitter
+            .with_funding_rates(|binance_ticker| state.get_binance_funding_rate(binance_ticker));
+    }
+    if config.normalize_decimals {
+        emitter = emitter.with_normalized_decimals();
+    }
+    if config.halt_rate_multiplier.is_some() || config.halt_profit_multiplier.is_some() {
+        emitter = emitter.with_dead_mans_switch(DeadMansSwitch::new(
+            config.halt_rate_multiplier,
+            config.halt_profit_multiplier,
+            Duration::from_secs(config.halt_baseline_window),
+        ));
+    }
+    let mut tasks = vec![];
+
+    if let Some(congestion_signal) = config.congestion_signal {
+        let congestion_monitor = Arc::new(CongestionMonitor::new(
+            match congestion_signal {
+                CongestionSignalKind::PriorityFee => {
+                    Box::new(PrioritizationFeeSignal::new(&config.solana_rpc_url))
+                        as Box<dyn CongestionSignal>
+                }
+                CongestionSignalKind::SlotTime => {
+                    Box::new(SlotTimeSignal::new(&config.solana_rpc_url))
+                }
+            },
+            config.congestion_threshold.unwrap(), // required by clap
+        ));
+        emitter = emitter.with_congestion_monitor(congestion_monitor.clone());
+        tasks.push(
+            handle_congestion_monitoring(
+                congestion_monitor,
+                Duration::from_secs(config.congestion_check_interval),
+            )
+            .await,
+        );
+    }
+
+    tasks.push(handle_recent_ticks_dump().await);
+    if let Some(heartbeat_interval) = config.heartbeat {
+        tasks.push(handle_heartbeat(Duration::from_secs(heartbeat_interval)).await);
+    }
+    tasks.push(handle_pyth_price_update(config.emit_bounds.then(|| handle_bounds(config))).await);
+    tasks.push(handle_binance_ticker_data_update().await);
+    if !config.combine_with.is_empty() {
+        tasks.push(handle_coinbase_ticker_data_update().await);
+    }
+    if !config.dex_pools.is_empty() {
+        tasks.push(
+            handle_dex_pool_price_update(Duration::from_millis(config.dex_pool_interval_ms)).await,
+        );
+    }
+    let emitter = Arc::new(emitter);
+    let pair_supervisor = handle_finding_arbitrage_opportunities(
+        config,
+        config.dedup_state_file.as_ref().map(|dedup_state_file| {
+            let dedup_state =
+                DedupState::load(dedup_state_file).expect("Could not load deduplication state");
+            (dedup_state, Duration::from_secs(config.dedup_state_ttl))
+        }),
+        emitter.clone(),
+        stats.clone(),
+    )
+    .await;
+    if let Some(exchange_info_refresh) = config.exchange_info_refresh {
+        tasks.push(
+            handle_exchange_info_refresh(
+                config.market,
+                Duration::from_secs(exchange_info_refresh),
+                pair_supervisor.clone(),
+            )
+            .await,
+        );
+    }
+    if let Some(spread_snapshot) = config.spread_snapshot {
+        tasks.push(
+            handle_spread_snapshot(
+                Duration::from_secs(spread_snapshot),
+                pair_supervisor.clone(),
+            )
+            .await,
+        );
+    }
+    if let Some(control_socket_path) = &config.control_socket {
+        tasks.extend(
+            handle_control_socket(
+                control_socket_path,
+                pair_supervisor.clone(),
+                emitter,
+                stats.clone(),
+            )
+            .await,
+        );
+    }
+    if let Some(uds_path) = &config.uds_path {
+        tasks.push(handle_uds_sink(uds_path, config.uds_format, opportunity_sender.clone()).await);
+    }
+    if let Some(sqlite_path) = &config.sqlite {
+        tasks.push(handle_sqlite_sink(sqlite_path, &opportunity_sender).await);
+    }
+    if let Some(discord_webhook) = &config.discord_webhook {
+        tasks.push(handle_discord_sink(discord_webhook, &opportunity_sender).await);
+    }
+    if let Some(grpc_addr) = config.grpc_addr {
+        tasks.push(handle_grpc_sink(grpc_addr, opportunity_sender.clone()).await);
+    }
+    for sink_config in &config.sinks {
+        println!("Writing opportunities to {}", sink_config);
+        tasks.push(
+            handler::spawn_sink(sink_config, &opportunity_sender).expect("Could not open sink"),
+        );
+    }
+    #[cfg(feature = "redis")]
+    if let Some(redis_url) = &config.redis_url {
+        tasks.push(handle_redis_sink(config, redis_url, &opportunity_sender).await);
+    }
+    #[cfg(feature = "kafka")]
+    if let Some(kafka_topic) = &config.kafka_topic {
+        tasks
+            .push(handle_kafka_sink(&config.kafka_brokers, kafka_topic, &opportunity_sender).await);
+    }
 
-    handle_shutdown(tasks).await;
+    handle_shutdown(tasks, stats, pair_supervisor).await;
 }
 
-async fn handle_pyth_price_update() -> JoinHandle<()> {
+async fn handle_command(config: &Config, command: &Command) {
+    match command {
+        Command::Backtest { data, replay_speed } => {
+            let backtest =
+                Backtest::load_all(data, Binance::taker_fee(&config.pairs()[0].binance_ticker))
+                    .expect("Could not load recorded ticks")
+                    .with_replay_speed(*replay_speed);
+            let found = backtest.run(FinderSettings::from_config(config)).await;
+            for (tick, opportunity) in &found {
+                println!(
+                    "Found an opportunity at {}!\n{:#?}\n",
+                    tick.timestamp, opportunity
+                );
+            }
+            println!(
+                "Found {} opportunities in {} ticks",
+                found.len(),
+                backtest.len()
+            );
+        }
+        Command::Calibrate {
+            data,
+            min_factor,
+            max_factor,
+            step,
+        } => {
+            let backtest =
+                Backtest::load(data, Binance::taker_fee(&config.pairs()[0].binance_ticker))
+                    .expect("Could not load recorded ticks");
+            let results = calibration::calibrate(
+                &backtest,
+                FinderSettings::from_config(config),
+                *min_factor,
+                *max_factor,
+                *step,
+            )
+            .await
+            .expect("Could not calibrate");
+            print!("{}", calibration::format_results(&results));
+        }
+        Command::Bench { iterations } => {
+            let result = bench::bench(FinderSettings::from_config(config), *iterations).await;
+            print!("{}", bench::format_result(&result));
+        }
+        Command::VerifyMath => {
+            let results = verify::verify().await;
+            print!("{}", verify::format_results(&results));
+            if results.iter().any(|result| result.failure.is_some()) {
+                std::process::exit(1);
+            }
+        }
+        Command::Schema => println!("{}", schema::opportunity_schema()),
+        Command::Config => {
+            let arguments = Config::effective_arguments(std::env::args_os())
+                .expect("Arguments validated when parsing");
+            println!("{}", serde_json::to_string_pretty(&arguments).unwrap());
+        }
+        Command::ListPairs { filter } => {
+            let symbols = exchange_info::list_symbols(config.market, filter.as_deref())
+                .await
+                .expect("Could not list Binance symbols");
+            for symbol in symbols {
+                println!("{}", symbol);
+            }
+        }
+    }
+}
+
+async fn handle_heartbeat(interval: Duration) -> JoinHandle<()> {
+    let state = STATE.get_or_init(|| async { State::new().await }).await;
+
+    tokio::spawn(Heartbeat::new(interval).run(|| state.pairs(), |line| println!("{}", line)))
+}
+
+async fn handle_recent_ticks_dump() -> JoinHandle<()> {
+    let mut user_defined_signal =
+        signal(SignalKind::user_defined1()).expect("Could not listen for SIGUSR1");
+
+    tokio::spawn({
+        let state = STATE.get_or_init(|| async { State::new().await }).await;
+
+        async move {
+            while user_defined_signal.recv().await.is_some() {
+                for pair in state.pairs() {
+                    println!("{}", pair.dump_recent_ticks());
+                }
+            }
+        }
+    })
+}
+
+fn handle_bounds(config: &Config) -> BoundsReporter {
+    let (bounds_sender, mut bounds_receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(bounds_event) = bounds_receiver.recv().await {
+            println!("{}", serde_json::to_string(&bounds_event).unwrap());
+        }
+    });
+
+    BoundsReporter::new(FinderSettings::from_config(config), bounds_sender)
+}
+
+async fn handle_pyth_price_update(mut bounds: Option<BoundsReporter>) -> JoinHandle<()> {
     println!("Spawning Pyth price updater");
 
+    tokio::spawn({
+        let state = STATE.get_or_init(|| async { State::new().await }).await;
+
+        async move {
+            let mut backoff = Backoff::new(PYTH_RETRY_INITIAL_DELAY, PYTH_RETRY_MAX_DELAY);
+            loop {
+                let result = state.update_latest_pyth_price().await;
+                if let Some(bounds) = &mut bounds {
+                    for pair in state.pairs() {
+                        if let Some(price) = *pair.get_latest_pyth_price().read().await {
+                            bounds.record_at(
+                                SystemClock.unix_millis(),
+                                &pair.binance_ticker,
+                                price,
+                            );
+                        }
+                    }
+                }
+                match result {
+                    Ok(()) => backoff.reset(),
+                    Err(err) => {
+                        let delay = backoff.next_delay();
+                        eprintln!("{:#}, retrying in {:?}", err, delay);
+                        tokio::time::sleep(delay).await;
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn handle_dex_pool_price_update(interval: Duration) -> JoinHandle<()> {
+    println!("Spawning DEX pool price updater");
+
     tokio::spawn({
         let state = STATE.get_or_init(|| async { State::new().await }).await;
 
         async move {
             loop {
-                state.update_latest_pyth_price().await;
+                if let Err(err) = state.update_latest_dex_pool_prices().await {
+                    eprintln!("{:#}", err);
+                }
+                tokio::time::sleep(interval).await;
             }
         }
     })
@@ -46,31 +387,379 @@ async fn handle_binance_ticker_data_update() -> JoinHandle<()> {
     })
 }
 
-async fn handle_finding_arbitrage_opportunities() -> JoinHandle<()> {
+async fn handle_coinbase_ticker_data_update() -> JoinHandle<()> {
+    println!("Spawning Coinbase ticker data updater");
+
+    tokio::spawn({
+        let state = STATE.get_or_init(|| async { State::new().await }).await;
+
+        async move {
+            let mut backoff = Backoff::new(COINBASE_RETRY_INITIAL_DELAY, COINBASE_RETRY_MAX_DELAY);
+            loop {
+                match state.update_latest_coinbase_ticker_data().await {
+                    Ok(()) => backoff.reset(),
+                    Err(err) => {
+                        let delay = backoff.next_delay();
+                        eprintln!(
+                            "Could not reconnect to Coinbase WS: {:#}, retrying in {:?}",
+                            err, delay
+                        );
+                        tokio::time::sleep(delay).await;
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn handle_congestion_monitoring(
+    congestion_monitor: Arc<CongestionMonitor>,
+    interval: Duration,
+) -> JoinHandle<()> {
+    println!("Spawning Solana congestion monitor");
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = congestion_monitor.update() {
+                eprintln!("Could not sample Solana congestion: {}", err);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
+
+async fn handle_finding_arbitrage_opportunities(
+    config: &Config,
+    restored_dedup_state: Option<(DedupState, Duration)>,
+    emitter: Arc<OpportunityEmitter>,
+    stats: Arc<std::sync::Mutex<Stats>>,
+) -> Arc<PairSupervisor> {
     println!("Searching for arbitrage opportunities");
 
+    let state = STATE.get_or_init(|| async { State::new().await }).await;
+    let normalize_decimals = config.normalize_decimals;
+    let worker_pool = WorkerPool::new(
+        config.workers as usize,
+        move |binance_ticker, opportunity, raw_inputs| {
+            if emitter.emit_with_raw_inputs(binance_ticker, opportunity, raw_inputs) {
+                let opportunity = match normalize_decimals {
+                    true => opportunity.normalized(), // printed like the emitted one
+                    false => opportunity,
+                };
+                println!(
+                    "Found an opportunity for {}!\n{:#?}\n",
+                    binance_ticker, opportunity
+                );
+                if let Some(decimals) = SYMBOL_DECIMALS
+                    .get()
+                    .and_then(|symbol_decimals| symbol_decimals.get(binance_ticker))
+                {
+                    println!(
+                        "Binance leg of {}: {} at {}\n",
+                        binance_ticker,
+                        decimals.format_quantity(opportunity.quantity),
+                        decimals.format_price(opportunity.binance_price)
+                    );
+                }
+            }
+        },
+    );
+
+    let mut pair_supervisor =
+        PairSupervisor::new(FinderSettings::from_config(config), worker_pool).with_stats(stats);
+    if let Some(symbol_decimals) = SYMBOL_DECIMALS.get() {
+        pair_supervisor = pair_supervisor.with_symbol_decimals(symbol_decimals);
+    }
+    let eval_interval = config.eval_interval_ms.map(Duration::from_millis);
+    if let Some(eval_interval) = eval_interval {
+        pair_supervisor = pair_supervisor.with_eval_interval(eval_interval);
+    }
+    if let Some(profit_bar_length) = config.profit_bars.map(Duration::from_secs) {
+        let (profit_bar_sender, mut profit_bar_receiver) = mpsc::unbounded_channel();
+        pair_supervisor = pair_supervisor.with_profit_bars(profit_bar_length, profit_bar_sender);
+        tokio::spawn(async move {
+            while let Some(profit_bar) = profit_bar_receiver.recv().await {
+                println!("{}", serde_json::to_string(&profit_bar).unwrap());
+            }
+        });
+    }
+    if config.emit_noops {
+        let (noop_sender, mut noop_receiver) = mpsc::unbounded_channel();
+        pair_supervisor =
+            pair_supervisor.with_noops(eval_interval.unwrap_or(DEFAULT_NOOP_INTERVAL), noop_sender);
+        tokio::spawn(async move {
+            while let Some(noop_event) = noop_receiver.recv().await {
+                println!("{}", serde_json::to_string(&noop_event).unwrap());
+            }
+        });
+    }
+    if config.emit_closes {
+        let (close_sender, mut close_receiver) = mpsc::unbounded_channel();
+        pair_supervisor = pair_supervisor.with_closes(close_sender);
+        tokio::spawn(async move {
+            while let Some(close_event) = close_receiver.recv().await {
+                println!("{}", serde_json::to_string(&close_event).unwrap());
+            }
+        });
+    }
+    if let Some((dedup_state, ttl)) = restored_dedup_state {
+        pair_supervisor = pair_supervisor.with_dedup_state(dedup_state, ttl);
+    }
+    let pair_supervisor = Arc::new(pair_supervisor);
+    for pair in state.pairs() {
+        pair_supervisor.start(pair);
+    }
+    pair_supervisor
+}
+
+async fn handle_exchange_info_refresh(
+    market: Market,
+    interval: Duration,
+    pair_supervisor: Arc<PairSupervisor>,
+) -> JoinHandle<()> {
+    println!("Spawning Binance exchange info refresher");
+
     tokio::spawn({
         let state = STATE.get_or_init(|| async { State::new().await }).await;
-        let mut arbitrage_finder = ArbitrageFinder::new();
 
         async move {
+            let mut poller = ExchangeInfoPoller::new(market, interval);
+            let mut delay = interval; // fetched at startup
             loop {
-                let maybe_opportunity = arbitrage_finder
-                    .find_opportunity(
-                        state.get_latest_pyth_price(),
-                        state.get_latest_binance_ticker_data(),
-                        state.binance_taker_fee,
-                    )
-                    .await;
-                if let Some(opportunity) = maybe_opportunity {
-                    println!("Found an opportunity!\n{:#?}\n", opportunity);
+                tokio::time::sleep(delay).await;
+                let (result, next_delay) = poller.poll().await;
+                delay = next_delay;
+                let json = match result {
+                    Ok(json) => json,
+                    Err(err) => {
+                        // Rate limits are reported by the poller
+                        if err.downcast_ref::<RateLimited>().is_none() {
+                            eprintln!("Could not refresh Binance exchange info: {}", err);
+                        }
+                        continue;
+                    }
+                };
+                let traded = match exchange_info::parse_symbols(&json, None) {
+                    Ok(traded) => traded,
+                    Err(err) => {
+                        eprintln!("Could not parse refreshed Binance exchange info: {}", err);
+                        continue;
+                    }
+                };
+
+                let pairs = state.pairs();
+                let tickers = pairs
+                    .iter()
+                    .map(|pair| pair.binance_ticker.as_str())
+                    .collect::<Vec<_>>();
+                if let Some(symbol_decimals) = SYMBOL_DECIMALS.get() {
+                    let _ = symbol_decimals.refresh(&json, &tickers); // parsed above
+                }
+                let ListingChanges { delisted, relisted } =
+                    pair_supervisor.apply_listings(pairs, &traded);
+                for binance_ticker in delisted {
+                    eprintln!(
+                        "WARNING: {} is no longer traded on Binance, paused evaluating its pair\n",
+                        binance_ticker
+                    );
+                }
+                for binance_ticker in relisted {
+                    println!(
+                        "{} is traded on Binance again, resumed evaluating its pair\n",
+                        binance_ticker
+                    );
                 }
             }
         }
     })
 }
 
-async fn handle_shutdown(tasks: Vec<JoinHandle<()>>) {
+async fn handle_spread_snapshot(
+    interval: Duration,
+    pair_supervisor: Arc<PairSupervisor>,
+) -> JoinHandle<()> {
+    println!("Spawning spread snapshot handler");
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await; // completes immediately
+        loop {
+            interval.tick().await;
+            let snapshot = pair_supervisor
+                .spread_snapshot(SystemClock.unix_millis())
+                .await;
+            println!("{}", serde_json::to_string(&snapshot).unwrap());
+        }
+    })
+}
+
+async fn handle_control_socket(
+    control_socket_path: &Path,
+    pair_supervisor: Arc<PairSupervisor>,
+    emitter: Arc<OpportunityEmitter>,
+    stats: Arc<std::sync::Mutex<Stats>>,
+) -> [JoinHandle<()>; 2] {
+    println!(
+        "Accepting control commands on {}",
+        control_socket_path.display()
+    );
+
+    let (sender, mut receiver) = mpsc::channel::<ControlRequest>(CONTROL_CHANNEL_CAPACITY);
+    let control_socket =
+        ControlSocket::bind(control_socket_path, sender).expect("Could not bind control socket");
+    let state = STATE.get_or_init(|| async { State::new().await }).await;
+
+    [
+        tokio::spawn(control_socket.serve()),
+        tokio::spawn(async move {
+            while let Some((command, result_sender)) = receiver.recv().await {
+                let result = match command {
+                    ControlCommand::Subscribe(pair_config) => state
+                        .add_pair(&pair_config)
+                        .await
+                        .map(|pair| pair_supervisor.start(pair)),
+                    ControlCommand::Unsubscribe(binance_ticker) => {
+                        pair_supervisor.stop(&binance_ticker);
+                        stats
+                            .lock()
+                            .unwrap()
+                            .record_pair_enabled(&binance_ticker, true); // no longer reported as disabled
+                        state.remove_pair(&binance_ticker).await
+                    }
+                    ControlCommand::Enable(binance_ticker) => {
+                        set_pair_enabled(state, &pair_supervisor, &binance_ticker, true)
+                    }
+                    ControlCommand::Disable(binance_ticker) => {
+                        set_pair_enabled(state, &pair_supervisor, &binance_ticker, false)
+                    }
+                    ControlCommand::Pause => {
+                        emitter.pause();
+                        println!("Paused emitting opportunities");
+                        Ok(())
+                    }
+                    ControlCommand::Resume => {
+                        emitter.resume();
+                        println!("Resumed emitting opportunities");
+                        Ok(())
+                    }
+                    ControlCommand::ResetStats => {
+                        stats.lock().unwrap().reset(SystemClock.unix_millis());
+                        println!("Reset stats");
+                        Ok(())
+                    }
+                };
+                let _ = result_sender.send(result); // the client might have disconnected
+            }
+        }),
+    ]
+}
+
+fn set_pair_enabled(
+    state: &State,
+    pair_supervisor: &PairSupervisor,
+    binance_ticker: &str,
+    enabled: bool,
+) -> anyhow::Result<()> {
+    let pair = state
+        .pair(binance_ticker)
+        .ok_or_else(|| anyhow::anyhow!("Pair {} is not monitored", binance_ticker))?;
+    pair_supervisor.set_enabled(&pair, enabled);
+    println!(
+        "{} {}",
+        if enabled { "Enabled" } else { "Disabled" },
+        binance_ticker
+    );
+    Ok(())
+}
+
+async fn handle_uds_sink(
+    uds_path: &Path,
+    uds_format: StreamFormat,
+    opportunity_sender: broadcast::Sender<EmittedOpportunity>,
+) -> JoinHandle<()> {
+    println!("Serving opportunities on {}", uds_path.display());
+
+    let sink = UdsSink::bind(uds_path, opportunity_sender).expect("Could not bind UDS listener");
+    let sink = match uds_format {
+        StreamFormat::Json => sink,
+        StreamFormat::Binary => sink.with_binary_frames(),
+    };
+    tokio::spawn(sink.serve())
+}
+
+async fn handle_sqlite_sink(
+    sqlite_path: &Path,
+    opportunity_sender: &broadcast::Sender<EmittedOpportunity>,
+) -> JoinHandle<()> {
+    println!("Storing opportunities in {}", sqlite_path.display());
+
+    let sink =
+        SqliteSink::open(sqlite_path, opportunity_sender).expect("Could not open SQLite database");
+    tokio::spawn(sink.serve())
+}
+
+async fn handle_discord_sink(
+    discord_webhook: &str,
+    opportunity_sender: &broadcast::Sender<EmittedOpportunity>,
+) -> JoinHandle<()> {
+    println!("Posting opportunities to Discord");
+
+    tokio::spawn(DiscordSink::new(discord_webhook, opportunity_sender).serve())
+}
+
+async fn handle_grpc_sink(
+    grpc_addr: SocketAddr,
+    opportunity_sender: broadcast::Sender<EmittedOpportunity>,
+) -> JoinHandle<()> {
+    println!("Serving opportunities over gRPC on {}", grpc_addr);
+
+    let sink = GrpcSink::bind(grpc_addr, opportunity_sender).expect("Could not bind gRPC listener");
+    tokio::spawn(sink.serve())
+}
+
+#[cfg(feature = "redis")]
+async fn handle_redis_sink(
+    config: &Config,
+    redis_url: &str,
+    opportunity_sender: &broadcast::Sender<EmittedOpportunity>,
+) -> JoinHandle<()> {
+    use keyrock_task::structs::sinks::redis::RedisSink;
+
+    println!(
+        "Publishing opportunities to Redis channel {}",
+        config.redis_channel
+    );
+
+    let sink = RedisSink::new(
+        redis_url,
+        &config.redis_channel,
+        config.redis_buffer_capacity,
+        opportunity_sender,
+    )
+    .expect("Invalid Redis URL");
+    tokio::spawn(sink.serve())
+}
+
+#[cfg(feature = "kafka")]
+async fn handle_kafka_sink(
+    kafka_brokers: &[String],
+    kafka_topic: &str,
+    opportunity_sender: &broadcast::Sender<EmittedOpportunity>,
+) -> JoinHandle<()> {
+    use keyrock_task::structs::sinks::kafka::{KafkaSink, RdKafkaProducer};
+
+    println!("Producing opportunities to Kafka topic {}", kafka_topic);
+
+    let producer = RdKafkaProducer::new(kafka_brokers).expect("Could not create Kafka producer");
+    let sink = KafkaSink::new(producer, kafka_topic, opportunity_sender);
+    tokio::spawn(sink.serve())
+}
+
+async fn handle_shutdown(
+    tasks: Vec<JoinHandle<()>>,
+    stats: Arc<std::sync::Mutex<Stats>>,
+    pair_supervisor: Arc<PairSupervisor>,
+) {
     match tokio::signal::ctrl_c().await {
         Ok(()) => {
             println!("\nAborting tasks...");
@@ -80,6 +769,27 @@ async fn handle_shutdown(tasks: Vec<JoinHandle<()>>) {
             let state = STATE.get_or_init(|| async { State::new().await }).await;
             state.terminate().await;
 
+            let config = CONFIG.get().unwrap();
+            if let Some(dedup_state_file) = &config.dedup_state_file {
+                println!("Saving deduplication state...");
+                if let Err(err) = pair_supervisor.dedup_state().await.save(dedup_state_file) {
+                    eprintln!("Could not save deduplication state: {}", err);
+                }
+            }
+            for socket_path in [&config.uds_path, &config.control_socket]
+                .into_iter()
+                .flatten()
+            {
+                let _ = std::fs::remove_file(socket_path);
+            }
+
+            println!("\n{}\n", stats.lock().unwrap().summary());
+            if numeric::precision_losses() > 0 {
+                println!(
+                    "Products rounded to fit Decimal precision: {}\n",
+                    numeric::precision_losses()
+                );
+            }
             println!("Finished");
         }
         Err(err) => {
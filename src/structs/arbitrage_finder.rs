@@ -1,21 +1,441 @@
-use std::{str::FromStr, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use pyth_sdk_solana::Price;
-use rust_decimal::Decimal;
+use rust_decimal::{prelude::ToPrimitive, Decimal, RoundingStrategy};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
+use uuid::Uuid;
 
-use super::cex::binance::BookTickerData;
+use crate::config::{
+    AlertOn, ComparisonBasis, Config, ExecutionPriceModelKind, FillModel, Leg,
+    QuantityStrategyKind, StrategyKind, TieBreak, ZeroProfitPolicy,
+};
+
+use super::{
+    cex::binance::{parse_decimal, BookTickerData, DepthData},
+    clock::{Clock, SystemClock},
+    closes::{CloseReason, CloseReporter},
+    dedup_state::{BucketFingerprint, FinderDedupState},
+    noops::NoopReporter,
+    numeric::{Numeric, PreciseDecimal},
+    on_chain::twap::PythTwap,
+    profit_bars::ProfitBars,
+    stats::{Stats, Suppression},
+};
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+// Binance execution price, crossed and opposite bounds of the probable price range, quantity and direction of an opportunity
+type Candidate = (Decimal, Decimal, Decimal, Decimal, ArbitrageDirection);
 
 /*
     Struct for finding arbitrage opportunities between Binance and DEXes
 */
 pub struct ArbitrageFinder {
+    settings: FinderSettings,
     last_found: Option<ArbitrageOpportunity>,
+    last_found_levels: Vec<ArbitrageOpportunity>,
+    last_found_in_bucket: HashMap<(ArbitrageDirection, Decimal), Instant>,
+    last_reported: Option<(ArbitrageDirection, Instant)>,
+    persisting_since: Option<(ArbitrageDirection, Instant, bool)>, // direction, start and whether it was reported
+    found_initial: bool, // whether an opportunity passed the deduplication since startup
+    open_since: Option<(ArbitrageDirection, Instant)>, // direction and start of the currently open opportunity
+    last_reported_profit: Option<Decimal>, // estimated profit last reported while the opportunity is open
+    stats: Option<Arc<std::sync::Mutex<Stats>>>, // where suppressed opportunities are counted
+    last_inputs: Option<RawInputs>,        // snapshots of the feeds the last search used
+    has_new_inputs: bool, // whether the last search used other snapshots than the search before it, i.e. new data
+    profit_bars: Option<ProfitBars>, // where evaluated ticks are aggregated into bars
+    noops: Option<NoopReporter>, // where evaluated ticks without a reported opportunity are sent
+    closes: Option<CloseReporter>, // where closes of open opportunities are sent
+    clock: Arc<dyn Clock>, // source of time of the cooldown, persistence and deduplication
+    last_reconnect: Option<Arc<AtomicU64>>, // milliseconds since Unix epoch of the last reconnection of the Binance feed, 0 if never
+    pyth_twap: Option<PythTwap>, // averaged Pyth prices compared instead of the latest one, if configured
+    dex_pool_price: Option<Arc<RwLock<Option<Decimal>>>>, // latest price of the on-chain pool of the pair, if it has one
+    last_suppression: Option<Suppression>, // reason the opportunity of the last search was suppressed for, if it was
+    tick_size: Option<Decimal>, // price tick of the Binance symbol the minimum edge is measured in
+    last_spread: Option<CurrentSpread>, // Binance mid and probable price range the last search compared
+}
+
+/*
+    Outcomes of evaluating a single tick
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalOutcome {
+    NoData,                            // either feed has no price yet
+    NoOpportunity,                     // prices do not cross the probable price range profitably
+    Filtered(Suppression),             // an opportunity was found but suppressed for the reason
+    Opportunity(ArbitrageOpportunity), // an opportunity to report
+}
+
+impl EvalOutcome {
+    /*
+        Collapses the outcome into the reported opportunity, if any
+    */
+    pub fn into_option(self) -> Option<ArbitrageOpportunity> {
+        match self {
+            Self::Opportunity(opportunity) => Some(opportunity),
+            Self::NoData | Self::NoOpportunity | Self::Filtered(_) => None,
+        }
+    }
+}
+
+/*
+    Struct holding parameters of the search for arbitrage opportunities
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FinderSettings {
+    pub confidence_factor: Decimal, // multiplier of Pyth confidence defining the probable price range
+    pub rounding_strategy: RoundingStrategy, // strategy of rounding estimated profits
+    pub pyth_band_bps: Option<u32>, // fixed band around Pyth price used instead of the confidence-based range
+    pub min_confidence: Option<ConfidenceFloor>, // floor of Pyth confidence, None uses the reported one as is
+    pub dedup_bucket: Option<Decimal>, // size of Binance price buckets within which repeated opportunities are suppressed
+    pub dedup_window: Duration, // time for which an opportunity suppresses the following ones in its bucket
+    pub max_dedup_buckets: usize, // maximum number of buckets remembered for deduplication, the oldest one is dropped beyond it
+    pub cooldown: Duration, // time for which a reported opportunity suppresses the following ones in its direction
+    pub strategy: Strategy, // condition of finding an opportunity
+    pub quantity_strategy: QuantityStrategy, // source of quantity of opportunities found at the best bid and ask
+    pub execution_price_model: ExecutionPriceModel, // Binance price of opportunities found at the best bid and ask
+    pub min_persistence: Duration, // time for which an opportunity has to be present before it is reported once
+    pub emit_initial: bool,        // whether the first opportunity found after startup is reported
+    pub inclusive_bounds: bool, // whether Binance price equal to a bound of the probable price range is an opportunity
+    pub tie_break: TieBreak,    // direction reported when a tick qualifies in both of them
+    pub comparison_basis: ComparisonBasis, // Binance price compared to the bounds at the best bid and ask
+    pub zero_profit: ZeroProfitPolicy, // handling of opportunities whose positive estimated profit rounds to zero
+    pub confidence_age_growth: Option<Decimal>, // fraction of confidence factor added per second since Pyth price was published
+    pub max_feed_skew: Option<Duration>, // maximum time between Binance and Pyth prices an opportunity is found in
+    pub pyth_time_offset_ms: i64, // added to Pyth publish time before the skew is measured, modelling a known lead or lag
+    pub leg_fees: Option<LegFees>, // fees of both legs, None pays the given Binance fee on the Binance leg only
+    pub solana_fee: Option<SolanaFee>, // fee of the Solana transaction executing the DEX leg, None if it is not paid
+    pub reemit_improvement: Option<Decimal>, // increase of estimated profit over the last report required to report an open opportunity again
+    pub post_reconnect_grace: Duration, // time after a reconnection of the Binance feed during which opportunities are suppressed
+    pub fill_probability: Option<Decimal>, // probability of filling the Binance leg profits are scaled by, None assuming it is always filled
+    pub invert_pyth: bool, // whether the probable price range is inverted before comparing, for feeds quoting the inverse of the pair
+    pub pyth_twap_window: Option<Duration>, // window of the time-weighted average Pyth price and confidence compared instead of the latest ones
+    pub min_edge_ticks: Option<u32>, // minimum difference between Binance price and the crossed bound in ticks, applied if the tick size is known
+    pub dex_quantity: Option<Decimal>, // quantity available on the DEX leg, None filling any quantity
+    pub fill_model: FillModel, // model of filling both legs if the DEX quantity differs from the Binance one
+    pub alert_on: AlertOn, // whether every opportunity is reported or only the ones changing the direction
+    pub hysteresis: Option<SigmaHysteresis>, // margins beyond the crossed bound opening and keeping opportunities at the best bid and ask
+    pub dex_price_weight: Decimal, // weight of the on-chain pool price blended into the Pyth price, 1 replacing it
+}
+
+/*
+    Struct holding fees of both legs of the round trip as fractions of their notionals
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegFees {
+    pub binance: Decimal,
+    pub dex: Decimal,
+}
+
+/*
+    Struct holding fees of the Solana transaction executing the DEX leg, paid in SOL regardless of the quantity
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolanaFee {
+    pub lamports: u64,              // base fee and priority fee
+    pub sol_price: Option<Decimal>, // price of SOL in the quote currency, None uses the Pyth price of the pair
+}
+
+impl SolanaFee {
+    /*
+        Returns the fee in the quote currency at the price of SOL, None on overflow
+    */
+    pub fn in_quote(&self, sol_price: Decimal) -> Option<Decimal> {
+        Decimal::from(self.lamports)
+            .precise_mul(sol_price)?
+            .checked_div(Decimal::from(LAMPORTS_PER_SOL))
+    }
+}
+
+/*
+    Struct holding margins beyond the crossed bound required of Binance price, in multiples of Pyth confidence (sigma)
+    A closed opportunity opens beyond the open margin and stays open while beyond the close one, which is at most the open one
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigmaHysteresis {
+    pub open: Decimal,
+    pub close: Decimal,
+}
+
+impl SigmaHysteresis {
+    /*
+        Returns the margin in units of the price for the sigma, depending on whether the opportunity is open, None on overflow
+    */
+    pub fn margin(&self, sigma: Decimal, is_open: bool) -> Option<Decimal> {
+        sigma.precise_mul(if is_open { self.close } else { self.open })
+    }
+}
+
+/*
+    Supported floors of Pyth confidence
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfidenceFloor {
+    Absolute(Decimal), // in units of the price
+    Bps(u32),          // in basis points of the price
+}
+
+impl ConfidenceFloor {
+    /*
+        Returns the Pyth price with its confidence raised to the floor if it is smaller, None on overflow
+    */
+    pub fn apply(&self, pyth_price: Price) -> Option<Price> {
+        let floor = match self {
+            Self::Absolute(min_confidence) => {
+                let unit = Decimal::try_new(1, pyth_price.expo.unsigned_abs()).ok()?;
+                min_confidence.checked_div(unit)?.ceil().to_u64()?
+            }
+            Self::Bps(bps) => {
+                let floor =
+                    (pyth_price.price.unsigned_abs() as u128 * *bps as u128).div_ceil(10_000); // doesn't overflow
+                u64::try_from(floor).ok()?
+            }
+        };
+
+        Some(Price {
+            conf: pyth_price.conf.max(floor),
+            ..pyth_price
+        })
+    }
+}
+
+/*
+    Supported conditions of finding an opportunity
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    Bound,                  // Binance price beyond the crossed bound of the probable price range
+    ExpectedValue(Decimal), // expected profit over the distribution of DEX price above the threshold
+}
+
+impl Strategy {
+    /*
+        Returns prices Binance bid has to be above and ask below, given the bounds of the probable price range, None on overflow
+        Pyth price follows Laplace distribution symmetric around its mid, so expected DEX price is the mid and
+        the expected profit, linear in DEX price, is the profit at the mid
+    */
+    pub fn reference_prices(&self, higher: Decimal, lower: Decimal) -> Option<(Decimal, Decimal)> {
+        match self {
+            Self::Bound => Some((higher, lower)),
+            Self::ExpectedValue(_) => {
+                let mid = higher.checked_add(lower)?.checked_div(Decimal::TWO)?;
+                Some((mid, mid))
+            }
+        }
+    }
+}
+
+/*
+    Supported sources of quantity of an opportunity
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantityStrategy {
+    BookSide,       // quantity of the side of the book the opportunity trades against
+    MinBoth,        // the lower of bid and ask quantities
+    Fixed(Decimal), // configured quantity regardless of the book
+}
+
+impl QuantityStrategy {
+    /*
+        Returns quantity of the opportunity given quantities of the traded and the opposite side of the book
+    */
+    pub fn quantity(
+        &self,
+        book_side_quantity: Decimal,
+        opposite_side_quantity: Decimal,
+    ) -> Decimal {
+        match self {
+            Self::BookSide => book_side_quantity,
+            Self::MinBoth => book_side_quantity.min(opposite_side_quantity),
+            Self::Fixed(quantity) => *quantity,
+        }
+    }
+}
+
+/*
+    Supported assumptions about the Binance price an opportunity found at the best bid and ask is executed at
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionPriceModel {
+    Best,           // the best price of the traded side of the book
+    Mid,            // the middle of the spread
+    OffsetBps(u32), // the best price moved into the spread by basis points of it, up to the opposite best price
+}
+
+impl ExecutionPriceModel {
+    /*
+        Returns execution price given the best prices of the traded and the opposite side of the book, None on overflow
+    */
+    pub fn price(&self, best_price: Decimal, opposite_best_price: Decimal) -> Option<Decimal> {
+        match self {
+            Self::Best => Some(best_price),
+            Self::Mid => best_price
+                .checked_add(opposite_best_price)?
+                .checked_div(Decimal::TWO),
+            Self::OffsetBps(bps) => {
+                let offset = best_price
+                    .precise_mul(Decimal::from(*bps))?
+                    .checked_div(Decimal::new(10000, 0))?;
+                if opposite_best_price.gt(&best_price) {
+                    Some(best_price.checked_add(offset)?.min(opposite_best_price))
+                } else {
+                    Some(best_price.checked_sub(offset)?.max(opposite_best_price))
+                }
+            }
+        }
+    }
 }
 
 impl ArbitrageFinder {
     pub fn new() -> Self {
-        return Self { last_found: None };
+        Self::with_settings(FinderSettings::default())
+    }
+
+    pub fn with_settings(settings: FinderSettings) -> Self {
+        Self {
+            settings,
+            last_found: None,
+            last_found_levels: Vec::new(),
+            last_found_in_bucket: HashMap::new(),
+            last_reported: None,
+            persisting_since: None,
+            found_initial: false,
+            open_since: None,
+            last_reported_profit: None,
+            stats: None,
+            last_inputs: None,
+            has_new_inputs: true,
+            profit_bars: None,
+            noops: None,
+            closes: None,
+            clock: Arc::new(SystemClock),
+            last_reconnect: None,
+            pyth_twap: settings.pyth_twap_window.map(PythTwap::new),
+            dex_pool_price: None,
+            last_suppression: None,
+            tick_size: None,
+            last_spread: None,
+        }
+    }
+
+    /*
+        Suppresses opportunities within the post-reconnect grace of the time stored by the Binance feed on every reconnection
+    */
+    pub fn with_last_reconnect(mut self, last_reconnect: Arc<AtomicU64>) -> Self {
+        self.last_reconnect = Some(last_reconnect);
+        self
+    }
+
+    /*
+        Blends the latest price of the on-chain pool stored by the pool price updater into the Pyth price, once it is known
+    */
+    pub fn with_dex_pool_price(mut self, dex_pool_price: Arc<RwLock<Option<Decimal>>>) -> Self {
+        self.dex_pool_price = Some(dex_pool_price);
+        self
+    }
+
+    /*
+        Measures the minimum edge in ticks of the given size, without it the minimum edge is not applied
+    */
+    pub fn with_tick_size(mut self, tick_size: Decimal) -> Self {
+        self.tick_size = Some(tick_size);
+        self
+    }
+
+    /*
+        Counts opportunities suppressed by the finder in the stats
+    */
+    pub fn with_stats(mut self, stats: Arc<std::sync::Mutex<Stats>>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /*
+        Aggregates every evaluated tick, along with whether an opportunity was found at it, into the profit bars
+    */
+    pub fn with_profit_bars(mut self, profit_bars: ProfitBars) -> Self {
+        self.profit_bars = Some(profit_bars);
+        self
+    }
+
+    /*
+        Reports every evaluated tick at which no opportunity is returned, found or suppressed, as a no-op event
+    */
+    pub fn with_noops(mut self, noops: NoopReporter) -> Self {
+        self.noops = Some(noops);
+        self
+    }
+
+    /*
+        Sends an event whenever an open opportunity closes, with the reason it closed for
+    */
+    pub fn with_closes(mut self, closes: CloseReporter) -> Self {
+        self.closes = Some(closes);
+        self
+    }
+
+    /*
+        Reads the time from the clock instead of the system, so time-based suppressions can be tested deterministically
+    */
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /*
+        Returns the state deduplicating opportunities, which can be restored by a finder of the next run
+    */
+    pub fn dedup_state(&self) -> FinderDedupState {
+        let now = self.clock.unix_millis();
+        let instant_now = self.clock.now();
+
+        FinderDedupState {
+            saved_at: now,
+            last_found: self.last_found,
+            found_in_buckets: self
+                .last_found_in_bucket
+                .iter()
+                .map(|((direction, bucket), found_at)| BucketFingerprint {
+                    direction: *direction,
+                    bucket: *bucket,
+                    found_at: now
+                        .saturating_sub(instant_now.duration_since(*found_at).as_millis() as u64),
+                })
+                .collect(),
+        }
+    }
+
+    /*
+        Restores the state deduplicating opportunities saved by a finder of the previous run
+        Parts of the state older than ttl are dropped, so only recently found opportunities are suppressed
+    */
+    pub fn restore_dedup_state(&mut self, dedup_state: &FinderDedupState, ttl: Duration) {
+        let now = self.clock.unix_millis();
+        let instant_now = self.clock.now();
+        let age = |timestamp: u64| Duration::from_millis(now.saturating_sub(timestamp));
+
+        if age(dedup_state.saved_at) < ttl {
+            self.last_found = dedup_state.last_found;
+        }
+        for fingerprint in &dedup_state.found_in_buckets {
+            let found_age = age(fingerprint.found_at);
+            if found_age >= ttl {
+                continue;
+            }
+            if let Some(found_at) = instant_now.checked_sub(found_age) {
+                self.last_found_in_bucket
+                    .insert((fingerprint.direction, fingerprint.bucket), found_at);
+            }
+        }
     }
 
     /*
@@ -26,6 +446,56 @@ impl ArbitrageFinder {
         latest_pyth_price: Arc<RwLock<Option<Price>>>,
         latest_binance_ticker_data: Arc<RwLock<Option<BookTickerData>>>,
         binance_fee: Decimal,
+    ) -> Option<ArbitrageOpportunity> {
+        self.evaluate_tick(latest_pyth_price, latest_binance_ticker_data, binance_fee)
+            .await
+            .into_option()
+    }
+
+    /*
+        Compares Binance and Pyth prices like find_opportunity, telling apart why no opportunity is reported
+    */
+    pub async fn evaluate_tick(
+        &mut self,
+        latest_pyth_price: Arc<RwLock<Option<Price>>>,
+        latest_binance_ticker_data: Arc<RwLock<Option<BookTickerData>>>,
+        binance_fee: Decimal,
+    ) -> EvalOutcome {
+        let previous_inputs = self.last_inputs.take();
+        self.last_suppression = None;
+        self.last_spread = None;
+        let maybe_opportunity = self
+            .search_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                binance_fee,
+                previous_inputs,
+            )
+            .await;
+
+        if let (Some(profit_bars), Some(raw_inputs)) = (&mut self.profit_bars, &self.last_inputs) {
+            profit_bars.record(raw_inputs, maybe_opportunity.is_some());
+        }
+        if let (Some(noops), Some(raw_inputs), None) =
+            (&mut self.noops, &self.last_inputs, &maybe_opportunity)
+        {
+            noops.record_at(self.clock.unix_millis(), raw_inputs);
+        }
+
+        match (maybe_opportunity, self.last_suppression, &self.last_inputs) {
+            (Some(opportunity), _, _) => EvalOutcome::Opportunity(opportunity),
+            (None, Some(suppression), _) => EvalOutcome::Filtered(suppression),
+            (None, None, None) => EvalOutcome::NoData,
+            (None, None, Some(_)) => EvalOutcome::NoOpportunity,
+        }
+    }
+
+    async fn search_opportunity(
+        &mut self,
+        latest_pyth_price: Arc<RwLock<Option<Price>>>,
+        latest_binance_ticker_data: Arc<RwLock<Option<BookTickerData>>>,
+        binance_fee: Decimal,
+        previous_inputs: Option<RawInputs>,
     ) -> Option<ArbitrageOpportunity> {
         let (latest_pyth_price_read, latest_binance_ticker_data_read) =
             tokio::join!(latest_pyth_price.read(), latest_binance_ticker_data.read());
@@ -38,188 +508,2989 @@ impl ArbitrageFinder {
         drop(latest_pyth_price_read);
         let binance_ticker_data = (*latest_binance_ticker_data_read).clone().unwrap();
         drop(latest_binance_ticker_data_read);
+        let inputs = RawInputs {
+            pyth: pyth_price,
+            binance: binance_ticker_data.clone(),
+        };
+        self.has_new_inputs = previous_inputs.as_ref() != Some(&inputs);
+        self.last_inputs = Some(inputs);
+
+        let dex_pool_price = match &self.dex_pool_price {
+            Some(dex_pool_price) => *dex_pool_price.read().await,
+            None => None,
+        };
 
+        let pyth_price = self.reference_pyth_price(pyth_price);
+        let pyth_price = self.blend_dex_pool_price(pyth_price, dex_pool_price)?;
         let (pyth_confident_95_price_higher, pyth_confident_95_price_lower) =
-            self.calculate_pyth_confident_95_price(pyth_price);
+            self.calculate_pyth_confident_95_price(pyth_price)?;
+        let (sell_reference_price, buy_reference_price) = self.settings.strategy.reference_prices(
+            pyth_confident_95_price_higher,
+            pyth_confident_95_price_lower,
+        )?;
+
+        let binance_best_bid_price = parse_decimal(&binance_ticker_data.b)?;
+        let binance_best_ask_price = parse_decimal(&binance_ticker_data.a)?;
+        self.last_spread = CurrentSpread::new(
+            binance_best_bid_price,
+            binance_best_ask_price,
+            pyth_confident_95_price_lower,
+            pyth_confident_95_price_higher,
+        );
+        // With mid basis, the mid is both compared and traded at, regardless of the execution price model
+        let binance_mid_price = match self.settings.comparison_basis {
+            ComparisonBasis::Crossing => None,
+            ComparisonBasis::Mid => Some(
+                binance_best_bid_price
+                    .checked_add(binance_best_ask_price)?
+                    .checked_div(Decimal::TWO)?,
+            ),
+        };
+
+        let (sell_margin, buy_margin) = self.hysteresis_margins(pyth_price)?;
+
+        let mut candidates = Vec::with_capacity(2);
 
         // Search for SellBinanceBuyDex opportunity
-        let binance_best_bid_price = Decimal::from_str(&binance_ticker_data.b).unwrap();
-        if binance_best_bid_price.gt(&pyth_confident_95_price_higher) {
-            let quantity = Decimal::from_str(&binance_ticker_data.B).unwrap();
-            return self.calculate_arbitrage_opportunity(
-                binance_best_bid_price,
-                pyth_confident_95_price_higher,
-                binance_fee,
+        if self.is_above(
+            binance_mid_price.unwrap_or(binance_best_bid_price),
+            sell_reference_price.checked_add(sell_margin)?,
+        ) {
+            let binance_quantity = parse_decimal(&binance_ticker_data.B)?;
+            let quantity = self.filled_quantity(
+                self.settings
+                    .quantity_strategy
+                    .quantity(binance_quantity, parse_decimal(&binance_ticker_data.A)?),
+                binance_quantity,
+            )?;
+            let binance_execution_price = match binance_mid_price {
+                Some(binance_mid_price) => binance_mid_price,
+                None => self
+                    .settings
+                    .execution_price_model
+                    .price(binance_best_bid_price, binance_best_ask_price)?,
+            };
+            candidates.push((
+                binance_execution_price,
+                sell_reference_price,
+                pyth_confident_95_price_lower,
                 quantity,
                 ArbitrageDirection::SellBinanceBuyDex,
-            );
+            ));
         }
 
         // Search for BuyBinanceSellDex opportunity
-        let binance_best_ask_price = Decimal::from_str(&binance_ticker_data.a).unwrap();
-        if binance_best_ask_price.lt(&pyth_confident_95_price_lower) {
-            let quantity = Decimal::from_str(&binance_ticker_data.A).unwrap();
+        if self.is_below(
+            binance_mid_price.unwrap_or(binance_best_ask_price),
+            buy_reference_price.checked_sub(buy_margin)?,
+        ) {
+            let binance_quantity = parse_decimal(&binance_ticker_data.A)?;
+            let quantity = self.filled_quantity(
+                self.settings
+                    .quantity_strategy
+                    .quantity(binance_quantity, parse_decimal(&binance_ticker_data.B)?),
+                binance_quantity,
+            )?;
+            let binance_execution_price = match binance_mid_price {
+                Some(binance_mid_price) => binance_mid_price,
+                None => self
+                    .settings
+                    .execution_price_model
+                    .price(binance_best_ask_price, binance_best_bid_price)?,
+            };
+            candidates.push((
+                binance_execution_price,
+                buy_reference_price,
+                pyth_confident_95_price_higher,
+                quantity,
+                ArbitrageDirection::BuyBinanceSellDex,
+            ));
+        }
+
+        let candidate = match candidates.as_slice() {
+            [] => None,
+            [candidate] => Some(*candidate),
+            [sell, buy] => match self.settings.tie_break {
+                TieBreak::Reject => return None, // the tick is not trusted in either direction
+                TieBreak::SellFirst => Some(*sell),
+                TieBreak::BuyFirst => Some(*buy),
+                TieBreak::MaxProfit => {
+                    let estimated_profit = |candidate: &Candidate| {
+                        let (binance_price, pyth_price, opposite_pyth_price, quantity, direction) =
+                            *candidate;
+                        self.build_opportunity(
+                            binance_price,
+                            pyth_price,
+                            opposite_pyth_price,
+                            binance_fee,
+                            quantity,
+                            direction,
+                        )
+                        .map(|opportunity| opportunity.estimated_profit)
+                    };
+                    // Sell side wins a tie, as without the policy
+                    if estimated_profit(buy) > estimated_profit(sell) {
+                        Some(*buy)
+                    } else {
+                        Some(*sell)
+                    }
+                }
+            },
+            _ => unreachable!(), // at most one candidate per direction
+        };
+        if let Some((binance_price, pyth_price, opposite_pyth_price, quantity, direction)) =
+            candidate
+        {
             return self.calculate_arbitrage_opportunity(
-                binance_best_ask_price,
-                pyth_confident_95_price_lower,
+                binance_price,
+                pyth_price,
+                opposite_pyth_price,
                 binance_fee,
                 quantity,
-                ArbitrageDirection::BuyBinanceSellDex,
+                direction,
             );
         }
 
+        self.persisting_since = None; // the condition lapsed
+        self.track_lifetime(None);
         None
     }
 
     /*
-        Calculates probable (95%) price using Pyth price and confidence feed and Laplace distribution
-        https://docs.pyth.network/documentation/solana-price-feeds/best-practices#confidence-intervals
+        Returns snapshots of the Pyth price and Binance ticker data used by the last call of find_opportunity
     */
-    fn calculate_pyth_confident_95_price(&self, pyth_price: Price) -> (Decimal, Decimal) {
-        let exponential = pyth_price.expo.abs() as u32;
-        let price = Decimal::new(pyth_price.price, exponential);
-        let confidence = Decimal::new(pyth_price.conf.try_into().unwrap(), exponential);
-        let confidence_95 = confidence.checked_mul(Decimal::new(212, 2)).unwrap();
+    pub fn last_inputs(&self) -> Option<&RawInputs> {
+        self.last_inputs.as_ref()
+    }
 
-        (
-            price.checked_add(confidence_95).unwrap(),
-            price.checked_sub(confidence_95).unwrap(),
-        )
+    /*
+        Returns Binance mid and the probable price range compared by the last call of find_opportunity, if its prices were valid
+    */
+    pub fn current_spread(&self) -> Option<CurrentSpread> {
+        self.last_spread
     }
 
     /*
-        Calculates estimated profit and returns Option<ArbitrageOpportunity> instance depending on the calculation
+        Compares Pyth price with every level of the Binance order book to find all the arbitrage opportunities
+        Each level beyond the probable price range yields its own opportunity, best levels first
+        Returns an empty vector if the found opportunities are the same as the previously found ones
     */
-    fn calculate_arbitrage_opportunity(
+    pub async fn find_opportunities(
         &mut self,
-        binance_price: Decimal,
-        pyth_price: Decimal,
+        latest_pyth_price: Arc<RwLock<Option<Price>>>,
+        latest_binance_depth_data: Arc<RwLock<Option<DepthData>>>,
         binance_fee: Decimal,
-        quantity: Decimal,
-        arbitrage_direction: ArbitrageDirection,
-    ) -> Option<ArbitrageOpportunity> {
-        let estimated_profit = (binance_price - pyth_price)
-            .abs()
-            .checked_mul(quantity)
-            .unwrap()
-            - quantity
-                .checked_mul(binance_price)
-                .unwrap()
-                .checked_mul(binance_fee)
-                .unwrap();
+    ) -> Vec<ArbitrageOpportunity> {
+        let (latest_pyth_price_read, latest_binance_depth_data_read) =
+            tokio::join!(latest_pyth_price.read(), latest_binance_depth_data.read());
 
-        if estimated_profit.le(&Decimal::ZERO) {
-            return None;
+        if latest_pyth_price_read.is_none() || latest_binance_depth_data_read.is_none() {
+            return Vec::new();
         }
 
-        let opportunity = ArbitrageOpportunity {
-            direction: arbitrage_direction,
-            quantity: quantity.normalize(),
-            estimated_profit: estimated_profit.normalize().round_dp(8),
-            binance_price: binance_price.normalize(),
-            pyth_price: pyth_price.normalize(),
+        let pyth_price = (*latest_pyth_price_read).unwrap();
+        drop(latest_pyth_price_read);
+        let binance_depth_data = (*latest_binance_depth_data_read).clone().unwrap();
+        drop(latest_binance_depth_data_read);
+
+        let pyth_price = self.reference_pyth_price(pyth_price);
+        let Some((pyth_confident_95_price_higher, pyth_confident_95_price_lower)) =
+            self.calculate_pyth_confident_95_price(pyth_price)
+        else {
+            return Vec::new();
         };
+        let Some((sell_reference_price, buy_reference_price)) =
+            self.settings.strategy.reference_prices(
+                pyth_confident_95_price_higher,
+                pyth_confident_95_price_lower,
+            )
+        else {
+            return Vec::new();
+        };
+        let mut opportunities = Vec::new();
 
-        if let Some(last_opportunity) = self.last_found {
-            if last_opportunity == opportunity {
-                return None;
+        // Search for SellBinanceBuyDex opportunities, bids are sorted from the highest
+        for [price, quantity] in &binance_depth_data.bids {
+            let (Some(binance_bid_price), Some(quantity)) =
+                (parse_decimal(price), parse_decimal(quantity))
+            else {
+                continue; // malformed level
+            };
+            if !self.is_above(binance_bid_price, sell_reference_price) {
+                break;
             }
+            opportunities.extend(self.build_opportunity(
+                binance_bid_price,
+                sell_reference_price,
+                pyth_confident_95_price_lower,
+                binance_fee,
+                quantity,
+                ArbitrageDirection::SellBinanceBuyDex,
+            ));
         }
-        self.last_found = Some(opportunity);
 
-        return self.last_found;
-    }
-}
+        // Search for BuyBinanceSellDex opportunities, asks are sorted from the lowest
+        for [price, quantity] in &binance_depth_data.asks {
+            let (Some(binance_ask_price), Some(quantity)) =
+                (parse_decimal(price), parse_decimal(quantity))
+            else {
+                continue; // malformed level
+            };
+            if !self.is_below(binance_ask_price, buy_reference_price) {
+                break;
+            }
+            opportunities.extend(self.build_opportunity(
+                binance_ask_price,
+                buy_reference_price,
+                pyth_confident_95_price_higher,
+                binance_fee,
+                quantity,
+                ArbitrageDirection::BuyBinanceSellDex,
+            ));
+        }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct ArbitrageOpportunity {
-    pub direction: ArbitrageDirection,
-    pub quantity: Decimal,
-    pub estimated_profit: Decimal,
-    pub binance_price: Decimal,
-    pub pyth_price: Decimal,
-}
+        if opportunities == self.last_found_levels {
+            return Vec::new();
+        }
+        self.last_found_levels = opportunities.clone();
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ArbitrageDirection {
-    SellBinanceBuyDex,
-    BuyBinanceSellDex,
-}
+        opportunities
+    }
 
-#[cfg(test)]
-mod tests {
-    use std::{str::FromStr, sync::Arc};
+    /*
+        Returns quantity filled on both legs given the requested one and the one available on Binance, None on overflow
+        With full-min, both legs fill the smaller of the requested and DEX quantities
+        With proportional, each leg covers min(1, available / requested) of the requested quantity and both are sized
+        to the smaller of the two fractions, so neither leg is sized above its available quantity
+    */
+    fn filled_quantity(
+        &self,
+        requested_quantity: Decimal,
+        binance_quantity: Decimal,
+    ) -> Option<Decimal> {
+        match self.settings.fill_model {
+            FillModel::FullMin => Some(match self.settings.dex_quantity {
+                Some(dex_quantity) => requested_quantity.min(dex_quantity),
+                None => requested_quantity,
+            }),
+            FillModel::Proportional => {
+                if requested_quantity.is_zero() {
+                    return Some(requested_quantity);
+                }
+                let fraction = |available: Decimal| {
+                    Some(available.checked_div(requested_quantity)?.min(Decimal::ONE))
+                };
+                let mut common_fraction = fraction(binance_quantity)?;
+                if let Some(dex_quantity) = self.settings.dex_quantity {
+                    common_fraction = common_fraction.min(fraction(dex_quantity)?);
+                }
 
-    use pyth_sdk_solana::Price;
-    use rust_decimal::Decimal;
-    use tokio::sync::RwLock;
+                requested_quantity.checked_mul(common_fraction)
+            }
+        }
+    }
 
-    use crate::structs::cex::binance::BookTickerData;
+    /*
+        Returns margins beyond the sell and buy reference prices Binance price has to reach, zero without hysteresis
+        Sigma is the confidence of the compared Pyth price, regardless of the band, floor or aging modifying the probable price range,
+        the close margin applies to the direction of the currently open opportunity, None on overflow
+    */
+    fn hysteresis_margins(&self, pyth_price: Price) -> Option<(Decimal, Decimal)> {
+        let Some(hysteresis) = self.settings.hysteresis else {
+            return Some((Decimal::ZERO, Decimal::ZERO));
+        };
+        let sigma = self.pyth_sigma(pyth_price)?;
+        let is_open = |direction| {
+            self.open_since
+                .is_some_and(|(open_direction, _)| open_direction == direction)
+        };
+
+        Some((
+            hysteresis.margin(sigma, is_open(ArbitrageDirection::SellBinanceBuyDex))?,
+            hysteresis.margin(sigma, is_open(ArbitrageDirection::BuyBinanceSellDex))?,
+        ))
+    }
+
+    /*
+        Returns Pyth confidence in units of the compared price, i.e. confidence / price^2 once inverted, None on overflow
+    */
+    fn pyth_sigma(&self, pyth_price: Price) -> Option<Decimal> {
+        let scale = pyth_price.expo.unsigned_abs();
+        let confidence = Decimal::try_new(pyth_price.conf.try_into().ok()?, scale).ok()?;
+        if !self.settings.invert_pyth {
+            return Some(confidence);
+        }
+        let price = Decimal::try_new(pyth_price.price, scale).ok()?;
+
+        confidence.checked_div(price.checked_mul(price)?)
+    }
+
+    /*
+        Moves the Pyth price towards the pool price by the configured weight, keeping its confidence,
+        so the probable price range is centered on the price the DEX leg is executed at
+        The blend is rounded to the exponent of the Pyth price, returns None on overflow
+    */
+    fn blend_dex_pool_price(
+        &self,
+        pyth_price: Price,
+        dex_pool_price: Option<Decimal>,
+    ) -> Option<Price> {
+        let Some(dex_pool_price) = dex_pool_price else {
+            return Some(pyth_price);
+        };
+        let unit = Decimal::try_new(1, pyth_price.expo.unsigned_abs()).ok()?;
+        let price = Decimal::try_new(pyth_price.price, pyth_price.expo.unsigned_abs()).ok()?;
+        let blended = price.checked_add(
+            dex_pool_price
+                .checked_sub(price)?
+                .checked_mul(self.settings.dex_price_weight)?,
+        )?;
+
+        Some(Price {
+            price: blended.checked_div(unit)?.round().to_i64()?,
+            ..pyth_price
+        })
+    }
+
+    /*
+        Returns the Pyth price the probable price range is computed from, the time-weighted average over the window if configured
+    */
+    fn reference_pyth_price(&mut self, pyth_price: Price) -> Price {
+        let now = self.clock.unix_millis();
+        match &mut self.pyth_twap {
+            Some(pyth_twap) => pyth_twap.record(pyth_price, now),
+            None => pyth_price,
+        }
+    }
+
+    /*
+        Calculates probable (95% by default) price using Pyth price and confidence feed and Laplace distribution
+        https://docs.pyth.network/documentation/solana-price-feeds/best-practices#confidence-intervals
+        If a fixed band is configured, the confidence feed is ignored and price * (1 +- band) is returned instead
+        Returns None if the Pyth price cannot be represented as Decimal, e.g. its exponent exceeds the maximum scale,
+        or if the inverted range is configured and its lower bound is not positive
+    */
+    fn calculate_pyth_confident_95_price(&self, pyth_price: Price) -> Option<(Decimal, Decimal)> {
+        let maybe_range = self
+            .aged_settings(pyth_price)
+            .and_then(|settings| Self::calculate_pyth_range(pyth_price, settings));
+        if maybe_range.is_none() {
+            eprintln!(
+                "Skipping Pyth price {} with confidence {} and exponent {}, it cannot be represented as Decimal or inverted",
+                pyth_price.price, pyth_price.conf, pyth_price.expo
+            );
+        }
+        maybe_range
+    }
+
+    /*
+        Returns the settings with confidence factor grown by the configured rate per second since the Pyth price was published
+        Prices published in the future are treated as fresh, returns None on overflow
+    */
+    fn aged_settings(&self, pyth_price: Price) -> Option<FinderSettings> {
+        let mut settings = self.settings;
+        if let Some(growth) = settings.confidence_age_growth {
+            let age_millis = (self.clock.unix_millis() as i64)
+                .saturating_sub(pyth_price.publish_time.saturating_mul(1000))
+                .max(0);
+            let growth = growth.precise_mul(Decimal::new(age_millis, 3))?;
+            settings.confidence_factor = settings
+                .confidence_factor
+                .precise_mul(Decimal::ONE.checked_add(growth)?)?;
+        }
+
+        Some(settings)
+    }
+
+    /*
+        Inverts the range bound by bound, as 1 / (price -+ confidence) is exact for any confidence factor
+        while propagating the confidence itself, i.e. confidence / price^2, would make the range symmetric and too narrow above
+    */
+    fn calculate_pyth_range(
+        pyth_price: Price,
+        settings: FinderSettings,
+    ) -> Option<(Decimal, Decimal)> {
+        let (higher, lower) = Self::calculate_published_pyth_range(pyth_price, settings)?;
+        if !settings.invert_pyth {
+            return Some((higher, lower));
+        }
+        if lower <= Decimal::ZERO {
+            return None;
+        }
+
+        Some((
+            Decimal::ONE.checked_div(lower)?,
+            Decimal::ONE.checked_div(higher)?,
+        ))
+    }
+
+    fn calculate_published_pyth_range(
+        pyth_price: Price,
+        settings: FinderSettings,
+    ) -> Option<(Decimal, Decimal)> {
+        if let Some(pyth_band_bps) = settings.pyth_band_bps {
+            let price = Decimal::try_new(pyth_price.price, pyth_price.expo.unsigned_abs()).ok()?;
+            let band = price.precise_mul(Decimal::new(pyth_band_bps.into(), 4))?;
+            return Some((price.checked_add(band)?, price.checked_sub(band)?));
+        }
+
+        let pyth_price = match settings.min_confidence {
+            Some(min_confidence) => min_confidence.apply(pyth_price)?,
+            None => pyth_price,
+        };
+        let (lower, higher) =
+            calculate_pyth_confidence_bounds(pyth_price, settings.confidence_factor)?;
+        Some((higher, lower))
+    }
+
+    /*
+        Calculates estimated profit and returns Option<ArbitrageOpportunity> instance depending on the calculation
+        pyth_price is the crossed reference price of the strategy and opposite_pyth_price is the opposite bound of the probable price range
+    */
+    fn calculate_arbitrage_opportunity(
+        &mut self,
+        binance_price: Decimal,
+        pyth_price: Decimal,
+        opposite_pyth_price: Decimal,
+        binance_fee: Decimal,
+        quantity: Decimal,
+        arbitrage_direction: ArbitrageDirection,
+    ) -> Option<ArbitrageOpportunity> {
+        let Some(opportunity) = self.build_opportunity(
+            binance_price,
+            pyth_price,
+            opposite_pyth_price,
+            binance_fee,
+            quantity,
+            arbitrage_direction,
+        ) else {
+            self.persisting_since = None; // the condition lapsed
+            self.track_lifetime(None);
+            return None;
+        };
+        self.track_lifetime(Some(opportunity.direction));
+
+        // Suppressed before any state is updated, so it neither starts persistence nor deduplicates the following ones
+        if self.settings.zero_profit == ZeroProfitPolicy::Suppress
+            && opportunity.estimated_profit.is_zero()
+            && !opportunity.profit_breakdown.net().is_zero()
+        {
+            return self.suppress(Suppression::ZeroProfit);
+        }
+
+        if self.is_skewed() {
+            return self.suppress(Suppression::Skew);
+        }
+
+        if self.is_within_reconnect_grace() {
+            return self.suppress(Suppression::Reconnect);
+        }
+
+        if !self.has_persisted(opportunity.direction) {
+            return self.suppress(Suppression::Persistence);
+        }
+
+        if self.is_cooling_down(opportunity.direction) {
+            return self.suppress(Suppression::Cooldown);
+        }
+
+        if !self.has_improved(&opportunity) {
+            return self.suppress(Suppression::NotImproved);
+        }
+
+        if !self.has_changed_direction(opportunity.direction) {
+            return self.suppress(Suppression::SameDirection);
+        }
+
+        let maybe_opportunity = match self.settings.dedup_bucket {
+            Some(dedup_bucket) => self.deduplicate_by_bucket(opportunity, dedup_bucket),
+            None => self.deduplicate(opportunity),
+        };
+        if maybe_opportunity.is_none() {
+            return self.suppress(Suppression::Duplicate);
+        }
+        if !self.found_initial {
+            self.found_initial = true;
+            if !self.settings.emit_initial {
+                return self.suppress(Suppression::Initial); // the gap may have existed before startup
+            }
+        }
+        if let Some(opportunity) = &maybe_opportunity {
+            self.last_reported = Some((opportunity.direction, self.clock.now()));
+            self.last_reported_profit = Some(opportunity.estimated_profit);
+        }
+
+        maybe_opportunity
+    }
+
+    /*
+        Returns whether the Binance price is beyond the upper bound, or equal to it with inclusive bounds
+    */
+    fn is_above(&self, binance_price: Decimal, bound: Decimal) -> bool {
+        if self.settings.inclusive_bounds {
+            binance_price.ge(&bound)
+        } else {
+            binance_price.gt(&bound)
+        }
+    }
+
+    /*
+        Returns whether the Binance price is beyond the lower bound, or equal to it with inclusive bounds
+    */
+    fn is_below(&self, binance_price: Decimal, bound: Decimal) -> bool {
+        if self.settings.inclusive_bounds {
+            binance_price.le(&bound)
+        } else {
+            binance_price.lt(&bound)
+        }
+    }
+
+    /*
+        Counts the suppression in the stats if any, returns None for the suppressed opportunity
+        Only a suppression at new snapshots of the feeds is counted, so re-evaluating unchanged data does not inflate the counters
+    */
+    fn suppress(&mut self, suppression: Suppression) -> Option<ArbitrageOpportunity> {
+        self.last_suppression = Some(suppression);
+        if let (Some(stats), true) = (&self.stats, self.has_new_inputs) {
+            stats.lock().unwrap().record_suppression(suppression);
+        }
+        None
+    }
+
+    /*
+        Opens an opportunity in the direction found at this tick unless it is already open, None meaning no opportunity
+        An open opportunity closes once none is found or one in the other direction is, its lifetime is recorded in the stats
+        Lifetimes are tracked regardless of suppressions, as they describe the market rather than what was reported
+        Once the Pyth price is too far in time from the Binance one, the open opportunity is invalidated instead,
+        and none is opened or closed until the prices are close in time again
+    */
+    fn track_lifetime(&mut self, direction: Option<ArbitrageDirection>) {
+        let now = self.clock.now();
+        if self.is_skewed() {
+            if let Some((open_direction, since)) = self.open_since.take() {
+                self.close(
+                    open_direction,
+                    now.duration_since(since),
+                    CloseReason::InvalidatedStale,
+                );
+                self.last_reported_profit = None;
+            }
+            return;
+        }
+
+        match (self.open_since, direction) {
+            (Some((open_direction, _)), Some(direction)) if open_direction == direction => {}
+            (maybe_open, direction) => {
+                if let Some((open_direction, since)) = maybe_open {
+                    let reason = match direction {
+                        Some(_) => CloseReason::Reversed,
+                        None => CloseReason::Converged,
+                    };
+                    self.close(open_direction, now.duration_since(since), reason);
+                }
+                self.open_since = direction.map(|direction| (direction, now));
+                self.last_reported_profit = None;
+            }
+        }
+    }
+
+    /*
+        Records the closed opportunity in the stats, its lifetime only if it was not invalidated, and sends its close event
+    */
+    fn close(&self, direction: ArbitrageDirection, lifetime: Duration, reason: CloseReason) {
+        if let Some(stats) = &self.stats {
+            let mut stats = stats.lock().unwrap();
+            match reason {
+                CloseReason::InvalidatedStale => stats.invalidated_stale += 1,
+                CloseReason::Converged | CloseReason::Reversed => stats.record_lifetime(lifetime),
+            }
+        }
+        if let Some(closes) = &self.closes {
+            closes.record_at(self.clock.unix_millis(), direction, lifetime, reason);
+        }
+    }
+
+    /*
+        Returns whether the Pyth price of the last search, shifted by the configured offset, is too far in time from the Binance one
+        Binance prices are streamed, so they are treated as current at the time of the search
+    */
+    fn is_skewed(&self) -> bool {
+        let (Some(max_feed_skew), Some(last_inputs)) =
+            (self.settings.max_feed_skew, &self.last_inputs)
+        else {
+            return false;
+        };
+        let pyth_time = last_inputs
+            .pyth
+            .publish_time
+            .saturating_mul(1000)
+            .saturating_add(self.settings.pyth_time_offset_ms);
+        let skew = (self.clock.unix_millis() as i64).abs_diff(pyth_time);
+
+        skew > max_feed_skew.as_millis() as u64 // doesn't overflow
+    }
+
+    /*
+        Returns whether the Binance feed reconnected within the post-reconnect grace, so its first ticks may reflect the gap
+    */
+    fn is_within_reconnect_grace(&self) -> bool {
+        let Some(last_reconnect) = &self.last_reconnect else {
+            return false;
+        };
+        match last_reconnect.load(Ordering::Relaxed) {
+            0 => false,
+            last_reconnect => {
+                self.clock.unix_millis().saturating_sub(last_reconnect)
+                    < self.settings.post_reconnect_grace.as_millis() as u64 // doesn't overflow
+            }
+        }
+    }
+
+    /*
+        Returns whether the opportunity in the direction has been present for the minimum persistence and was not reported yet
+        The first opportunity of a direction starts the timer, which is reset once no opportunity is found
+    */
+    fn has_persisted(&mut self, direction: ArbitrageDirection) -> bool {
+        if self.settings.min_persistence.is_zero() {
+            return true;
+        }
+
+        let now = self.clock.now();
+        match &mut self.persisting_since {
+            Some((persisting_direction, since, reported)) if *persisting_direction == direction => {
+                if *reported || now.duration_since(*since) < self.settings.min_persistence {
+                    return false;
+                }
+                *reported = true;
+                true
+            }
+            _ => {
+                self.persisting_since = Some((direction, now, false));
+                false
+            }
+        }
+    }
+
+    /*
+        Returns whether the estimated profit exceeds the one last reported while the opportunity is open by the configured improvement
+        The first report of an opportunity after it opens is not held back
+    */
+    fn has_improved(&self, opportunity: &ArbitrageOpportunity) -> bool {
+        match (self.settings.reemit_improvement, self.last_reported_profit) {
+            (Some(reemit_improvement), Some(last_reported_profit)) => {
+                opportunity.estimated_profit - last_reported_profit >= reemit_improvement
+            }
+            _ => true,
+        }
+    }
+
+    /*
+        Returns whether an opportunity in the direction was reported within the cooldown
+        Reporting an opportunity in the other direction ends the cooldown immediately
+    */
+    fn is_cooling_down(&self, direction: ArbitrageDirection) -> bool {
+        match self.last_reported {
+            Some((last_direction, reported_at)) => {
+                last_direction == direction
+                    && self.clock.now().duration_since(reported_at) < self.settings.cooldown
+            }
+            None => false,
+        }
+    }
+
+    /*
+        Returns whether the direction differs from the one last reported, always true unless only direction changes are reported
+        The first opportunity since startup changes the direction
+    */
+    fn has_changed_direction(&self, direction: ArbitrageDirection) -> bool {
+        self.settings.alert_on == AlertOn::EveryTick
+            || self
+                .last_reported
+                .is_none_or(|(last_direction, _)| last_direction != direction)
+    }
+
+    /*
+        Suppresses the opportunity if it is equal to the previously found one
+    */
+    fn deduplicate(&mut self, opportunity: ArbitrageOpportunity) -> Option<ArbitrageOpportunity> {
+        if let Some(last_opportunity) = self.last_found {
+            if last_opportunity == opportunity {
+                return None;
+            }
+        }
+        self.last_found = Some(opportunity);
+
+        self.last_found
+    }
+
+    /*
+        Suppresses the opportunity if another one in the same direction and Binance price bucket was found within the window
+        At most max_dedup_buckets are remembered, the oldest one is forgotten to make room for a new one
+        If the bucket of the price cannot be computed, e.g. of a zero size, the opportunity is not deduplicated
+    */
+    fn deduplicate_by_bucket(
+        &mut self,
+        opportunity: ArbitrageOpportunity,
+        dedup_bucket: Decimal,
+    ) -> Option<ArbitrageOpportunity> {
+        let Some(bucket) = opportunity.binance_price.checked_div(dedup_bucket) else {
+            return Some(opportunity);
+        };
+        let bucket = bucket.floor();
+        let now = self.clock.now();
+        let window = self.settings.dedup_window;
+        self.last_found_in_bucket
+            .retain(|_, found_at| now.duration_since(*found_at) < window);
+
+        if self
+            .last_found_in_bucket
+            .contains_key(&(opportunity.direction, bucket))
+        {
+            return None;
+        }
+        if self.last_found_in_bucket.len() >= self.settings.max_dedup_buckets {
+            let oldest = self
+                .last_found_in_bucket
+                .iter()
+                .min_by_key(|(_, found_at)| **found_at)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.last_found_in_bucket.remove(&oldest);
+            }
+        }
+        self.last_found_in_bucket
+            .insert((opportunity.direction, bucket), now);
+
+        Some(opportunity)
+    }
+
+    /*
+        Builds ArbitrageOpportunity instance if the estimated profit is positive, regardless of previously found ones
+        Estimated profit is the worst case within the probable price range, the best case is reached at the opposite bound
+        With expected value strategy, estimated profit is the expected one and has to exceed the threshold as well
+        With minimum edge in ticks, Binance price has to be at least that many ticks beyond the crossed bound
+        Skips the opportunity if any of the calculations overflows Decimal
+    */
+    fn build_opportunity(
+        &self,
+        binance_price: Decimal,
+        pyth_price: Decimal,
+        opposite_pyth_price: Decimal,
+        binance_fee: Decimal,
+        quantity: Decimal,
+        arbitrage_direction: ArbitrageDirection,
+    ) -> Option<ArbitrageOpportunity> {
+        if let (Some(min_edge_ticks), Some(tick_size)) =
+            (self.settings.min_edge_ticks, self.tick_size)
+        {
+            let min_edge = tick_size.precise_mul(Decimal::from(min_edge_ticks))?;
+            if binance_price.checked_sub(pyth_price)?.abs().lt(&min_edge) {
+                return None;
+            }
+        }
+
+        let Some((profit_breakdown, profit_high)) = self.calculate_profits(
+            binance_price,
+            pyth_price,
+            opposite_pyth_price,
+            binance_fee,
+            quantity,
+        ) else {
+            eprintln!(
+                "Skipping {:?} opportunity at Binance price {} and quantity {}, its profit overflows",
+                arbitrage_direction, binance_price, quantity
+            );
+            return None;
+        };
+        let Some(profit_low) = self.calculate_profit_low(
+            binance_price,
+            pyth_price,
+            opposite_pyth_price,
+            binance_fee,
+            quantity,
+            arbitrage_direction,
+        ) else {
+            eprintln!(
+                "Skipping {:?} opportunity at Binance price {} and quantity {}, its low profit overflows",
+                arbitrage_direction, binance_price, quantity
+            );
+            return None;
+        };
+        let Some(required_capital) =
+            RequiredCapital::calculate(quantity, binance_price, pyth_price)
+        else {
+            eprintln!(
+                "Skipping {:?} opportunity at Binance price {} and quantity {}, its required capital overflows",
+                arbitrage_direction, binance_price, quantity
+            );
+            return None;
+        };
+        // Expected values over the fill of the Binance leg, the breakdown included so its net stays the estimated profit
+        let expected = |profit: Decimal| match self.settings.fill_probability {
+            Some(fill_probability) => profit
+                .precise_mul(fill_probability)
+                .map(|profit| profit.normalize()),
+            None => Some(profit),
+        };
+        let (
+            Some(gross_edge),
+            Some(binance_fee),
+            Some(dex_fee),
+            Some(profit_low),
+            Some(profit_high),
+        ) = (
+            expected(profit_breakdown.gross_edge),
+            expected(profit_breakdown.binance_fee),
+            expected(profit_breakdown.dex_fee),
+            expected(profit_low),
+            expected(profit_high),
+        )
+        else {
+            return None; // doesn't overflow, as the probability is at most 1
+        };
+        let profit_breakdown = ProfitBreakdown {
+            gross_edge,
+            binance_fee,
+            dex_fee,
+        };
+        let estimated_profit = profit_breakdown.net();
+
+        // Break-even is an opportunity only with inclusive bounds
+        if estimated_profit.lt(&Decimal::ZERO)
+            || (estimated_profit.is_zero() && !self.settings.inclusive_bounds)
+        {
+            return None;
+        }
+        if let Strategy::ExpectedValue(min_expected_profit) = self.settings.strategy {
+            if estimated_profit.le(&min_expected_profit) {
+                return None;
+            }
+        }
+
+        let round = |value: Decimal| {
+            value
+                .normalize()
+                .round_dp_with_strategy(8, self.settings.rounding_strategy)
+        };
+
+        let opportunity = ArbitrageOpportunity {
+            id: Uuid::new_v4(),
+            direction: arbitrage_direction,
+            quantity: quantity.normalize(),
+            estimated_profit: round(estimated_profit),
+            profit_low: round(profit_low),
+            profit_high: round(profit_high),
+            binance_price: binance_price.normalize(),
+            pyth_price: pyth_price.normalize(),
+            profit_breakdown,
+            required_capital,
+        };
+
+        Some(opportunity)
+    }
+
+    /*
+        Calculates profit if the DEX price is at the crossed bound of the probable price range, whatever price the strategy compares with
+        Under the ev strategy it is below the estimated profit, negative until Binance price crosses the bound, returns None on overflow
+    */
+    fn calculate_profit_low(
+        &self,
+        binance_price: Decimal,
+        pyth_price: Decimal,
+        opposite_pyth_price: Decimal,
+        binance_fee: Decimal,
+        quantity: Decimal,
+        arbitrage_direction: ArbitrageDirection,
+    ) -> Option<Decimal> {
+        // The ev strategy compares with the mid of the range, so the crossed bound mirrors the opposite one around it
+        let crossed_pyth_price = match self.settings.strategy {
+            Strategy::Bound => pyth_price,
+            Strategy::ExpectedValue(_) => pyth_price
+                .checked_mul(Decimal::TWO)?
+                .checked_sub(opposite_pyth_price)?,
+        };
+        let (at_crossed_bound, _) = self.calculate_profits(
+            binance_price,
+            crossed_pyth_price,
+            opposite_pyth_price,
+            binance_fee,
+            quantity,
+        )?;
+        let edge = match arbitrage_direction {
+            ArbitrageDirection::SellBinanceBuyDex => {
+                binance_price.checked_sub(crossed_pyth_price)?
+            }
+            ArbitrageDirection::BuyBinanceSellDex => {
+                crossed_pyth_price.checked_sub(binance_price)?
+            }
+        };
+
+        edge.precise_mul(quantity)?
+            .checked_sub(at_crossed_bound.binance_fee)?
+            .checked_sub(at_crossed_bound.dex_fee)
+    }
+
+    /*
+        Calculates profit breakdown at the crossed bound and profit at the opposite bound, returns None on overflow
+        With fees of both legs configured, they replace the Binance fee and the DEX leg pays its fee on the notional at the bound
+        Solana transaction fee is added to the DEX fee, converted at the Pyth price at the bound unless the price of SOL is configured
+    */
+    fn calculate_profits(
+        &self,
+        binance_price: Decimal,
+        pyth_price: Decimal,
+        opposite_pyth_price: Decimal,
+        binance_fee: Decimal,
+        quantity: Decimal,
+    ) -> Option<(ProfitBreakdown, Decimal)> {
+        let (binance_fee, dex_fee) = match self.settings.leg_fees {
+            Some(leg_fees) => (leg_fees.binance, leg_fees.dex),
+            None => (binance_fee, Decimal::ZERO),
+        };
+        let (gross_edge, binance_fee, profit_high) = calculate_profits(
+            binance_price,
+            pyth_price,
+            opposite_pyth_price,
+            binance_fee,
+            quantity,
+        )?;
+        let transaction_fee = match self.settings.solana_fee {
+            Some(solana_fee) => solana_fee.in_quote(solana_fee.sol_price.unwrap_or(pyth_price))?,
+            None => Decimal::ZERO,
+        };
+        let dex_fee_at = |price: Decimal| {
+            quantity
+                .precise_mul(price)?
+                .precise_mul(dex_fee)?
+                .checked_add(transaction_fee)
+        };
+        let profit_breakdown = ProfitBreakdown {
+            gross_edge: gross_edge.normalize(),
+            binance_fee: binance_fee.normalize(),
+            dex_fee: dex_fee_at(pyth_price)?.normalize(),
+        };
+
+        Some((
+            profit_breakdown,
+            profit_high.checked_sub(dex_fee_at(opposite_pyth_price)?)?,
+        ))
+    }
+}
+
+/*
+    Calculates gross edge and Binance fee at the crossed bound of the probable price range and profit at the opposite one
+    Returns None on overflow
+*/
+fn calculate_profits<N: Numeric>(
+    binance_price: N,
+    pyth_price: N,
+    opposite_pyth_price: N,
+    binance_fee: N,
+    quantity: N,
+) -> Option<(N, N, N)> {
+    let gross_edge = binance_price
+        .checked_sub(pyth_price)?
+        .abs()
+        .checked_mul(quantity)?;
+    let binance_fee = quantity
+        .checked_mul(binance_price)?
+        .checked_mul(binance_fee)?;
+    let profit_high = binance_price
+        .checked_sub(opposite_pyth_price)?
+        .abs()
+        .checked_mul(quantity)?
+        .checked_sub(binance_fee)?;
+
+    Some((gross_edge, binance_fee, profit_high))
+}
+
+/*
+    Calculates bounds of the probable price range like calculate_pyth_confidence_bounds in any numeric type
+*/
+fn calculate_confidence_bounds<N: Numeric>(
+    pyth_price: Price,
+    confidence_factor: N,
+) -> Option<(N, N)> {
+    let price = N::from_mantissa(pyth_price.price, pyth_price.expo)?;
+    let confidence = N::from_mantissa(pyth_price.conf.try_into().ok()?, pyth_price.expo)?;
+    let scaled_confidence = confidence.checked_mul(confidence_factor)?;
+
+    Some((
+        price.checked_sub(scaled_confidence)?,
+        price.checked_add(scaled_confidence)?,
+    ))
+}
+
+/*
+    Struct representing an opportunity found by evaluate
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Evaluation<N> {
+    pub direction: ArbitrageDirection,
+    pub quantity: N,
+    pub estimated_profit: N,
+    pub profit_high: N,
+    pub binance_price: N,
+    pub pyth_price: N, // the crossed bound of the probable price range
+}
+
+/*
+    Evaluates the Binance top of the book against the Pyth price in the numeric type, without any state
+    Finds the same opportunities, with the same profits before rounding, as find_opportunity with default settings
+    apart from the confidence factor, and without deduplication
+    Evaluation in f64 (behind f64 feature) skips Decimal arithmetic, but its results are off by rounding errors
+    of about 1e-15 relative to the values, so prices exactly at a bound may be classified differently
+*/
+pub fn evaluate<N: Numeric>(
+    pyth_price: Price,
+    binance_ticker_data: &BookTickerData,
+    confidence_factor: N,
+    binance_fee: N,
+) -> Option<Evaluation<N>> {
+    let (lower, higher) = calculate_confidence_bounds(pyth_price, confidence_factor)?;
+    let bid = N::parse(&binance_ticker_data.b)?;
+    let ask = N::parse(&binance_ticker_data.a)?;
+
+    let (direction, binance_price, quantity, pyth_price, opposite_pyth_price) = if bid > higher {
+        let quantity = N::parse(&binance_ticker_data.B)?;
+        (
+            ArbitrageDirection::SellBinanceBuyDex,
+            bid,
+            quantity,
+            higher,
+            lower,
+        )
+    } else if ask < lower {
+        let quantity = N::parse(&binance_ticker_data.A)?;
+        (
+            ArbitrageDirection::BuyBinanceSellDex,
+            ask,
+            quantity,
+            lower,
+            higher,
+        )
+    } else {
+        return None;
+    };
+
+    let (gross_edge, binance_fee, profit_high) = calculate_profits(
+        binance_price,
+        pyth_price,
+        opposite_pyth_price,
+        binance_fee,
+        quantity,
+    )?;
+    let estimated_profit = gross_edge.checked_sub(binance_fee)?;
+    if estimated_profit <= N::ZERO {
+        return None;
+    }
+
+    Some(Evaluation {
+        direction,
+        quantity,
+        estimated_profit,
+        profit_high,
+        binance_price,
+        pyth_price,
+    })
+}
+
+/**
+    Calculates bounds of the probable price range using Pyth price and confidence and Laplace distribution
+    The confidence factor scales the confidence, e.g. 2.12 for 95% probability
+    Returns (lower, upper), None if the price cannot be represented as Decimal, e.g. its exponent exceeds the maximum scale
+
+    ```
+    use keyrock_task::structs::arbitrage_finder::calculate_pyth_confidence_bounds;
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+
+    let price = Price {
+        price: 4856126854,
+        conf: 612455,
+        expo: -5,
+        ..Default::default()
+    };
+    let (lower, upper) = calculate_pyth_confidence_bounds(price, Decimal::new(212, 2)).unwrap();
+    assert_eq!(lower.normalize().to_string(), "48548.284494");
+    assert_eq!(upper.normalize().to_string(), "48574.252586");
+    ```
+*/
+pub fn calculate_pyth_confidence_bounds(
+    pyth_price: Price,
+    confidence_factor: Decimal,
+) -> Option<(Decimal, Decimal)> {
+    calculate_confidence_bounds(pyth_price, confidence_factor)
+}
+
+/*
+    Calculates lower and upper bounds of the probable price range the way arbitrage finders with the settings do,
+    applying the fixed band, confidence floor and inversion, but neither the confidence age growth nor the TWAP
+*/
+pub fn calculate_probable_price_range(
+    pyth_price: Price,
+    settings: FinderSettings,
+) -> Option<(Decimal, Decimal)> {
+    let (higher, lower) = ArbitrageFinder::calculate_pyth_range(pyth_price, settings)?;
+    Some((lower, higher))
+}
+
+impl Default for ArbitrageFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FinderSettings {
+    /*
+        Creates FinderSettings instance from CLI arguments
+    */
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            confidence_factor: config.confidence_factor(),
+            rounding_strategy: config.rounding_mode.strategy(),
+            pyth_band_bps: config.pyth_band_bps,
+            min_confidence: match (config.min_confidence, config.min_confidence_bps) {
+                (Some(min_confidence), _) => Some(ConfidenceFloor::Absolute(min_confidence)),
+                (None, Some(bps)) => Some(ConfidenceFloor::Bps(bps)),
+                (None, None) => None,
+            },
+            dedup_bucket: config.dedup_bucket,
+            dedup_window: Duration::from_secs(config.dedup_window),
+            max_dedup_buckets: config.max_dedup_buckets,
+            cooldown: Duration::from_millis(config.cooldown),
+            strategy: match config.strategy {
+                StrategyKind::Bound => Strategy::Bound,
+                StrategyKind::Ev => Strategy::ExpectedValue(config.min_expected_profit),
+            },
+            quantity_strategy: match config.quantity_strategy {
+                QuantityStrategyKind::BookSide => QuantityStrategy::BookSide,
+                QuantityStrategyKind::MinBoth => QuantityStrategy::MinBoth,
+                QuantityStrategyKind::Fixed => {
+                    QuantityStrategy::Fixed(config.fixed_quantity.unwrap()) // required by clap
+                }
+            },
+            execution_price_model: match config.execution_price_model {
+                ExecutionPriceModelKind::Best => ExecutionPriceModel::Best,
+                ExecutionPriceModelKind::Mid => ExecutionPriceModel::Mid,
+                ExecutionPriceModelKind::Offset => {
+                    ExecutionPriceModel::OffsetBps(config.execution_offset_bps.unwrap())
+                    // required by clap
+                }
+            },
+            min_persistence: Duration::from_millis(config.min_persistence_ms),
+            emit_initial: config.emit_initial,
+            inclusive_bounds: config.inclusive_bounds,
+            tie_break: config.tie_break,
+            comparison_basis: config.comparison_basis,
+            zero_profit: config.zero_profit,
+            confidence_age_growth: config.confidence_age_growth,
+            max_feed_skew: config.max_feed_skew_ms.map(Duration::from_millis),
+            pyth_time_offset_ms: config.pyth_time_offset_ms,
+            reemit_improvement: config.reemit_improvement,
+            post_reconnect_grace: Duration::from_millis(config.post_reconnect_grace_ms),
+            fill_probability: config.fill_probability,
+            invert_pyth: config.invert_pyth,
+            pyth_twap_window: config.pyth_twap_window.map(Duration::from_secs),
+            min_edge_ticks: config.min_edge_ticks,
+            dex_quantity: config.dex_quantity,
+            fill_model: config.fill_model,
+            alert_on: config.alert_on,
+            hysteresis: config.hysteresis_open_sigma.map(|open| SigmaHysteresis {
+                open,
+                close: config.hysteresis_close_sigma.min(open),
+            }),
+            dex_price_weight: config.dex_price_weight,
+            leg_fees: config.taker_leg.map(|taker_leg| {
+                let (maker_fee, taker_fee) = (config.maker_fee.unwrap(), config.taker_fee.unwrap()); // required by clap
+                match taker_leg {
+                    Leg::Binance => LegFees {
+                        binance: taker_fee,
+                        dex: maker_fee,
+                    },
+                    Leg::Dex => LegFees {
+                        binance: maker_fee,
+                        dex: taker_fee,
+                    },
+                }
+            }),
+            solana_fee: match (
+                config.solana_base_fee_lamports,
+                config.priority_fee_lamports,
+            ) {
+                (None, None) => None,
+                (base_fee, priority_fee) => Some(SolanaFee {
+                    lamports: base_fee
+                        .unwrap_or(0)
+                        .saturating_add(priority_fee.unwrap_or(0)),
+                    sol_price: config.sol_quote_price,
+                }),
+            },
+        }
+    }
+}
+
+impl Default for FinderSettings {
+    fn default() -> Self {
+        Self {
+            confidence_factor: Decimal::new(212, 2),
+            rounding_strategy: RoundingStrategy::MidpointNearestEven,
+            pyth_band_bps: None,
+            min_confidence: None,
+            dedup_bucket: None,
+            dedup_window: Duration::from_secs(60),
+            max_dedup_buckets: 1024,
+            cooldown: Duration::ZERO,
+            strategy: Strategy::Bound,
+            quantity_strategy: QuantityStrategy::BookSide,
+            execution_price_model: ExecutionPriceModel::Best,
+            min_persistence: Duration::ZERO,
+            emit_initial: true,
+            inclusive_bounds: false,
+            tie_break: TieBreak::SellFirst,
+            comparison_basis: ComparisonBasis::Crossing,
+            zero_profit: ZeroProfitPolicy::Emit,
+            confidence_age_growth: None,
+            max_feed_skew: None,
+            pyth_time_offset_ms: 0,
+            leg_fees: None,
+            solana_fee: None,
+            reemit_improvement: None,
+            post_reconnect_grace: Duration::ZERO,
+            fill_probability: None,
+            invert_pyth: false,
+            pyth_twap_window: None,
+            min_edge_ticks: None,
+            dex_quantity: None,
+            fill_model: FillModel::FullMin,
+            alert_on: AlertOn::EveryTick,
+            hysteresis: None,
+            dex_price_weight: Decimal::ONE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct ArbitrageOpportunity {
+    #[serde(default)]
+    pub id: Uuid, // random, correlating the opportunity across sinks, nil in states saved before it was added
+    pub direction: ArbitrageDirection,
+    pub quantity: Decimal,
+    pub estimated_profit: Decimal,
+    pub profit_low: Decimal, // profit if DEX price is at the crossed bound of the probable range, equal to estimated_profit with the bound strategy
+    pub profit_high: Decimal, // profit if DEX price is at the opposite bound of the probable range
+    pub binance_price: Decimal,
+    pub pyth_price: Decimal,
+    pub profit_breakdown: ProfitBreakdown,
+    pub required_capital: RequiredCapital,
+}
+
+// Opportunities are compared without their ids, so the same one found again is a duplicate
+impl PartialEq for ArbitrageOpportunity {
+    fn eq(&self, other: &Self) -> bool {
+        let Self {
+            id: _,
+            direction,
+            quantity,
+            estimated_profit,
+            profit_low,
+            profit_high,
+            binance_price,
+            pyth_price,
+            profit_breakdown,
+            required_capital,
+        } = self;
+
+        (
+            direction,
+            quantity,
+            estimated_profit,
+            profit_low,
+            profit_high,
+            binance_price,
+            pyth_price,
+            profit_breakdown,
+            required_capital,
+        ) == (
+            &other.direction,
+            &other.quantity,
+            &other.estimated_profit,
+            &other.profit_low,
+            &other.profit_high,
+            &other.binance_price,
+            &other.pyth_price,
+            &other.profit_breakdown,
+            &other.required_capital,
+        )
+    }
+}
+
+impl Eq for ArbitrageOpportunity {}
+
+impl ArbitrageOpportunity {
+    /*
+        Returns the opportunity with trailing zeros trimmed from all its decimal values, which are equal to the original ones
+    */
+    pub fn normalized(self) -> Self {
+        Self {
+            quantity: self.quantity.normalize(),
+            estimated_profit: self.estimated_profit.normalize(),
+            profit_low: self.profit_low.normalize(),
+            profit_high: self.profit_high.normalize(),
+            binance_price: self.binance_price.normalize(),
+            pyth_price: self.pyth_price.normalize(),
+            profit_breakdown: ProfitBreakdown {
+                gross_edge: self.profit_breakdown.gross_edge.normalize(),
+                binance_fee: self.profit_breakdown.binance_fee.normalize(),
+                dex_fee: self.profit_breakdown.dex_fee.normalize(),
+            },
+            required_capital: RequiredCapital {
+                cex: self.required_capital.cex.normalize(),
+                dex: self.required_capital.dex.normalize(),
+            },
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+impl ArbitrageOpportunity {
+    /*
+        Returns the opportunity of selling 0.8574 on Binance at 71.3833 against the upper bound of 71.27225988, shared by tests of its consumers
+    */
+    pub fn sample() -> Self {
+        Self {
+            id: Uuid::nil(),
+            direction: ArbitrageDirection::SellBinanceBuyDex,
+            quantity: Decimal::new(8574, 4),
+            estimated_profit: Decimal::new(3400176, 8),
+            profit_low: Decimal::new(3400176, 8),
+            profit_high: Decimal::new(246870031, 8),
+            binance_price: Decimal::new(713833, 4),
+            pyth_price: Decimal::new(7127225988, 8),
+            profit_breakdown: ProfitBreakdown {
+                gross_edge: Decimal::new(95205798888, 12),
+                binance_fee: Decimal::new(6120404142, 11),
+                dex_fee: Decimal::ZERO,
+            },
+            required_capital: RequiredCapital {
+                cex: Decimal::new(6120404142, 8),
+                dex: Decimal::new(6110843562, 8),
+            },
+        }
+    }
+}
+
+/*
+    Struct showing how estimated profit of an opportunity was built up
+    Components are not rounded, estimated profit is their net value rounded to 8 decimal places with the configured strategy
+    With a fill probability, the components are its expected values like the profits, so the net value stays the estimated profit
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ProfitBreakdown {
+    pub gross_edge: Decimal,  // price difference times quantity
+    pub binance_fee: Decimal, // fee paid for the Binance leg
+    #[serde(default)]
+    pub dex_fee: Decimal, // fee paid for the DEX leg including the Solana transaction fee, zero unless any of them is configured
+}
+
+impl ProfitBreakdown {
+    /*
+        Returns profit left after subtracting all the costs from the gross edge
+    */
+    pub fn net(&self) -> Decimal {
+        self.gross_edge - self.binance_fee - self.dex_fee
+    }
+}
+
+/*
+    Struct holding notional (quantity times price) needed to open both legs of an opportunity
+    DEX leg is valued at the crossed bound of the probable price range, as the estimated profit is
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RequiredCapital {
+    pub cex: Decimal, // quote asset spent on Binance, or received when selling there
+    pub dex: Decimal, // quote asset spent on the DEX, or received when selling there
+}
+
+impl RequiredCapital {
+    /*
+        Calculates notional of both legs, returns None on overflow
+    */
+    pub fn calculate(
+        quantity: Decimal,
+        binance_price: Decimal,
+        pyth_price: Decimal,
+    ) -> Option<Self> {
+        Some(Self {
+            cex: quantity.precise_mul(binance_price)?.normalize(),
+            dex: quantity.precise_mul(pyth_price)?.normalize(),
+        })
+    }
+}
+
+/*
+    Struct holding snapshots of the feeds an opportunity was found in, which make it reproducible
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RawInputs {
+    pub pyth: Price,
+    pub binance: BookTickerData,
+}
+
+/*
+    Struct holding the middle of the Binance spread and the probable price range derived from Pyth
+    Signed edge is Binance mid minus the middle of the range, positive when Binance trades above Pyth
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CurrentSpread {
+    pub binance_mid: Decimal,
+    pub pyth_low: Decimal,
+    pub pyth_high: Decimal,
+    pub signed_edge: Decimal,
+}
+
+impl CurrentSpread {
+    /*
+        Returns None on overflow
+    */
+    pub fn new(bid: Decimal, ask: Decimal, pyth_low: Decimal, pyth_high: Decimal) -> Option<Self> {
+        let binance_mid = bid.checked_add(ask)?.checked_div(Decimal::TWO)?;
+        let pyth_mid = pyth_low.checked_add(pyth_high)?.checked_div(Decimal::TWO)?;
+
+        Some(Self {
+            binance_mid: binance_mid.normalize(),
+            pyth_low: pyth_low.normalize(),
+            pyth_high: pyth_high.normalize(),
+            signed_edge: binance_mid.checked_sub(pyth_mid)?.normalize(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum ArbitrageDirection {
+    SellBinanceBuyDex,
+    BuyBinanceSellDex,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+    use tokio::sync::RwLock;
+
+    use crate::{
+        config::{AlertOn, ComparisonBasis, FillModel, TieBreak, ZeroProfitPolicy},
+        structs::{
+            cex::binance::{BookTickerData, DepthData},
+            clock::MockClock,
+            dedup_state::DedupState,
+            stats::{LifetimeHistogram, Stats, Suppression},
+            verify::opportunity_vectors,
+        },
+    };
+
+    use rust_decimal::RoundingStrategy;
+
+    use super::{
+        evaluate, ArbitrageDirection, ArbitrageFinder, ArbitrageOpportunity, ConfidenceFloor,
+        EvalOutcome, ExecutionPriceModel, FinderSettings, LegFees, QuantityStrategy,
+        RequiredCapital, SigmaHysteresis, SolanaFee, Strategy,
+    };
+
+    #[test]
+    fn test_calculate_pyth_confident_95_price() {
+        let arbitrage_finder = ArbitrageFinder::new();
+        let price = Price {
+            price: 4856126854,
+            conf: 612455,
+            expo: -5,
+            ..Default::default()
+        };
+
+        let (higher, lower) = arbitrage_finder
+            .calculate_pyth_confident_95_price(price)
+            .unwrap();
+        assert_eq!(lower.normalize().to_string(), "48548.284494");
+        assert_eq!(higher.normalize().to_string(), "48574.252586");
+    }
+
+    #[test]
+    fn test_invert_pyth() {
+        // 4 +- 1
+        let price = Price {
+            price: 400,
+            conf: 100,
+            expo: -2,
+            ..Default::default()
+        };
+        let arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            confidence_factor: Decimal::ONE,
+            invert_pyth: true,
+            ..Default::default()
+        });
+
+        // 1 / 5 and 1 / 3 around 0.25, not 0.25 +- 1 / 4^2
+        let (higher, lower) = arbitrage_finder
+            .calculate_pyth_confident_95_price(price)
+            .unwrap();
+        assert_eq!(lower, Decimal::from_str("0.2").unwrap());
+        assert_eq!(
+            higher,
+            Decimal::from_str("0.3333333333333333333333333333").unwrap()
+        );
+
+        // The range of 4 +- 4 * 1 reaches zero, so it cannot be inverted
+        let arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            confidence_factor: Decimal::new(4, 0),
+            invert_pyth: true,
+            ..Default::default()
+        });
+        assert!(arbitrage_finder
+            .calculate_pyth_confident_95_price(price)
+            .is_none());
+    }
+
+    #[test]
+    fn test_calculate_pyth_band_price() {
+        let price = Price {
+            price: 4856126854,
+            conf: 612455,
+            expo: -5,
+            ..Default::default()
+        };
+
+        // 5 bps: 48561.26854 +- 24.28063427, wider than the confidence-based range
+        let arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            pyth_band_bps: Some(5),
+            ..Default::default()
+        });
+        let (higher, lower) = arbitrage_finder
+            .calculate_pyth_confident_95_price(price)
+            .unwrap();
+        assert_eq!(lower.normalize().to_string(), "48536.98790573");
+        assert_eq!(higher.normalize().to_string(), "48585.54917427");
+
+        let (confident_higher, confident_lower) = ArbitrageFinder::new()
+            .calculate_pyth_confident_95_price(price)
+            .unwrap();
+        assert!(higher > confident_higher);
+        assert!(lower < confident_lower);
+
+        // The band does not depend on the confidence
+        let (higher_without_confidence, lower_without_confidence) = arbitrage_finder
+            .calculate_pyth_confident_95_price(Price { conf: 0, ..price })
+            .unwrap();
+        assert_eq!(higher_without_confidence, higher);
+        assert_eq!(lower_without_confidence, lower);
+    }
+
+    #[test]
+    fn test_confidence_age_growth() {
+        let clock = Arc::new(MockClock::starting_at(1_700_000_000_000));
+        let arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            confidence_age_growth: Some(Decimal::new(1, 1)),
+            ..Default::default()
+        })
+        .with_clock(clock.clone());
+        let price = Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            publish_time: 1_700_000_000,
+        };
+        let range = |arbitrage_finder: &ArbitrageFinder| {
+            let (higher, lower) = arbitrage_finder
+                .calculate_pyth_confident_95_price(price)
+                .unwrap();
+            (
+                higher.normalize().to_string(),
+                lower.normalize().to_string(),
+            )
+        };
+
+        // A fresh price has the usual range
+        assert_eq!(
+            range(&arbitrage_finder),
+            ("71.27225988".to_string(), "68.43263012".to_string())
+        );
+        // 10% per second doubles the half-width of 1.41981488 in 10 seconds
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(
+            range(&arbitrage_finder),
+            ("72.69207476".to_string(), "67.01281524".to_string())
+        );
+        clock.advance(Duration::from_millis(2500));
+        assert_eq!(
+            range(&arbitrage_finder),
+            ("73.04702848".to_string(), "66.65786152".to_string())
+        );
+    }
+
+    #[test]
+    fn test_calculate_pyth_confident_95_price_out_of_range_expo() {
+        let arbitrage_finder = ArbitrageFinder::new();
+        let price = Price {
+            price: 4856126854,
+            conf: 612455,
+            expo: -29,
+            ..Default::default()
+        };
+        assert!(arbitrage_finder
+            .calculate_pyth_confident_95_price(price)
+            .is_none());
+
+        // The maximum scale is still supported
+        let (higher, lower) = arbitrage_finder
+            .calculate_pyth_confident_95_price(Price { expo: -28, ..price })
+            .unwrap();
+        assert!(lower < higher);
+    }
+
+    #[tokio::test]
+    async fn test_find_opportunity_out_of_range_expo() {
+        let mut arbitrage_finder = ArbitrageFinder::new();
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -40,
+            ..Default::default()
+        })));
+        let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+            b: "71.3833".to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        })));
+
+        let result = arbitrage_finder
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await;
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_find_opportunity_data_none() {
+        let mut arbitrage_finder = ArbitrageFinder::new();
+
+        // Both none
+        {
+            let result = arbitrage_finder
+                .find_opportunity(
+                    Arc::new(RwLock::new(None)),
+                    Arc::new(RwLock::new(None)),
+                    Decimal::default(),
+                )
+                .await;
+            assert!(result.is_none());
+        }
+
+        // Only binance data none
+        {
+            let result = arbitrage_finder
+                .find_opportunity(
+                    Arc::new(RwLock::new(Some(Price::default()))),
+                    Arc::new(RwLock::new(None)),
+                    Decimal::default(),
+                )
+                .await;
+            assert!(result.is_none());
+        }
+
+        // Only pyth data none
+        {
+            let result = arbitrage_finder
+                .find_opportunity(
+                    Arc::new(RwLock::new(None)),
+                    Arc::new(RwLock::new(Some(BookTickerData::default()))),
+                    Decimal::default(),
+                )
+                .await;
+            assert!(result.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_opportunity() {
+        let mut arbitrage_finder = ArbitrageFinder::new();
+
+        // SellBinanceBuyDex direction
+        {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: "71.3833".to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(1, 3),
+                )
+                .await
+                .unwrap();
+            assert_eq!(result.direction, ArbitrageDirection::SellBinanceBuyDex);
+            assert_eq!(result.quantity, Decimal::from_str("0.8574").unwrap());
+            assert_eq!(
+                result.estimated_profit,
+                Decimal::from_str("0.03400176").unwrap()
+            );
+            assert_eq!(
+                result.profit_breakdown.gross_edge,
+                Decimal::from_str("0.095205798888").unwrap()
+            );
+            assert_eq!(
+                result.profit_breakdown.binance_fee,
+                Decimal::from_str("0.06120404142").unwrap()
+            );
+            assert_eq!(
+                result.profit_breakdown.net().round_dp(8),
+                result.estimated_profit
+            );
+            // The range brackets the point estimate, which is its worst case
+            assert_eq!(result.profit_low, result.estimated_profit);
+            assert_eq!(result.profit_high, Decimal::from_str("2.46870031").unwrap());
+            assert_eq!(
+                result.required_capital,
+                RequiredCapital {
+                    cex: Decimal::from_str("61.20404142").unwrap(),
+                    dex: Decimal::from_str("61.108835621112").unwrap(),
+                }
+            );
+            assert!(result.profit_low <= result.estimated_profit);
+            assert!(result.estimated_profit <= result.profit_high);
+        }
+
+        // SellBinanceBuyDex direction, but too large fee
+        {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: "71.3833".to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(5, 3),
+                )
+                .await;
+            assert!(result.is_none())
+        }
+
+        // BuyBinanceSellDex direction
+        {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: "67.5421".to_string(),
+                B: "1.1258".to_string(),
+                a: "67.8423".to_string(),
+                A: "2.5569".to_string(),
+                ..Default::default()
+            })));
+
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(1, 3),
+                )
+                .await
+                .unwrap();
+            assert_eq!(result.direction, ArbitrageDirection::BuyBinanceSellDex);
+            assert_eq!(result.quantity, Decimal::from_str("2.5569").unwrap());
+            assert_eq!(
+                result.estimated_profit,
+                Decimal::from_str("1.33594911").unwrap()
+            );
+            assert_eq!(
+                result.profit_breakdown.net().round_dp(8),
+                result.estimated_profit
+            );
+            // The range brackets the point estimate, which is its worst case
+            assert_eq!(result.profit_low, result.estimated_profit);
+            assert_eq!(result.profit_high, Decimal::from_str("8.59659844").unwrap());
+            assert_eq!(
+                result.required_capital,
+                RequiredCapital {
+                    cex: Decimal::from_str("173.46597687").unwrap(),
+                    dex: Decimal::from_str("174.975391953828").unwrap(),
+                }
+            );
+            assert!(result.profit_low <= result.estimated_profit);
+            assert!(result.estimated_profit <= result.profit_high);
+        }
+
+        // BuyBinanceSellDex direction, but too large fee
+        {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: "67.5421".to_string(),
+                B: "1.1258".to_string(),
+                a: "67.8423".to_string(),
+                A: "2.5569".to_string(),
+                ..Default::default()
+            })));
+
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(1, 2),
+                )
+                .await;
+            assert!(result.is_none());
+        }
+
+        // No opportunity found
+        {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: "69.2222".to_string(),
+                B: "1.1258".to_string(),
+                a: "69.1111".to_string(),
+                A: "2.5569".to_string(),
+                ..Default::default()
+            })));
+
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(1, 3),
+                )
+                .await;
+            assert!(result.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_opportunities_deep_book() {
+        let mut arbitrage_finder = ArbitrageFinder::new();
+        // l: 68.43263012 h: 71.27225988
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        })));
+        let level = |price: &str, quantity: &str| [price.to_string(), quantity.to_string()];
+        let latest_binance_depth_data = Arc::new(RwLock::new(Some(DepthData {
+            bids: vec![level("71.5", "1"), level("71.4", "2"), level("71.2", "5")],
+            asks: vec![level("71.6", "3"), level("71.7", "4")],
+            ..Default::default()
+        })));
+
+        let result = arbitrage_finder
+            .find_opportunities(
+                latest_pyth_price.clone(),
+                latest_binance_depth_data.clone(),
+                Decimal::new(1, 3),
+            )
+            .await;
+        assert_eq!(
+            result
+                .iter()
+                .map(|opportunity| (
+                    opportunity.direction,
+                    opportunity.binance_price,
+                    opportunity.quantity,
+                    opportunity.estimated_profit
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    ArbitrageDirection::SellBinanceBuyDex,
+                    Decimal::from_str("71.5").unwrap(),
+                    Decimal::ONE,
+                    Decimal::from_str("0.15624012").unwrap()
+                ),
+                (
+                    ArbitrageDirection::SellBinanceBuyDex,
+                    Decimal::from_str("71.4").unwrap(),
+                    Decimal::new(2, 0),
+                    Decimal::from_str("0.11268024").unwrap()
+                ),
+            ]
+        );
+
+        // The same book is not reported twice
+        let result = arbitrage_finder
+            .find_opportunities(
+                latest_pyth_price,
+                latest_binance_depth_data,
+                Decimal::new(1, 3),
+            )
+            .await;
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_rounding_strategy() {
+        let build_opportunity = |rounding_strategy| {
+            let settings = FinderSettings {
+                rounding_strategy,
+                ..Default::default()
+            };
+            // The profit of 0.000000125 lies exactly between two values with 8 decimal places
+            ArbitrageFinder::with_settings(settings)
+                .build_opportunity(
+                    Decimal::from_str("70.000000125").unwrap(),
+                    Decimal::from_str("70").unwrap(),
+                    Decimal::from_str("70").unwrap(),
+                    Decimal::ZERO,
+                    Decimal::ONE,
+                    ArbitrageDirection::SellBinanceBuyDex,
+                )
+                .unwrap()
+                .estimated_profit
+        };
+
+        assert_eq!(
+            build_opportunity(RoundingStrategy::MidpointNearestEven),
+            Decimal::from_str("0.00000012").unwrap()
+        );
+        assert_eq!(
+            build_opportunity(RoundingStrategy::MidpointAwayFromZero),
+            Decimal::from_str("0.00000013").unwrap()
+        );
+        assert_eq!(
+            build_opportunity(RoundingStrategy::ToZero),
+            Decimal::from_str("0.00000012").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deduplicate_by_bucket() {
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            dedup_bucket: Some(Decimal::new(1, 1)),
+            ..Default::default()
+        });
+        let mut find = |binance_price: &str, quantity: &str| {
+            arbitrage_finder.calculate_arbitrage_opportunity(
+                Decimal::from_str(binance_price).unwrap(),
+                Decimal::from_str("71.27225988").unwrap(),
+                Decimal::from_str("68.43263012").unwrap(),
+                Decimal::new(1, 3),
+                Decimal::from_str(quantity).unwrap(),
+                ArbitrageDirection::SellBinanceBuyDex,
+            )
+        };
+
+        assert!(find("71.3833", "0.8574").is_some());
+        // Different opportunities within the same 71.3-71.4 bucket
+        assert!(find("71.3833", "0.9").is_none());
+        assert!(find("71.3999", "0.8574").is_none());
+        // Jump to a new bucket
+        assert!(find("71.4512", "0.8574").is_some());
+        assert!(find("71.3001", "0.8574").is_none());
+
+        // A zero bucket does not deduplicate instead of dividing by zero
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            dedup_bucket: Some(Decimal::ZERO),
+            ..Default::default()
+        });
+        for quantity in ["0.8574", "0.9"] {
+            assert!(arbitrage_finder
+                .calculate_arbitrage_opportunity(
+                    Decimal::from_str("71.3833").unwrap(),
+                    Decimal::from_str("71.27225988").unwrap(),
+                    Decimal::from_str("68.43263012").unwrap(),
+                    Decimal::new(1, 3),
+                    Decimal::from_str(quantity).unwrap(),
+                    ArbitrageDirection::SellBinanceBuyDex,
+                )
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn test_max_dedup_buckets() {
+        let clock = Arc::new(MockClock::new());
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            dedup_bucket: Some(Decimal::new(1, 4)),
+            max_dedup_buckets: 100,
+            ..Default::default()
+        })
+        .with_clock(clock.clone());
+
+        // Every price in its own bucket, all within the window
+        for tick in 0..10_000 {
+            clock.advance(Duration::from_millis(1));
+            assert!(arbitrage_finder
+                .calculate_arbitrage_opportunity(
+                    Decimal::new(713833 + tick, 4),
+                    Decimal::from_str("71.27225988").unwrap(),
+                    Decimal::from_str("68.43263012").unwrap(),
+                    Decimal::new(1, 3),
+                    Decimal::from_str("0.8574").unwrap(),
+                    ArbitrageDirection::SellBinanceBuyDex,
+                )
+                .is_some());
+            assert!(arbitrage_finder.last_found_in_bucket.len() <= 100);
+        }
+        assert_eq!(arbitrage_finder.last_found_in_bucket.len(), 100);
+        assert_eq!(arbitrage_finder.dedup_state().found_in_buckets.len(), 100);
+    }
+
+    #[test]
+    fn test_cooldown_reset_on_direction_flip() {
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            cooldown: Duration::from_secs(60),
+            ..Default::default()
+        });
+        let mut find = |binance_price: &str, arbitrage_direction| {
+            let (pyth_price, opposite_pyth_price) = match arbitrage_direction {
+                ArbitrageDirection::SellBinanceBuyDex => ("71.27225988", "68.43263012"),
+                ArbitrageDirection::BuyBinanceSellDex => ("68.43263012", "71.27225988"),
+            };
+            arbitrage_finder.calculate_arbitrage_opportunity(
+                Decimal::from_str(binance_price).unwrap(),
+                Decimal::from_str(pyth_price).unwrap(),
+                Decimal::from_str(opposite_pyth_price).unwrap(),
+                Decimal::new(1, 3),
+                Decimal::ONE,
+                arbitrage_direction,
+            )
+        };
+
+        assert!(find("71.3833", ArbitrageDirection::SellBinanceBuyDex).is_some());
+        // A different opportunity in the same direction is suppressed within the cooldown
+        assert!(find("71.4512", ArbitrageDirection::SellBinanceBuyDex).is_none());
+        // The flip is reported immediately and resets the cooldown
+        assert!(find("67.8423", ArbitrageDirection::BuyBinanceSellDex).is_some());
+        assert!(find("67.5421", ArbitrageDirection::BuyBinanceSellDex).is_none());
+        assert!(find("71.4512", ArbitrageDirection::SellBinanceBuyDex).is_some());
+    }
+
+    #[test]
+    fn test_cooldown_expiry() {
+        let clock = Arc::new(MockClock::new());
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            cooldown: Duration::from_secs(60),
+            ..Default::default()
+        })
+        .with_clock(clock.clone());
+        let mut find = |binance_price: &str| {
+            arbitrage_finder.calculate_arbitrage_opportunity(
+                Decimal::from_str(binance_price).unwrap(),
+                Decimal::from_str("71.27225988").unwrap(),
+                Decimal::from_str("68.43263012").unwrap(),
+                Decimal::new(1, 3),
+                Decimal::ONE,
+                ArbitrageDirection::SellBinanceBuyDex,
+            )
+        };
 
-    use super::{ArbitrageDirection, ArbitrageFinder};
+        assert!(find("71.3833").is_some());
+        clock.advance(Duration::from_millis(59_999));
+        assert!(find("71.4512").is_none());
+        // The cooldown ends exactly 60 seconds after the report
+        clock.advance(Duration::from_millis(1));
+        assert!(find("71.4512").is_some());
+        assert!(find("71.3833").is_none());
+    }
 
-    #[test]
-    fn test_calculate_pyth_confident_95_price() {
-        let arbitrage_finder = ArbitrageFinder::new();
-        let price = Price {
-            price: 4856126854,
-            conf: 612455,
-            expo: -5,
+    #[tokio::test]
+    async fn test_tie_break() {
+        let find = |tie_break, bid_quantity: &str, ask_quantity: &str| {
+            let bid_quantity = bid_quantity.to_string();
+            let ask_quantity = ask_quantity.to_string();
+            async move {
+                let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+                    tie_break,
+                    ..Default::default()
+                });
+                // Zero confidence makes both bounds 70, which the crossed book is on both sides of
+                let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                    price: 70000000,
+                    conf: 0,
+                    expo: -6,
+                    ..Default::default()
+                })));
+                let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                    b: "70.5".to_string(),
+                    B: bid_quantity,
+                    a: "69".to_string(),
+                    A: ask_quantity,
+                    ..Default::default()
+                })));
+                arbitrage_finder
+                    .find_opportunity(
+                        latest_pyth_price,
+                        latest_binance_ticker_data,
+                        Decimal::new(1, 3),
+                    )
+                    .await
+            }
+        };
+
+        // Sell profit: 0.5 * 1 - 0.0705 = 0.4295, buy profit: 1 * 2 - 0.138 = 1.862
+        assert!(find(TieBreak::Reject, "1", "2").await.is_none());
+        let sell = find(TieBreak::SellFirst, "1", "2").await.unwrap();
+        assert_eq!(sell.direction, ArbitrageDirection::SellBinanceBuyDex);
+        assert_eq!(sell.estimated_profit, Decimal::from_str("0.4295").unwrap());
+        let buy = find(TieBreak::BuyFirst, "1", "2").await.unwrap();
+        assert_eq!(buy.direction, ArbitrageDirection::BuyBinanceSellDex);
+        assert_eq!(buy.estimated_profit, Decimal::from_str("1.862").unwrap());
+        assert_eq!(find(TieBreak::MaxProfit, "1", "2").await, Some(buy));
+
+        // Sell profit: 0.5 * 3 - 0.2115 = 1.2885, buy profit: 1 * 0.1 - 0.0069 = 0.0931
+        let max_profit = find(TieBreak::MaxProfit, "3", "0.1").await.unwrap();
+        assert_eq!(max_profit.direction, ArbitrageDirection::SellBinanceBuyDex);
+        assert_eq!(
+            max_profit.estimated_profit,
+            Decimal::from_str("1.2885").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quantity_strategy() {
+        let find = |quantity_strategy| async move {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: "71.3833".to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.5".to_string(),
+                ..Default::default()
+            })));
+
+            ArbitrageFinder::with_settings(FinderSettings {
+                quantity_strategy,
+                ..Default::default()
+            })
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await
+            .unwrap()
+        };
+
+        let result = find(QuantityStrategy::BookSide).await;
+        assert_eq!(result.quantity, Decimal::from_str("0.8574").unwrap());
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.03400176").unwrap()
+        );
+
+        let result = find(QuantityStrategy::MinBoth).await;
+        assert_eq!(result.quantity, Decimal::from_str("0.5").unwrap());
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.01982841").unwrap()
+        );
+
+        let result = find(QuantityStrategy::Fixed(Decimal::new(2, 0))).await;
+        assert_eq!(result.quantity, Decimal::new(2, 0));
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.07931364").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fill_model() {
+        async fn find(
+            fill_model: FillModel,
+            quantity_strategy: QuantityStrategy,
+            dex_quantity: Option<Decimal>,
+        ) -> ArbitrageOpportunity {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: "71.3833".to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+
+            ArbitrageFinder::with_settings(FinderSettings {
+                fill_model,
+                quantity_strategy,
+                dex_quantity,
+                ..Default::default()
+            })
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await
+            .unwrap()
+        }
+
+        // Without the DEX quantity, both models fill the Binance one
+        for fill_model in [FillModel::FullMin, FillModel::Proportional] {
+            let result = find(fill_model, QuantityStrategy::BookSide, None).await;
+            assert_eq!(result.quantity, Decimal::from_str("0.8574").unwrap());
+        }
+
+        // 0.8574 on Binance and 0.5 on the DEX, both models fill the smaller DEX quantity
+        let dex_quantity = Some(Decimal::new(5, 1));
+        for fill_model in [FillModel::FullMin, FillModel::Proportional] {
+            let result = find(fill_model, QuantityStrategy::BookSide, dex_quantity).await;
+            assert_eq!(result.quantity, Decimal::new(5, 1));
+            assert_eq!(
+                result.estimated_profit,
+                Decimal::from_str("0.01982841").unwrap()
+            );
+        }
+
+        // 1 requested, 0.8574 on Binance and 0.9 on the DEX
+        let fixed = QuantityStrategy::Fixed(Decimal::ONE);
+        let dex_quantity = Some(Decimal::new(9, 1));
+        let result = find(FillModel::FullMin, fixed, dex_quantity).await;
+        assert_eq!(result.quantity, Decimal::new(9, 1));
+        // Binance covers 0.8574 and the DEX 0.9 of the requested quantity, both legs are sized to 0.8574 of it
+        let result = find(FillModel::Proportional, fixed, dex_quantity).await;
+        assert_eq!(result.quantity, Decimal::from_str("0.8574").unwrap());
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.03400176").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_opportunity_overflow() {
+        let mut arbitrage_finder = ArbitrageFinder::new();
+        // l: 68.43263012 h: 71.27225988
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        })));
+        let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+            b: Decimal::MAX.to_string(),
+            B: "1000".to_string(),
+            a: Decimal::MAX.to_string(),
+            A: "1000".to_string(),
+            ..Default::default()
+        })));
+
+        let result = arbitrage_finder
+            .find_opportunity(
+                latest_pyth_price.clone(),
+                latest_binance_ticker_data.clone(),
+                Decimal::new(1, 3),
+            )
+            .await;
+        assert!(result.is_none());
+
+        // The finder keeps working after skipping the tick
+        *latest_binance_ticker_data.write().await = Some(BookTickerData {
+            b: "71.3833".to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        });
+        let result = arbitrage_finder
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await;
+        assert!(result.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_min_persistence() {
+        let min_persistence = Duration::from_millis(100);
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            min_persistence,
+            ..Default::default()
+        });
+        // l: 68.43263012 h: 71.27225988
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        })));
+        let latest_binance_ticker_data = Arc::new(RwLock::new(None));
+        let book = |bid: &str| BookTickerData {
+            b: bid.to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
             ..Default::default()
         };
 
-        let (higher, lower) = arbitrage_finder.calculate_pyth_confident_95_price(price);
-        assert_eq!(lower.normalize().to_string(), "48548.284494");
-        assert_eq!(higher.normalize().to_string(), "48574.252586");
+        for bid in ["71.3833", "70", "71.3833"] {
+            *latest_binance_ticker_data.write().await = Some(book(bid));
+            tokio::time::sleep(min_persistence / 2).await;
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price.clone(),
+                    latest_binance_ticker_data.clone(),
+                    Decimal::new(1, 3),
+                )
+                .await;
+            assert!(result.is_none());
+        }
+
+        // The gap restarted at the third tick and keeps holding
+        tokio::time::sleep(min_persistence).await;
+        let result = arbitrage_finder
+            .find_opportunity(
+                latest_pyth_price.clone(),
+                latest_binance_ticker_data.clone(),
+                Decimal::new(1, 3),
+            )
+            .await;
+        assert!(result.is_some());
+
+        // The sustained gap is reported once
+        *latest_binance_ticker_data.write().await = Some(book("71.4512"));
+        let result = arbitrage_finder
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await;
+        assert!(result.is_none());
     }
 
     #[tokio::test]
-    async fn test_find_opportunity_data_none() {
+    async fn test_find_opportunity_scientific_notation() {
         let mut arbitrage_finder = ArbitrageFinder::new();
+        // l: 68.43263012 h: 71.27225988
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        })));
+        let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+            b: "7.13833E1".to_string(),
+            B: "8.574e-1".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        })));
 
-        // Both none
-        {
+        let result = arbitrage_finder
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.binance_price, Decimal::from_str("71.3833").unwrap());
+        assert_eq!(result.quantity, Decimal::from_str("0.8574").unwrap());
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.03400176").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_emit_initial() {
+        // l: 68.43263012 h: 71.27225988
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        })));
+        let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+            b: "71.3833".to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        })));
+
+        for emit_initial in [true, false] {
+            let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+                emit_initial,
+                ..Default::default()
+            });
             let result = arbitrage_finder
                 .find_opportunity(
-                    Arc::new(RwLock::new(None)),
-                    Arc::new(RwLock::new(None)),
-                    Decimal::default(),
+                    latest_pyth_price.clone(),
+                    latest_binance_ticker_data.clone(),
+                    Decimal::new(1, 3),
+                )
+                .await;
+            assert_eq!(result.is_some(), emit_initial);
+
+            // The same gap is not reported later either way
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price.clone(),
+                    latest_binance_ticker_data.clone(),
+                    Decimal::new(1, 3),
+                )
+                .await;
+            assert!(result.is_none());
+        }
+
+        // A different opportunity is reported after the initial one was suppressed
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            emit_initial: false,
+            ..Default::default()
+        });
+        arbitrage_finder
+            .find_opportunity(
+                latest_pyth_price.clone(),
+                latest_binance_ticker_data.clone(),
+                Decimal::new(1, 3),
+            )
+            .await;
+        latest_binance_ticker_data.write().await.as_mut().unwrap().b = "71.4512".to_string();
+        let result = arbitrage_finder
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await;
+        assert!(result.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_restore_dedup_state() {
+        // l: 68.43263012 h: 71.27225988
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        })));
+        let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+            b: "71.3833".to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        })));
+        let path = std::env::temp_dir().join(format!("dedup-state-{}.json", std::process::id()));
+        let ttl = Duration::from_secs(60);
+
+        for dedup_bucket in [None, Some(Decimal::new(1, 1))] {
+            let settings = FinderSettings {
+                dedup_bucket,
+                ..Default::default()
+            };
+            let mut arbitrage_finder = ArbitrageFinder::with_settings(settings);
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price.clone(),
+                    latest_binance_ticker_data.clone(),
+                    Decimal::new(1, 3),
+                )
+                .await;
+            assert!(result.is_some());
+            DedupState {
+                pairs: HashMap::from([("solusdt".to_string(), arbitrage_finder.dedup_state())]),
+            }
+            .save(&path)
+            .unwrap();
+
+            // The finder of the next run suppresses the repeated opportunity
+            let dedup_state = DedupState::load(&path).unwrap();
+            let mut arbitrage_finder = ArbitrageFinder::with_settings(settings);
+            arbitrage_finder.restore_dedup_state(&dedup_state.pairs["solusdt"], ttl);
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price.clone(),
+                    latest_binance_ticker_data.clone(),
+                    Decimal::new(1, 3),
                 )
                 .await;
             assert!(result.is_none());
+
+            // Unless the state outlived the ttl
+            let mut arbitrage_finder = ArbitrageFinder::with_settings(settings);
+            arbitrage_finder.restore_dedup_state(&dedup_state.pairs["solusdt"], Duration::ZERO);
+            let result = arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price.clone(),
+                    latest_binance_ticker_data.clone(),
+                    Decimal::new(1, 3),
+                )
+                .await;
+            assert!(result.is_some());
+        }
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(DedupState::load(&path).unwrap(), DedupState::default());
+    }
+
+    // Pyth prices and Binance tops of the book of the tests above, along with Binance fees
+    // Shared with verify-math command
+    fn evaluation_vectors() -> Vec<(Price, BookTickerData, Decimal)> {
+        opportunity_vectors()
+            .into_iter()
+            .map(|vector| {
+                (
+                    vector.pyth_price,
+                    vector.binance_ticker_data,
+                    vector.binance_fee,
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_evaluate() {
+        for (pyth_price, binance_ticker_data, binance_fee) in evaluation_vectors() {
+            let opportunity = ArbitrageFinder::new()
+                .find_opportunity(
+                    Arc::new(RwLock::new(Some(pyth_price))),
+                    Arc::new(RwLock::new(Some(binance_ticker_data.clone()))),
+                    binance_fee,
+                )
+                .await;
+            let evaluation = evaluate(
+                pyth_price,
+                &binance_ticker_data,
+                Decimal::new(212, 2),
+                binance_fee,
+            );
+
+            match (opportunity, evaluation) {
+                (Some(opportunity), Some(evaluation)) => {
+                    assert_eq!(evaluation.direction, opportunity.direction);
+                    assert_eq!(evaluation.quantity, opportunity.quantity);
+                    assert_eq!(evaluation.binance_price, opportunity.binance_price);
+                    assert_eq!(evaluation.pyth_price, opportunity.pyth_price);
+                    assert_eq!(
+                        evaluation
+                            .estimated_profit
+                            .round_dp_with_strategy(8, RoundingStrategy::MidpointNearestEven),
+                        opportunity.estimated_profit
+                    );
+                }
+                (None, None) => {}
+                result => panic!("Evaluation differs from find_opportunity: {:?}", result),
+            }
+        }
+    }
+
+    #[cfg(feature = "f64")]
+    #[test]
+    fn test_evaluate_f64() {
+        let to_f64 = |value: Decimal| value.to_string().parse::<f64>().unwrap();
+        let assert_close = |value: f64, expected: Decimal| {
+            let expected = to_f64(expected);
+            assert!(
+                (value - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+                "{} differs from {}",
+                value,
+                expected
+            );
+        };
+
+        for (pyth_price, binance_ticker_data, binance_fee) in evaluation_vectors() {
+            let exact = evaluate(
+                pyth_price,
+                &binance_ticker_data,
+                Decimal::new(212, 2),
+                binance_fee,
+            );
+            let fast = evaluate(pyth_price, &binance_ticker_data, 2.12, to_f64(binance_fee));
+
+            match (exact, fast) {
+                (Some(exact), Some(fast)) => {
+                    assert_eq!(fast.direction, exact.direction);
+                    assert_close(fast.quantity, exact.quantity);
+                    assert_close(fast.estimated_profit, exact.estimated_profit);
+                    assert_close(fast.profit_high, exact.profit_high);
+                    assert_close(fast.binance_price, exact.binance_price);
+                    assert_close(fast.pyth_price, exact.pyth_price);
+                }
+                (None, None) => {}
+                result => panic!("f64 evaluation differs from Decimal one: {:?}", result),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execution_price_model() {
+        let find = |execution_price_model, b: &str, a: &str| {
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: b.to_string(),
+                B: "0.8574".to_string(),
+                a: a.to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+            async move {
+                // l: 68.43263012 h: 71.27225988
+                let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                    price: 69852445,
+                    conf: 669724,
+                    expo: -6,
+                    ..Default::default()
+                })));
+
+                ArbitrageFinder::with_settings(FinderSettings {
+                    execution_price_model,
+                    ..Default::default()
+                })
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(1, 3),
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        let result = find(ExecutionPriceModel::Best, "71.3833", "72.0012").await;
+        assert_eq!(result.binance_price, Decimal::from_str("71.3833").unwrap());
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.03400176").unwrap()
+        );
+
+        let result = find(ExecutionPriceModel::Mid, "71.3833", "72.0012").await;
+        assert_eq!(result.binance_price, Decimal::from_str("71.69225").unwrap());
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.29863059").unwrap()
+        );
+
+        let result = find(ExecutionPriceModel::OffsetBps(10), "71.3833", "72.0012").await;
+        assert_eq!(
+            result.binance_price,
+            Decimal::from_str("71.4546833").unwrap()
+        );
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.09514459").unwrap()
+        );
+
+        // The offset does not cross the spread
+        let result = find(ExecutionPriceModel::OffsetBps(10000), "71.3833", "72.0012").await;
+        assert_eq!(result.binance_price, Decimal::from_str("72.0012").unwrap());
+
+        // Buying moves the price down into the spread
+        let result = find(ExecutionPriceModel::Mid, "67.5", "68.1").await;
+        assert_eq!(result.direction, ArbitrageDirection::BuyBinanceSellDex);
+        assert_eq!(result.binance_price, Decimal::from_str("67.8").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_count_suppressions() {
+        async fn find(
+            arbitrage_finder: &mut ArbitrageFinder,
+            update_id: u64,
+            bid: &str,
+        ) -> Option<ArbitrageOpportunity> {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                u: update_id,
+                b: bid.to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+            arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(1, 3),
+                )
+                .await
+        }
+        let stats = Arc::new(std::sync::Mutex::new(Stats::default()));
+        let finder = |settings| ArbitrageFinder::with_settings(settings).with_stats(stats.clone());
+
+        let mut arbitrage_finder = finder(FinderSettings {
+            min_persistence: Duration::from_secs(60),
+            ..Default::default()
+        });
+        assert!(find(&mut arbitrage_finder, 1, "71.3833").await.is_none());
+        assert_eq!(stats.lock().unwrap().suppressed_by_persistence, 1);
+        // Re-evaluating the same snapshot is not counted again, a new update is
+        for _ in 0..100 {
+            assert!(find(&mut arbitrage_finder, 1, "71.3833").await.is_none());
         }
+        assert_eq!(stats.lock().unwrap().suppressed_by_persistence, 1);
+        assert!(find(&mut arbitrage_finder, 2, "71.3833").await.is_none());
+        assert_eq!(stats.lock().unwrap().suppressed_by_persistence, 2);
+
+        let mut arbitrage_finder = finder(FinderSettings {
+            cooldown: Duration::from_secs(60),
+            ..Default::default()
+        });
+        assert!(find(&mut arbitrage_finder, 1, "71.3833").await.is_some());
+        assert!(find(&mut arbitrage_finder, 2, "71.4512").await.is_none());
+        assert_eq!(stats.lock().unwrap().suppressed_by_cooldown, 1);
+
+        let mut arbitrage_finder = finder(FinderSettings::default());
+        assert!(find(&mut arbitrage_finder, 1, "71.3833").await.is_some());
+        assert!(find(&mut arbitrage_finder, 2, "71.3833").await.is_none());
+        assert_eq!(stats.lock().unwrap().suppressed_as_duplicate, 1);
+
+        let mut arbitrage_finder = finder(FinderSettings {
+            emit_initial: false,
+            ..Default::default()
+        });
+        assert!(find(&mut arbitrage_finder, 1, "71.3833").await.is_none());
+        assert_eq!(stats.lock().unwrap().suppressed_as_initial, 1);
+
+        // No opportunity is not a suppression
+        assert!(find(&mut arbitrage_finder, 2, "71").await.is_none());
+        assert_eq!(
+            *stats.lock().unwrap(),
+            Stats {
+                suppressed_by_persistence: 2,
+                suppressed_by_cooldown: 1,
+                suppressed_as_duplicate: 1,
+                suppressed_as_initial: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inclusive_bounds() {
+        let find = |inclusive_bounds, b: &str, a: &str| {
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: b.to_string(),
+                B: "0.8574".to_string(),
+                a: a.to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+            async move {
+                // l: 68.43263012 h: 71.27225988
+                let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                    price: 69852445,
+                    conf: 669724,
+                    expo: -6,
+                    ..Default::default()
+                })));
+
+                ArbitrageFinder::with_settings(FinderSettings {
+                    inclusive_bounds,
+                    ..Default::default()
+                })
+                .find_opportunity(latest_pyth_price, latest_binance_ticker_data, Decimal::ZERO)
+                .await
+            }
+        };
+
+        // Bid equal to the upper bound
+        assert!(find(false, "71.27225988", "72.0012").await.is_none());
+        let result = find(true, "71.27225988", "72.0012").await.unwrap();
+        assert_eq!(result.direction, ArbitrageDirection::SellBinanceBuyDex);
+        assert_eq!(result.estimated_profit, Decimal::ZERO);
+
+        // Ask equal to the lower bound
+        assert!(find(false, "68.1", "68.43263012").await.is_none());
+        let result = find(true, "68.1", "68.43263012").await.unwrap();
+        assert_eq!(result.direction, ArbitrageDirection::BuyBinanceSellDex);
+        assert_eq!(result.estimated_profit, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_zero_profit() {
+        let stats = Arc::new(std::sync::Mutex::new(Stats::default()));
+        let finder = |zero_profit| {
+            ArbitrageFinder::with_settings(FinderSettings {
+                zero_profit,
+                emit_initial: false,
+                ..Default::default()
+            })
+            .with_stats(stats.clone())
+        };
+        let find = |arbitrage_finder: &mut ArbitrageFinder, binance_price: &str| {
+            arbitrage_finder.calculate_arbitrage_opportunity(
+                Decimal::from_str(binance_price).unwrap(),
+                Decimal::from_str("70").unwrap(),
+                Decimal::from_str("69").unwrap(),
+                Decimal::ZERO,
+                Decimal::ONE,
+                ArbitrageDirection::SellBinanceBuyDex,
+            )
+        };
+
+        // The profit of 0.000000004 rounds to zero, the first one found is suppressed as initial
+        let mut arbitrage_finder = finder(ZeroProfitPolicy::Emit);
+        assert!(find(&mut arbitrage_finder, "70.000000004").is_none());
+        assert!(
+            find(&mut arbitrage_finder, "70.000000003").is_some_and(|opportunity| {
+                opportunity.estimated_profit.is_zero() && opportunity.profit_high == Decimal::ONE
+            })
+        );
+        assert!(find(&mut arbitrage_finder, "70.000000003").is_none());
+        assert_eq!(stats.lock().unwrap().suppressed_as_duplicate, 1);
+
+        // Zero-profit ones neither count as the initial one nor deduplicate the following ones
+        *stats.lock().unwrap() = Stats::default();
+        let mut arbitrage_finder = finder(ZeroProfitPolicy::Suppress);
+        assert!(find(&mut arbitrage_finder, "70.000000004").is_none());
+        assert!(find(&mut arbitrage_finder, "70.000000004").is_none());
+        assert!(find(&mut arbitrage_finder, "70.1").is_none());
+        assert_eq!(
+            find(&mut arbitrage_finder, "70.2")
+                .unwrap()
+                .estimated_profit,
+            Decimal::from_str("0.2").unwrap()
+        );
+        assert_eq!(
+            *stats.lock().unwrap(),
+            Stats {
+                suppressed_as_zero_profit: 2,
+                suppressed_as_initial: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expected_value_strategy() {
+        let find = |strategy, bid: &str| {
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: bid.to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+            async move {
+                // l: 68.43263012 mid: 69.852445 h: 71.27225988
+                let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                    price: 69852445,
+                    conf: 669724,
+                    expo: -6,
+                    ..Default::default()
+                })));
 
-        // Only binance data none
-        {
-            let result = arbitrage_finder
+                ArbitrageFinder::with_settings(FinderSettings {
+                    strategy,
+                    ..Default::default()
+                })
                 .find_opportunity(
-                    Arc::new(RwLock::new(Some(Price::default()))),
-                    Arc::new(RwLock::new(None)),
-                    Decimal::default(),
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(1, 3),
                 )
-                .await;
-            assert!(result.is_none());
-        }
+                .await
+            }
+        };
 
-        // Only pyth data none
-        {
-            let result = arbitrage_finder
-                .find_opportunity(
-                    Arc::new(RwLock::new(None)),
-                    Arc::new(RwLock::new(Some(BookTickerData::default()))),
-                    Decimal::default(),
-                )
-                .await;
-            assert!(result.is_none());
-        }
+        // Bid within the probable price range, but above its mid
+        assert!(find(Strategy::Bound, "70.5").await.is_none());
+        let result = find(Strategy::ExpectedValue(Decimal::ZERO), "70.5")
+            .await
+            .unwrap();
+        assert_eq!(result.direction, ArbitrageDirection::SellBinanceBuyDex);
+        assert_eq!(result.pyth_price, Decimal::from_str("69.852445").unwrap());
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.49476696").unwrap()
+        );
+        assert_eq!(result.profit_high, Decimal::from_str("1.71211624").unwrap());
+        // At the upper bound the trade loses (70.5 - 71.27225988) * 0.8574 - 0.8574 * 70.5 * 0.001
+        assert_eq!(result.profit_low, Decimal::from_str("-0.72258232").unwrap());
+        assert!(result.profit_low < result.estimated_profit);
+        assert!(find(Strategy::ExpectedValue(Decimal::new(5, 1)), "70.5")
+            .await
+            .is_none());
+
+        // Beyond the bound, the expected profit exceeds the worst case one
+        let bound = find(Strategy::Bound, "71.3833").await.unwrap();
+        let expected_value = find(Strategy::ExpectedValue(Decimal::ZERO), "71.3833")
+            .await
+            .unwrap();
+        assert_eq!(
+            bound.estimated_profit,
+            Decimal::from_str("0.03400176").unwrap()
+        );
+        assert!(expected_value.estimated_profit > bound.estimated_profit);
+        assert_eq!(expected_value.profit_low, bound.profit_low);
+        assert_eq!(expected_value.profit_high, bound.profit_high);
     }
 
     #[tokio::test]
-    async fn test_find_opportunity() {
-        let mut arbitrage_finder = ArbitrageFinder::new();
-
-        // SellBinanceBuyDex direction
-        {
+    async fn test_opportunity_lifetime() {
+        async fn find(arbitrage_finder: &mut ArbitrageFinder, bid: &str, ask: &str) {
             // l: 68.43263012 h: 71.27225988
             let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
                 price: 69852445,
@@ -228,58 +3499,313 @@ mod tests {
                 ..Default::default()
             })));
             let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
-                b: "71.3833".to_string(),
+                b: bid.to_string(),
                 B: "0.8574".to_string(),
-                a: "72.0012".to_string(),
+                a: ask.to_string(),
                 A: "0.9245".to_string(),
                 ..Default::default()
             })));
-
-            let result = arbitrage_finder
+            arbitrage_finder
                 .find_opportunity(
                     latest_pyth_price,
                     latest_binance_ticker_data,
                     Decimal::new(1, 3),
                 )
-                .await
-                .unwrap();
-            assert_eq!(result.direction, ArbitrageDirection::SellBinanceBuyDex);
-            assert_eq!(result.quantity, Decimal::from_str("0.8574").unwrap());
-            assert_eq!(
-                result.estimated_profit,
-                Decimal::from_str("0.03400176").unwrap()
-            );
+                .await;
         }
 
-        // SellBinanceBuyDex direction, but too large fee
-        {
-            // l: 68.43263012 h: 71.27225988
+        let stats = Arc::new(std::sync::Mutex::new(Stats {
+            lifetime_histogram: LifetimeHistogram::new(vec![
+                Duration::from_secs(10),
+                Duration::from_millis(100),
+                Duration::from_secs(1),
+            ]),
+            ..Default::default()
+        }));
+        let clock = Arc::new(MockClock::new());
+        let mut arbitrage_finder = ArbitrageFinder::new()
+            .with_stats(stats.clone())
+            .with_clock(clock.clone());
+
+        // Open for 50 milliseconds, including a suppressed duplicate
+        find(&mut arbitrage_finder, "71.3833", "72.0012").await;
+        clock.advance(Duration::from_millis(30));
+        find(&mut arbitrage_finder, "71.3833", "72.0012").await;
+        clock.advance(Duration::from_millis(20));
+        find(&mut arbitrage_finder, "71", "72.0012").await;
+
+        // Open for 500 milliseconds, closed by the other direction, which stays open for 20 seconds
+        find(&mut arbitrage_finder, "71.4512", "72.0012").await;
+        clock.advance(Duration::from_millis(500));
+        find(&mut arbitrage_finder, "68", "68.1").await;
+        clock.advance(Duration::from_secs(20));
+        find(&mut arbitrage_finder, "69", "70").await;
+
+        // Open for exactly 10 seconds, the lower edge is inclusive
+        find(&mut arbitrage_finder, "68", "68.1").await;
+        clock.advance(Duration::from_secs(10));
+        find(&mut arbitrage_finder, "69", "70").await;
+
+        // Still open, so not recorded
+        find(&mut arbitrage_finder, "68", "68.1").await;
+        clock.advance(Duration::from_secs(5));
+        find(&mut arbitrage_finder, "68", "68.2").await;
+
+        let stats = stats.lock().unwrap();
+        assert_eq!(
+            stats
+                .lifetime_histogram
+                .buckets()
+                .iter()
+                .map(|(_, _, count)| *count)
+                .collect::<Vec<_>>(),
+            vec![1, 1, 0, 2]
+        );
+        assert!(stats.summary().ends_with(
+            "Opportunity lifetime histogram:\n  < 100ms: 1\n  100ms - 1s: 1\n  1s - 10s: 0\n  >= 10s: 2"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_min_confidence() {
+        let find = |min_confidence| async move {
+            // 69.852445 +- 0.000001
             let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
                 price: 69852445,
-                conf: 669724,
+                conf: 1,
                 expo: -6,
                 ..Default::default()
             })));
             let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
-                b: "71.3833".to_string(),
+                b: "69.9".to_string(),
                 B: "0.8574".to_string(),
-                a: "72.0012".to_string(),
+                a: "69.95".to_string(),
                 A: "0.9245".to_string(),
                 ..Default::default()
             })));
 
-            let result = arbitrage_finder
-                .find_opportunity(
-                    latest_pyth_price,
-                    latest_binance_ticker_data,
-                    Decimal::new(5, 3),
+            ArbitrageFinder::with_settings(FinderSettings {
+                min_confidence,
+                ..Default::default()
+            })
+            .find_opportunity(latest_pyth_price, latest_binance_ticker_data, Decimal::ZERO)
+            .await
+        };
+
+        // The collapsed range makes a deviation of 0.05 an opportunity
+        assert!(find(None).await.is_some());
+        // h: 69.958445
+        let min_confidence = ConfidenceFloor::Absolute(Decimal::from_str("0.05").unwrap());
+        assert!(find(Some(min_confidence)).await.is_none());
+        // h: 70.00053336
+        assert!(find(Some(ConfidenceFloor::Bps(10))).await.is_none());
+
+        // Larger reported confidence is kept
+        let price = Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        };
+        assert_eq!(min_confidence.apply(price), Some(price));
+        assert_eq!(ConfidenceFloor::Bps(10).apply(price), Some(price));
+        assert_eq!(
+            ConfidenceFloor::Bps(10)
+                .apply(Price { conf: 1, ..price })
+                .unwrap()
+                .conf,
+            69853
+        );
+    }
+
+    #[tokio::test]
+    async fn test_leg_fees() {
+        // l: 68.43263012 h: 71.27225988
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        })));
+        let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+            b: "71.3833".to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        })));
+        // Binance taker, DEX maker, the given fee of 0.005 is replaced
+        let result = ArbitrageFinder::with_settings(FinderSettings {
+            leg_fees: Some(LegFees {
+                binance: Decimal::new(1, 3),
+                dex: Decimal::new(2, 4),
+            }),
+            ..Default::default()
+        })
+        .find_opportunity(
+            latest_pyth_price,
+            latest_binance_ticker_data,
+            Decimal::new(5, 3),
+        )
+        .await
+        .unwrap();
+
+        // gross edge: (71.3833 - 71.27225988) * 0.8574 = 0.095205798888
+        // Binance fee: 0.8574 * 71.3833 * 0.001 = 0.06120404142
+        // DEX fee: 0.8574 * 71.27225988 * 0.0002 = 0.0122217671242224
+        assert_eq!(
+            result.profit_breakdown.dex_fee,
+            Decimal::from_str("0.0122217671242224").unwrap()
+        );
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.02177999").unwrap()
+        );
+        // At the lower bound: (71.3833 - 68.43263012) * 0.8574 - 0.06120404142 - 0.8574 * 68.43263012 * 0.0002
+        assert_eq!(result.profit_high, Decimal::from_str("2.45696549").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_solana_fee() {
+        // l: 68.43263012 h: 71.27225988
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        })));
+        let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+            b: "71.3833".to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        })));
+        // Base fee of 5000 and priority fee of 95000 lamports, i.e. 0.0001 SOL
+        let finder = |sol_price| {
+            ArbitrageFinder::with_settings(FinderSettings {
+                solana_fee: Some(SolanaFee {
+                    lamports: 100000,
+                    sol_price,
+                }),
+                ..Default::default()
+            })
+        };
+
+        // 0.0001 SOL at 150 is 0.015, net of the fee-free 0.034001757468 and 2.468700313692
+        let result = finder(Some(Decimal::from(150)))
+            .find_opportunity(
+                latest_pyth_price.clone(),
+                latest_binance_ticker_data.clone(),
+                Decimal::new(1, 3),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.profit_breakdown.dex_fee, Decimal::new(15, 3));
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.01900176").unwrap()
+        );
+        assert_eq!(result.profit_high, Decimal::from_str("2.45370031").unwrap());
+
+        // At the crossed bound of the pair quoting SOL: 0.0001 * 71.27225988 = 0.007127225988
+        let result = finder(None)
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.profit_breakdown.dex_fee,
+            Decimal::from_str("0.007127225988").unwrap()
+        );
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.02687453").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_reemit_improvement() {
+        let stats = Arc::new(std::sync::Mutex::new(Stats::default()));
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            reemit_improvement: Some(Decimal::from_str("0.1").unwrap()),
+            ..Default::default()
+        })
+        .with_stats(stats.clone());
+        let mut find = |binance_price: &str| {
+            arbitrage_finder
+                .calculate_arbitrage_opportunity(
+                    Decimal::from_str(binance_price).unwrap(),
+                    Decimal::from_str("70").unwrap(),
+                    Decimal::from_str("69").unwrap(),
+                    Decimal::ZERO,
+                    Decimal::ONE,
+                    ArbitrageDirection::SellBinanceBuyDex,
                 )
-                .await;
-            assert!(result.is_none())
-        }
+                .map(|opportunity| opportunity.estimated_profit)
+        };
 
-        // BuyBinanceSellDex direction
-        {
+        assert_eq!(find("70.1"), Some(Decimal::from_str("0.1").unwrap()));
+        // Improvements by less than 0.1 over the last report are suppressed, as are worsenings
+        assert_eq!(find("70.15"), None);
+        assert_eq!(find("70.05"), None);
+        assert_eq!(find("70.19"), None);
+        assert_eq!(find("70.3"), Some(Decimal::from_str("0.3").unwrap()));
+        assert_eq!(find("70.35"), None);
+        assert_eq!(stats.lock().unwrap().suppressed_as_not_improved, 4);
+
+        // Once closed, the opportunity is reported again as soon as it reopens
+        assert_eq!(find("70"), None);
+        assert_eq!(find("70.1"), Some(Decimal::from_str("0.1").unwrap()));
+    }
+
+    #[test]
+    fn test_alert_on_direction_change() {
+        let stats = Arc::new(std::sync::Mutex::new(Stats::default()));
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            alert_on: AlertOn::DirectionChange,
+            ..Default::default()
+        })
+        .with_stats(stats.clone());
+        let mut find = |binance_price: &str, direction| {
+            arbitrage_finder
+                .calculate_arbitrage_opportunity(
+                    Decimal::from_str(binance_price).unwrap(),
+                    Decimal::from_str("70").unwrap(),
+                    Decimal::from_str("69").unwrap(),
+                    Decimal::ZERO,
+                    Decimal::ONE,
+                    direction,
+                )
+                .map(|opportunity| opportunity.direction)
+        };
+        let (sell, buy) = (
+            ArbitrageDirection::SellBinanceBuyDex,
+            ArbitrageDirection::BuyBinanceSellDex,
+        );
+
+        assert_eq!(find("70.1", sell), Some(sell));
+        // Changed opportunities in the same direction are suppressed, even after it closes
+        assert_eq!(find("70.2", sell), None);
+        assert_eq!(find("70", sell), None);
+        assert_eq!(find("70.3", sell), None);
+        assert_eq!(stats.lock().unwrap().suppressed_as_same_direction, 2);
+        // Only the first one in the other direction is reported
+        assert_eq!(find("69.9", buy), Some(buy));
+        assert_eq!(find("69.8", buy), None);
+        assert_eq!(find("70.1", sell), Some(sell));
+    }
+
+    #[tokio::test]
+    async fn test_comparison_basis() {
+        async fn find(
+            comparison_basis: ComparisonBasis,
+            bid: &str,
+            ask: &str,
+        ) -> Option<ArbitrageOpportunity> {
             // l: 68.43263012 h: 71.27225988
             let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
                 price: 69852445,
@@ -288,32 +3814,245 @@ mod tests {
                 ..Default::default()
             })));
             let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
-                b: "67.5421".to_string(),
-                B: "1.1258".to_string(),
-                a: "67.8423".to_string(),
-                A: "2.5569".to_string(),
+                b: bid.to_string(),
+                B: "1".to_string(),
+                a: ask.to_string(),
+                A: "2".to_string(),
                 ..Default::default()
             })));
+            ArbitrageFinder::with_settings(FinderSettings {
+                comparison_basis,
+                ..Default::default()
+            })
+            .find_opportunity(latest_pyth_price, latest_binance_ticker_data, Decimal::ZERO)
+            .await
+        }
 
-            let result = arbitrage_finder
-                .find_opportunity(
-                    latest_pyth_price,
-                    latest_binance_ticker_data,
+        // Mid of 71.3 is above the upper bound while the bid is not
+        assert!(find(ComparisonBasis::Crossing, "71.2", "71.4")
+            .await
+            .is_none());
+        let result = find(ComparisonBasis::Mid, "71.2", "71.4").await.unwrap();
+        assert_eq!(result.direction, ArbitrageDirection::SellBinanceBuyDex);
+        assert_eq!(result.binance_price, Decimal::from_str("71.3").unwrap());
+        assert_eq!(result.quantity, Decimal::ONE);
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.02774012").unwrap()
+        );
+
+        // Both fire once the whole book is beyond a bound, mid assuming a fill half the spread better
+        let crossing = find(ComparisonBasis::Crossing, "68", "68.2").await.unwrap();
+        let mid = find(ComparisonBasis::Mid, "68", "68.2").await.unwrap();
+        assert_eq!(crossing.direction, mid.direction);
+        assert_eq!(mid.binance_price, Decimal::from_str("68.1").unwrap());
+        // (68.43263012 - 68.2) * 2 and (68.43263012 - 68.1) * 2
+        assert_eq!(
+            crossing.estimated_profit,
+            Decimal::from_str("0.46526024").unwrap()
+        );
+        assert_eq!(
+            mid.estimated_profit,
+            Decimal::from_str("0.66526024").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_post_reconnect_grace() {
+        let stats = Arc::new(std::sync::Mutex::new(Stats::default()));
+        let clock = Arc::new(MockClock::starting_at(1_000_000));
+        let last_reconnect = Arc::new(AtomicU64::new(0));
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            post_reconnect_grace: Duration::from_millis(500),
+            ..Default::default()
+        })
+        .with_clock(clock.clone())
+        .with_stats(stats.clone())
+        .with_last_reconnect(last_reconnect.clone());
+        let mut find = |binance_price: &str| {
+            arbitrage_finder
+                .calculate_arbitrage_opportunity(
+                    Decimal::from_str(binance_price).unwrap(),
+                    Decimal::from_str("70").unwrap(),
+                    Decimal::from_str("69").unwrap(),
+                    Decimal::ZERO,
+                    Decimal::ONE,
+                    ArbitrageDirection::SellBinanceBuyDex,
+                )
+                .is_some()
+        };
+
+        // Without a reconnection nothing is suppressed
+        assert!(find("70.1"));
+
+        last_reconnect.store(1_000_000, Ordering::Relaxed);
+        assert!(!find("70.2"));
+        clock.advance(Duration::from_millis(499));
+        assert!(!find("70.3"));
+        assert_eq!(stats.lock().unwrap().suppressed_after_reconnect, 2);
+
+        clock.advance(Duration::from_millis(1));
+        assert!(find("70.4"));
+    }
+
+    #[test]
+    fn test_fill_probability() {
+        let find = |fill_probability| {
+            build_opportunity(
+                FinderSettings {
+                    fill_probability,
+                    ..Default::default()
+                },
+                "70.5",
+                "70",
+                ArbitrageDirection::SellBinanceBuyDex,
+            )
+            .unwrap()
+        };
+
+        // Gross edge of (70.5 - 70) * 2 less the fee of 2 * 70.5 * 0.001
+        let filled = find(None);
+        assert_eq!(filled.estimated_profit, Decimal::from_str("0.859").unwrap());
+        // Profits and the breakdown are scaled by the fill probability alike, so the breakdown still adds up
+        let result = find(Some(Decimal::from_str("0.6").unwrap()));
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("0.5154").unwrap()
+        );
+        assert_eq!(result.profit_low, result.estimated_profit);
+        assert_eq!(result.profit_high, Decimal::from_str("1.7154").unwrap());
+        assert_eq!(
+            result.profit_breakdown.gross_edge,
+            Decimal::from_str("0.6").unwrap()
+        );
+        assert_eq!(
+            result.profit_breakdown.binance_fee,
+            Decimal::from_str("0.0846").unwrap()
+        );
+        assert_eq!(result.profit_breakdown.net(), result.estimated_profit);
+    }
+
+    #[tokio::test]
+    async fn test_pyth_twap() {
+        async fn find(
+            arbitrage_finder: &mut ArbitrageFinder,
+            price: i64,
+            conf: u64,
+            publish_time: i64,
+        ) -> Option<ArbitrageOpportunity> {
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price,
+                conf,
+                expo: -2,
+                publish_time,
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: "104".to_string(),
+                B: "1".to_string(),
+                a: "104.5".to_string(),
+                A: "1".to_string(),
+                ..Default::default()
+            })));
+            arbitrage_finder
+                .find_opportunity(latest_pyth_price, latest_binance_ticker_data, Decimal::ZERO)
+                .await
+        }
+
+        let settings = FinderSettings {
+            confidence_factor: Decimal::ONE,
+            ..Default::default()
+        };
+        let clock = Arc::new(MockClock::starting_at(5000));
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            pyth_twap_window: Some(Duration::from_secs(10)),
+            ..settings
+        })
+        .with_clock(clock.clone());
+
+        // 100 +- 1 for 5 seconds, then 110 +- 3 for 5 seconds average to 105 +- 2
+        let result = find(&mut arbitrage_finder, 10000, 100, 0).await.unwrap();
+        assert_eq!(result.direction, ArbitrageDirection::SellBinanceBuyDex); // the bid of 104 is above 99 - 101
+        clock.advance(Duration::from_secs(5));
+        assert!(find(&mut arbitrage_finder, 11000, 300, 5).await.is_none());
+        assert_eq!(
+            arbitrage_finder.reference_pyth_price(Price {
+                price: 11000,
+                conf: 300,
+                expo: -2,
+                publish_time: 5,
+            }),
+            Price {
+                price: 10500,
+                conf: 200,
+                expo: -2,
+                publish_time: 5,
+            }
+        );
+
+        // The ask of 104.5 is within 103 - 107, while it is below 109 - 111 of the latest price
+        let result = find(&mut ArbitrageFinder::with_settings(settings), 11000, 300, 5)
+            .await
+            .unwrap();
+        assert_eq!(result.direction, ArbitrageDirection::BuyBinanceSellDex);
+        assert_eq!(result.pyth_price, Decimal::from_str("107").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_tick() {
+        // l: 68.43263012 h: 71.27225988
+        let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        })));
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            cooldown: Duration::from_secs(60),
+            ..Default::default()
+        });
+        async fn evaluate(
+            arbitrage_finder: &mut ArbitrageFinder,
+            latest_pyth_price: &Arc<RwLock<Option<Price>>>,
+            bid: Option<&str>,
+        ) -> EvalOutcome {
+            let latest_binance_ticker_data = bid.map(|bid| BookTickerData {
+                b: bid.to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            });
+            arbitrage_finder
+                .evaluate_tick(
+                    latest_pyth_price.clone(),
+                    Arc::new(RwLock::new(latest_binance_ticker_data)),
                     Decimal::new(1, 3),
                 )
                 .await
-                .unwrap();
-            assert_eq!(result.direction, ArbitrageDirection::BuyBinanceSellDex);
-            assert_eq!(result.quantity, Decimal::from_str("2.5569").unwrap());
-            assert_eq!(
-                result.estimated_profit,
-                Decimal::from_str("1.33594911").unwrap()
-            );
         }
 
-        // BuyBinanceSellDex direction, but too large fee
-        {
-            // l: 68.43263012 h: 71.27225988
+        let finder = &mut arbitrage_finder;
+        assert_eq!(
+            evaluate(finder, &latest_pyth_price, None).await,
+            EvalOutcome::NoData
+        );
+        assert_eq!(
+            evaluate(finder, &latest_pyth_price, Some("69.9")).await,
+            EvalOutcome::NoOpportunity
+        );
+        let outcome = evaluate(finder, &latest_pyth_price, Some("71.3833")).await;
+        assert!(matches!(outcome, EvalOutcome::Opportunity(opportunity)
+            if opportunity.estimated_profit == Decimal::from_str("0.03400176").unwrap()));
+        assert!(outcome.into_option().is_some());
+        let outcome = evaluate(finder, &latest_pyth_price, Some("71.4512")).await;
+        assert_eq!(outcome, EvalOutcome::Filtered(Suppression::Cooldown));
+        assert_eq!(outcome.into_option(), None);
+    }
+
+    #[tokio::test]
+    async fn test_sigma_hysteresis() {
+        async fn find(arbitrage_finder: &mut ArbitrageFinder, bid: &str) -> bool {
+            // l: 68.43263012 h: 71.27225988, sigma: 0.669724
             let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
                 price: 69852445,
                 conf: 669724,
@@ -321,25 +4060,62 @@ mod tests {
                 ..Default::default()
             })));
             let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
-                b: "67.5421".to_string(),
-                B: "1.1258".to_string(),
-                a: "67.8423".to_string(),
-                A: "2.5569".to_string(),
+                b: bid.to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
                 ..Default::default()
             })));
-
-            let result = arbitrage_finder
-                .find_opportunity(
-                    latest_pyth_price,
-                    latest_binance_ticker_data,
-                    Decimal::new(1, 2),
-                )
-                .await;
-            assert!(result.is_none());
+            arbitrage_finder
+                .find_opportunity(latest_pyth_price, latest_binance_ticker_data, Decimal::ZERO)
+                .await
+                .is_some()
         }
+        let finder = |close: &str| {
+            ArbitrageFinder::with_settings(FinderSettings {
+                hysteresis: Some(SigmaHysteresis {
+                    open: Decimal::from_str("0.5").unwrap(),
+                    close: Decimal::from_str(close).unwrap(),
+                }),
+                ..Default::default()
+            })
+        };
 
-        // No opportunity found
-        {
+        // Opens above 71.27225988 + 0.5 * 0.669724 = 71.60712188, closes within the range
+        let mut arbitrage_finder = finder("0");
+        assert!(!find(&mut arbitrage_finder, "71.6").await);
+        assert!(find(&mut arbitrage_finder, "71.61").await);
+        assert!(find(&mut arbitrage_finder, "71.3").await);
+        assert!(!find(&mut arbitrage_finder, "71.27").await);
+        assert!(!find(&mut arbitrage_finder, "71.3").await);
+
+        // Stays open above 71.27225988 + 0.25 * 0.669724 = 71.43969088
+        let mut arbitrage_finder = finder("0.25");
+        assert!(find(&mut arbitrage_finder, "71.61").await);
+        assert!(find(&mut arbitrage_finder, "71.44").await);
+        assert!(!find(&mut arbitrage_finder, "71.43").await);
+        assert!(!find(&mut arbitrage_finder, "71.5").await);
+
+        // The band of 1% replaces the range, h: 70.55096945, but sigma stays the Pyth confidence,
+        // so it opens above 70.55096945 + 0.5 * 0.669724 = 70.88583145
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            pyth_band_bps: Some(100),
+            hysteresis: Some(SigmaHysteresis {
+                open: Decimal::from_str("0.5").unwrap(),
+                close: Decimal::ZERO,
+            }),
+            ..Default::default()
+        });
+        assert!(!find(&mut arbitrage_finder, "70.88").await);
+        assert!(find(&mut arbitrage_finder, "70.89").await);
+    }
+
+    #[tokio::test]
+    async fn test_dex_pool_price() {
+        async fn find(
+            dex_pool_price: Option<&str>,
+            dex_price_weight: &str,
+        ) -> Option<ArbitrageOpportunity> {
             // l: 68.43263012 h: 71.27225988
             let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
                 price: 69852445,
@@ -348,21 +4124,135 @@ mod tests {
                 ..Default::default()
             })));
             let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
-                b: "69.2222".to_string(),
-                B: "1.1258".to_string(),
-                a: "69.1111".to_string(),
-                A: "2.5569".to_string(),
+                b: "71.3833".to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
                 ..Default::default()
             })));
+            ArbitrageFinder::with_settings(FinderSettings {
+                dex_price_weight: Decimal::from_str(dex_price_weight).unwrap(),
+                ..Default::default()
+            })
+            .with_dex_pool_price(Arc::new(RwLock::new(
+                dex_pool_price.map(|price| Decimal::from_str(price).unwrap()),
+            )))
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await
+        }
 
-            let result = arbitrage_finder
-                .find_opportunity(
-                    latest_pyth_price,
-                    latest_binance_ticker_data,
-                    Decimal::new(1, 3),
-                )
-                .await;
-            assert!(result.is_none());
+        // Until the pool price is known, the Pyth price is compared
+        assert_eq!(
+            find(None, "1").await.unwrap().estimated_profit,
+            Decimal::from_str("0.03400176").unwrap()
+        );
+        // The pool trades higher, l: 69.08018512 h: 71.91981488
+        assert!(find(Some("70.5"), "1").await.is_none());
+        // Blended halfway, 70.1762225, l: 68.75640762 h: 71.59603738
+        assert!(find(Some("70.5"), "0.5").await.is_none());
+        // The pool trades lower, l: 68.08018512 h: 70.91981488
+        let opportunity = find(Some("69.5"), "1").await.unwrap();
+        assert_eq!(
+            opportunity.pyth_price,
+            Decimal::from_str("70.91981488").unwrap()
+        );
+        assert_eq!(
+            opportunity.estimated_profit,
+            Decimal::from_str("0.3361881").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_min_edge_ticks() {
+        async fn find(tick_size: Decimal) -> Option<ArbitrageOpportunity> {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: "71.3833".to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+            ArbitrageFinder::with_settings(FinderSettings {
+                min_edge_ticks: Some(3),
+                ..Default::default()
+            })
+            .with_tick_size(tick_size)
+            .find_opportunity(
+                latest_pyth_price,
+                latest_binance_ticker_data,
+                Decimal::new(1, 3),
+            )
+            .await
         }
+
+        // The edge of 71.3833 - 71.27225988 = 0.11104012 is at least 3 ticks of 0.01, i.e. 0.03
+        assert!(find(Decimal::new(1, 2)).await.is_some());
+        // But not 3 ticks of 0.05, i.e. 0.15
+        assert!(find(Decimal::new(5, 2)).await.is_none());
+    }
+
+    // Builds the opportunity of 2 units with a Binance fee of 0.001 and the opposite bound at 69
+    fn build_opportunity(
+        settings: FinderSettings,
+        binance_price: &str,
+        pyth_price: &str,
+        direction: ArbitrageDirection,
+    ) -> Option<ArbitrageOpportunity> {
+        ArbitrageFinder::with_settings(settings).build_opportunity(
+            Decimal::from_str(binance_price).unwrap(),
+            Decimal::from_str(pyth_price).unwrap(),
+            Decimal::from_str("69").unwrap(),
+            Decimal::from_str("0.001").unwrap(),
+            Decimal::from_str("2").unwrap(),
+            direction,
+        )
+    }
+
+    #[test]
+    fn test_profit_breakdown() {
+        // Gross edge is the absolute difference times quantity, (68.5 - 68) * 2, and the net subtracts fees of both legs
+        let result = build_opportunity(
+            FinderSettings {
+                leg_fees: Some(LegFees {
+                    binance: Decimal::from_str("0.002").unwrap(),
+                    dex: Decimal::from_str("0.001").unwrap(),
+                }),
+                ..Default::default()
+            },
+            "68",
+            "68.5",
+            ArbitrageDirection::BuyBinanceSellDex,
+        )
+        .unwrap();
+        assert_eq!(result.profit_breakdown.gross_edge, Decimal::ONE);
+        assert_eq!(
+            result.profit_breakdown.binance_fee,
+            Decimal::from_str("0.272").unwrap()
+        );
+        assert_eq!(
+            result.profit_breakdown.dex_fee,
+            Decimal::from_str("0.137").unwrap()
+        );
+        assert_eq!(
+            result.profit_breakdown.net(),
+            Decimal::from_str("0.591").unwrap()
+        );
+        assert_eq!(result.estimated_profit, result.profit_breakdown.net());
+    }
+
+    #[test]
+    fn test_required_capital_overflow() {
+        assert!(RequiredCapital::calculate(Decimal::MAX, Decimal::TWO, Decimal::ONE).is_none());
     }
 }
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+/*
+    Struct computing exponentially growing delays between retries of a failing operation
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    next_delay: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            max_delay,
+            next_delay: initial_delay,
+        }
+    }
+
+    /*
+        Returns delay before the next retry, doubling the following one up to the maximum
+    */
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next_delay;
+        self.next_delay = (delay * 2).min(self.max_delay);
+        delay
+    }
+
+    /*
+        Starts over from the initial delay once the operation succeeds
+    */
+    pub fn reset(&mut self) {
+        self.next_delay = self.initial_delay;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Backoff;
+
+    #[test]
+    fn test_next_delay() {
+        let mut backoff = Backoff::new(Duration::from_millis(500), Duration::from_secs(3));
+        let delays = (0..5).map(|_| backoff.next_delay()).collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [500, 1000, 2000, 3000, 3000].map(Duration::from_millis)
+        );
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(500));
+    }
+}
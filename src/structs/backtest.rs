@@ -0,0 +1,425 @@
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use pyth_sdk_solana::Price;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::{
+    arbitrage_finder::{ArbitrageFinder, ArbitrageOpportunity, FinderSettings},
+    cex::binance::BookTickerData,
+    clock::MockClock,
+};
+
+/*
+    Struct representing a single recorded state of both feeds
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedTick {
+    pub timestamp: u64, // milliseconds since UNIX epoch
+    pub pyth: Price,
+    pub binance: BookTickerData,
+    // Whether a gap present at this tick persisted long enough to be captured, if known
+    #[serde(default)]
+    pub captured: Option<bool>,
+}
+
+/*
+    Struct replaying recorded ticks through the arbitrage finder
+*/
+pub struct Backtest {
+    ticks: Vec<RecordedTick>,
+    binance_fee: Decimal,
+    replay_speed: f64,
+}
+
+impl Backtest {
+    pub fn new(ticks: Vec<RecordedTick>, binance_fee: Decimal) -> Self {
+        Self {
+            ticks,
+            binance_fee,
+            replay_speed: 0.0,
+        }
+    }
+
+    /*
+        Sets pace of replaying the ticks relative to the recorded one, e.g. 1.0 for real time and 2.0 for twice as fast
+        0.0 replays the ticks as fast as possible
+    */
+    pub fn with_replay_speed(mut self, replay_speed: f64) -> Self {
+        self.replay_speed = replay_speed;
+        self
+    }
+
+    /*
+        Loads ticks from a file holding one JSON object per line
+    */
+    pub fn load(path: &Path, binance_fee: Decimal) -> Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut ticks = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let tick = serde_json::from_str::<RecordedTick>(&line)
+                .map_err(|err| anyhow!(format!("Invalid tick in line {}: {}", index + 1, err)))?;
+            ticks.push(tick);
+        }
+
+        Ok(Self::new(ticks, binance_fee))
+    }
+
+    /*
+        Loads ticks from all the files, expanding * and ? in their names, and merges them into a single stream in timestamp order
+        Ticks with equal timestamps keep the order of the files and of their lines, ticks repeated in overlapping files are kept once
+    */
+    pub fn load_all(paths: &[PathBuf], binance_fee: Decimal) -> Result<Self> {
+        let mut ticks = Vec::new();
+        for path in expand_paths(paths)? {
+            let backtest = Self::load(&path, binance_fee)
+                .map_err(|err| anyhow!("Could not load {}: {}", path.display(), err))?;
+            ticks.extend(backtest.ticks);
+        }
+
+        ticks.sort_by_key(|tick| tick.timestamp); // stable
+        ticks.dedup();
+
+        Ok(Self::new(ticks, binance_fee))
+    }
+
+    /*
+        Returns number of the loaded ticks
+    */
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+
+    /*
+        Runs a fresh finder over all the ticks, returning every found opportunity along with the tick it was found at
+        The finder reads the recorded time of each tick, so time-based suppressions do not depend on the replay speed
+    */
+    pub async fn run(
+        &self,
+        settings: FinderSettings,
+    ) -> Vec<(&RecordedTick, ArbitrageOpportunity)> {
+        let clock = Arc::new(MockClock::starting_at(
+            self.ticks.first().map_or(0, |tick| tick.timestamp),
+        ));
+        let mut arbitrage_finder =
+            ArbitrageFinder::with_settings(settings).with_clock(clock.clone());
+        let mut found = Vec::new();
+        let mut previous_timestamp = None;
+
+        for tick in &self.ticks {
+            if let Some(previous_timestamp) = previous_timestamp {
+                self.wait_between(previous_timestamp, tick.timestamp).await;
+                clock.advance(Duration::from_millis(
+                    tick.timestamp.saturating_sub(previous_timestamp),
+                ));
+            }
+            previous_timestamp = Some(tick.timestamp);
+
+            let maybe_opportunity = arbitrage_finder
+                .find_opportunity(
+                    Arc::new(RwLock::new(Some(tick.pyth))),
+                    Arc::new(RwLock::new(Some(tick.binance.clone()))),
+                    self.binance_fee,
+                )
+                .await;
+            if let Some(opportunity) = maybe_opportunity {
+                found.push((tick, opportunity));
+            }
+        }
+
+        found
+    }
+
+    /*
+        Sleeps for the recorded time between the ticks scaled by the replay speed
+    */
+    async fn wait_between(&self, previous_timestamp: u64, timestamp: u64) {
+        if self.replay_speed <= 0.0 {
+            return;
+        }
+
+        let gap = Duration::from_millis(timestamp.saturating_sub(previous_timestamp));
+        tokio::time::sleep(gap.div_f64(self.replay_speed)).await;
+    }
+}
+
+/*
+    Replaces every path whose file name contains * or ? with the matching files of its directory, sorted by name
+    Fails if such a pattern matches no file
+*/
+fn expand_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        let Some(pattern) = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .filter(|file_name| file_name.contains(['*', '?']))
+        else {
+            expanded.push(path.clone());
+            continue;
+        };
+
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut matching = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|file_name| file_name.to_str())
+                    .is_some_and(|file_name| wildcard_match(pattern, file_name))
+            })
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            return Err(anyhow!("No file matches {}", path.display()));
+        }
+        matching.sort();
+        expanded.extend(matching);
+    }
+
+    Ok(expanded)
+}
+
+/*
+    Returns whether the name matches the pattern, in which * matches any sequence of characters and ? a single one
+*/
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    // Whether the pattern prefix of length i matches the name prefix of length j
+    let mut matches = vec![vec![false; name.len() + 1]; pattern.len() + 1];
+    matches[0][0] = true;
+
+    for i in 1..=pattern.len() {
+        for j in 0..=name.len() {
+            matches[i][j] = match pattern[i - 1] {
+                '*' => matches[i - 1][j] || (j > 0 && matches[i][j - 1]),
+                '?' => j > 0 && matches[i - 1][j - 1],
+                c => j > 0 && matches[i - 1][j - 1] && name[j - 1] == c,
+            };
+        }
+    }
+
+    matches[pattern.len()][name.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+
+    use crate::structs::{
+        arbitrage_finder::{ArbitrageDirection, FinderSettings},
+        cex::binance::BookTickerData,
+    };
+
+    use super::{Backtest, RecordedTick};
+
+    fn ticks_at(timestamps: &[u64]) -> Vec<RecordedTick> {
+        timestamps
+            .iter()
+            .map(|timestamp| RecordedTick {
+                timestamp: *timestamp,
+                pyth: Price::default(),
+                binance: BookTickerData {
+                    b: "0".to_string(),
+                    B: "0".to_string(),
+                    a: "0".to_string(),
+                    A: "0".to_string(),
+                    ..Default::default()
+                },
+                captured: None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_load_and_run() {
+        let path = std::env::temp_dir().join(format!("keyrock-task-{}.jsonl", std::process::id()));
+        let ticks = [
+            r#"{"timestamp":1000,"pyth":{"price":"69852445","conf":"669724","expo":-6,"publish_time":1},"binance":{"u":1,"s":"SOLUSDT","b":"71.3833","B":"0.8574","a":"72.0012","A":"0.9245"}}"#,
+            "",
+            r#"{"timestamp":2000,"pyth":{"price":"69852445","conf":"669724","expo":-6,"publish_time":2},"binance":{"u":2,"s":"SOLUSDT","b":"69.2222","B":"1.1258","a":"69.1111","A":"2.5569"},"captured":false}"#,
+        ];
+        std::fs::write(&path, ticks.join("\n")).unwrap();
+
+        let backtest = Backtest::load(&path, Decimal::new(1, 3)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(backtest.ticks.len(), 2);
+        assert_eq!(backtest.ticks[1].captured, Some(false));
+
+        let found = backtest.run(FinderSettings::default()).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0.timestamp, 1000);
+        assert_eq!(found[0].1.direction, ArbitrageDirection::SellBinanceBuyDex);
+    }
+
+    #[tokio::test]
+    async fn test_pyth_time_offset() {
+        // Pyth prices are published 2 seconds before the ticks are recorded, apart from the last one
+        let ticks = [(10_000, 8), (11_000, 9), (12_000, 10), (13_000, 13)]
+            .into_iter()
+            .enumerate()
+            .map(|(index, (timestamp, publish_time))| RecordedTick {
+                timestamp,
+                pyth: Price {
+                    price: 69852445,
+                    conf: 669724,
+                    expo: -6,
+                    publish_time,
+                },
+                binance: BookTickerData {
+                    b: "71.3833".to_string(),
+                    B: format!("0.{}", 8574 + index), // distinct, so none of them is deduplicated
+                    a: "72.0012".to_string(),
+                    A: "0.9245".to_string(),
+                    ..Default::default()
+                },
+                captured: None,
+            })
+            .collect::<Vec<_>>();
+        let backtest = Backtest::new(ticks, Decimal::new(1, 3));
+        let found_at = |pyth_time_offset_ms| {
+            let backtest = &backtest;
+            async move {
+                backtest
+                    .run(FinderSettings {
+                        max_feed_skew: Some(Duration::from_millis(500)),
+                        pyth_time_offset_ms,
+                        ..Default::default()
+                    })
+                    .await
+                    .into_iter()
+                    .map(|(tick, _)| tick.timestamp)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(found_at(0).await, vec![13_000]);
+        // Modelling the 2 second lag keeps the lagging ticks and drops the one which is not lagging
+        assert_eq!(found_at(2000).await, vec![10_000, 11_000, 12_000]);
+        assert_eq!(backtest.run(FinderSettings::default()).await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_load_all() {
+        let directory =
+            std::env::temp_dir().join(format!("keyrock-task-merge-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let tick = |timestamp: u64, u: u64, bid: &str| {
+            format!(
+                r#"{{"timestamp":{},"pyth":{{"price":"69852445","conf":"669724","expo":-6,"publish_time":1}},"binance":{{"u":{},"s":"SOLUSDT","b":"{}","B":"0.8574","a":"72.0012","A":"0.9245"}}}}"#,
+                timestamp, u, bid
+            )
+        };
+        // The second day overlaps with the first one, which is out of order
+        std::fs::write(
+            directory.join("day-1.jsonl"),
+            [
+                tick(3000, 3, "71.3833"),
+                tick(1000, 1, "70"),
+                tick(4000, 4, "70"),
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("day-2.jsonl"),
+            [
+                tick(4000, 4, "70"),
+                tick(2000, 2, "71.4512"),
+                tick(5000, 5, "71.5"),
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+        std::fs::write(directory.join("notes.txt"), "not a recording").unwrap();
+
+        let backtest =
+            Backtest::load_all(&[directory.join("day-?.jsonl")], Decimal::new(1, 3)).unwrap();
+        let listed = Backtest::load_all(
+            &[directory.join("day-2.jsonl"), directory.join("day-1.jsonl")],
+            Decimal::new(1, 3),
+        )
+        .unwrap();
+        let missing = Backtest::load_all(&[directory.join("day-*.csv")], Decimal::new(1, 3));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            backtest
+                .ticks
+                .iter()
+                .map(|tick| tick.timestamp)
+                .collect::<Vec<_>>(),
+            vec![1000, 2000, 3000, 4000, 5000]
+        );
+        assert_eq!(listed.ticks, backtest.ticks);
+        assert!(missing.is_err());
+
+        let found = backtest.run(FinderSettings::default()).await;
+        assert_eq!(
+            found
+                .iter()
+                .map(|(tick, _)| tick.timestamp)
+                .collect::<Vec<_>>(),
+            vec![2000, 3000, 5000]
+        );
+    }
+
+    #[test]
+    fn test_load_invalid_line() {
+        let path =
+            std::env::temp_dir().join(format!("keyrock-task-invalid-{}.jsonl", std::process::id()));
+        std::fs::write(&path, "{\"timestamp\":1000}\n").unwrap();
+
+        let result = Backtest::load(&path, Decimal::new(1, 3));
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_replay_speed() {
+        let ticks = ticks_at(&[1_000, 1_100, 1_300]);
+
+        let backtest = Backtest::new(ticks.clone(), Decimal::ZERO);
+        let start = Instant::now();
+        backtest.run(FinderSettings::default()).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        let backtest = Backtest::new(ticks.clone(), Decimal::ZERO).with_replay_speed(1.0);
+        let start = Instant::now();
+        backtest.run(FinderSettings::default()).await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(300));
+        assert!(elapsed < Duration::from_millis(400));
+
+        let backtest = Backtest::new(ticks, Decimal::ZERO).with_replay_speed(2.0);
+        let start = Instant::now();
+        backtest.run(FinderSettings::default()).await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(150));
+        assert!(elapsed < Duration::from_millis(250));
+    }
+}
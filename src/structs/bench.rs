@@ -0,0 +1,149 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use pyth_sdk_solana::Price;
+use rust_decimal::Decimal;
+use tokio::sync::RwLock;
+
+use super::{
+    arbitrage_finder::{ArbitrageFinder, FinderSettings},
+    cex::binance::BookTickerData,
+};
+
+/*
+    Struct holding throughput and latency of evaluations measured by a benchmark run
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    pub iterations: u64,
+    pub opportunities: u64, // evaluations which found an opportunity
+    pub total: Duration,
+    pub evaluations_per_second: f64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/*
+    Runs a fresh finder over synthetic ticks the given number of times, measuring every call of find_opportunity
+    Binance prices sweep across both bounds of the probable price range, so both found and missed opportunities are measured
+*/
+pub async fn bench(settings: FinderSettings, iterations: u64) -> BenchResult {
+    let mut arbitrage_finder = ArbitrageFinder::with_settings(settings);
+    let binance_fee = Decimal::new(1, 3);
+    // l: 68.43263012 h: 71.27225988
+    let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+        price: 69852445,
+        conf: 669724,
+        expo: -6,
+        ..Default::default()
+    })));
+    let latest_binance_ticker_data = Arc::new(RwLock::new(None));
+
+    let mut latencies = Vec::with_capacity(iterations as usize);
+    let mut opportunities = 0;
+    for iteration in 0..iterations {
+        *latest_binance_ticker_data.write().await = Some(synthetic_ticker_data(iteration));
+
+        let started = Instant::now();
+        let maybe_opportunity = arbitrage_finder
+            .find_opportunity(
+                latest_pyth_price.clone(),
+                latest_binance_ticker_data.clone(),
+                binance_fee,
+            )
+            .await;
+        latencies.push(started.elapsed());
+
+        if maybe_opportunity.is_some() {
+            opportunities += 1;
+        }
+    }
+
+    let total = latencies.iter().sum::<Duration>();
+    latencies.sort();
+    BenchResult {
+        iterations,
+        opportunities,
+        total,
+        evaluations_per_second: match total.as_secs_f64() {
+            seconds if seconds > 0.0 => iterations as f64 / seconds,
+            _ => 0.0,
+        },
+        p50: percentile(&latencies, 50),
+        p90: percentile(&latencies, 90),
+        p99: percentile(&latencies, 99),
+        max: latencies.last().copied().unwrap_or_default(),
+    }
+}
+
+/*
+    Formats the result as a report of throughput and latency percentiles
+*/
+pub fn format_result(result: &BenchResult) -> String {
+    format!(
+        "Evaluations: {} ({} with an opportunity) in {:?}\nThroughput: {:.0} evaluations per second\nLatency: p50 {:?}, p90 {:?}, p99 {:?}, max {:?}\n",
+        result.iterations,
+        result.opportunities,
+        result.total,
+        result.evaluations_per_second,
+        result.p50,
+        result.p90,
+        result.p99,
+        result.max
+    )
+}
+
+/*
+    Returns ticker data with the bid and ask moving by 1 cent per iteration between 67.5 and 72.49 and spread of 10 cents
+*/
+fn synthetic_ticker_data(iteration: u64) -> BookTickerData {
+    let bid = Decimal::new(6750 + (iteration % 500) as i64, 2);
+    BookTickerData {
+        u: iteration,
+        s: "SOLUSDT".to_string(),
+        b: bid.to_string(),
+        B: "0.8574".to_string(),
+        a: (bid + Decimal::new(1, 1)).to_string(),
+        A: "0.9245".to_string(),
+    }
+}
+
+/*
+    Returns the latency below which the given percent of the sorted latencies are
+*/
+fn percentile(sorted_latencies: &[Duration], percent: usize) -> Duration {
+    match sorted_latencies.len() {
+        0 => Duration::ZERO,
+        len => sorted_latencies[(len - 1) * percent / 100],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structs::arbitrage_finder::FinderSettings;
+
+    use super::{bench, format_result};
+
+    #[tokio::test]
+    async fn test_bench() {
+        let result = bench(FinderSettings::default(), 1000).await;
+        assert_eq!(result.iterations, 1000);
+        // Some of the bids are above and some of the asks are below the probable price range
+        assert!(result.opportunities > 0);
+        assert!(result.opportunities < 1000);
+        assert!(result.evaluations_per_second > 0.0);
+        assert!(result.p50 <= result.p90);
+        assert!(result.p90 <= result.p99);
+        assert!(result.p99 <= result.max);
+        assert!(result.max <= result.total);
+        assert!(format_result(&result).starts_with("Evaluations: 1000 ("));
+
+        let result = bench(FinderSettings::default(), 0).await;
+        assert_eq!(result.opportunities, 0);
+        assert_eq!(result.evaluations_per_second, 0.0);
+    }
+}
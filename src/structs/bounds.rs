@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use pyth_sdk_solana::Price;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::arbitrage_finder::{calculate_probable_price_range, FinderSettings};
+
+/*
+    Struct describing the probable price range computed from a Pyth price of a pair, independent of Binance data
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BoundsEvent {
+    pub pair: String,      // Binance ticker of the pair
+    pub timestamp: u64,    // milliseconds since Unix epoch
+    pub publish_time: i64, // seconds since Unix epoch at which the Pyth price was published
+    pub lower: Decimal,
+    pub mid: Decimal, // the Pyth price, inverted if configured
+    pub upper: Decimal,
+    pub confidence: Decimal, // as published
+}
+
+/*
+    Struct sending the bounds of every new Pyth price of each pair, for visualizing how the probable price range moves
+*/
+pub struct BoundsReporter {
+    settings: FinderSettings,
+    sender: UnboundedSender<BoundsEvent>,
+    last_prices: HashMap<String, Price>, // per Binance ticker
+}
+
+impl BoundsReporter {
+    pub fn new(settings: FinderSettings, sender: UnboundedSender<BoundsEvent>) -> Self {
+        Self {
+            settings,
+            sender,
+            last_prices: HashMap::new(),
+        }
+    }
+
+    /*
+        Sends the event of the Pyth price of the pair stored at the timestamp, unless it is the one recorded last
+        Prices whose bounds cannot be computed are ignored
+    */
+    pub fn record_at(&mut self, timestamp: u64, binance_ticker: &str, price: Price) {
+        if self.last_prices.get(binance_ticker) == Some(&price) {
+            return;
+        }
+        self.last_prices.insert(binance_ticker.to_string(), price);
+        let Some(event) = self.event(timestamp, binance_ticker, price) else {
+            return;
+        };
+
+        let _ = self.sender.send(event); // fails only if events are not consumed
+    }
+
+    fn event(&self, timestamp: u64, binance_ticker: &str, price: Price) -> Option<BoundsEvent> {
+        let (lower, upper) = calculate_probable_price_range(price, self.settings)?;
+        let exponential = price.expo.unsigned_abs();
+        let mut mid = Decimal::try_new(price.price, exponential).ok()?;
+        if self.settings.invert_pyth {
+            mid = Decimal::ONE.checked_div(mid)?;
+        }
+        let confidence = Decimal::try_new(price.conf.try_into().ok()?, exponential).ok()?;
+
+        Some(BoundsEvent {
+            pair: binance_ticker.to_string(),
+            timestamp,
+            publish_time: price.publish_time,
+            lower: lower.normalize(),
+            mid: mid.normalize(),
+            upper: upper.normalize(),
+            confidence: confidence.normalize(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+    use tokio::sync::mpsc;
+
+    use crate::structs::arbitrage_finder::FinderSettings;
+
+    use super::{BoundsEvent, BoundsReporter};
+
+    #[test]
+    fn test_record_at() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut reporter = BoundsReporter::new(FinderSettings::default(), sender);
+        let price = Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            publish_time: 1000,
+        };
+
+        reporter.record_at(1_000_500, "solusdt", price);
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            BoundsEvent {
+                pair: "solusdt".to_string(),
+                timestamp: 1_000_500,
+                publish_time: 1000,
+                lower: Decimal::from_str("68.43263012").unwrap(),
+                mid: Decimal::from_str("69.852445").unwrap(),
+                upper: Decimal::from_str("71.27225988").unwrap(),
+                confidence: Decimal::from_str("0.669724").unwrap(),
+            }
+        );
+        // The same price stored again is not a Pyth update
+        reporter.record_at(1_001_000, "solusdt", price);
+        assert!(receiver.try_recv().is_err());
+        // Pairs are tracked separately
+        reporter.record_at(1_001_000, "solbtc", price);
+        assert_eq!(receiver.try_recv().unwrap().pair, "solbtc");
+    }
+}
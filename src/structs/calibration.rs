@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+use super::{arbitrage_finder::FinderSettings, backtest::Backtest};
+
+/*
+    Struct holding metrics of a backtest run with a single confidence factor
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalibrationResult {
+    pub confidence_factor: Decimal,
+    pub opportunities: usize,
+    pub captured: usize,      // opportunities found at ticks marked as captured
+    pub false_signals: usize, // opportunities found at ticks marked as not captured
+    pub captured_profit: Decimal, // sum of estimated profits of captured opportunities
+}
+
+/*
+    Runs the backtest for every confidence factor from min_factor to max_factor (inclusive) with the given step
+    All the other settings are taken from base_settings
+    Fails without running the backtest if min_factor exceeds max_factor or the step is not positive
+*/
+pub async fn calibrate(
+    backtest: &Backtest,
+    base_settings: FinderSettings,
+    min_factor: Decimal,
+    max_factor: Decimal,
+    step: Decimal,
+) -> Result<Vec<CalibrationResult>> {
+    if min_factor > max_factor {
+        return Err(anyhow!(
+            "Min factor {} exceeds max factor {}",
+            min_factor,
+            max_factor
+        ));
+    }
+    if step <= Decimal::ZERO {
+        return Err(anyhow!("Step {} is not positive", step));
+    }
+    let mut results = Vec::new();
+    let mut confidence_factor = min_factor;
+
+    while confidence_factor <= max_factor {
+        let mut settings = base_settings;
+        settings.confidence_factor = confidence_factor;
+        let found = backtest.run(settings).await;
+
+        let mut result = CalibrationResult {
+            confidence_factor,
+            opportunities: found.len(),
+            captured: 0,
+            false_signals: 0,
+            captured_profit: Decimal::ZERO,
+        };
+        for (tick, opportunity) in found {
+            match tick.captured {
+                Some(true) => {
+                    result.captured += 1;
+                    result.captured_profit += opportunity.estimated_profit;
+                }
+                Some(false) => result.false_signals += 1,
+                None => {}
+            }
+        }
+        results.push(result);
+
+        confidence_factor += step;
+    }
+
+    Ok(results)
+}
+
+/*
+    Formats the results as a table, followed by the factors maximizing captured profit and minimizing false signals
+*/
+pub fn format_results(results: &[CalibrationResult]) -> String {
+    let mut output = format!(
+        "{:>8} | {:>13} | {:>8} | {:>13} | {:>15}\n",
+        "factor", "opportunities", "captured", "false signals", "captured profit"
+    );
+    for result in results {
+        output.push_str(&format!(
+            "{:>8} | {:>13} | {:>8} | {:>13} | {:>15}\n",
+            result.confidence_factor.normalize(),
+            result.opportunities,
+            result.captured,
+            result.false_signals,
+            result.captured_profit.normalize()
+        ));
+    }
+
+    // Ties are resolved in favour of the lower factor, which reports more opportunities
+    let best_by_profit = results
+        .iter()
+        .rev()
+        .max_by_key(|result| result.captured_profit);
+    let best_by_false_signals = results
+        .iter()
+        .rev()
+        .min_by_key(|result| (result.false_signals, -result.captured_profit));
+    if let (Some(best_by_profit), Some(best_by_false_signals)) =
+        (best_by_profit, best_by_false_signals)
+    {
+        output.push_str(&format!(
+            "\nHighest captured profit: {}\nFewest false signals: {}\n",
+            best_by_profit.confidence_factor.normalize(),
+            best_by_false_signals.confidence_factor.normalize()
+        ));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+
+    use crate::structs::{
+        arbitrage_finder::FinderSettings,
+        backtest::{Backtest, RecordedTick},
+        cex::binance::BookTickerData,
+    };
+
+    use super::{calibrate, format_results};
+
+    fn tick(timestamp: u64, bid: &str, bid_quantity: &str, captured: bool) -> RecordedTick {
+        RecordedTick {
+            timestamp,
+            // h: 69.852445 + 0.669724 * factor
+            pyth: Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            },
+            binance: BookTickerData {
+                b: bid.to_string(),
+                B: bid_quantity.to_string(),
+                a: "73.5".to_string(),
+                A: "1".to_string(),
+                ..Default::default()
+            },
+            captured: Some(captured),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calibrate() {
+        let backtest = Backtest::new(
+            vec![
+                tick(1000, "71.3833", "0.8574", false),
+                tick(2000, "73", "1", true),
+            ],
+            Decimal::new(1, 3),
+        );
+
+        let results = calibrate(
+            &backtest,
+            FinderSettings::default(),
+            Decimal::ONE,
+            Decimal::new(4, 0),
+            Decimal::ONE,
+        )
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 4);
+
+        // The small gap is reported only with the narrow ranges
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| (result.opportunities, result.captured, result.false_signals))
+                .collect::<Vec<_>>(),
+            vec![(2, 1, 1), (2, 1, 1), (1, 1, 0), (1, 1, 0)]
+        );
+        assert_eq!(
+            results[0].captured_profit,
+            Decimal::from_str("2.404831").unwrap()
+        );
+        assert_eq!(
+            results[2].captured_profit,
+            Decimal::from_str("1.065383").unwrap()
+        );
+
+        let output = format_results(&results);
+        assert!(output.contains("Highest captured profit: 1\n"));
+        assert!(output.contains("Fewest false signals: 3\n"));
+    }
+
+    #[tokio::test]
+    async fn test_calibrate_invalid_range() {
+        let backtest = Backtest::new(vec![tick(1000, "73", "1", true)], Decimal::new(1, 3));
+        let calibrate = |min_factor, max_factor, step| {
+            calibrate(
+                &backtest,
+                FinderSettings::default(),
+                min_factor,
+                max_factor,
+                step,
+            )
+        };
+
+        assert!(calibrate(Decimal::new(4, 0), Decimal::ONE, Decimal::ONE)
+            .await
+            .is_err());
+        assert!(calibrate(Decimal::ONE, Decimal::new(4, 0), Decimal::ZERO)
+            .await
+            .is_err());
+        // A single factor is swept if both bounds are equal
+        assert_eq!(
+            calibrate(Decimal::ONE, Decimal::ONE, Decimal::ONE)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
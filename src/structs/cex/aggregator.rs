@@ -0,0 +1,237 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+use rust_decimal::Decimal;
+
+use super::{
+    binance::{parse_decimal, BookTickerData},
+    SOURCE_NAMES,
+};
+
+/*
+    Supported ways of weighting the CEX sources combined into a single top of the book
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Weighting {
+    Volume,                          // by quantity offered at each price
+    Fixed(HashMap<String, Decimal>), // by configured weight per source name, sources without one are ignored
+}
+
+impl FromStr for Weighting {
+    type Err = Error;
+
+    /*
+        Parses volume or comma-separated <source>=<weight> pairs of known sources with positive weights, e.g. binance=3,coinbase=1
+    */
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "volume" {
+            return Ok(Self::Volume);
+        }
+
+        let mut weights = HashMap::new();
+        for source_weight in s.split(',') {
+            let (source_name, weight) = source_weight.split_once('=').ok_or_else(|| {
+                anyhow!(
+                    "{} is neither volume nor in form <source>=<weight>",
+                    source_weight
+                )
+            })?;
+            if !SOURCE_NAMES.contains(&source_name) {
+                return Err(anyhow!(
+                    "{} is not a known source, expected one of {}",
+                    source_name,
+                    SOURCE_NAMES.join(", ")
+                ));
+            }
+            let weight = Decimal::from_str(weight)?;
+            if weight <= Decimal::ZERO {
+                return Err(anyhow!(
+                    "Weight {} of {} is not positive",
+                    weight,
+                    source_name
+                ));
+            }
+            if weights.insert(source_name.to_string(), weight).is_some() {
+                return Err(anyhow!("{} is weighted more than once", source_name));
+            }
+        }
+
+        Ok(Self::Fixed(weights))
+    }
+}
+
+/*
+    Struct combining the latest top of the book of a pair from several CEX sources into a single one
+    Combined prices are weighted averages of the sources' best prices, combined quantities are their sums
+*/
+pub struct TickerAggregator {
+    weighting: Weighting,
+    latest: HashMap<String, BookTickerData>, // latest top of the book per source name
+    updates: u64,
+}
+
+impl TickerAggregator {
+    pub fn new(weighting: Weighting) -> Self {
+        Self {
+            weighting,
+            latest: HashMap::new(),
+            updates: 0,
+        }
+    }
+
+    /*
+        Stores the latest top of the book of the source
+    */
+    pub fn update(&mut self, source_name: &str, ticker_data: BookTickerData) {
+        self.latest.insert(source_name.to_string(), ticker_data);
+        self.updates += 1;
+    }
+
+    /*
+        Returns the combined top of the book, with the number of received updates as its update id
+        Returns None until a weighted source is received, or if any of the numbers is malformed or the weights sum to 0
+    */
+    pub fn combined(&self) -> Option<BookTickerData> {
+        let mut bid = WeightedAverage::default();
+        let mut ask = WeightedAverage::default();
+        let mut symbol = None;
+
+        for (source_name, ticker_data) in &self.latest {
+            let bid_quantity = parse_decimal(&ticker_data.B)?;
+            let ask_quantity = parse_decimal(&ticker_data.A)?;
+            let (bid_weight, ask_weight) = match &self.weighting {
+                Weighting::Volume => (bid_quantity, ask_quantity),
+                Weighting::Fixed(weights) => match weights.get(source_name) {
+                    Some(weight) => (*weight, *weight),
+                    None => continue,
+                },
+            };
+
+            bid.add(parse_decimal(&ticker_data.b)?, bid_weight, bid_quantity)?;
+            ask.add(parse_decimal(&ticker_data.a)?, ask_weight, ask_quantity)?;
+            symbol.get_or_insert_with(|| ticker_data.s.clone());
+        }
+
+        Some(BookTickerData {
+            u: self.updates,
+            s: symbol?,
+            b: bid.average()?.normalize().to_string(),
+            B: bid.quantity.normalize().to_string(),
+            a: ask.average()?.normalize().to_string(),
+            A: ask.quantity.normalize().to_string(),
+        })
+    }
+}
+
+/*
+    Struct accumulating a weighted average of prices along with the sum of their quantities, None on overflow
+*/
+#[derive(Default)]
+struct WeightedAverage {
+    weighted_sum: Decimal,
+    weights: Decimal,
+    quantity: Decimal,
+}
+
+impl WeightedAverage {
+    fn add(&mut self, price: Decimal, weight: Decimal, quantity: Decimal) -> Option<()> {
+        self.weighted_sum = self.weighted_sum.checked_add(price.checked_mul(weight)?)?;
+        self.weights = self.weights.checked_add(weight)?;
+        self.quantity = self.quantity.checked_add(quantity)?;
+        Some(())
+    }
+
+    fn average(&self) -> Option<Decimal> {
+        self.weighted_sum.checked_div(self.weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use rust_decimal::Decimal;
+
+    use crate::structs::cex::binance::BookTickerData;
+
+    use super::{TickerAggregator, Weighting};
+
+    fn book(s: &str, b: &str, bid_quantity: &str, a: &str, ask_quantity: &str) -> BookTickerData {
+        BookTickerData {
+            u: 1,
+            s: s.to_string(),
+            b: b.to_string(),
+            B: bid_quantity.to_string(),
+            a: a.to_string(),
+            A: ask_quantity.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_combined() {
+        let mut aggregator = TickerAggregator::new(Weighting::Volume);
+        assert!(aggregator.combined().is_none());
+
+        aggregator.update("binance", book("SOLUSDT", "100", "1", "101", "2"));
+        aggregator.update("coinbase", book("SOLUSDT", "100.5", "3", "100.8", "2"));
+        assert_eq!(
+            aggregator.combined().unwrap(),
+            BookTickerData {
+                u: 2,
+                ..book("SOLUSDT", "100.375", "4", "100.9", "4")
+            }
+        );
+
+        // The latest update of a source replaces its previous one
+        aggregator.update("coinbase", book("SOLUSDT", "100.5", "1", "100.8", "2"));
+        assert_eq!(
+            aggregator.combined().unwrap(),
+            BookTickerData {
+                u: 3,
+                ..book("SOLUSDT", "100.25", "2", "100.9", "4")
+            }
+        );
+
+        let mut aggregator = TickerAggregator::new(Weighting::Fixed(HashMap::from([
+            ("binance".to_string(), Decimal::from_str("3").unwrap()),
+            ("coinbase".to_string(), Decimal::ONE),
+        ])));
+        aggregator.update("binance", book("SOLUSDT", "100", "1", "101", "2"));
+        aggregator.update("coinbase", book("SOLUSDT", "100.5", "3", "100.8", "2"));
+        aggregator.update("kraken", book("SOLUSDT", "90", "100", "91", "100"));
+        assert_eq!(
+            aggregator.combined().unwrap(),
+            BookTickerData {
+                u: 3,
+                ..book("SOLUSDT", "100.125", "4", "100.95", "4")
+            }
+        );
+
+        // No quantity to weight by
+        let mut aggregator = TickerAggregator::new(Weighting::Volume);
+        aggregator.update("binance", book("SOLUSDT", "100", "0", "101", "0"));
+        assert!(aggregator.combined().is_none());
+    }
+
+    #[test]
+    fn test_weighting_from_str() {
+        assert_eq!(Weighting::from_str("volume").unwrap(), Weighting::Volume);
+        assert_eq!(
+            Weighting::from_str("binance=3,coinbase=0.5").unwrap(),
+            Weighting::Fixed(HashMap::from([
+                ("binance".to_string(), Decimal::from(3)),
+                ("coinbase".to_string(), Decimal::new(5, 1)),
+            ]))
+        );
+        for s in [
+            "",
+            "binance",
+            "kraken=1",
+            "binance=0",
+            "binance=-1",
+            "binance=1,binance=2",
+        ] {
+            assert!(Weighting::from_str(s).is_err(), "{} was parsed", s);
+        }
+    }
+}
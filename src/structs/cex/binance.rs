@@ -1,54 +1,192 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{anyhow, Result};
 use futures_util::{
     stream::{SplitSink, SplitStream},
     FutureExt, SinkExt, StreamExt,
 };
-use serde::Deserialize;
+use metrics::counter;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use tokio::{net::TcpStream, sync::RwLock};
 use tokio_tungstenite::{
     tungstenite::{handshake::client::Response, Message},
     MaybeTlsStream, WebSocketStream,
 };
 
+use crate::{
+    config::{Market, StreamEndpoint},
+    structs::telemetry,
+};
+
+use super::TickerSource;
+
 const BINANCE_WEBSOCKET_URL: &str = "wss://stream.binance.com:9443/stream";
+const BINANCE_FUTURES_WEBSOCKET_URL: &str = "wss://fstream.binance.com/stream";
+const BINANCE_RAW_WEBSOCKET_URL: &str = "wss://stream.binance.com:9443/ws";
+const BINANCE_FUTURES_RAW_WEBSOCKET_URL: &str = "wss://fstream.binance.com/ws";
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
 /*
     Struct representing Binance CEX responsible for connecting to Binance WS and fetching data about provided ticker/pair
 */
 pub struct Binance {
-    write: RwLock<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>,
-    read: RwLock<SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>>,
+    url: String,
+    write: RwLock<SplitSink<Socket, Message>>,
+    read: RwLock<SplitStream<Socket>>,
+    connected_at: RwLock<Instant>,
+    last_message_at: RwLock<Instant>, // time the last message of any kind was received, or of connecting
+    connect_timeout: Duration,        // limit of establishing a connection and subscribing
+    market: Market,
+    funding_rates: std::sync::RwLock<HashMap<String, Decimal>>, // latest funding rates of perp tickers
 }
 
 impl Binance {
     /*
-        Connects to the WS
+        Connects to the spot market WS
     */
     pub async fn connect() -> Result<(Self, Response)> {
-        let (socket, response) = tokio_tungstenite::connect_async(BINANCE_WEBSOCKET_URL).await?;
+        Self::connect_to_market(
+            Market::Spot,
+            StreamEndpoint::Combined,
+            DEFAULT_CONNECT_TIMEOUT,
+        )
+        .await
+    }
+
+    /*
+        Connects to the WS endpoint of the provided market, failing if it takes longer than connect_timeout
+        Messages of both endpoints are parsed the same way, see parse_message
+    */
+    pub async fn connect_to_market(
+        market: Market,
+        endpoint: StreamEndpoint,
+        connect_timeout: Duration,
+    ) -> Result<(Self, Response)> {
+        let url = match (market, endpoint) {
+            (Market::Spot, StreamEndpoint::Combined) => BINANCE_WEBSOCKET_URL,
+            (Market::Perp, StreamEndpoint::Combined) => BINANCE_FUTURES_WEBSOCKET_URL,
+            (Market::Spot, StreamEndpoint::Raw) => BINANCE_RAW_WEBSOCKET_URL,
+            (Market::Perp, StreamEndpoint::Raw) => BINANCE_FUTURES_RAW_WEBSOCKET_URL,
+        };
+        Self::connect_to(url, market, connect_timeout).await
+    }
+
+    /*
+        Connects to the WS of the market under the provided URL, failing if it takes longer than connect_timeout
+        The timeout also limits every reconnection and waiting for the response to its subscription
+    */
+    pub async fn connect_to(
+        url: &str,
+        market: Market,
+        connect_timeout: Duration,
+    ) -> Result<(Self, Response)> {
+        let (socket, response) = open_socket(url, connect_timeout).await?;
         let (write, read) = socket.split();
         Ok((
             Self {
+                url: url.to_string(),
                 write: RwLock::new(write),
                 read: RwLock::new(read),
+                connected_at: RwLock::new(Instant::now()),
+                last_message_at: RwLock::new(Instant::now()),
+                connect_timeout,
+                market,
+                funding_rates: std::sync::RwLock::new(HashMap::new()),
             },
             response,
         ))
     }
 
     /*
-        Subscribes to a stream providing data about the ticker/pair
+        Returns time elapsed since the current connection was established
+    */
+    pub async fn connection_age(&self) -> Duration {
+        self.connected_at.read().await.elapsed()
+    }
+
+    /*
+        Replaces the connection with a new one and subscribes to the tickers/pairs again if the current connection is at least max_age old
+        Binance drops connections after 24 hours, so cycling them in advance avoids a gap in data
+        Returns id of the new subscription if the connection was replaced
+    */
+    pub async fn cycle_connection_if_expired(
+        &self,
+        max_age: Duration,
+        tickers: &[&str],
+    ) -> Result<Option<i64>> {
+        if self.connection_age().await < max_age {
+            return Ok(None);
+        }
+
+        self.reconnect(tickers).await.map(Some)
+    }
+
+    /*
+        Returns time elapsed since the last message of any kind, including pings, was received
     */
-    pub async fn subscribe_to_ticker(&self, ticker: &str) -> Result<i64> {
+    pub async fn idle_time(&self) -> Duration {
+        self.last_message_at.read().await.elapsed()
+    }
+
+    /*
+        Replaces the connection with a new one and subscribes to the tickers/pairs again if no message was received for idle_timeout
+        A connection hanging without being closed would otherwise stall the data indefinitely
+        Returns id of the new subscription if the connection was replaced
+    */
+    pub async fn reconnect_if_idle(
+        &self,
+        idle_timeout: Duration,
+        tickers: &[&str],
+    ) -> Result<Option<i64>> {
+        if self.idle_time().await < idle_timeout {
+            return Ok(None);
+        }
+
+        self.reconnect(tickers).await.map(Some)
+    }
+
+    /*
+        Replaces the connection with a new one and subscribes to the tickers/pairs again, returning id of the new subscription
+    */
+    async fn reconnect(&self, tickers: &[&str]) -> Result<i64> {
+        let (socket, _) = open_socket(&self.url, self.connect_timeout).await?;
+        let (write, read) = socket.split();
+
+        let mut write_write_lock = self.write.write().await;
+        let mut read_write_lock = self.read.write().await;
+        let mut old_write = std::mem::replace(&mut *write_write_lock, write);
+        *read_write_lock = read;
+        *self.connected_at.write().await = Instant::now();
+        *self.last_message_at.write().await = Instant::now();
+        drop(read_write_lock);
+        drop(write_write_lock);
+
+        let _ = old_write.close().await; // the old connection is abandoned anyway
+        counter!(telemetry::BINANCE_RECONNECTS).increment(1);
+        tokio::time::timeout(self.connect_timeout, self.subscribe_to_tickers(tickers))
+            .await
+            .map_err(|_| anyhow!("Timed out waiting for response to the subscription"))?
+    }
+
+    /*
+        Subscribes to streams providing data about all the tickers/pairs with a single request
+    */
+    pub async fn subscribe_to_tickers(&self, tickers: &[&str]) -> Result<i64> {
         let current_timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_millis() as i64; // doesn't overflow
         let subscribe_request = format!(
-            "{{\"method\":\"SUBSCRIBE\",\"params\":[\"{}@bookTicker\"],\"id\":{}}}",
-            ticker, current_timestamp
+            "{{\"method\":\"SUBSCRIBE\",\"params\":[{}],\"id\":{}}}",
+            self.stream_names(tickers),
+            current_timestamp
         );
         let message = Message::Text(subscribe_request);
 
@@ -59,8 +197,9 @@ impl Binance {
             let message = String::from_utf8(inner.unwrap().into_data()).unwrap();
             if !message.contains("\"result\":null") {
                 return Err(anyhow!(format!(
-                    "Could not subscribe for ticker {}: {}",
-                    ticker, message
+                    "Could not subscribe for tickers {}: {}",
+                    tickers.join(", "),
+                    message
                 )));
             }
         }
@@ -69,12 +208,49 @@ impl Binance {
     }
 
     /*
-        Unsubscribes from the stream associated with the provided ticker and id returned while subscribing to it.
+        Requests subscription to the streams of the tickers/pairs without waiting for the response
+        Meant for changes while the stream is being read, which skips the response
+    */
+    pub async fn request_subscription(&self, tickers: &[&str]) -> Result<()> {
+        self.send_subscription_request("SUBSCRIBE", tickers).await
+    }
+
+    /*
+        Requests unsubscription from the streams of the tickers/pairs without waiting for the response or closing the connection
     */
-    pub async fn unsubscribe(&self, ticker: &str, id: i64) -> Result<()> {
+    pub async fn request_unsubscription(&self, tickers: &[&str]) -> Result<()> {
+        self.send_subscription_request("UNSUBSCRIBE", tickers).await
+    }
+
+    async fn send_subscription_request(&self, method: &str, tickers: &[&str]) -> Result<()> {
+        let current_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64; // doesn't overflow
+        let request = format!(
+            "{{\"method\":\"{}\",\"params\":[{}],\"id\":{}}}",
+            method,
+            self.stream_names(tickers),
+            current_timestamp
+        );
+
+        self.write
+            .write()
+            .await
+            .send(Message::Text(request))
+            .await?;
+
+        Ok(())
+    }
+
+    /*
+        Unsubscribes from the streams associated with the provided tickers and id returned while subscribing to them.
+    */
+    pub async fn unsubscribe_from_tickers(&self, tickers: &[&str], id: i64) -> Result<()> {
         let unsubscribe_request = format!(
-            "{{\"method\":\"UNSUBSCRIBE\",\"params\":[\"{}@bookTicker\"],\"id\":{}}}",
-            ticker, id
+            "{{\"method\":\"UNSUBSCRIBE\",\"params\":[{}],\"id\":{}}}",
+            self.stream_names(tickers),
+            id
         );
         let message = Message::Text(unsubscribe_request);
 
@@ -92,11 +268,54 @@ impl Binance {
         }
 
         Err(anyhow!(format!(
-            "Could not unsubscribe for ticker {} and id {}",
-            ticker, id
+            "Could not unsubscribe for tickers {} and id {}",
+            tickers.join(", "),
+            id
         )))
     }
 
+    /*
+        Returns the taker fee charged for trading the ticker/pair
+    */
+    pub fn taker_fee(ticker: &str) -> Decimal {
+        if ticker.contains("bnb") {
+            Decimal::new(75, 5)
+        } else {
+            Decimal::new(1, 3)
+        }
+    }
+
+    /*
+        Returns the latest funding rate of the perp ticker/pair, if any was received
+    */
+    pub fn funding_rate(&self, ticker: &str) -> Option<Decimal> {
+        self.funding_rates
+            .read()
+            .unwrap()
+            .get(&ticker.to_lowercase())
+            .copied()
+    }
+
+    /*
+        Formats names of the bookTicker streams of the tickers as JSON array elements
+        On the perp market, markPrice streams providing funding rates are added
+    */
+    fn stream_names(&self, tickers: &[&str]) -> String {
+        let suffixes: &[&str] = match self.market {
+            Market::Spot => &["bookTicker"],
+            Market::Perp => &["bookTicker", "markPrice"],
+        };
+        tickers
+            .iter()
+            .flat_map(|ticker| {
+                suffixes
+                    .iter()
+                    .map(move |suffix| format!("\"{}@{}\"", ticker, suffix))
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
     /*
         Reads the next element of the stream and parses the JSON into BinanceResponse object
     */
@@ -107,6 +326,7 @@ impl Binance {
             .next()
             .then(|element| async {
                 if let Some(result) = element {
+                    *self.last_message_at.write().await = Instant::now();
                     let message = result.unwrap();
 
                     if let Message::Ping(ping) = message {
@@ -120,8 +340,18 @@ impl Binance {
                     }
 
                     let message_str = String::from_utf8(message.into_data()).unwrap();
-
-                    return Some(serde_json::from_str::<BinanceResponse>(&message_str).unwrap());
+                    match parse_message(&message_str)? {
+                        StreamMessage::BookTicker(binance_response) => {
+                            return Some(binance_response);
+                        }
+                        StreamMessage::MarkPrice(mark_price_data) => {
+                            self.funding_rates.write().unwrap().insert(
+                                mark_price_data.s.to_lowercase(),
+                                parse_decimal(&mark_price_data.r)?,
+                            );
+                            return None;
+                        }
+                    }
                 }
 
                 None
@@ -130,18 +360,94 @@ impl Binance {
     }
 }
 
+/*
+    Opens a WS connection to the URL, failing if it is not established within the timeout
+*/
+async fn open_socket(url: &str, connect_timeout: Duration) -> Result<(Socket, Response)> {
+    tokio::time::timeout(connect_timeout, tokio_tungstenite::connect_async(url))
+        .await
+        .map_err(|_| anyhow!(format!("Timed out connecting to {}", url)))?
+        .map_err(Into::into)
+}
+
+impl TickerSource for Binance {
+    fn name(&self) -> &str {
+        "binance"
+    }
+
+    async fn read_next_ticker(&self) -> Option<BookTickerData> {
+        self.read_next_message()
+            .await
+            .map(|binance_response| binance_response.data)
+    }
+}
+
+/*
+    Parses a number sent by Binance, which for very small or large values may be in scientific notation
+    Returns None if it is not a number
+*/
+pub fn parse_decimal(value: &str) -> Option<Decimal> {
+    Decimal::from_str(value)
+        .or_else(|_| Decimal::from_scientific(value))
+        .ok()
+}
+
+/*
+    Data messages of the subscribed streams
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamMessage {
+    BookTicker(BinanceResponse),
+    MarkPrice(MarkPriceData),
+}
+
+/*
+    Parses a message of either endpoint, None for responses to subscription requests and malformed messages
+    The combined-stream endpoint wraps the payload in {stream, data} envelope, the raw one sends it as is,
+    in which case the stream name is derived from the symbol and event type of the payload
+*/
+pub fn parse_message(message: &str) -> Option<StreamMessage> {
+    let value = serde_json::from_str::<serde_json::Value>(message).ok()?;
+
+    let (stream, data) = match (value.get("stream"), value.get("data")) {
+        (Some(stream), Some(data)) => (stream.as_str()?.to_string(), data.clone()),
+        _ => {
+            if value.get("result").is_some() || value.get("id").is_some() {
+                return None;
+            }
+            let suffix = match value["e"].as_str() {
+                Some("markPriceUpdate") => "markPrice",
+                _ => "bookTicker", // spot bookTicker payloads carry no event type
+            };
+            let stream = format!("{}@{}", value["s"].as_str()?.to_lowercase(), suffix);
+            (stream, value)
+        }
+    };
+
+    if stream.ends_with("@markPrice") {
+        return serde_json::from_value::<MarkPriceData>(data)
+            .ok()
+            .map(StreamMessage::MarkPrice);
+    }
+    Some(StreamMessage::BookTicker(BinanceResponse {
+        stream,
+        data: serde_json::from_value::<BookTickerData>(data).ok()?,
+    }))
+}
+
 /*
     Structs representing JSON messages from the stream
 */
 
-#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct BinanceResponse {
     pub stream: String,
     pub data: BookTickerData,
 }
 
-#[allow(non_snake_case)]
-#[derive(Debug, Deserialize, Clone, Default)]
+#[allow(non_snake_case, dead_code)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
 pub struct BookTickerData {
     pub u: u64,    // order book updateId
     pub s: String, // symbol
@@ -151,14 +457,210 @@ pub struct BookTickerData {
     pub A: String, // best ask quantity
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct MarkPriceData {
+    pub s: String, // symbol
+    pub r: String, // funding rate
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct DepthData {
+    pub lastUpdateId: u64,
+    pub bids: Vec<[String; 2]>, // price and quantity levels, from the highest price
+    pub asks: Vec<[String; 2]>, // price and quantity levels, from the lowest price
+}
+
 #[cfg(test)]
 mod test {
-    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use std::{
+        str::FromStr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use futures_util::{SinkExt, StreamExt};
+    use rust_decimal::Decimal;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::{http::StatusCode, Message};
+
+    use crate::config::Market;
+
+    use super::{
+        parse_decimal, parse_message, Binance, BinanceResponse, BookTickerData, MarkPriceData,
+        StreamMessage, DEFAULT_CONNECT_TIMEOUT,
+    };
+
+    /*
+        Spawns a local WS server acknowledging subscriptions and sending a bookTicker message after each of them
+        Returns its URL and a counter of accepted connections
+    */
+    async fn spawn_mock_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        tokio::spawn({
+            let connections = connections.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    tokio::spawn(async move {
+                        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                        while let Some(Ok(message)) = socket.next().await {
+                            if !message.to_string().contains("SUBSCRIBE") {
+                                continue;
+                            }
+                            for response in [
+                                "{\"result\":null,\"id\":1}",
+                                "{\"stream\":\"btcusdt@bookTicker\",\"data\":{\"u\":1,\"s\":\"BTCUSDT\",\"b\":\"42000.1\",\"B\":\"0.5\",\"a\":\"42000.2\",\"A\":\"0.7\"}}",
+                            ] {
+                                if socket.send(Message::Text(response.to_string())).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    });
+                }
+            }
+        });
+
+        (url, connections)
+    }
+
+    #[tokio::test]
+    async fn test_cycle_connection_if_expired() {
+        let (url, connections) = spawn_mock_server().await;
+        let (binance, _) = Binance::connect_to(&url, Market::Spot, DEFAULT_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
+        binance.subscribe_to_tickers(&["btcusdt"]).await.unwrap();
+        assert_eq!(
+            binance.read_next_message().await.unwrap().data.s,
+            "BTCUSDT".to_string()
+        );
+
+        let max_age = Duration::from_millis(200);
+        let result = binance
+            .cycle_connection_if_expired(max_age, &["btcusdt"])
+            .await
+            .unwrap();
+        assert!(result.is_none());
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(max_age).await;
+        let result = binance
+            .cycle_connection_if_expired(max_age, &["btcusdt"])
+            .await
+            .unwrap();
+        assert!(result.is_some());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        assert!(binance.connection_age().await < max_age);
+
+        // Data keeps flowing through the new connection
+        assert_eq!(
+            binance.read_next_message().await.unwrap().data.s,
+            "BTCUSDT".to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_if_idle() {
+        // The first connection hangs without sending anything, the next ones behave like the mock server
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let connections = connections.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let hangs = connections.fetch_add(1, Ordering::SeqCst) == 0;
+                    tokio::spawn(async move {
+                        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                        while let Some(Ok(message)) = socket.next().await {
+                            if hangs || !message.to_string().contains("SUBSCRIBE") {
+                                continue;
+                            }
+                            for response in [
+                                "{\"result\":null,\"id\":1}",
+                                "{\"stream\":\"btcusdt@bookTicker\",\"data\":{\"u\":1,\"s\":\"BTCUSDT\",\"b\":\"42000.1\",\"B\":\"0.5\",\"a\":\"42000.2\",\"A\":\"0.7\"}}",
+                            ] {
+                                if socket.send(Message::Text(response.to_string())).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    });
+                }
+            }
+        });
+
+        let idle_timeout = Duration::from_millis(200);
+        let (binance, _) = Binance::connect_to(&url, Market::Spot, DEFAULT_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
+        binance.request_subscription(&["btcusdt"]).await.unwrap();
+        assert!(binance
+            .reconnect_if_idle(idle_timeout, &["btcusdt"])
+            .await
+            .unwrap()
+            .is_none());
 
-    use futures_util::StreamExt;
-    use tokio_tungstenite::tungstenite::http::StatusCode;
+        assert!(
+            tokio::time::timeout(idle_timeout, binance.read_next_message())
+                .await
+                .is_err()
+        );
+        assert!(binance.idle_time().await >= idle_timeout);
+        assert!(binance
+            .reconnect_if_idle(idle_timeout, &["btcusdt"])
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
 
-    use super::Binance;
+        // Data flows through the new connection
+        assert_eq!(
+            binance.read_next_message().await.unwrap().data.s,
+            "BTCUSDT".to_string()
+        );
+        assert!(binance.idle_time().await < idle_timeout);
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        // Accepts TCP connections without completing the WS handshake
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let result = Binance::connect_to(&url, Market::Spot, Duration::from_millis(100)).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_request_subscription() {
+        let (url, _) = spawn_mock_server().await;
+        let (binance, _) = Binance::connect_to(&url, Market::Spot, DEFAULT_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
+        binance.request_subscription(&["btcusdt"]).await.unwrap();
+
+        // The response to the request is skipped
+        assert!(binance.read_next_message().await.is_none());
+        assert_eq!(
+            binance.read_next_message().await.unwrap().data.s,
+            "BTCUSDT".to_string()
+        );
+    }
 
     #[tokio::test]
     async fn test_connect() {
@@ -169,7 +671,7 @@ mod test {
     #[tokio::test]
     async fn test_subscribe_to_ticker() {
         let (binance, _) = Binance::connect().await.unwrap();
-        let id = binance.subscribe_to_ticker("btcusdt").await.unwrap();
+        let id = binance.subscribe_to_tickers(&["btcusdt"]).await.unwrap();
         assert!(
             id <= SystemTime::now()
                 .duration_since(UNIX_EPOCH)
@@ -181,8 +683,11 @@ mod test {
     #[tokio::test]
     async fn test_unsubscribe() {
         let (binance, _) = Binance::connect().await.unwrap();
-        let id = binance.subscribe_to_ticker("btcusdt").await.unwrap();
-        binance.unsubscribe("btcusdt", id).await.unwrap();
+        let id = binance.subscribe_to_tickers(&["btcusdt"]).await.unwrap();
+        binance
+            .unsubscribe_from_tickers(&["btcusdt"], id)
+            .await
+            .unwrap();
 
         let _ = binance.read.write().await.next().await;
         assert_eq!(binance.read.into_inner().count().await, 0);
@@ -199,10 +704,101 @@ mod test {
     #[tokio::test]
     async fn test_read_next_message() {
         let (binance, _) = Binance::connect().await.unwrap();
-        binance.subscribe_to_ticker("btcusdt").await.unwrap();
+        binance.subscribe_to_tickers(&["btcusdt"]).await.unwrap();
 
         let next_message = binance.read_next_message().await.unwrap();
         assert_eq!(next_message.stream, "btcusdt@bookTicker".to_string());
         assert_eq!(next_message.data.s, "BTCUSDT".to_string());
     }
+
+    #[tokio::test]
+    async fn test_stream_names() {
+        let (url, _) = spawn_mock_server().await;
+        let (binance, _) = Binance::connect_to(&url, Market::Spot, DEFAULT_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(
+            binance.stream_names(&["btcusdt", "solusdt"]),
+            "\"btcusdt@bookTicker\",\"solusdt@bookTicker\""
+        );
+
+        let (binance, _) = Binance::connect_to(&url, Market::Perp, DEFAULT_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(
+            binance.stream_names(&["btcusdt"]),
+            "\"btcusdt@bookTicker\",\"btcusdt@markPrice\""
+        );
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(
+            parse_decimal("25.35190000").unwrap(),
+            Decimal::from_str("25.3519").unwrap()
+        );
+        assert_eq!(
+            parse_decimal("1.23E-5").unwrap(),
+            Decimal::from_str("0.0000123").unwrap()
+        );
+        assert_eq!(
+            parse_decimal("4.5e3").unwrap(),
+            Decimal::from_str("4500").unwrap()
+        );
+        assert!(parse_decimal("1.23E").is_none());
+        assert!(parse_decimal("").is_none());
+    }
+
+    #[test]
+    fn test_parse_futures_book_ticker() {
+        let message = "{\"stream\":\"bnbusdt@bookTicker\",\"data\":{\"e\":\"bookTicker\",\"u\":400900217,\"E\":1568014460893,\"T\":1568014460891,\"s\":\"BNBUSDT\",\"b\":\"25.35190000\",\"B\":\"31.21000000\",\"a\":\"25.36520000\",\"A\":\"40.66000000\"}}";
+        let response = serde_json::from_str::<BinanceResponse>(message).unwrap();
+        assert_eq!(response.stream, "bnbusdt@bookTicker");
+        assert_eq!(response.data.u, 400900217);
+        assert_eq!(response.data.s, "BNBUSDT");
+        assert_eq!(response.data.b, "25.35190000");
+        assert_eq!(response.data.B, "31.21000000");
+        assert_eq!(response.data.a, "25.36520000");
+        assert_eq!(response.data.A, "40.66000000");
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let book_ticker = BookTickerData {
+            u: 400900217,
+            s: "BNBUSDT".to_string(),
+            b: "25.35190000".to_string(),
+            B: "31.21000000".to_string(),
+            a: "25.36520000".to_string(),
+            A: "40.66000000".to_string(),
+        };
+        let expected = Some(StreamMessage::BookTicker(BinanceResponse {
+            stream: "bnbusdt@bookTicker".to_string(),
+            data: book_ticker,
+        }));
+
+        // Raw stream
+        let raw = "{\"u\":400900217,\"s\":\"BNBUSDT\",\"b\":\"25.35190000\",\"B\":\"31.21000000\",\"a\":\"25.36520000\",\"A\":\"40.66000000\"}";
+        assert_eq!(parse_message(raw), expected);
+        // Combined stream
+        let combined = format!("{{\"stream\":\"bnbusdt@bookTicker\",\"data\":{}}}", raw);
+        assert_eq!(parse_message(&combined), expected);
+
+        let mark_price = "{\"e\":\"markPriceUpdate\",\"E\":1562305380000,\"s\":\"BTCUSDT\",\"p\":\"11794.15000000\",\"r\":\"0.00038167\",\"T\":1562306400000}";
+        let expected = Some(StreamMessage::MarkPrice(MarkPriceData {
+            s: "BTCUSDT".to_string(),
+            r: "0.00038167".to_string(),
+        }));
+        assert_eq!(parse_message(mark_price), expected);
+        assert_eq!(
+            parse_message(&format!(
+                "{{\"stream\":\"btcusdt@markPrice\",\"data\":{}}}",
+                mark_price
+            )),
+            expected
+        );
+
+        assert_eq!(parse_message("{\"result\":null,\"id\":1}"), None);
+        assert_eq!(parse_message("not json"), None);
+    }
 }
@@ -0,0 +1,360 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use futures_util::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, StreamExt,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{net::TcpStream, sync::RwLock};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::{binance::BookTickerData, TickerSource};
+
+const COINBASE_WEBSOCKET_URL: &str = "wss://ws-feed.exchange.coinbase.com";
+// Quote assets recognized at the end of Binance tickers, usdt and usdc before usd so they are not taken for it
+const QUOTE_ASSETS: [&str; 6] = ["usdt", "usdc", "usd", "eur", "btc", "eth"];
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/*
+    Struct representing Coinbase Exchange CEX responsible for connecting to its WS feed and streaming the top of the book of the products matching Binance tickers
+    The ticker channel carries the best bid and ask along with every trade, the heartbeat channel keeps quiet products from looking idle
+*/
+pub struct Coinbase {
+    url: String,
+    write: RwLock<SplitSink<Socket, Message>>,
+    read: RwLock<SplitStream<Socket>>,
+    last_message_at: RwLock<Instant>, // time the last message of any kind was received, or of connecting
+    connect_timeout: Duration,        // limit of establishing a connection
+    products: std::sync::RwLock<HashMap<String, String>>, // Binance tickers of the subscribed products per product id
+}
+
+impl Coinbase {
+    /*
+        Connects to the WS feed, failing if it takes longer than connect_timeout
+    */
+    pub async fn connect(connect_timeout: Duration) -> Result<Self> {
+        Self::connect_to(COINBASE_WEBSOCKET_URL, connect_timeout).await
+    }
+
+    /*
+        Connects to the WS feed under the provided URL, failing if it takes longer than connect_timeout
+        The timeout also limits every reconnection
+    */
+    pub async fn connect_to(url: &str, connect_timeout: Duration) -> Result<Self> {
+        let (write, read) = open_socket(url, connect_timeout).await?.split();
+        Ok(Self {
+            url: url.to_string(),
+            write: RwLock::new(write),
+            read: RwLock::new(read),
+            last_message_at: RwLock::new(Instant::now()),
+            connect_timeout,
+            products: std::sync::RwLock::new(HashMap::new()),
+        })
+    }
+
+    /*
+        Returns id of the product matching the Binance ticker, e.g. SOL-USDT for solusdt, None if its quote asset is not recognized
+    */
+    pub fn product_id(binance_ticker: &str) -> Option<String> {
+        let binance_ticker = binance_ticker.to_lowercase();
+        QUOTE_ASSETS.iter().find_map(|quote| {
+            let base = binance_ticker
+                .strip_suffix(quote)
+                .filter(|base| !base.is_empty())?;
+            Some(format!("{}-{}", base.to_uppercase(), quote.to_uppercase()))
+        })
+    }
+
+    /*
+        Subscribes to the products matching the Binance tickers without waiting for the response, skipping tickers without one
+    */
+    pub async fn subscribe(&self, binance_tickers: &[&str]) -> Result<()> {
+        let product_ids = {
+            let mut products = self.products.write().unwrap();
+            binance_tickers
+                .iter()
+                .filter_map(|binance_ticker| {
+                    let product_id = Self::product_id(binance_ticker)?;
+                    products.insert(product_id.clone(), binance_ticker.to_uppercase());
+                    Some(product_id)
+                })
+                .collect::<Vec<_>>()
+        };
+        self.send_subscription_request("subscribe", &product_ids)
+            .await
+    }
+
+    /*
+        Unsubscribes from the products matching the Binance tickers without waiting for the response or closing the connection
+    */
+    pub async fn unsubscribe(&self, binance_tickers: &[&str]) -> Result<()> {
+        let product_ids = {
+            let mut products = self.products.write().unwrap();
+            binance_tickers
+                .iter()
+                .filter_map(|binance_ticker| {
+                    let product_id = Self::product_id(binance_ticker)?;
+                    products.remove(&product_id).map(|_| product_id)
+                })
+                .collect::<Vec<_>>()
+        };
+        self.send_subscription_request("unsubscribe", &product_ids)
+            .await
+    }
+
+    async fn send_subscription_request(&self, kind: &str, product_ids: &[String]) -> Result<()> {
+        if product_ids.is_empty() {
+            return Ok(());
+        }
+        let request = json!({
+            "type": kind,
+            "product_ids": product_ids,
+            "channels": ["ticker", "heartbeat"],
+        });
+
+        self.write
+            .write()
+            .await
+            .send(Message::Text(request.to_string()))
+            .await?;
+
+        Ok(())
+    }
+
+    /*
+        Returns time elapsed since the last message of any kind, including heartbeats, was received
+    */
+    pub async fn idle_time(&self) -> Duration {
+        self.last_message_at.read().await.elapsed()
+    }
+
+    /*
+        Replaces the connection with a new one and subscribes to the products again if no message was received for idle_timeout
+        Returns whether the connection was replaced
+    */
+    pub async fn reconnect_if_idle(&self, idle_timeout: Duration) -> Result<bool> {
+        if self.idle_time().await < idle_timeout {
+            return Ok(false);
+        }
+
+        self.reconnect().await.map(|_| true)
+    }
+
+    /*
+        Replaces the connection with a new one and subscribes to the products again
+    */
+    pub async fn reconnect(&self) -> Result<()> {
+        let (write, read) = open_socket(&self.url, self.connect_timeout).await?.split();
+
+        let mut write_write_lock = self.write.write().await;
+        let mut read_write_lock = self.read.write().await;
+        let mut old_write = std::mem::replace(&mut *write_write_lock, write);
+        *read_write_lock = read;
+        *self.last_message_at.write().await = Instant::now();
+        drop(read_write_lock);
+        drop(write_write_lock);
+
+        let _ = old_write.close().await; // the old connection is abandoned anyway
+        let product_ids = self
+            .products
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        self.send_subscription_request("subscribe", &product_ids)
+            .await
+    }
+
+    /*
+        Reads the next message of the feed, returning the top of the book it carries with the Binance ticker of its product as the symbol
+        Returns None for other messages, or an error once the connection is closed or broken, after which it has to be replaced
+    */
+    pub async fn read_next_message(&self) -> Result<Option<BookTickerData>> {
+        let message = self
+            .read
+            .write()
+            .await
+            .next()
+            .await
+            .ok_or_else(|| anyhow!("Coinbase WS connection was closed"))??;
+        *self.last_message_at.write().await = Instant::now();
+
+        match message {
+            Message::Ping(ping) => {
+                self.write.write().await.send(Message::Pong(ping)).await?;
+                Ok(None)
+            }
+            Message::Text(text) => {
+                let Some(ticker) = parse_ticker(&text) else {
+                    return Ok(None);
+                };
+                let products = self.products.read().unwrap();
+                Ok(products
+                    .get(&ticker.product_id)
+                    .map(|binance_ticker| BookTickerData {
+                        u: ticker.sequence,
+                        s: binance_ticker.clone(),
+                        b: ticker.best_bid,
+                        B: ticker.best_bid_size,
+                        a: ticker.best_ask,
+                        A: ticker.best_ask_size,
+                    }))
+            }
+            Message::Close(_) => Err(anyhow!("Coinbase WS connection was closed")),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl TickerSource for Coinbase {
+    fn name(&self) -> &str {
+        "coinbase"
+    }
+
+    async fn read_next_ticker(&self) -> Option<BookTickerData> {
+        self.read_next_message().await.ok().flatten()
+    }
+}
+
+/*
+    Opens a WS connection to the URL, failing if it is not established within the timeout
+*/
+async fn open_socket(url: &str, connect_timeout: Duration) -> Result<Socket> {
+    let (socket, _) = tokio::time::timeout(connect_timeout, tokio_tungstenite::connect_async(url))
+        .await
+        .map_err(|_| anyhow!(format!("Timed out connecting to {}", url)))??;
+    Ok(socket)
+}
+
+/*
+    Parses a message of the ticker channel, None for messages of other types and malformed ones
+    Errors reported by Coinbase, e.g. about an unknown product, are printed
+*/
+pub fn parse_ticker(message: &str) -> Option<TickerMessage> {
+    let value = serde_json::from_str::<serde_json::Value>(message).ok()?;
+    match value["type"].as_str()? {
+        "ticker" => serde_json::from_value(value).ok(),
+        "error" => {
+            eprintln!("Coinbase WS reported an error: {}", message);
+            None
+        }
+        _ => None,
+    }
+}
+
+/*
+    Struct representing the fields of a ticker channel message used as the top of the book
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TickerMessage {
+    pub product_id: String,
+    pub sequence: u64,
+    pub best_bid: String,
+    pub best_bid_size: String,
+    pub best_ask: String,
+    pub best_ask_size: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message;
+
+    use crate::structs::cex::{binance::BookTickerData, TickerSource};
+
+    use super::{parse_ticker, Coinbase, TickerMessage};
+
+    const TICKER: &str = "{\"type\":\"ticker\",\"sequence\":37475248783,\"product_id\":\"SOL-USDT\",\"price\":\"71.38\",\"open_24h\":\"70.1\",\"volume_24h\":\"41728.6\",\"best_bid\":\"71.37\",\"best_bid_size\":\"12.5\",\"best_ask\":\"71.39\",\"best_ask_size\":\"3.2\",\"side\":\"buy\",\"time\":\"2024-01-02T12:00:00.000000Z\",\"trade_id\":1,\"last_size\":\"0.5\"}";
+
+    #[test]
+    fn test_product_id() {
+        assert_eq!(Coinbase::product_id("solusdt").unwrap(), "SOL-USDT");
+        assert_eq!(Coinbase::product_id("BTCUSDC").unwrap(), "BTC-USDC");
+        assert_eq!(Coinbase::product_id("ethusd").unwrap(), "ETH-USD");
+        assert_eq!(Coinbase::product_id("solbtc").unwrap(), "SOL-BTC");
+        assert!(Coinbase::product_id("usdt").is_none());
+        assert!(Coinbase::product_id("soltry").is_none());
+    }
+
+    #[test]
+    fn test_parse_ticker() {
+        assert_eq!(
+            parse_ticker(TICKER),
+            Some(TickerMessage {
+                product_id: "SOL-USDT".to_string(),
+                sequence: 37475248783,
+                best_bid: "71.37".to_string(),
+                best_bid_size: "12.5".to_string(),
+                best_ask: "71.39".to_string(),
+                best_ask_size: "3.2".to_string(),
+            })
+        );
+        let heartbeat = "{\"type\":\"heartbeat\",\"last_trade_id\":1,\"product_id\":\"SOL-USDT\",\"sequence\":37475248784,\"time\":\"2024-01-02T12:00:01.000000Z\"}";
+        assert!(parse_ticker(heartbeat).is_none());
+        assert!(parse_ticker("{\"type\":\"error\",\"message\":\"Failed to subscribe\"}").is_none());
+        assert!(parse_ticker("not json").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_read_next_ticker() {
+        // Local WS server answering a subscription with a heartbeat and a ticker message
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let subscription = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let subscription = socket.next().await.unwrap().unwrap().to_string();
+            for message in [
+                "{\"type\":\"heartbeat\",\"product_id\":\"SOL-USDT\",\"sequence\":1}",
+                TICKER,
+            ] {
+                socket
+                    .send(Message::Text(message.to_string()))
+                    .await
+                    .unwrap();
+            }
+            socket.next().await; // kept open until the client is done
+            subscription
+        });
+
+        let coinbase = Coinbase::connect_to(&url, Duration::from_secs(5))
+            .await
+            .unwrap();
+        coinbase.subscribe(&["solusdt", "soltry"]).await.unwrap();
+        assert!(coinbase.read_next_ticker().await.is_none());
+        assert_eq!(
+            coinbase.read_next_ticker().await.unwrap(),
+            BookTickerData {
+                u: 37475248783,
+                s: "SOLUSDT".to_string(),
+                b: "71.37".to_string(),
+                B: "12.5".to_string(),
+                a: "71.39".to_string(),
+                A: "3.2".to_string(),
+            }
+        );
+        drop(coinbase);
+
+        // Tickers without a matching product are not subscribed to
+        let subscription: serde_json::Value =
+            serde_json::from_str(&subscription.await.unwrap()).unwrap();
+        assert_eq!(
+            subscription,
+            serde_json::json!({
+                "type": "subscribe",
+                "product_ids": ["SOL-USDT"],
+                "channels": ["ticker", "heartbeat"],
+            })
+        );
+    }
+}
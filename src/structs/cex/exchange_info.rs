@@ -0,0 +1,508 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::RwLock,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+
+use crate::config::Market;
+
+const BINANCE_EXCHANGE_INFO_URL: &str = "https://api.binance.com/api/v3/exchangeInfo";
+const BINANCE_FUTURES_EXCHANGE_INFO_URL: &str = "https://fapi.binance.com/fapi/v1/exchangeInfo";
+const CACHE_TTL: Duration = Duration::from_secs(300);
+// Maximum multiple of the configured interval the refresh interval grows to under sustained rate limiting
+const MAX_RATE_LIMIT_FACTOR: u32 = 16;
+
+// Decimals of the monitored symbols, fetched at startup and refreshed if configured
+pub static SYMBOL_DECIMALS: OnceCell<SymbolDecimalsCache> = OnceCell::const_new();
+
+/*
+    Struct representing the part of Binance exchange info response describing the listed symbols
+*/
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExchangeInfo {
+    pub symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolInfo {
+    pub symbol: String,
+    pub status: String, // TRADING if the symbol can be traded
+    pub base_asset: String,
+    pub quote_asset: String,
+    #[serde(default)]
+    pub filters: Vec<SymbolFilter>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolFilter {
+    pub filter_type: String, // PRICE_FILTER and LOT_SIZE define the decimals
+    pub tick_size: Option<String>, // of PRICE_FILTER
+    pub step_size: Option<String>, // of LOT_SIZE
+}
+
+/*
+    Struct holding numbers of decimal places of prices and quantities of a symbol, derived from its tick and step sizes
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymbolDecimals {
+    pub price: u32,
+    pub quantity: u32,
+    pub tick_size: Decimal, // minimum price movement
+}
+
+impl SymbolDecimals {
+    /*
+        Reads the decimals from PRICE_FILTER and LOT_SIZE filters of the symbol, None if any of them is missing or malformed
+    */
+    pub fn from_symbol_info(symbol_info: &SymbolInfo) -> Option<Self> {
+        let size = |filter_type: &str, size: fn(&SymbolFilter) -> Option<&String>| {
+            let filter = symbol_info
+                .filters
+                .iter()
+                .find(|filter| filter.filter_type == filter_type)?;
+            Some(Decimal::from_str(size(filter)?).ok()?.normalize())
+        };
+        let tick_size = size("PRICE_FILTER", |filter| filter.tick_size.as_ref())?;
+
+        Some(Self {
+            price: tick_size.scale(),
+            quantity: size("LOT_SIZE", |filter| filter.step_size.as_ref())?.scale(),
+            tick_size,
+        })
+    }
+
+    pub fn format_price(&self, price: Decimal) -> String {
+        format!("{:.*}", self.price as usize, price)
+    }
+
+    pub fn format_quantity(&self, quantity: Decimal) -> String {
+        format!("{:.*}", self.quantity as usize, quantity)
+    }
+}
+
+/*
+    Struct holding decimals of symbols by their lowercase tickers
+*/
+#[derive(Debug, Default)]
+pub struct SymbolDecimalsCache {
+    decimals: RwLock<HashMap<String, SymbolDecimals>>,
+}
+
+impl SymbolDecimalsCache {
+    /*
+        Fetches decimals of the tickers of the market, failing if any of them is not listed
+    */
+    pub async fn fetch(market: Market, tickers: &[&str]) -> Result<Self> {
+        Self::parse(&fetch_exchange_info(market).await?, tickers)
+    }
+
+    /*
+        Parses decimals of the tickers (ignoring case) from exchange info response, failing if any of them is not listed
+    */
+    pub fn parse(json: &str, tickers: &[&str]) -> Result<Self> {
+        let exchange_info = serde_json::from_str::<ExchangeInfo>(json)?;
+
+        let decimals = tickers
+            .iter()
+            .map(|ticker| {
+                let ticker = ticker.to_lowercase();
+                let symbol_info = exchange_info
+                    .symbols
+                    .iter()
+                    .find(|symbol_info| symbol_info.symbol.to_lowercase() == ticker)
+                    .ok_or_else(|| {
+                        anyhow!("Symbol {} not found in Binance exchange info", ticker)
+                    })?;
+                let decimals = SymbolDecimals::from_symbol_info(symbol_info)
+                    .ok_or_else(|| anyhow!("Symbol {} has no valid tick or step size", ticker))?;
+                Ok((ticker, decimals))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            decimals: RwLock::new(decimals),
+        })
+    }
+
+    /*
+        Updates decimals of the tickers (ignoring case) from refreshed exchange info response, keeping the last known ones of the tickers it does not list
+    */
+    pub fn refresh(&self, json: &str, tickers: &[&str]) -> Result<()> {
+        let exchange_info = serde_json::from_str::<ExchangeInfo>(json)?;
+        let tickers = tickers
+            .iter()
+            .map(|ticker| ticker.to_lowercase())
+            .collect::<Vec<_>>();
+
+        let mut decimals = self.decimals.write().unwrap();
+        for symbol_info in &exchange_info.symbols {
+            let ticker = symbol_info.symbol.to_lowercase();
+            if !tickers.contains(&ticker) {
+                continue;
+            }
+            if let Some(symbol_decimals) = SymbolDecimals::from_symbol_info(symbol_info) {
+                decimals.insert(ticker, symbol_decimals);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, ticker: &str) -> Option<SymbolDecimals> {
+        self.decimals
+            .read()
+            .unwrap()
+            .get(&ticker.to_lowercase())
+            .copied()
+    }
+}
+
+/*
+    Returns Binance tickers of the market which can be traded, containing the filter if any
+    Exchange info is cached in the temporary directory for 5 minutes, so repeated listing does not query Binance
+*/
+pub async fn list_symbols(market: Market, filter: Option<&str>) -> Result<Vec<String>> {
+    parse_symbols(&fetch_exchange_info(market).await?, filter)
+}
+
+/*
+    Returns exchange info response of the market, from the cache if it is fresh
+*/
+async fn fetch_exchange_info(market: Market) -> Result<String> {
+    let cache_path = cache_path(market);
+    if let Some(json) = read_fresh_cache(&cache_path) {
+        return Ok(json);
+    }
+
+    download_exchange_info(market).await
+}
+
+/*
+    Returns exchange info response of the market from Binance, updating the cache
+*/
+async fn download_exchange_info(market: Market) -> Result<String> {
+    let json = download(exchange_info_url(market)).await?;
+    let _ = fs::write(cache_path(market), &json); // fetching works without the cache
+    Ok(json)
+}
+
+fn exchange_info_url(market: Market) -> &'static str {
+    match market {
+        Market::Spot => BINANCE_EXCHANGE_INFO_URL,
+        Market::Perp => BINANCE_FUTURES_EXCHANGE_INFO_URL,
+    }
+}
+
+/*
+    Returns body of the response of the url, failing with RateLimited if Binance rate limited the request
+*/
+async fn download(url: &str) -> Result<String> {
+    let response = reqwest::get(url).await?;
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::IM_A_TEAPOT {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        return Err(RateLimited {
+            status: status.as_u16(),
+            retry_after,
+        }
+        .into());
+    }
+
+    Ok(response.error_for_status()?.text().await?)
+}
+
+/*
+    Error of a request rate limited by Binance, with 429 once the limit is exceeded or 418 once the IP is banned for ignoring it
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    pub status: u16,
+    pub retry_after: Option<Duration>, // time Binance asked to wait for, in whole seconds
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rate limited by Binance with status {}", self.status)?;
+        match self.retry_after {
+            Some(retry_after) => write!(f, ", retry after {:?}", retry_after),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/*
+    Struct polling exchange info of the market at a configured interval, backing off when rate limited
+    Each rate limited request doubles the interval up to 16 times the configured one, each successful one halves it back,
+    and the next request is never sent before the time Binance asked to wait for
+*/
+pub struct ExchangeInfoPoller {
+    url: String,
+    cache_path: Option<PathBuf>, // where responses are cached for listing symbols, if any
+    base_interval: Duration,
+    interval: Duration,
+}
+
+impl ExchangeInfoPoller {
+    pub fn new(market: Market, interval: Duration) -> Self {
+        Self {
+            cache_path: Some(cache_path(market)),
+            ..Self::with_url(exchange_info_url(market), interval)
+        }
+    }
+
+    /*
+        Polls the url instead of Binance, without caching the responses
+    */
+    pub fn with_url(url: &str, interval: Duration) -> Self {
+        Self {
+            url: url.to_string(),
+            cache_path: None,
+            base_interval: interval,
+            interval,
+        }
+    }
+
+    /*
+        Returns the current interval between requests
+    */
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /*
+        Downloads exchange info, returning it along with the time to wait for before the next poll
+    */
+    pub async fn poll(&mut self) -> (Result<String>, Duration) {
+        let result = download(&self.url).await;
+        match result
+            .as_ref()
+            .map_err(|err| err.downcast_ref::<RateLimited>())
+        {
+            Err(Some(rate_limited)) => {
+                self.interval = (self.interval * 2).min(self.base_interval * MAX_RATE_LIMIT_FACTOR);
+                let delay = rate_limited
+                    .retry_after
+                    .map_or(self.interval, |retry_after| retry_after.max(self.interval));
+                eprintln!(
+                    "WARNING: {} while refreshing exchange info, polling every {:?}, next poll in {:?}\n",
+                    rate_limited, self.interval, delay
+                );
+                return (result, delay);
+            }
+            Ok(json) => {
+                if let Some(cache_path) = &self.cache_path {
+                    let _ = fs::write(cache_path, json); // fetching works without the cache
+                }
+                self.interval = (self.interval / 2).max(self.base_interval);
+            }
+            Err(None) => {}
+        }
+
+        (result, self.interval)
+    }
+}
+
+/*
+    Parses exchange info response into sorted lowercase tickers which can be traded, containing the filter (ignoring case) if any
+*/
+pub fn parse_symbols(json: &str, filter: Option<&str>) -> Result<Vec<String>> {
+    let exchange_info = serde_json::from_str::<ExchangeInfo>(json)?;
+    let filter = filter.map(str::to_lowercase);
+
+    let mut symbols = exchange_info
+        .symbols
+        .into_iter()
+        .filter(|symbol_info| symbol_info.status == "TRADING")
+        .map(|symbol_info| symbol_info.symbol.to_lowercase())
+        .filter(|symbol| filter.as_ref().is_none_or(|filter| symbol.contains(filter)))
+        .collect::<Vec<_>>();
+    symbols.sort();
+
+    Ok(symbols)
+}
+
+fn cache_path(market: Market) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "keyrock-task-exchange-info-{}.json",
+        format!("{:?}", market).to_lowercase()
+    ))
+}
+
+/*
+    Returns the cached response if it was written within the cache ttl
+*/
+fn read_fresh_cache(path: &Path) -> Option<String> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    if SystemTime::now().duration_since(modified).ok()? >= CACHE_TTL {
+        return None;
+    }
+
+    fs::read_to_string(path).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        str::FromStr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use rust_decimal::Decimal;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::{
+        parse_symbols, ExchangeInfoPoller, RateLimited, SymbolDecimals, SymbolDecimalsCache,
+    };
+
+    #[tokio::test]
+    async fn test_poll_rate_limited() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/api/v3/exchangeInfo",
+            listener.local_addr().unwrap()
+        );
+        let requests = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let requests = requests.clone();
+            async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let mut buffer = [0; 4096];
+                    let _ = stream.read(&mut buffer).await;
+                    // The first two requests are rate limited, the second one without Retry-After
+                    let response = match requests.fetch_add(1, Ordering::SeqCst) {
+                        0 => "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 7\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        1 => "HTTP/1.1 418 I'm a teapot\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        _ => "HTTP/1.1 200 OK\r\nContent-Length: 14\r\nConnection: close\r\n\r\n{\"symbols\":[]}",
+                    };
+                    let _ = stream.write_all(response.as_bytes()).await;
+                }
+            }
+        });
+
+        let mut poller = ExchangeInfoPoller::with_url(&url, Duration::from_secs(1));
+        // Waits as long as asked to, polling less often
+        let (result, delay) = poller.poll().await;
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<RateLimited>(),
+            Some(&RateLimited {
+                status: 429,
+                retry_after: Some(Duration::from_secs(7)),
+            })
+        );
+        assert_eq!(delay, Duration::from_secs(7));
+        assert_eq!(poller.interval(), Duration::from_secs(2));
+
+        let (result, delay) = poller.poll().await;
+        assert_eq!(
+            result
+                .unwrap_err()
+                .downcast_ref::<RateLimited>()
+                .unwrap()
+                .status,
+            418
+        );
+        assert_eq!(delay, Duration::from_secs(4));
+
+        // Recovers gradually
+        let (result, delay) = poller.poll().await;
+        assert_eq!(result.unwrap(), r#"{"symbols":[]}"#);
+        assert_eq!(delay, Duration::from_secs(2));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_parse_symbols() {
+        let json = r#"{
+            "timezone": "UTC",
+            "serverTime": 1700000000000,
+            "symbols": [
+                {"symbol": "SOLUSDT", "status": "TRADING", "baseAsset": "SOL", "quoteAsset": "USDT", "filters": []},
+                {"symbol": "BTCUSDT", "status": "TRADING", "baseAsset": "BTC", "quoteAsset": "USDT", "filters": []},
+                {"symbol": "SOLBTC", "status": "TRADING", "baseAsset": "SOL", "quoteAsset": "BTC", "filters": []},
+                {"symbol": "SOLBUSD", "status": "BREAK", "baseAsset": "SOL", "quoteAsset": "BUSD", "filters": []}
+            ]
+        }"#;
+
+        assert_eq!(
+            parse_symbols(json, None).unwrap(),
+            vec!["btcusdt", "solbtc", "solusdt"]
+        );
+        assert_eq!(
+            parse_symbols(json, Some("SOL")).unwrap(),
+            vec!["solbtc", "solusdt"]
+        );
+        assert!(parse_symbols(json, Some("eth")).unwrap().is_empty());
+        assert!(parse_symbols("{}", None).is_err());
+    }
+
+    #[test]
+    fn test_symbol_decimals() {
+        let json = r#"{
+            "symbols": [
+                {"symbol": "SOLUSDT", "status": "TRADING", "baseAsset": "SOL", "quoteAsset": "USDT", "filters": [
+                    {"filterType": "PRICE_FILTER", "minPrice": "0.01000000", "maxPrice": "10000.00000000", "tickSize": "0.01000000"},
+                    {"filterType": "LOT_SIZE", "minQty": "0.00100000", "maxQty": "9000000.00000000", "stepSize": "0.00100000"}
+                ]},
+                {"symbol": "BTCUSDT", "status": "TRADING", "baseAsset": "BTC", "quoteAsset": "USDT", "filters": []}
+            ]
+        }"#;
+
+        let cache = SymbolDecimalsCache::parse(json, &["solusdt"]).unwrap();
+        let decimals = cache.get("SOLUSDT").unwrap();
+        assert_eq!(
+            decimals,
+            SymbolDecimals {
+                price: 2,
+                quantity: 3,
+                tick_size: Decimal::new(1, 2)
+            }
+        );
+        assert_eq!(
+            decimals.format_price(Decimal::from_str("71.3833").unwrap()),
+            "71.38"
+        );
+        assert_eq!(decimals.format_quantity(Decimal::new(2, 0)), "2.000");
+        assert!(cache.get("btcusdt").is_none());
+
+        assert!(SymbolDecimalsCache::parse(json, &["solusdt", "ethusdt"]).is_err());
+        assert!(SymbolDecimalsCache::parse(json, &["btcusdt"]).is_err());
+
+        // Refreshed tick size, the delisted symbol keeps its last known decimals
+        let refreshed = r#"{
+            "symbols": [
+                {"symbol": "SOLUSDT", "status": "TRADING", "baseAsset": "SOL", "quoteAsset": "USDT", "filters": [
+                    {"filterType": "PRICE_FILTER", "tickSize": "0.00100000"},
+                    {"filterType": "LOT_SIZE", "stepSize": "0.00100000"}
+                ]}
+            ]
+        }"#;
+        cache.refresh(refreshed, &["SOLUSDT"]).unwrap();
+        assert_eq!(cache.get("solusdt").unwrap().price, 3);
+        cache.refresh(r#"{"symbols": []}"#, &["solusdt"]).unwrap();
+        assert_eq!(cache.get("solusdt").unwrap().price, 3);
+    }
+}
@@ -1 +1,26 @@
+use std::future::Future;
+
+use self::binance::BookTickerData;
+
+pub mod aggregator;
 pub mod binance;
+pub mod coinbase;
+pub mod exchange_info;
+
+// Names of the supported CEX sources, as returned by TickerSource::name
+pub const SOURCE_NAMES: [&str; 2] = ["binance", "coinbase"];
+
+/*
+    Trait of CEX connections streaming the best bid and ask of the subscribed pairs
+*/
+pub trait TickerSource {
+    /*
+        Returns name identifying the CEX, e.g. in the weights of aggregated sources
+    */
+    fn name(&self) -> &str;
+
+    /*
+        Waits for the next top of the book update, returns None if the message was not one
+    */
+    fn read_next_ticker(&self) -> impl Future<Output = Option<BookTickerData>> + Send;
+}
@@ -0,0 +1,102 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/*
+    Trait of time sources of the time-based features, so they can be driven by a mock in tests
+*/
+pub trait Clock: Send + Sync {
+    // Returns the current monotonic time
+    fn now(&self) -> Instant;
+    // Returns the current wall-clock time in milliseconds since Unix epoch
+    fn unix_millis(&self) -> u64;
+}
+
+/*
+    Clock reading the time of the system
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_millis(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64 // doesn't overflow
+    }
+}
+
+/*
+    Clock standing still until it is advanced manually, starting at the time it was created
+*/
+#[derive(Debug)]
+pub struct MockClock {
+    started_at: Instant,
+    started_at_unix_millis: u64,
+    elapsed: Mutex<Duration>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self::starting_at(SystemClock.unix_millis())
+    }
+
+    /*
+        Creates the clock showing the given wall-clock time in milliseconds since Unix epoch
+    */
+    pub fn starting_at(unix_millis: u64) -> Self {
+        Self {
+            started_at: Instant::now(),
+            started_at_unix_millis: unix_millis,
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /*
+        Moves both the monotonic and the wall-clock time forward by the duration
+    */
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.started_at + *self.elapsed.lock().unwrap()
+    }
+
+    fn unix_millis(&self) -> u64 {
+        let elapsed = self.elapsed.lock().unwrap().as_millis() as u64; // doesn't overflow
+        self.started_at_unix_millis + elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Clock, MockClock};
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new();
+        let (now, unix_millis) = (clock.now(), clock.unix_millis());
+        assert_eq!(clock.now(), now);
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now().duration_since(now), Duration::from_millis(1500));
+        assert_eq!(clock.unix_millis(), unix_millis + 1500);
+    }
+}
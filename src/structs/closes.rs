@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::arbitrage_finder::ArbitrageDirection;
+
+/*
+    Reasons an open opportunity is closed for
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CloseReason {
+    Converged,        // no opportunity is found anymore
+    Reversed,         // an opportunity in the other direction is found
+    InvalidatedStale, // the Pyth price went stale, so it is unknown whether the opportunity was still open
+}
+
+/*
+    Struct describing an opportunity which stopped being open, along with how long it was open
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CloseEvent {
+    pub pair: String,   // Binance ticker of the pair
+    pub timestamp: u64, // milliseconds since Unix epoch
+    pub direction: ArbitrageDirection,
+    pub lifetime_ms: u64,
+    pub reason: CloseReason,
+}
+
+/*
+    Struct sending close events of the opportunities of a pair
+*/
+pub struct CloseReporter {
+    binance_ticker: String,
+    sender: UnboundedSender<CloseEvent>,
+}
+
+impl CloseReporter {
+    pub fn new(binance_ticker: &str, sender: UnboundedSender<CloseEvent>) -> Self {
+        Self {
+            binance_ticker: binance_ticker.to_string(),
+            sender,
+        }
+    }
+
+    /*
+        Sends the event of the opportunity in the direction closed at the timestamp after the lifetime
+    */
+    pub fn record_at(
+        &self,
+        timestamp: u64,
+        direction: ArbitrageDirection,
+        lifetime: Duration,
+        reason: CloseReason,
+    ) {
+        let _ = self.sender.send(CloseEvent {
+            pair: self.binance_ticker.clone(),
+            timestamp,
+            direction,
+            lifetime_ms: lifetime.as_millis() as u64, // doesn't overflow
+            reason,
+        }); // fails only if events are not consumed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+    use tokio::sync::{mpsc, RwLock};
+
+    use crate::structs::{
+        arbitrage_finder::{ArbitrageDirection, ArbitrageFinder, FinderSettings},
+        cex::binance::BookTickerData,
+        clock::{Clock, MockClock},
+        stats::Stats,
+    };
+
+    use super::{CloseEvent, CloseReason, CloseReporter};
+
+    #[tokio::test]
+    async fn test_invalidated_stale() {
+        async fn find(arbitrage_finder: &mut ArbitrageFinder, publish_time: i64, bid: &str) {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                publish_time,
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: bid.to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+            arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(1, 3),
+                )
+                .await;
+        }
+
+        let clock = Arc::new(MockClock::starting_at(1_000_000));
+        let stats = Arc::new(std::sync::Mutex::new(Stats::default()));
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(FinderSettings {
+            max_feed_skew: Some(Duration::from_secs(5)),
+            ..Default::default()
+        })
+        .with_clock(clock.clone())
+        .with_stats(stats.clone())
+        .with_closes(CloseReporter::new("solusdt", sender));
+        let published = 1000; // seconds since Unix epoch
+
+        // Open while the Pyth price is fresh, closed by convergence
+        find(&mut arbitrage_finder, published, "71.3833").await;
+        clock.advance(Duration::from_secs(1));
+        find(&mut arbitrage_finder, published, "69.9").await;
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(event.reason, CloseReason::Converged);
+        assert_eq!(event.lifetime_ms, 1000);
+
+        // Reopened, then the same Pyth price grows older than the maximum skew by the next Binance update
+        find(&mut arbitrage_finder, published, "71.3833").await;
+        clock.advance(Duration::from_secs(5));
+        find(&mut arbitrage_finder, published, "71.4512").await;
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(
+            event,
+            CloseEvent {
+                pair: "solusdt".to_string(),
+                timestamp: clock.unix_millis(),
+                direction: ArbitrageDirection::SellBinanceBuyDex,
+                lifetime_ms: 5000,
+                reason: CloseReason::InvalidatedStale,
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&event).unwrap()["reason"],
+            "invalidated-stale"
+        );
+        // Stale ticks neither reopen nor close it again
+        find(&mut arbitrage_finder, published, "69.9").await;
+        assert!(receiver.try_recv().is_err());
+
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.invalidated_stale, 1);
+        assert_eq!(stats.suppressed_by_skew, 1);
+    }
+}
@@ -0,0 +1,231 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{mpsc, oneshot},
+};
+
+use crate::{config::PairConfig, structs::sinks::uds::remove_stale_socket};
+
+/*
+    Commands accepted by the control socket, one per line
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    Subscribe(PairConfig), // subscribe <binance_ticker>:<pyth_price_id>
+    Unsubscribe(String),   // unsubscribe <binance_ticker>
+    Pause,                 // pause
+    Resume,                // resume
+    ResetStats,            // reset-stats
+    Enable(String),        // enable <binance_ticker>
+    Disable(String),       // disable <binance_ticker>
+}
+
+impl FromStr for ControlCommand {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "pause" => return Ok(Self::Pause),
+            "resume" => return Ok(Self::Resume),
+            "reset-stats" => return Ok(Self::ResetStats),
+            _ => {}
+        }
+
+        match s.trim().split_once(' ') {
+            Some(("subscribe", pair)) => Ok(Self::Subscribe(PairConfig::from_str(pair.trim())?)),
+            Some(("unsubscribe", binance_ticker)) => {
+                Ok(Self::Unsubscribe(binance_ticker.trim().to_lowercase()))
+            }
+            Some(("enable", binance_ticker)) => {
+                Ok(Self::Enable(binance_ticker.trim().to_lowercase()))
+            }
+            Some(("disable", binance_ticker)) => {
+                Ok(Self::Disable(binance_ticker.trim().to_lowercase()))
+            }
+            _ => Err(anyhow!(
+                "Unknown command {}, expected subscribe <binance_ticker>:<pyth_price_id>, unsubscribe <binance_ticker>, enable <binance_ticker>, disable <binance_ticker>, pause, resume or reset-stats",
+                s.trim()
+            )),
+        }
+    }
+}
+
+/*
+    Command along with the channel through which the result of applying it is reported back to the client
+*/
+pub type ControlRequest = (ControlCommand, oneshot::Sender<Result<()>>);
+
+/*
+    Struct accepting commands changing the monitored pairs on a Unix domain socket
+    Commands are forwarded to the channel and answered with "ok" or "error: <reason>" lines
+*/
+pub struct ControlSocket {
+    listener: UnixListener,
+    sender: mpsc::Sender<ControlRequest>,
+}
+
+impl ControlSocket {
+    /*
+        Binds a listener to the provided path, removing a socket left over by a previous run, see remove_stale_socket
+    */
+    pub fn bind(path: &Path, sender: mpsc::Sender<ControlRequest>) -> Result<Self> {
+        remove_stale_socket(path)?;
+
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            sender,
+        })
+    }
+
+    /*
+        Accepts clients and spawns a handler for each of them
+    */
+    pub async fn serve(self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(Self::handle_client(stream, self.sender.clone()));
+                }
+                Err(err) => eprintln!("Could not accept control client: {}", err),
+            }
+        }
+    }
+
+    /*
+        Forwards every command sent by the client and writes back its result, until the client disconnects
+    */
+    async fn handle_client(stream: UnixStream, sender: mpsc::Sender<ControlRequest>) {
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let result = match ControlCommand::from_str(&line) {
+                Ok(command) => {
+                    let (result_sender, result_receiver) = oneshot::channel();
+                    if sender.send((command, result_sender)).await.is_err() {
+                        return; // commands are no longer applied
+                    }
+                    result_receiver
+                        .await
+                        .unwrap_or_else(|_| Err(anyhow!("Command was dropped")))
+                }
+                Err(err) => Err(err),
+            };
+
+            let response = match result {
+                Ok(()) => "ok\n".to_string(),
+                Err(err) => format!("error: {}\n", err),
+            };
+            if write.write_all(response.as_bytes()).await.is_err() {
+                return; // client disconnected
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use anyhow::anyhow;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixStream,
+        sync::mpsc,
+    };
+
+    use crate::config::PairConfig;
+
+    use super::{ControlCommand, ControlSocket};
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            ControlCommand::from_str(
+                "subscribe BTCUSDT:GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU"
+            )
+            .unwrap(),
+            ControlCommand::Subscribe(PairConfig {
+                binance_ticker: "btcusdt".to_string(),
+                pyth_price_id: "GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU".to_string(),
+                pyth_quote_price_id: None,
+                enabled: true,
+            })
+        );
+        assert_eq!(
+            ControlCommand::from_str("unsubscribe BTCUSDT\n").unwrap(),
+            ControlCommand::Unsubscribe("btcusdt".to_string())
+        );
+        assert_eq!(
+            ControlCommand::from_str("pause\n").unwrap(),
+            ControlCommand::Pause
+        );
+        assert_eq!(
+            ControlCommand::from_str("resume").unwrap(),
+            ControlCommand::Resume
+        );
+        assert_eq!(
+            ControlCommand::from_str("reset-stats").unwrap(),
+            ControlCommand::ResetStats
+        );
+        assert_eq!(
+            ControlCommand::from_str("disable SOLUSDT").unwrap(),
+            ControlCommand::Disable("solusdt".to_string())
+        );
+        assert!(ControlCommand::from_str("pause now").is_err());
+        assert!(ControlCommand::from_str("subscribe btcusdt").is_err());
+        assert!(ControlCommand::from_str("restart").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serve_forwards_commands() {
+        let path =
+            std::env::temp_dir().join(format!("keyrock-task-control-{}.sock", std::process::id()));
+        let (sender, mut receiver) = mpsc::channel(16);
+        tokio::spawn(ControlSocket::bind(&path, sender.clone()).unwrap().serve());
+        // The socket served above is not replaced
+        assert!(ControlSocket::bind(&path, sender).is_err());
+        tokio::spawn(async move {
+            while let Some((command, result_sender)) = receiver.recv().await {
+                let _ = result_sender.send(match command {
+                    ControlCommand::Subscribe(_)
+                    | ControlCommand::Pause
+                    | ControlCommand::Resume
+                    | ControlCommand::ResetStats
+                    | ControlCommand::Enable(_)
+                    | ControlCommand::Disable(_) => Ok(()),
+                    ControlCommand::Unsubscribe(binance_ticker) => {
+                        Err(anyhow!("Pair {} is not monitored", binance_ticker))
+                    }
+                });
+            }
+        });
+
+        let (read, mut write) = UnixStream::connect(&path).await.unwrap().into_split();
+        let mut lines = BufReader::new(read).lines();
+        write
+            .write_all(b"subscribe btcusdt:GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU\nunsubscribe ethusdt\nrestart\n")
+            .await
+            .unwrap();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "ok");
+        assert_eq!(
+            lines.next_line().await.unwrap().unwrap(),
+            "error: Pair ethusdt is not monitored"
+        );
+        assert!(lines
+            .next_line()
+            .await
+            .unwrap()
+            .unwrap()
+            .starts_with("error: Unknown command restart"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rust_decimal::Decimal;
+
+use super::clock::{Clock, SystemClock};
+
+// The recent window compared with the baseline is this fraction of the baseline window
+const RECENT_WINDOW_DIVISOR: u32 = 10;
+// Maximum number of tracked opportunities, the oldest one is dropped beyond it even if it is still within the baseline window
+const MAX_TRACKED: usize = 100_000;
+
+/*
+    Struct tracking emitted opportunities to detect a rate or average profit far outside the recent norms,
+    which more likely comes from a data or logic problem than from the market
+    The last tenth of the baseline window is compared with the rest of it, once the switch has observed a full window
+*/
+pub struct DeadMansSwitch {
+    rate_multiplier: Option<Decimal>, // maximum ratio of the recent rate to the baseline one
+    profit_multiplier: Option<Decimal>, // maximum ratio of the recent average profit to the baseline one
+    baseline_window: Duration,
+    observing_since: Instant,
+    emitted: VecDeque<(Instant, Decimal)>, // times and estimated profits within the baseline window
+    clock: Arc<dyn Clock>,
+}
+
+impl DeadMansSwitch {
+    pub fn new(
+        rate_multiplier: Option<Decimal>,
+        profit_multiplier: Option<Decimal>,
+        baseline_window: Duration,
+    ) -> Self {
+        Self {
+            rate_multiplier,
+            profit_multiplier,
+            baseline_window,
+            observing_since: SystemClock.now(),
+            emitted: VecDeque::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /*
+        Replaces the source of time, observing from its current time
+    */
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.observing_since = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /*
+        Records the opportunity about to be emitted, returns why emission should halt if the recent window departs from the baseline
+        Tripping starts observing anew, so a resumed emission is not halted again by the same opportunities
+    */
+    pub fn record(&mut self, estimated_profit: Decimal) -> Option<String> {
+        let now = self.clock.now();
+        if self.emitted.len() == MAX_TRACKED {
+            self.emitted.pop_front();
+        }
+        self.emitted.push_back((now, estimated_profit));
+        while self
+            .emitted
+            .front()
+            .is_some_and(|(emitted_at, _)| now.duration_since(*emitted_at) > self.baseline_window)
+        {
+            self.emitted.pop_front();
+        }
+        if now.duration_since(self.observing_since) < self.baseline_window {
+            return None; // no baseline yet
+        }
+
+        let reason = self.check(now);
+        if reason.is_some() {
+            self.emitted.clear();
+            self.observing_since = now;
+        }
+        reason
+    }
+
+    fn check(&self, now: Instant) -> Option<String> {
+        let recent_window = self.baseline_window / RECENT_WINDOW_DIVISOR;
+        let (recent, baseline): (Vec<_>, Vec<_>) = self
+            .emitted
+            .iter()
+            .partition(|(emitted_at, _)| now.duration_since(*emitted_at) <= recent_window);
+        let millis = |duration: Duration| Decimal::from(duration.as_millis() as u64); // doesn't overflow
+
+        // A quieter baseline counts as one opportunity per recent window, so a few ones after it do not trip the switch
+        let recent_rate = Decimal::from(recent.len()) / millis(recent_window).max(Decimal::ONE);
+        let baseline_rate = (Decimal::from(baseline.len())
+            / millis(self.baseline_window - recent_window).max(Decimal::ONE))
+        .max(Decimal::ONE / millis(recent_window).max(Decimal::ONE));
+        if let Some(rate_multiplier) = self.rate_multiplier {
+            if recent_rate > baseline_rate * rate_multiplier {
+                return Some(format!(
+                    "{} opportunities in the last {:?} exceed {} times the baseline rate",
+                    recent.len(),
+                    recent_window,
+                    rate_multiplier
+                ));
+            }
+        }
+
+        let average = |emitted: &[&(Instant, Decimal)]| {
+            emitted.iter().map(|(_, profit)| *profit).sum::<Decimal>()
+                / Decimal::from(emitted.len())
+        };
+        if let (Some(profit_multiplier), false) = (self.profit_multiplier, baseline.is_empty()) {
+            let (recent_average, baseline_average) = (average(&recent), average(&baseline));
+            if baseline_average > Decimal::ZERO
+                && recent_average > baseline_average * profit_multiplier
+            {
+                return Some(format!(
+                    "average estimated profit of {} in the last {:?} exceeds {} times the baseline of {}",
+                    recent_average.normalize(),
+                    recent_window,
+                    profit_multiplier,
+                    baseline_average.normalize()
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use rust_decimal::Decimal;
+
+    use crate::structs::clock::MockClock;
+
+    use super::DeadMansSwitch;
+
+    #[test]
+    fn test_dead_mans_switch() {
+        // Opportunity with profit of 0.1 every 10 seconds for the whole baseline window of 100 seconds
+        let observed_switch = |rate_multiplier, profit_multiplier| {
+            let clock = Arc::new(MockClock::new());
+            let mut switch =
+                DeadMansSwitch::new(rate_multiplier, profit_multiplier, Duration::from_secs(100))
+                    .with_clock(clock.clone());
+            for _ in 0..10 {
+                assert_eq!(switch.record(Decimal::new(1, 1)), None);
+                clock.advance(Duration::from_secs(10));
+            }
+            assert_eq!(switch.record(Decimal::new(1, 1)), None);
+            clock.advance(Duration::from_secs(1));
+            (switch, clock)
+        };
+
+        // The baseline rate is 0.1 per second, the last 10 seconds already held one from 100 seconds
+        let (mut switch, clock) = observed_switch(Some(Decimal::from(5)), None);
+        for _ in 0..4 {
+            assert_eq!(switch.record(Decimal::new(1, 1)), None);
+        }
+        assert!(switch
+            .record(Decimal::new(1, 1))
+            .unwrap()
+            .starts_with("6 opportunities in the last 10s exceed 5 times the baseline rate"));
+        // Observing starts anew
+        clock.advance(Duration::from_secs(99));
+        assert_eq!(switch.record(Decimal::new(1, 1)), None);
+
+        // The average of 0.1 and 1 exceeds 3 times the baseline average of 0.1
+        let (mut switch, _) = observed_switch(None, Some(Decimal::from(3)));
+        assert_eq!(
+            switch.record(Decimal::ONE),
+            Some(
+                "average estimated profit of 0.55 in the last 10s exceeds 3 times the baseline of 0.1"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use std::{collections::HashMap, fs, io::ErrorKind, path::Path};
+
+use anyhow::Result;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::arbitrage_finder::{ArbitrageDirection, ArbitrageOpportunity};
+
+/*
+    Struct holding deduplication state of the arbitrage finders of all pairs, persisted between runs
+    so opportunities found before a restart are not reported again
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DedupState {
+    pub pairs: HashMap<String, FinderDedupState>, // per Binance ticker
+}
+
+/*
+    Struct holding deduplication state of a single arbitrage finder
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinderDedupState {
+    pub saved_at: u64, // milliseconds since Unix epoch
+    pub last_found: Option<ArbitrageOpportunity>,
+    pub found_in_buckets: Vec<BucketFingerprint>,
+}
+
+/*
+    Struct identifying an opportunity found in a Binance price bucket
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketFingerprint {
+    pub direction: ArbitrageDirection,
+    pub bucket: Decimal,
+    pub found_at: u64, // milliseconds since Unix epoch
+}
+
+impl DedupState {
+    /*
+        Loads the state from the JSON file, returning an empty state if the file does not exist
+    */
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /*
+        Saves the state to the JSON file, replacing the previous one
+    */
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
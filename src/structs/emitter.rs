@@ -0,0 +1,790 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use metrics::{counter, gauge};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use super::{
+    arbitrage_finder::{ArbitrageOpportunity, RawInputs},
+    dead_mans_switch::DeadMansSwitch,
+    on_chain::congestion::CongestionMonitor,
+    stats::{Stats, Suppression},
+    telemetry,
+};
+
+// Returns the latest funding rate of the pair, if it has one
+type FundingRate = Box<dyn Fn(&str) -> Option<Decimal> + Send + Sync>;
+
+/*
+    Struct deciding whether found opportunities are emitted, recording them in stats and forwarding them to the sinks
+*/
+pub struct OpportunityEmitter {
+    sender: broadcast::Sender<EmittedOpportunity>,
+    stats: Arc<Mutex<Stats>>,
+    congestion_monitor: Option<Arc<CongestionMonitor>>,
+    net_positive_only: bool,
+    include_raw: bool,
+    funding_rate: Option<FundingRate>,
+    normalize_decimals: bool,
+    marginal_band_bps: Option<u32>,
+    max_plausible_profit_pct: Option<Decimal>,
+    anomaly_halt_after: Option<u64>,
+    anomalies: AtomicU64, // suppressed as anomalous since emission was last resumed
+    dead_mans_switch: Option<Mutex<DeadMansSwitch>>,
+    paused: AtomicBool,
+    next_seq: AtomicU64,
+}
+
+impl OpportunityEmitter {
+    pub fn new(sender: broadcast::Sender<EmittedOpportunity>, stats: Arc<Mutex<Stats>>) -> Self {
+        Self {
+            sender,
+            stats,
+            congestion_monitor: None,
+            net_positive_only: false,
+            include_raw: false,
+            funding_rate: None,
+            normalize_decimals: false,
+            marginal_band_bps: None,
+            max_plausible_profit_pct: None,
+            anomaly_halt_after: None,
+            anomalies: AtomicU64::new(0),
+            dead_mans_switch: None,
+            paused: AtomicBool::new(false),
+            next_seq: AtomicU64::new(0),
+        }
+    }
+
+    /*
+        Suppresses opportunities while the monitor reports Solana congestion
+    */
+    pub fn with_congestion_monitor(mut self, congestion_monitor: Arc<CongestionMonitor>) -> Self {
+        self.congestion_monitor = Some(congestion_monitor);
+        self
+    }
+
+    /*
+        Suppresses opportunities whose estimated profit is not positive once all the costs are applied and it is rounded
+    */
+    pub fn with_net_positive_only(mut self) -> Self {
+        self.net_positive_only = true;
+        self
+    }
+
+    /*
+        Flags emitted opportunities whose estimated profit is not negative and at most the band of their Binance notional as marginal
+    */
+    pub fn with_marginal_band_bps(mut self, marginal_band_bps: u32) -> Self {
+        self.marginal_band_bps = Some(marginal_band_bps);
+        self
+    }
+
+    /*
+        Suppresses opportunities whose estimated profit exceeds the percent of their Binance notional as anomalies
+        If halt_after is provided, emission is paused once that many anomalies were suppressed since it was last resumed
+    */
+    pub fn with_max_plausible_profit_pct(
+        mut self,
+        max_plausible_profit_pct: Decimal,
+        halt_after: Option<u64>,
+    ) -> Self {
+        self.max_plausible_profit_pct = Some(max_plausible_profit_pct);
+        self.anomaly_halt_after = halt_after;
+        self
+    }
+
+    /*
+        Halts emission once the switch finds the rate or average profit of emitted opportunities far outside their baseline
+    */
+    pub fn with_dead_mans_switch(mut self, dead_mans_switch: DeadMansSwitch) -> Self {
+        self.dead_mans_switch = Some(Mutex::new(dead_mans_switch));
+        self
+    }
+
+    /*
+        Attaches snapshots of the feeds to the emitted opportunities
+    */
+    pub fn with_raw_inputs(mut self) -> Self {
+        self.include_raw = true;
+        self
+    }
+
+    /*
+        Attaches the latest funding rate of the pair, as returned by funding_rate, to the emitted opportunities of pairs which have one
+    */
+    pub fn with_funding_rates(
+        mut self,
+        funding_rate: impl Fn(&str) -> Option<Decimal> + Send + Sync + 'static,
+    ) -> Self {
+        self.funding_rate = Some(Box::new(funding_rate));
+        self
+    }
+
+    /*
+        Trims trailing zeros of the decimal values of emitted opportunities, so all the sinks receive them alike
+    */
+    pub fn with_normalized_decimals(mut self) -> Self {
+        self.normalize_decimals = true;
+        self
+    }
+
+    /*
+        Suppresses all the opportunities until resumed, while they are still found and counted in the stats
+    */
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.anomalies.store(0, Ordering::Relaxed);
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /*
+        Emits the opportunity found for the pair unless it is suppressed, returns whether it was emitted
+    */
+    pub fn emit(&self, binance_ticker: &str, opportunity: ArbitrageOpportunity) -> bool {
+        self.emit_with_raw_inputs(binance_ticker, opportunity, None)
+    }
+
+    /*
+        Emits the opportunity like emit, attaching snapshots of the feeds it was found in if configured to
+    */
+    pub fn emit_with_raw_inputs(
+        &self,
+        binance_ticker: &str,
+        opportunity: ArbitrageOpportunity,
+        raw_inputs: Option<RawInputs>,
+    ) -> bool {
+        let mut stats = self.stats.lock().unwrap();
+
+        if self.is_paused() {
+            stats.record_suppression(Suppression::Paused);
+            return false;
+        }
+
+        if self.is_anomalous(&opportunity) {
+            stats.record_suppression(Suppression::Anomalous);
+            eprintln!(
+                "WARNING: Suppressed anomalous opportunity for {}, its profit is implausible and likely caused by bad data\n{:#?}\n",
+                binance_ticker, opportunity
+            );
+            let anomalies = self.anomalies.fetch_add(1, Ordering::Relaxed) + 1;
+            if self
+                .anomaly_halt_after
+                .is_some_and(|halt_after| anomalies >= halt_after)
+            {
+                self.pause();
+                eprintln!(
+                    "WARNING: Halted emission after {} anomalous opportunities, send resume command to continue\n",
+                    anomalies
+                );
+            }
+            return false;
+        }
+
+        if let Some(congestion_monitor) = &self.congestion_monitor {
+            if congestion_monitor.is_congested() {
+                stats.record_suppression(Suppression::Congestion);
+                return false;
+            }
+        }
+
+        if let Some(dead_mans_switch) = &self.dead_mans_switch {
+            if let Some(reason) = dead_mans_switch
+                .lock()
+                .unwrap()
+                .record(opportunity.estimated_profit)
+            {
+                self.pause();
+                stats.record_suppression(Suppression::Paused);
+                eprintln!(
+                    "WARNING: Halted emission at opportunity for {}, {}, likely caused by bad data or a bug, send resume command to continue\n{:#?}\n",
+                    binance_ticker, reason, opportunity
+                );
+                return false;
+            }
+        }
+
+        // Net-positive is the final gate
+        if self.net_positive_only && opportunity.estimated_profit.le(&Decimal::ZERO) {
+            stats.record_suppression(Suppression::NotNetPositive);
+            return false;
+        }
+
+        stats.record_opportunity(&opportunity);
+        counter!(
+            telemetry::OPPORTUNITIES,
+            "pair" => binance_ticker.to_string(),
+            "direction" => format!("{:?}", opportunity.direction),
+        )
+        .increment(1);
+        gauge!(telemetry::ESTIMATED_PROFIT, "pair" => binance_ticker.to_string())
+            .set(opportunity.estimated_profit.to_f64().unwrap_or_default());
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        drop(stats); // held until seq is assigned, so sequence numbers follow the order of recording
+
+        let emitted = EmittedOpportunity {
+            marginal: self.is_marginal(&opportunity),
+            raw: raw_inputs.filter(|_| self.include_raw),
+            funding_rate: self
+                .funding_rate
+                .as_ref()
+                .and_then(|funding_rate| funding_rate(binance_ticker))
+                .map(|funding_rate| match self.normalize_decimals {
+                    true => funding_rate.normalize(),
+                    false => funding_rate,
+                }),
+            ..EmittedOpportunity::new(
+                seq,
+                binance_ticker,
+                match self.normalize_decimals {
+                    true => opportunity.normalized(),
+                    false => opportunity,
+                },
+            )
+        };
+        let _ = self.sender.send(emitted); // fails only if there are no sinks
+        true
+    }
+
+    /*
+        Returns whether estimated profit of the opportunity exceeds the plausible percent of its Binance notional
+        A profit too large to be scaled is anomalous, while a plausible profit too large to be represented exceeds any profit
+    */
+    fn is_anomalous(&self, opportunity: &ArbitrageOpportunity) -> bool {
+        let Some(max_plausible_profit_pct) = self.max_plausible_profit_pct else {
+            return false;
+        };
+        let notional = opportunity.required_capital.cex;
+
+        match (
+            opportunity
+                .estimated_profit
+                .checked_mul(Decimal::ONE_HUNDRED),
+            notional.checked_mul(max_plausible_profit_pct),
+        ) {
+            (Some(profit), Some(plausible_profit)) => profit > plausible_profit,
+            (None, _) => true,
+            (Some(_), None) => false,
+        }
+    }
+
+    /*
+        Returns whether estimated profit of the opportunity lies within the marginal band of its Binance notional
+        False if no band is configured or the band overflows, as it would be far beyond any plausible profit
+    */
+    fn is_marginal(&self, opportunity: &ArbitrageOpportunity) -> bool {
+        let Some(marginal_band_bps) = self.marginal_band_bps else {
+            return false;
+        };
+        let Some(band) = opportunity
+            .required_capital
+            .cex
+            .checked_mul(Decimal::from(marginal_band_bps))
+        else {
+            return false;
+        };
+        let band = band / Decimal::from(10_000); // doesn't overflow
+
+        opportunity.estimated_profit >= Decimal::ZERO && opportunity.estimated_profit <= band
+    }
+}
+
+/*
+    Struct carrying an emitted opportunity to the sinks along with the pair it was found for
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EmittedOpportunity {
+    pub seq: u64,       // number of opportunities emitted before this one, across all pairs
+    pub pair: String,   // Binance ticker of the pair
+    pub timestamp: u64, // milliseconds since Unix epoch
+    #[serde(flatten)]
+    pub opportunity: ArbitrageOpportunity,
+    pub marginal: bool, // whether estimated profit is within the marginal band
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw: Option<RawInputs>, // snapshots of the feeds, if configured to be included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub funding_rate: Option<Decimal>, // latest funding rate of the perp pair, if configured to be included
+}
+
+impl EmittedOpportunity {
+    pub fn new(seq: u64, binance_ticker: &str, opportunity: ArbitrageOpportunity) -> Self {
+        Self {
+            seq,
+            pair: binance_ticker.to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64, // doesn't overflow
+            opportunity,
+            marginal: false,
+            raw: None,
+            funding_rate: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        str::FromStr,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+    use serde_json::json;
+    use tokio::sync::{broadcast, RwLock};
+    use uuid::Uuid;
+
+    use crate::structs::{
+        arbitrage_finder::{
+            ArbitrageDirection, ArbitrageFinder, ArbitrageOpportunity, ProfitBreakdown, RawInputs,
+            RequiredCapital,
+        },
+        cex::binance::BookTickerData,
+        clock::MockClock,
+        dead_mans_switch::DeadMansSwitch,
+        on_chain::congestion::{tests::MockSignal, CongestionMonitor},
+        sinks::{
+            grpc::proto::Opportunity,
+            handler::{LineFormat, WriterHandler},
+        },
+        stats::Stats,
+    };
+
+    use super::OpportunityEmitter;
+
+    #[test]
+    fn test_emit_congestion() {
+        let opportunity = ArbitrageOpportunity::sample();
+        let level = Arc::new(AtomicU64::new(1000));
+        let congestion_monitor = Arc::new(CongestionMonitor::new(
+            Box::new(MockSignal(level.clone())),
+            500,
+        ));
+        congestion_monitor.update().unwrap();
+
+        let (sender, mut receiver) = broadcast::channel(16);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        let emitter = OpportunityEmitter::new(sender, stats.clone())
+            .with_congestion_monitor(congestion_monitor.clone());
+
+        // Above threshold
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(stats.lock().unwrap().suppressed_by_congestion, 1);
+        assert_eq!(stats.lock().unwrap().opportunities(), 0);
+
+        // Below threshold
+        level.store(100, Ordering::Relaxed);
+        congestion_monitor.update().unwrap();
+        assert!(emitter.emit("solusdt", opportunity));
+        let emitted = receiver.try_recv().unwrap();
+        assert_eq!(emitted.pair, "solusdt");
+        assert_eq!(emitted.opportunity, opportunity);
+        assert_eq!(stats.lock().unwrap().suppressed_by_congestion, 1);
+        assert_eq!(stats.lock().unwrap().opportunities(), 1);
+    }
+
+    #[test]
+    fn test_emit_seq() {
+        let opportunity = ArbitrageOpportunity::sample();
+        let (sender, mut receiver) = broadcast::channel(16);
+        let emitter = OpportunityEmitter::new(sender, Arc::new(Mutex::new(Stats::default())));
+
+        for binance_ticker in ["solusdt", "btcusdt", "solusdt", "bnbusdt"] {
+            assert!(emitter.emit(binance_ticker, opportunity));
+        }
+        let seqs = (0..4)
+            .map(|_| receiver.try_recv().unwrap().seq)
+            .collect::<Vec<_>>();
+        assert_eq!(seqs, vec![0, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_emit_net_positive_only() {
+        async fn find(
+            bid: &str,
+            quantity: &str,
+            binance_fee: Decimal,
+        ) -> Option<ArbitrageOpportunity> {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: bid.to_string(),
+                B: quantity.to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+            ArbitrageFinder::new()
+                .find_opportunity(latest_pyth_price, latest_binance_ticker_data, binance_fee)
+                .await
+        }
+        let (sender, mut receiver) = broadcast::channel(16);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        let emitter =
+            OpportunityEmitter::new(sender.clone(), stats.clone()).with_net_positive_only();
+
+        // Gross edge of 0.0952058 is positive, but the fee of 0.1224081 exceeds it, so the finder never reports it
+        assert!(find("71.3833", "0.8574", Decimal::new(2, 3))
+            .await
+            .is_none());
+
+        // Gross edge of 0.0713436035 exceeds the fee by 0.0000000000165, which rounds to zero net profit
+        let opportunity = find("71.3436034835", "1", Decimal::new(1, 3))
+            .await
+            .unwrap();
+        assert!(opportunity.profit_breakdown.gross_edge > opportunity.profit_breakdown.binance_fee);
+        assert_eq!(opportunity.estimated_profit, Decimal::ZERO);
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(stats.lock().unwrap().opportunities(), 0);
+        assert_eq!(stats.lock().unwrap().suppressed_as_not_net_positive, 1);
+
+        let emitter = OpportunityEmitter::new(sender, stats.clone());
+        assert!(emitter.emit("solusdt", opportunity));
+        assert_eq!(receiver.try_recv().unwrap().opportunity, opportunity);
+    }
+
+    #[test]
+    fn test_emit_marginal() {
+        // Estimated profit is about 5.56 bps of 71.3833 * 0.8574
+        let opportunity = ArbitrageOpportunity::sample();
+        let (sender, mut receiver) = broadcast::channel(16);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+
+        let emitter =
+            OpportunityEmitter::new(sender.clone(), stats.clone()).with_marginal_band_bps(6);
+        assert!(emitter.emit("solusdt", opportunity));
+        let emitted = receiver.try_recv().unwrap();
+        assert!(emitted.marginal);
+        assert_eq!(serde_json::to_value(emitted).unwrap()["marginal"], true);
+
+        let emitter =
+            OpportunityEmitter::new(sender.clone(), stats.clone()).with_marginal_band_bps(5);
+        assert!(emitter.emit("solusdt", opportunity));
+        assert!(!receiver.try_recv().unwrap().marginal);
+
+        // An overflowing band emits the opportunity as not marginal
+        let emitter =
+            OpportunityEmitter::new(sender.clone(), stats.clone()).with_marginal_band_bps(6);
+        let overflowing = ArbitrageOpportunity {
+            required_capital: RequiredCapital {
+                cex: Decimal::MAX,
+                ..opportunity.required_capital
+            },
+            ..opportunity
+        };
+        assert!(emitter.emit("solusdt", overflowing));
+        assert!(!receiver.try_recv().unwrap().marginal);
+
+        let emitter = OpportunityEmitter::new(sender, stats);
+        assert!(emitter.emit("solusdt", opportunity));
+        assert!(!receiver.try_recv().unwrap().marginal);
+    }
+
+    #[test]
+    fn test_emit_anomalous() {
+        // Binance bid of 150 against Pyth price of 69.85, i.e. profit of over 50% of notional
+        let plausible = ArbitrageOpportunity::sample();
+        let opportunity = ArbitrageOpportunity {
+            estimated_profit: Decimal::from_str("67.39464778").unwrap(),
+            profit_low: Decimal::from_str("67.39464778").unwrap(),
+            profit_high: Decimal::from_str("69.82930631").unwrap(),
+            binance_price: Decimal::from_str("150").unwrap(),
+            profit_breakdown: ProfitBreakdown {
+                gross_edge: Decimal::from_str("67.523662378888").unwrap(),
+                binance_fee: Decimal::from_str("0.1286100").unwrap(),
+                dex_fee: Decimal::ZERO,
+            },
+            required_capital: RequiredCapital {
+                cex: Decimal::from_str("128.61").unwrap(),
+                ..plausible.required_capital
+            },
+            ..plausible
+        };
+        let (sender, mut receiver) = broadcast::channel(16);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        let emitter = OpportunityEmitter::new(sender, stats.clone())
+            .with_max_plausible_profit_pct(Decimal::from(50), Some(2));
+
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(stats.lock().unwrap().suppressed_as_anomalous, 1);
+        assert!(emitter.emit("solusdt", plausible));
+        assert_eq!(receiver.try_recv().unwrap().opportunity, plausible);
+
+        // Emission halts after the second anomaly
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert_eq!(stats.lock().unwrap().suppressed_as_anomalous, 2);
+        assert!(emitter.is_paused());
+        assert!(!emitter.emit("solusdt", plausible));
+        assert_eq!(stats.lock().unwrap().suppressed_while_paused, 1);
+
+        // Resuming starts counting anew, so the next anomaly alone does not halt emission again
+        emitter.resume();
+        assert!(emitter.emit("solusdt", plausible));
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert!(!emitter.is_paused());
+        assert!(emitter.emit("solusdt", plausible));
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert!(emitter.is_paused());
+        assert_eq!(stats.lock().unwrap().suppressed_as_anomalous, 4);
+
+        // Profit overflowing the check is anomalous
+        emitter.resume();
+        let overflowing = ArbitrageOpportunity {
+            estimated_profit: Decimal::MAX,
+            ..opportunity
+        };
+        assert!(!emitter.emit("solusdt", overflowing));
+        assert_eq!(stats.lock().unwrap().suppressed_as_anomalous, 5);
+    }
+
+    #[test]
+    fn test_emit_paused() {
+        let opportunity = ArbitrageOpportunity::sample();
+        let (sender, mut receiver) = broadcast::channel(16);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        let emitter = OpportunityEmitter::new(sender, stats.clone());
+
+        emitter.pause();
+        assert!(emitter.is_paused());
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(stats.lock().unwrap().suppressed_while_paused, 2);
+        assert_eq!(stats.lock().unwrap().opportunities(), 0);
+
+        emitter.resume();
+        assert!(!emitter.is_paused());
+        assert!(emitter.emit("solusdt", opportunity));
+        let emitted = receiver.try_recv().unwrap();
+        assert_eq!(emitted.seq, 0);
+        assert_eq!(emitted.opportunity, opportunity);
+        assert_eq!(stats.lock().unwrap().opportunities(), 1);
+    }
+
+    #[test]
+    fn test_emit_dead_mans_switch() {
+        let opportunity = ArbitrageOpportunity::sample();
+        let (sender, mut receiver) = broadcast::channel(16);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        let clock = Arc::new(MockClock::new());
+        let emitter = OpportunityEmitter::new(sender, stats.clone()).with_dead_mans_switch(
+            DeadMansSwitch::new(Some(Decimal::from(5)), None, Duration::from_secs(100))
+                .with_clock(clock.clone()),
+        );
+
+        // After a quiet baseline, a flood of more than 5 opportunities within 10 seconds halts emission
+        clock.advance(Duration::from_secs(100));
+        for _ in 0..5 {
+            assert!(emitter.emit("solusdt", opportunity));
+            assert!(receiver.try_recv().is_ok());
+        }
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert!(receiver.try_recv().is_err());
+        assert!(emitter.is_paused());
+        assert!(!emitter.emit("solusdt", opportunity));
+        assert_eq!(stats.lock().unwrap().suppressed_while_paused, 2);
+
+        emitter.resume();
+        assert!(emitter.emit("solusdt", opportunity));
+    }
+
+    #[test]
+    fn test_emit_with_raw_inputs() {
+        let opportunity = ArbitrageOpportunity::sample();
+        let raw_inputs = RawInputs {
+            pyth: Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                publish_time: 1700000000,
+            },
+            binance: BookTickerData {
+                u: 400900217,
+                s: "SOLUSDT".to_string(),
+                b: "71.3833".to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+            },
+        };
+        let (sender, mut receiver) = broadcast::channel(16);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+
+        let emitter = OpportunityEmitter::new(sender.clone(), stats.clone()).with_raw_inputs();
+        assert!(emitter.emit_with_raw_inputs("solusdt", opportunity, Some(raw_inputs.clone())));
+        let json = serde_json::to_value(receiver.try_recv().unwrap()).unwrap();
+        assert_eq!(
+            json["raw"],
+            json!({
+                "pyth": {"price": "69852445", "conf": "669724", "expo": -6, "publish_time": 1700000000},
+                "binance": {"u": 400900217, "s": "SOLUSDT", "b": "71.3833", "B": "0.8574", "a": "72.0012", "A": "0.9245"},
+            })
+        );
+        assert_eq!(json["estimated_profit"], "0.03400176");
+
+        // Not included unless configured
+        let emitter = OpportunityEmitter::new(sender, stats);
+        assert!(emitter.emit_with_raw_inputs("solusdt", opportunity, Some(raw_inputs)));
+        let json = serde_json::to_value(receiver.try_recv().unwrap()).unwrap();
+        assert!(json.get("raw").is_none());
+    }
+
+    #[test]
+    fn test_emit_with_funding_rates() {
+        let opportunity = ArbitrageOpportunity::sample();
+        let (sender, mut receiver) = broadcast::channel(16);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+
+        let emitter = OpportunityEmitter::new(sender.clone(), stats.clone())
+            .with_funding_rates(|binance_ticker| {
+                (binance_ticker == "solusdt").then(|| Decimal::from_str("0.00038100").unwrap())
+            })
+            .with_normalized_decimals();
+        assert!(emitter.emit("solusdt", opportunity));
+        let json = serde_json::to_value(receiver.try_recv().unwrap()).unwrap();
+        assert_eq!(json["funding_rate"], "0.000381");
+
+        // Left out for pairs without a funding rate and unless configured
+        assert!(emitter.emit("btcusdt", opportunity));
+        let json = serde_json::to_value(receiver.try_recv().unwrap()).unwrap();
+        assert!(json.get("funding_rate").is_none());
+        let emitter = OpportunityEmitter::new(sender, stats);
+        assert!(emitter.emit("solusdt", opportunity));
+        let json = serde_json::to_value(receiver.try_recv().unwrap()).unwrap();
+        assert!(json.get("funding_rate").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_opportunity_ids() {
+        async fn find(
+            arbitrage_finder: &mut ArbitrageFinder,
+            bid: &str,
+        ) -> Option<ArbitrageOpportunity> {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+                b: bid.to_string(),
+                B: "0.8574".to_string(),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            })));
+            arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data,
+                    Decimal::new(1, 3),
+                )
+                .await
+        }
+
+        let (sender, mut json_receiver) = broadcast::channel(16);
+        let mut grpc_receiver = sender.subscribe();
+        let emitter = OpportunityEmitter::new(sender, Arc::new(Mutex::new(Stats::default())));
+        let mut arbitrage_finder = ArbitrageFinder::new();
+
+        let first = find(&mut arbitrage_finder, "71.3833").await.unwrap();
+        let second = find(&mut arbitrage_finder, "71.4").await.unwrap();
+        assert!(!first.id.is_nil());
+        assert_ne!(first.id, second.id);
+        // Ids are not compared, so an opportunity found again is still a duplicate
+        assert_eq!(
+            first,
+            ArbitrageOpportunity {
+                id: Uuid::new_v4(),
+                ..first
+            }
+        );
+        assert!(find(&mut arbitrage_finder, "71.4").await.is_none());
+
+        for opportunity in [first, second] {
+            assert!(emitter.emit("solusdt", opportunity));
+            let id = opportunity.id.to_string();
+            let json = serde_json::to_value(json_receiver.try_recv().unwrap()).unwrap();
+            assert_eq!(json["id"], id);
+            let message = Opportunity::from(&grpc_receiver.try_recv().unwrap());
+            assert_eq!(message.id, id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_emit_normalized_decimals() {
+        // Scales as left by rounding and arithmetic rather than by the finder
+        let opportunity = ArbitrageOpportunity {
+            id: Uuid::nil(),
+            direction: ArbitrageDirection::SellBinanceBuyDex,
+            quantity: Decimal::from_str("0.85740").unwrap(),
+            estimated_profit: Decimal::from_str("0.03400000").unwrap(),
+            profit_low: Decimal::from_str("0.03400000").unwrap(),
+            profit_high: Decimal::from_str("2.46870000").unwrap(),
+            binance_price: Decimal::from_str("71.38330").unwrap(),
+            pyth_price: Decimal::from_str("71.27220000").unwrap(),
+            profit_breakdown: ProfitBreakdown {
+                gross_edge: Decimal::from_str("0.095200").unwrap(),
+                binance_fee: Decimal::from_str("0.061200").unwrap(),
+                dex_fee: Decimal::from_str("0.000").unwrap(),
+            },
+            required_capital: RequiredCapital {
+                cex: Decimal::from_str("61.2000").unwrap(),
+                dex: Decimal::from_str("61.1000").unwrap(),
+            },
+        };
+        let (sender, mut receiver) = broadcast::channel(16);
+        let emitter = OpportunityEmitter::new(sender, Arc::new(Mutex::new(Stats::default())))
+            .with_normalized_decimals();
+
+        assert!(emitter.emit("solusdt", opportunity));
+        let emitted = receiver.try_recv().unwrap();
+        let json = WriterHandler::new(Box::new(std::io::sink()), LineFormat::Json, false)
+            .format_line(&emitted);
+        for value in [
+            r#""quantity":"0.8574""#,
+            r#""estimated_profit":"0.034""#,
+            r#""profit_high":"2.4687""#,
+            r#""pyth_price":"71.2722""#,
+            r#""gross_edge":"0.0952""#,
+            r#""dex_fee":"0""#,
+            r#""cex":"61.2""#,
+        ] {
+            assert!(json.contains(value), "{} not in {}", value, json);
+        }
+        let csv = WriterHandler::new(Box::new(std::io::sink()), LineFormat::Csv, false)
+            .format_line(&emitted);
+        assert!(csv.contains(",solusdt,SellBinanceBuyDex,0.8574,0.034,71.3833,71.2722,"));
+        assert_eq!(Opportunity::from(&emitted).estimated_profit, "0.034");
+        // The values themselves are unchanged
+        assert_eq!(emitted.opportunity, opportunity);
+    }
+}
@@ -0,0 +1,120 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::time::MissedTickBehavior;
+
+use super::state::PairState;
+
+/*
+    Struct periodically reporting the state of the monitored pairs, so it is clear the application is alive during quiet markets
+*/
+pub struct Heartbeat {
+    interval: Duration,
+}
+
+impl Heartbeat {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+
+    /*
+        Calls on_beat with the heartbeat line of every pair returned by pairs once per interval, starting after the first one
+    */
+    pub async fn run<P, F>(self, pairs: P, on_beat: F)
+    where
+        P: Fn() -> Vec<Arc<PairState>>,
+        F: Fn(String),
+    {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval.tick().await; // completes immediately
+
+        loop {
+            interval.tick().await;
+            for pair in pairs() {
+                on_beat(pair.heartbeat().await);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use pyth_sdk_solana::Price;
+
+    use crate::{
+        config::PairConfig,
+        structs::{cex::binance::BookTickerData, state::PairState},
+    };
+
+    use super::Heartbeat;
+
+    #[tokio::test]
+    async fn test_run() {
+        let pair = Arc::new(PairState::new(&PairConfig {
+            binance_ticker: "solusdt".to_string(),
+            pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+            pyth_quote_price_id: None,
+            enabled: true,
+        }));
+        // No opportunity, the bid and ask are within the probable price range
+        pair.update_latest_pyth_price(Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        }))
+        .await;
+        pair.update_latest_binance_ticker_data(BookTickerData {
+            b: "69.8".to_string(),
+            B: "0.8574".to_string(),
+            a: "69.9".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let interval = Duration::from_millis(50);
+        let _ = tokio::time::timeout(
+            interval * 2 + interval / 2,
+            Heartbeat::new(interval).run(
+                {
+                    let pair = pair.clone();
+                    move || vec![pair.clone()]
+                },
+                {
+                    let lines = lines.clone();
+                    move |line| lines.lock().unwrap().push(line)
+                },
+            ),
+        )
+        .await;
+
+        let lines = lines.lock().unwrap().clone();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0]
+            .starts_with("Heartbeat solusdt: Binance bid 69.8 ask 69.9 spread 0.1 (updated "));
+        assert!(lines[0].contains("Pyth price 69.852445 conf 0.669724 (updated "));
+
+        let pair = PairState::new(&PairConfig {
+            binance_ticker: "bnbusdt".to_string(),
+            pyth_price_id: "4CkQJBxhU8EZ2UjhigbtdaPbpTe6mqf811fipYBFbSYN".to_string(),
+            pyth_quote_price_id: None,
+            enabled: true,
+        });
+        assert_eq!(
+            pair.heartbeat().await,
+            "Heartbeat bnbusdt: Binance no ticker data (never updated), Pyth no price (never updated)"
+        );
+        assert!(pair.set_enabled(false));
+        assert!(pair
+            .heartbeat()
+            .await
+            .starts_with("Heartbeat bnbusdt (disabled): Binance no ticker data"));
+    }
+}
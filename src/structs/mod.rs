@@ -1,4 +1,28 @@
 pub mod arbitrage_finder;
+pub mod backoff;
+pub mod backtest;
+pub mod bench;
+pub mod bounds;
+pub mod calibration;
 pub mod cex;
+pub mod clock;
+pub mod closes;
+pub mod control;
+pub mod dead_mans_switch;
+pub mod dedup_state;
+pub mod emitter;
+pub mod heartbeat;
+pub mod noops;
+pub mod numeric;
 pub mod on_chain;
+pub mod pair_supervisor;
+pub mod profit_bars;
+pub mod schema;
+pub mod sinks;
+pub mod spread_snapshot;
 pub mod state;
+pub mod stats;
+pub mod telemetry;
+pub mod tick_buffer;
+pub mod verify;
+pub mod worker_pool;
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{arbitrage_finder::RawInputs, cex::binance::parse_decimal};
+
+// Minimum time between no-op events of a pair if no evaluation interval is configured
+pub const DEFAULT_NOOP_INTERVAL: Duration = Duration::from_secs(1);
+
+/*
+    Struct describing an evaluated tick at which no opportunity was reported, along with the spread it was evaluated at
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NoopEvent {
+    pub pair: String,        // Binance ticker of the pair
+    pub timestamp: u64,      // milliseconds since Unix epoch
+    pub opportunity: String, // always none, telling the event apart from opportunities
+    pub bid: Decimal,
+    pub ask: Decimal,
+    pub spread: Decimal, // ask minus bid
+    pub pyth_price: Decimal,
+}
+
+/*
+    Struct sending no-op events of a pair, at most one per interval so evaluating every tick does not flood the consumers
+*/
+pub struct NoopReporter {
+    binance_ticker: String,
+    interval: u64, // milliseconds
+    sender: UnboundedSender<NoopEvent>,
+    last_reported: Option<u64>, // timestamp of the last sent event
+}
+
+impl NoopReporter {
+    pub fn new(
+        binance_ticker: &str,
+        interval: Duration,
+        sender: UnboundedSender<NoopEvent>,
+    ) -> Self {
+        Self {
+            binance_ticker: binance_ticker.to_string(),
+            interval: interval.as_millis() as u64, // doesn't overflow
+            sender,
+            last_reported: None,
+        }
+    }
+
+    /*
+        Sends the event of the tick evaluated at the timestamp unless one was sent within the interval
+        Ticks with malformed prices are ignored
+    */
+    pub fn record_at(&mut self, timestamp: u64, raw_inputs: &RawInputs) {
+        if self
+            .last_reported
+            .is_some_and(|last_reported| timestamp.saturating_sub(last_reported) < self.interval)
+        {
+            return;
+        }
+        let Some(event) = self.event(timestamp, raw_inputs) else {
+            return;
+        };
+
+        self.last_reported = Some(timestamp);
+        let _ = self.sender.send(event); // fails only if events are not consumed
+    }
+
+    fn event(&self, timestamp: u64, raw_inputs: &RawInputs) -> Option<NoopEvent> {
+        let bid = parse_decimal(&raw_inputs.binance.b)?;
+        let ask = parse_decimal(&raw_inputs.binance.a)?;
+        let pyth_price =
+            Decimal::try_new(raw_inputs.pyth.price, raw_inputs.pyth.expo.unsigned_abs()).ok()?;
+
+        Some(NoopEvent {
+            pair: self.binance_ticker.clone(),
+            timestamp,
+            opportunity: "none".to_string(),
+            bid: bid.normalize(),
+            ask: ask.normalize(),
+            spread: ask.checked_sub(bid)?.normalize(),
+            pyth_price: pyth_price.normalize(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc, time::Duration};
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+    use tokio::sync::{mpsc, RwLock};
+
+    use crate::structs::{
+        arbitrage_finder::{ArbitrageFinder, ArbitrageOpportunity},
+        cex::binance::BookTickerData,
+        clock::MockClock,
+    };
+
+    use super::{NoopEvent, NoopReporter};
+
+    #[tokio::test]
+    async fn test_noops() {
+        async fn find(
+            arbitrage_finder: &mut ArbitrageFinder,
+            latest_binance_ticker_data: &Arc<RwLock<Option<BookTickerData>>>,
+        ) -> Option<ArbitrageOpportunity> {
+            // l: 68.43263012 h: 71.27225988
+            let latest_pyth_price = Arc::new(RwLock::new(Some(Price {
+                price: 69852445,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            })));
+            arbitrage_finder
+                .find_opportunity(
+                    latest_pyth_price,
+                    latest_binance_ticker_data.clone(),
+                    Decimal::new(1, 3),
+                )
+                .await
+        }
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let clock = Arc::new(MockClock::starting_at(1_000_000));
+        let mut arbitrage_finder =
+            ArbitrageFinder::new()
+                .with_clock(clock.clone())
+                .with_noops(NoopReporter::new(
+                    "solusdt",
+                    Duration::from_millis(500),
+                    sender,
+                ));
+        let latest_binance_ticker_data = Arc::new(RwLock::new(Some(BookTickerData {
+            b: "69.5".to_string(),
+            B: "0.8574".to_string(),
+            a: "69.62".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        })));
+
+        assert!(find(&mut arbitrage_finder, &latest_binance_ticker_data)
+            .await
+            .is_none());
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            NoopEvent {
+                pair: "solusdt".to_string(),
+                timestamp: 1_000_000,
+                opportunity: "none".to_string(),
+                bid: Decimal::from_str("69.5").unwrap(),
+                ask: Decimal::from_str("69.62").unwrap(),
+                spread: Decimal::from_str("0.12").unwrap(),
+                pyth_price: Decimal::from_str("69.852445").unwrap(),
+            }
+        );
+
+        // Ticks within the interval are not reported
+        clock.advance(Duration::from_millis(499));
+        assert!(find(&mut arbitrage_finder, &latest_binance_ticker_data)
+            .await
+            .is_none());
+        assert!(receiver.try_recv().is_err());
+
+        clock.advance(Duration::from_millis(1));
+        assert!(find(&mut arbitrage_finder, &latest_binance_ticker_data)
+            .await
+            .is_none());
+        assert_eq!(receiver.try_recv().unwrap().timestamp, 1_000_500);
+
+        // Ticks with a reported opportunity are not no-ops
+        latest_binance_ticker_data.write().await.as_mut().unwrap().b = "71.3833".to_string();
+        clock.advance(Duration::from_secs(1));
+        assert!(find(&mut arbitrage_finder, &latest_binance_ticker_data)
+            .await
+            .is_some());
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    fmt::Debug,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use rust_decimal::Decimal;
+
+use super::cex::binance::parse_decimal;
+
+// Decimal products rounded to fit the precision, across the whole application
+static PRECISION_LOSSES: AtomicU64 = AtomicU64::new(0);
+
+/*
+    Trait of numeric types the arbitrage calculations can be carried out in
+    Decimal is exact and used by default, f64 (behind f64 feature) is faster but rounds every operation to the nearest binary fraction
+    Checked operations return None instead of overflowing
+*/
+pub trait Numeric: Copy + PartialOrd + Debug {
+    const ZERO: Self;
+
+    // Returns the mantissa scaled by 10 to the power of minus absolute value of the exponent, as Pyth prices are
+    fn from_mantissa(mantissa: i64, exponent: i32) -> Option<Self>;
+    fn parse(value: &str) -> Option<Self>;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn abs(self) -> Self;
+}
+
+impl Numeric for Decimal {
+    const ZERO: Self = Decimal::ZERO;
+
+    fn from_mantissa(mantissa: i64, exponent: i32) -> Option<Self> {
+        Decimal::try_new(mantissa, exponent.unsigned_abs()).ok()
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        parse_decimal(value)
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Decimal::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        Decimal::checked_sub(self, other)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        self.precise_mul(other)
+    }
+
+    fn abs(self) -> Self {
+        Decimal::abs(&self)
+    }
+}
+
+/*
+    Trait of Decimal arithmetic detecting loss of precision, which every product of the arbitrage calculations goes through
+    A product whose exact value needs more than 28 decimal places or 96 bits of mantissa is rounded half to even to fit,
+    that is counted and logged with a warning, once per power of two of the count so a persistent loss does not flood the log
+*/
+pub trait PreciseDecimal: Sized {
+    // Multiplies like checked_mul, None on overflow
+    fn precise_mul(self, other: Self) -> Option<Self>;
+}
+
+impl PreciseDecimal for Decimal {
+    fn precise_mul(self, other: Self) -> Option<Self> {
+        let product = self.checked_mul(other)?;
+        if !is_exact_product(self, other, product) {
+            let losses = PRECISION_LOSSES.fetch_add(1, Ordering::Relaxed) + 1;
+            if losses.is_power_of_two() {
+                eprintln!(
+                    "WARNING: Product of {} and {} was rounded to {} to fit Decimal precision, {} products rounded so far\n",
+                    self, other, product, losses
+                );
+            }
+        }
+        Some(product)
+    }
+}
+
+/*
+    Returns whether the product is the exact value of a times b
+    If the mantissas multiply beyond 128 bits, the product is taken as exact unless its scale was reduced
+*/
+pub fn is_exact_product(a: Decimal, b: Decimal, product: Decimal) -> bool {
+    let Some(mut mantissa) = a
+        .mantissa()
+        .unsigned_abs()
+        .checked_mul(b.mantissa().unsigned_abs())
+    else {
+        return product.scale() == a.scale() + b.scale();
+    };
+    let mut scale = a.scale() + b.scale();
+    while scale > 0 && mantissa % 10 == 0 {
+        mantissa /= 10;
+        scale -= 1;
+    }
+
+    i128::try_from(mantissa)
+        .ok()
+        .and_then(|mantissa| Decimal::try_from_i128_with_scale(mantissa, scale).ok())
+        .is_some_and(|exact| exact == product.abs())
+}
+
+/*
+    Returns number of Decimal products rounded to fit the precision since startup
+*/
+pub fn precision_losses() -> u64 {
+    PRECISION_LOSSES.load(Ordering::Relaxed)
+}
+
+#[cfg(feature = "f64")]
+impl Numeric for f64 {
+    const ZERO: Self = 0.0;
+
+    fn from_mantissa(mantissa: i64, exponent: i32) -> Option<Self> {
+        finite(mantissa as f64 / 10f64.powi(exponent.unsigned_abs().try_into().ok()?))
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        finite(value.parse().ok()?)
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        finite(self + other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        finite(self - other)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        finite(self * other)
+    }
+
+    fn abs(self) -> Self {
+        f64::abs(self)
+    }
+}
+
+/*
+    Returns the value unless it is infinite or NaN, which f64 operations produce instead of failing
+*/
+#[cfg(feature = "f64")]
+fn finite(value: f64) -> Option<f64> {
+    value.is_finite().then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use super::{is_exact_product, precision_losses, PreciseDecimal};
+
+    #[test]
+    fn test_precise_mul() {
+        let exact = |a: &str, b: &str| {
+            let (a, b) = (Decimal::from_str(a).unwrap(), Decimal::from_str(b).unwrap());
+            is_exact_product(a, b, a.checked_mul(b).unwrap())
+        };
+        assert!(exact("0.5", "0.2"));
+        assert!(exact("71.3833", "0.8574"));
+        assert!(exact("-0.00000000000001", "0.00000000000001"));
+        // 1e-29 needs 29 decimal places
+        assert!(!exact("0.00000000000001", "0.000000000000001"));
+        // 28 significant digits times 28 significant digits
+        assert!(!exact(
+            "1.234567890123456789012345678",
+            "9.876543210987654321098765432"
+        ));
+
+        // Rounded half to even and counted
+        let losses = precision_losses();
+        let product = Decimal::from_str("0.00000000000001")
+            .unwrap()
+            .precise_mul(Decimal::from_str("0.000000000000015").unwrap())
+            .unwrap();
+        assert_eq!(
+            product,
+            Decimal::from_str("0.0000000000000000000000000002").unwrap()
+        );
+        assert!(precision_losses() > losses);
+        assert_eq!(Decimal::MAX.precise_mul(Decimal::TWO), None);
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+
+/*
+    Trait of sources measuring how congested Solana currently is, the higher the value the more congested
+*/
+pub trait CongestionSignal: Send + Sync {
+    fn sample(&self) -> Result<u64>;
+}
+
+/*
+    Signal measuring median of recent prioritization fees in micro-lamports per compute unit
+*/
+pub struct PrioritizationFeeSignal {
+    client: RpcClient,
+}
+
+impl PrioritizationFeeSignal {
+    pub fn new(rpc_url: &str) -> Self {
+        Self {
+            client: RpcClient::new(rpc_url),
+        }
+    }
+}
+
+impl CongestionSignal for PrioritizationFeeSignal {
+    fn sample(&self) -> Result<u64> {
+        let mut fees = self
+            .client
+            .get_recent_prioritization_fees(&[])?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect::<Vec<_>>();
+        if fees.is_empty() {
+            return Err(anyhow!("No recent prioritization fees"));
+        }
+
+        fees.sort_unstable();
+        Ok(fees[fees.len() / 2])
+    }
+}
+
+/*
+    Signal measuring average time of recent slots in milliseconds
+*/
+pub struct SlotTimeSignal {
+    client: RpcClient,
+}
+
+impl SlotTimeSignal {
+    pub fn new(rpc_url: &str) -> Self {
+        Self {
+            client: RpcClient::new(rpc_url),
+        }
+    }
+}
+
+impl CongestionSignal for SlotTimeSignal {
+    fn sample(&self) -> Result<u64> {
+        let samples = self.client.get_recent_performance_samples(Some(5))?;
+        let slots = samples.iter().map(|sample| sample.num_slots).sum::<u64>();
+        if slots == 0 {
+            return Err(anyhow!("No recent performance samples"));
+        }
+
+        let period_millis = samples
+            .iter()
+            .map(|sample| sample.sample_period_secs as u64 * 1000)
+            .sum::<u64>();
+        Ok(period_millis / slots)
+    }
+}
+
+/*
+    Struct keeping the latest level of a congestion signal and comparing it against the threshold
+*/
+pub struct CongestionMonitor {
+    signal: Box<dyn CongestionSignal>,
+    threshold: u64,
+    latest: AtomicU64,
+}
+
+impl CongestionMonitor {
+    pub fn new(signal: Box<dyn CongestionSignal>, threshold: u64) -> Self {
+        Self {
+            signal,
+            threshold,
+            latest: AtomicU64::new(0),
+        }
+    }
+
+    /*
+        Samples the signal and stores its level, keeping the previous one if sampling fails
+    */
+    pub fn update(&self) -> Result<u64> {
+        let level = self.signal.sample()?;
+        self.latest.store(level, Ordering::Relaxed);
+        Ok(level)
+    }
+
+    /*
+        Checks whether the latest level of the signal exceeds the threshold
+    */
+    pub fn is_congested(&self) -> bool {
+        self.latest.load(Ordering::Relaxed) > self.threshold
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    use anyhow::{anyhow, Result};
+
+    use super::{CongestionMonitor, CongestionSignal};
+
+    /*
+        Signal returning the level stored in it, or an error if the level is u64::MAX
+    */
+    pub struct MockSignal(pub Arc<AtomicU64>);
+
+    impl CongestionSignal for MockSignal {
+        fn sample(&self) -> Result<u64> {
+            match self.0.load(Ordering::Relaxed) {
+                u64::MAX => Err(anyhow!("Signal unavailable")),
+                level => Ok(level),
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_congested() {
+        let level = Arc::new(AtomicU64::new(100));
+        let monitor = CongestionMonitor::new(Box::new(MockSignal(level.clone())), 500);
+        assert!(!monitor.is_congested());
+
+        monitor.update().unwrap();
+        assert!(!monitor.is_congested());
+
+        level.store(501, Ordering::Relaxed);
+        monitor.update().unwrap();
+        assert!(monitor.is_congested());
+
+        // Failed sample keeps the previous level
+        level.store(u64::MAX, Ordering::Relaxed);
+        assert!(monitor.update().is_err());
+        assert!(monitor.is_congested());
+
+        level.store(500, Ordering::Relaxed);
+        monitor.update().unwrap();
+        assert!(!monitor.is_congested());
+    }
+}
@@ -1 +1,4 @@
+pub mod congestion;
+pub mod pool;
 pub mod pyth;
+pub mod twap;
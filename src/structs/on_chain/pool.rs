@@ -0,0 +1,125 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use anyhow::{anyhow, Result};
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use solana_client::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+
+// Byte ranges of fields of an Orca Whirlpool account, after its 8-byte discriminator
+const SQRT_PRICE: std::ops::Range<usize> = 65..81; // u128, Q64.64
+const TOKEN_MINT_A: std::ops::Range<usize> = 101..133;
+const TOKEN_MINT_B: std::ops::Range<usize> = 181..213;
+// Byte offset of decimals of an SPL token mint account
+const MINT_DECIMALS: usize = 44;
+
+/*
+    Trait of sources of prices of on-chain pools, so the pool price updater can be tested without Solana
+*/
+pub trait PoolPriceSource: Send + Sync {
+    // Returns the current price of token A of the pool in units of token B
+    fn get_price(&self, pool: &Pubkey) -> Result<Decimal>;
+}
+
+/*
+    Struct reading prices of Orca Whirlpools (concentrated liquidity pools) from Solana RPC
+    Decimals of the tokens of a pool are fetched with its first price and kept, as mints never change them
+*/
+pub struct Whirlpool {
+    client: RpcClient,
+    decimals: Mutex<HashMap<Pubkey, (u8, u8)>>, // of token A and token B per pool
+}
+
+impl Whirlpool {
+    pub fn new(rpc_url: &str) -> Self {
+        Self {
+            client: RpcClient::new(rpc_url),
+            decimals: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn decimals(&self, data: &[u8]) -> Result<(u8, u8)> {
+        let mint = |range: std::ops::Range<usize>| -> Result<u8> {
+            let mint = Pubkey::try_from(
+                data.get(range)
+                    .ok_or_else(|| anyhow!("Whirlpool account is too short"))?,
+            )?;
+            self.client
+                .get_account_data(&mint)?
+                .get(MINT_DECIMALS)
+                .copied()
+                .ok_or_else(|| anyhow!("Mint account {} is too short", mint))
+        };
+
+        Ok((mint(TOKEN_MINT_A)?, mint(TOKEN_MINT_B)?))
+    }
+}
+
+impl PoolPriceSource for Whirlpool {
+    fn get_price(&self, pool: &Pubkey) -> Result<Decimal> {
+        let data = self.client.get_account_data(pool)?;
+        let cached = self.decimals.lock().unwrap().get(pool).copied();
+        let (decimals_a, decimals_b) = match cached {
+            Some(decimals) => decimals,
+            None => {
+                let decimals = self.decimals(&data)?;
+                self.decimals.lock().unwrap().insert(*pool, decimals);
+                decimals
+            }
+        };
+
+        whirlpool_price(&data, decimals_a, decimals_b)
+    }
+}
+
+/*
+    Returns the price of token A in units of token B of the Whirlpool account data, (sqrt_price / 2^64)^2 * 10^(decimals_a - decimals_b)
+    Fails if the data is too short or the price cannot be represented as Decimal
+*/
+pub fn whirlpool_price(data: &[u8], decimals_a: u8, decimals_b: u8) -> Result<Decimal> {
+    let sqrt_price = u128::from_le_bytes(
+        data.get(SQRT_PRICE)
+            .ok_or_else(|| anyhow!("Whirlpool account is too short"))?
+            .try_into()
+            .unwrap(), // of 16 bytes
+    );
+    let overflow = || anyhow!("Whirlpool sqrt price {} overflows Decimal", sqrt_price);
+    let sqrt_price = Decimal::from_u128(sqrt_price)
+        .ok_or_else(overflow)?
+        .checked_div(Decimal::from_u128(1 << 64).unwrap()) // fits 96 bits
+        .ok_or_else(overflow)?;
+    let price = sqrt_price.checked_mul(sqrt_price).ok_or_else(overflow)?;
+    let unit = Decimal::try_new(1, decimals_a.abs_diff(decimals_b).into())?; // 10^-difference
+    let price = if decimals_a >= decimals_b {
+        price.checked_div(unit)
+    } else {
+        price.checked_mul(unit)
+    };
+
+    Ok(price.ok_or_else(overflow)?.normalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use super::{whirlpool_price, SQRT_PRICE};
+
+    #[test]
+    fn test_whirlpool_price() {
+        // sqrt_price of 2^62 is a raw price of 0.0625, i.e. 62.5 USDC (6 decimals) per SOL (9 decimals)
+        let mut data = vec![0; 653];
+        data[SQRT_PRICE].copy_from_slice(&(1u128 << 62).to_le_bytes());
+        assert_eq!(
+            whirlpool_price(&data, 9, 6).unwrap(),
+            Decimal::from_str("62.5").unwrap()
+        );
+        assert_eq!(
+            whirlpool_price(&data, 6, 9).unwrap(),
+            Decimal::from_str("0.0000625").unwrap()
+        );
+
+        assert!(whirlpool_price(&data[..80], 9, 6).is_err());
+    }
+}
@@ -1,26 +1,75 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::Result;
-use pyth_sdk_solana::Price;
+use pyth_sdk_solana::{
+    state::{load_price_account, PriceAccount, PriceStatus},
+    Price, PriceFeed,
+};
+use rust_decimal::Decimal;
 use solana_client::rpc_client::RpcClient;
 use solana_program::pubkey::Pubkey;
 
+use crate::config::PythField;
+
 const PYTH_RPC_URL: &str = "http:/pythnet.rpcpool.com";
+const MAX_PRICE_AGE: u64 = 60; // seconds
+
+/*
+    Trait of sources of the prices Binance prices are compared with
+*/
+pub trait PriceSource: Send + Sync {
+    fn get_price(&self, price_id: &Pubkey) -> Result<Option<Price>>;
+}
 
 /*
     Struct representing a Pyth connection
 */
 pub struct Pyth {
     client: RpcClient,
+    publisher_trim: usize,
+    field: PythField,
+}
+
+impl Default for Pyth {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Pyth {
     pub fn new() -> Self {
+        Self::with_publisher_trim(0)
+    }
+
+    /*
+        Creates a Pyth connection replacing the aggregate price with the trimmed aggregate of publishers' prices
+        Number of the lowest and the highest prices dropped is publisher_trim, 0 disables trimming
+    */
+    pub fn with_publisher_trim(publisher_trim: usize) -> Self {
         Self {
             client: RpcClient::new(PYTH_RPC_URL),
+            publisher_trim,
+            field: PythField::Spot,
         }
     }
 
+    /*
+        Returns the given price and confidence pair of the feed instead of the aggregate ones
+        Publishers' prices are not averaged, so trimming applies to the aggregate only
+    */
+    pub fn with_field(mut self, field: PythField) -> Self {
+        self.field = field;
+        self
+    }
+
+    /*
+        Fails every request which is not completed within the timeout, including connecting, instead of waiting for it indefinitely
+    */
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = RpcClient::new_with_timeout(PYTH_RPC_URL.to_string(), timeout);
+        self
+    }
+
     /*
         Fetches the most current price from Pyth
     */
@@ -34,8 +83,121 @@ impl Pyth {
             .unwrap()
             .as_secs() as i64;
 
-        Ok(price_feed.get_price_no_older_than(current_time, 60))
+        let maybe_price = select_price(&price_feed, self.field, current_time);
+        if self.publisher_trim == 0 || self.field != PythField::Spot {
+            return Ok(maybe_price);
+        }
+
+        let components = trading_components(load_price_account(&price_account.data)?);
+
+        // Falls back to the standard aggregate if there are not enough publishers
+        Ok(maybe_price.map(
+            |price| match trimmed_aggregate(&components, self.publisher_trim) {
+                Some((trimmed_price, trimmed_conf)) => Price {
+                    price: trimmed_price,
+                    conf: trimmed_conf,
+                    ..price
+                },
+                None => price,
+            },
+        ))
+    }
+}
+
+impl PriceSource for Pyth {
+    fn get_price(&self, price_id: &Pubkey) -> Result<Option<Price>> {
+        Pyth::get_price(self, price_id)
+    }
+}
+
+/*
+    Source returning the same price with no confidence for every price feed, which decouples demos and tests from Solana
+*/
+pub struct FixedPrice {
+    price: Decimal,
+    publish_time: i64, // creation time, so the price never looks updated
+}
+
+impl FixedPrice {
+    pub fn new(price: Decimal) -> Self {
+        Self {
+            price: price.normalize(),
+            publish_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+        }
+    }
+}
+
+impl PriceSource for FixedPrice {
+    fn get_price(&self, _price_id: &Pubkey) -> Result<Option<Price>> {
+        Ok(Some(Price {
+            price: i64::try_from(self.price.mantissa())?,
+            conf: 0,
+            expo: -(self.price.scale() as i32),
+            publish_time: self.publish_time,
+        }))
+    }
+}
+
+/*
+    Returns the price and confidence pair of the feed, None if it was published more than a minute before the current time
+*/
+pub fn select_price(price_feed: &PriceFeed, field: PythField, current_time: i64) -> Option<Price> {
+    match field {
+        PythField::Spot => price_feed.get_price_no_older_than(current_time, MAX_PRICE_AGE),
+        PythField::Ema => price_feed.get_ema_price_no_older_than(current_time, MAX_PRICE_AGE),
+    }
+}
+
+/*
+    Derives price of base in terms of quote from their prices in a common currency, e.g. SOL/BTC from SOL/USD and BTC/USD
+    Confidence of the result combines confidences of both prices
+*/
+pub fn derive_cross_price(base: &Price, quote: &Price) -> Option<Price> {
+    base.get_price_in_quote(quote, base.expo.min(quote.expo))
+}
+
+/*
+    Returns the (price, confidence) components of the publishers trading in the price account
+    An account reporting more components than it has slots for is read up to its last slot
+*/
+pub fn trading_components(price_data: &PriceAccount) -> Vec<(i64, u64)> {
+    let num = (price_data.num as usize).min(price_data.comp.len());
+    price_data.comp[..num]
+        .iter()
+        .filter(|component| component.agg.status == PriceStatus::Trading)
+        .map(|component| (component.agg.price, component.agg.conf))
+        .collect()
+}
+
+/*
+    Drops trim lowest and trim highest of the (price, confidence) components and averages the remaining ones
+    Returns None if fewer than 2 * trim + 1 components are available
+*/
+pub fn trimmed_aggregate(components: &[(i64, u64)], trim: usize) -> Option<(i64, u64)> {
+    if components.len() <= 2 * trim {
+        return None;
     }
+
+    let mut sorted = components.to_vec();
+    sorted.sort_unstable_by_key(|(price, _)| *price);
+    let remaining = &sorted[trim..sorted.len() - trim];
+
+    let count = remaining.len() as i128;
+    let price = remaining
+        .iter()
+        .map(|(price, _)| *price as i128)
+        .sum::<i128>()
+        / count;
+    let conf = remaining
+        .iter()
+        .map(|(_, conf)| *conf as i128)
+        .sum::<i128>()
+        / count;
+
+    Some((price as i64, conf as u64))
 }
 
 #[cfg(test)]
@@ -44,7 +206,181 @@ mod tests {
 
     use solana_program::pubkey::Pubkey;
 
-    use super::Pyth;
+    use std::sync::Arc;
+
+    use pyth_sdk_solana::{
+        state::{PriceAccount, PriceStatus},
+        Price, PriceFeed, PriceIdentifier,
+    };
+    use rust_decimal::Decimal;
+    use tokio::sync::RwLock;
+
+    use crate::{
+        config::PythField,
+        structs::{
+            arbitrage_finder::{
+                calculate_pyth_confidence_bounds, ArbitrageDirection, ArbitrageFinder,
+            },
+            cex::binance::BookTickerData,
+        },
+    };
+
+    use super::{
+        derive_cross_price, select_price, trading_components, trimmed_aggregate, FixedPrice,
+        PriceSource, Pyth,
+    };
+
+    #[tokio::test]
+    async fn test_derive_cross_price() {
+        // 100 +- 0.1
+        let sol_usd = Price {
+            price: 10_000_000_000,
+            conf: 10_000_000,
+            expo: -8,
+            publish_time: 100,
+        };
+        // 50000 +- 25
+        let btc_usd = Price {
+            price: 5_000_000_000_000,
+            conf: 2_500_000_000,
+            expo: -8,
+            publish_time: 90,
+        };
+
+        // 0.002 +- (0.1 / 50000 + 100 * 25 / 50000^2)
+        let sol_btc = derive_cross_price(&sol_usd, &btc_usd).unwrap();
+        assert_eq!(
+            sol_btc,
+            Price {
+                price: 200_000,
+                conf: 300,
+                expo: -8,
+                publish_time: 90,
+            }
+        );
+
+        // l: 0.00199364 h: 0.00200636
+        let mut arbitrage_finder = ArbitrageFinder::new();
+        let result = arbitrage_finder
+            .find_opportunity(
+                Arc::new(RwLock::new(Some(sol_btc))),
+                Arc::new(RwLock::new(Some(BookTickerData {
+                    b: "0.002010".to_string(),
+                    B: "1000".to_string(),
+                    a: "0.002011".to_string(),
+                    A: "1000".to_string(),
+                    ..Default::default()
+                }))),
+                Decimal::new(1, 3),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.direction, ArbitrageDirection::SellBinanceBuyDex);
+        assert_eq!(result.pyth_price, Decimal::new(200636, 8));
+        assert_eq!(result.estimated_profit, Decimal::new(163, 5));
+    }
+
+    #[tokio::test]
+    async fn test_fixed_price() {
+        let fixed_price = FixedPrice::new(Decimal::from_str("69.8524450").unwrap());
+        let price = fixed_price
+            .get_price(&Pubkey::from([0; 32]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(price.price, 69852445);
+        assert_eq!(price.conf, 0);
+        assert_eq!(price.expo, -6);
+
+        // Without a band, the fixed price is the whole probable price range
+        let mut arbitrage_finder = ArbitrageFinder::new();
+        let result = arbitrage_finder
+            .find_opportunity(
+                Arc::new(RwLock::new(Some(price))),
+                Arc::new(RwLock::new(Some(BookTickerData {
+                    b: "71.3833".to_string(),
+                    B: "0.8574".to_string(),
+                    a: "72.0012".to_string(),
+                    A: "0.9245".to_string(),
+                    ..Default::default()
+                }))),
+                Decimal::new(1, 3),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.direction, ArbitrageDirection::SellBinanceBuyDex);
+        assert_eq!(result.pyth_price, Decimal::from_str("69.852445").unwrap());
+        assert_eq!(
+            result.estimated_profit,
+            Decimal::from_str("1.25135104").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_select_price() {
+        // 69.852445 +- 0.669724
+        let spot = Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            publish_time: 1000,
+        };
+        // 70.1 +- 0.5
+        let ema = Price {
+            price: 70100000,
+            conf: 500000,
+            expo: -6,
+            publish_time: 1000,
+        };
+        let price_feed = PriceFeed::new(PriceIdentifier::new([0; 32]), spot, ema);
+
+        assert_eq!(select_price(&price_feed, PythField::Spot, 1060), Some(spot));
+        let ema_price = select_price(&price_feed, PythField::Ema, 1060).unwrap();
+        assert_eq!(ema_price, ema);
+        let (lower, upper) =
+            calculate_pyth_confidence_bounds(ema_price, Decimal::new(212, 2)).unwrap();
+        assert_eq!(lower.normalize(), Decimal::from_str("69.04").unwrap());
+        assert_eq!(upper.normalize(), Decimal::from_str("71.16").unwrap());
+
+        // Stale prices are not used
+        assert!(select_price(&price_feed, PythField::Ema, 1061).is_none());
+    }
+
+    #[test]
+    fn test_trimmed_aggregate() {
+        let components = [
+            (10_000, 10),
+            (10_100, 12),
+            (9_900, 8),
+            (25_000, 500), // outlier
+            (10_050, 10),
+            (1_000, 400), // outlier
+            (9_950, 10),
+        ];
+
+        assert_eq!(trimmed_aggregate(&components, 1), Some((10_000, 10)));
+        assert_eq!(trimmed_aggregate(&components, 0), Some((10_857, 135)));
+        assert_eq!(trimmed_aggregate(&components, 3), Some((10_000, 10)));
+        assert_eq!(trimmed_aggregate(&components[..2], 1), None);
+    }
+
+    #[test]
+    fn test_trading_components() {
+        let mut price_data = PriceAccount::default();
+        for (i, component) in price_data.comp.iter_mut().enumerate().step_by(2) {
+            component.agg.price = 10_000 + i as i64;
+            component.agg.conf = 10;
+            component.agg.status = PriceStatus::Trading;
+        }
+
+        price_data.num = 4;
+        assert_eq!(
+            trading_components(&price_data),
+            vec![(10_000, 10), (10_002, 10)]
+        );
+        // More components than slots are read up to the last slot
+        price_data.num = 40;
+        assert_eq!(trading_components(&price_data).len(), 16);
+    }
 
     #[test]
     fn test_get_price_account_does_not_exist() {
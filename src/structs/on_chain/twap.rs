@@ -0,0 +1,135 @@
+use std::{collections::VecDeque, time::Duration};
+
+use pyth_sdk_solana::Price;
+
+// Maximum number of prices kept, the oldest one is dropped beyond it even if it is still within the window
+const MAX_SAMPLES: usize = 4096;
+
+/*
+    Struct averaging Pyth prices and confidences over a trailing window, each weighted by the time it was the latest one
+    A price holds from its publish time until the next one is published, the latest one until now
+    At most MAX_SAMPLES prices are kept, so a long window over a fast feed averages only the most recent ones
+*/
+#[derive(Debug, Clone)]
+pub struct PythTwap {
+    window: u64,              // milliseconds
+    samples: VecDeque<Price>, // distinct published prices, from the oldest
+}
+
+impl PythTwap {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: window.as_millis() as u64, // doesn't overflow
+            samples: VecDeque::new(),
+        }
+    }
+
+    /*
+        Records the latest Pyth price and returns the time-weighted averages of the window ending at now, in milliseconds since Unix epoch
+        The average carries the latest publish time, a price with another exponent starts the window anew
+    */
+    pub fn record(&mut self, price: Price, now: u64) -> Price {
+        if self
+            .samples
+            .back()
+            .is_some_and(|last| last.expo != price.expo)
+        {
+            self.samples.clear();
+        }
+        if self
+            .samples
+            .back()
+            .is_none_or(|last| last.publish_time != price.publish_time)
+        {
+            if self.samples.len() == MAX_SAMPLES {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(price);
+        }
+
+        let start = now.saturating_sub(self.window);
+        let held_since = |price: &Price| (price.publish_time.max(0) as u64).saturating_mul(1000);
+        // Prices superseded before the window starts no longer count
+        while self.samples.len() > 1 && held_since(&self.samples[1]) <= start {
+            self.samples.pop_front();
+        }
+
+        let (mut weighted_price, mut weighted_conf, mut total_weight) = (0i128, 0i128, 0i128);
+        for (i, sample) in self.samples.iter().enumerate() {
+            let held_until = self.samples.get(i + 1).map_or(now, held_since);
+            let weight = held_until
+                .min(now)
+                .saturating_sub(held_since(sample).max(start)) as i128;
+            weighted_price += sample.price as i128 * weight;
+            weighted_conf += sample.conf as i128 * weight;
+            total_weight += weight;
+        }
+        if total_weight == 0 {
+            return price; // only published at the end of the window
+        }
+
+        Price {
+            price: (weighted_price / total_weight) as i64, // an average of i64 values
+            conf: (weighted_conf / total_weight) as u64,   // an average of u64 values
+            ..price
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pyth_sdk_solana::Price;
+
+    use super::{PythTwap, MAX_SAMPLES};
+
+    #[test]
+    fn test_twap() {
+        let price = |price: i64, conf: u64, publish_time: i64| Price {
+            price,
+            conf,
+            expo: -2,
+            publish_time,
+        };
+        let mut twap = PythTwap::new(Duration::from_secs(10));
+
+        // 100 +- 1 published at 0 s, 110 +- 3 at 5 s
+        assert_eq!(
+            twap.record(price(10000, 100, 0), 5000),
+            price(10000, 100, 0)
+        );
+        assert_eq!(
+            twap.record(price(11000, 300, 5), 5000),
+            price(10000, 100, 5)
+        );
+        assert_eq!(
+            twap.record(price(11000, 300, 5), 10000),
+            price(10500, 200, 5)
+        );
+        assert_eq!(
+            twap.record(price(11000, 300, 5), 12500),
+            price(10750, 250, 5)
+        );
+        // The first price no longer counts once the window starts after it was superseded
+        assert_eq!(
+            twap.record(price(11000, 300, 5), 20000),
+            price(11000, 300, 5)
+        );
+
+        // Another exponent starts anew
+        let scaled = Price {
+            expo: -3,
+            ..price(110000, 3000, 21)
+        };
+        assert_eq!(twap.record(scaled, 21000), scaled);
+
+        // A price published every second within a window far longer than the capacity
+        let mut twap = PythTwap::new(Duration::from_secs(1_000_000));
+        for publish_time in 0..5000 {
+            twap.record(price(10000, 100, publish_time), publish_time as u64 * 1000);
+            assert!(twap.samples.len() <= MAX_SAMPLES);
+        }
+        assert_eq!(twap.samples.len(), MAX_SAMPLES);
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{mpsc::UnboundedSender, Mutex},
+    task::JoinHandle,
+    time::MissedTickBehavior,
+};
+
+use super::{
+    arbitrage_finder::{ArbitrageFinder, FinderSettings},
+    cex::exchange_info::SymbolDecimalsCache,
+    closes::{CloseEvent, CloseReporter},
+    dedup_state::DedupState,
+    noops::{NoopEvent, NoopReporter},
+    profit_bars::{ProfitBar, ProfitBars},
+    spread_snapshot::SpreadSnapshot,
+    state::PairState,
+    stats::Stats,
+    worker_pool::{PairEvaluation, WorkerPool},
+};
+
+// Task evaluating a pair along with its arbitrage finder
+type PairTask = (JoinHandle<()>, Arc<Mutex<ArbitrageFinder>>);
+
+/*
+    Struct managing lifecycle of the tasks submitting evaluations of the monitored pairs to the worker pool
+    Each pair has its own task and arbitrage finder, so pairs can be added and removed at runtime
+*/
+pub struct PairSupervisor {
+    settings: FinderSettings,
+    worker_pool: Arc<WorkerPool>,
+    tasks: StdMutex<HashMap<String, PairTask>>,
+    stats: Option<Arc<StdMutex<Stats>>>,
+    eval_interval: Option<Duration>, // minimum time between evaluations of a pair, None evaluates continuously
+    profit_bars: Option<(Duration, UnboundedSender<ProfitBar>)>, // bar length and where closed bars are sent
+    noops: Option<(Duration, UnboundedSender<NoopEvent>)>, // minimum time between no-op events of a pair and where they are sent
+    closes: Option<UnboundedSender<CloseEvent>>, // where closes of open opportunities are sent
+    restored_dedup_state: Option<(DedupState, Duration)>, // state saved by the previous run and its ttl
+    delisted: StdMutex<HashSet<String>>, // pairs stopped because their symbols are no longer traded
+    symbol_decimals: Option<&'static SymbolDecimalsCache>, // where tick sizes of the pairs are looked up when they are started
+}
+
+/*
+    Struct holding tickers of the pairs stopped or started again by applying refreshed listings
+*/
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ListingChanges {
+    pub delisted: Vec<String>,
+    pub relisted: Vec<String>,
+}
+
+impl PairSupervisor {
+    pub fn new(settings: FinderSettings, worker_pool: WorkerPool) -> Self {
+        Self {
+            settings,
+            worker_pool: Arc::new(worker_pool),
+            tasks: StdMutex::new(HashMap::new()),
+            stats: None,
+            eval_interval: None,
+            profit_bars: None,
+            noops: None,
+            closes: None,
+            restored_dedup_state: None,
+            delisted: StdMutex::new(HashSet::new()),
+            symbol_decimals: None,
+        }
+    }
+
+    /*
+        Counts opportunities suppressed by the arbitrage finders in the stats
+    */
+    pub fn with_stats(mut self, stats: Arc<StdMutex<Stats>>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /*
+        Measures the minimum edge of each pair in the tick size of its symbol, if it is cached
+    */
+    pub fn with_symbol_decimals(mut self, symbol_decimals: &'static SymbolDecimalsCache) -> Self {
+        self.symbol_decimals = Some(symbol_decimals);
+        self
+    }
+
+    /*
+        Evaluates each pair at most once per interval, coalescing the updates received in between into the latest snapshot
+    */
+    pub fn with_eval_interval(mut self, eval_interval: Duration) -> Self {
+        self.eval_interval = Some(eval_interval);
+        self
+    }
+
+    /*
+        Aggregates evaluated ticks of each pair into bars of the given length, sending every closed bar
+    */
+    pub fn with_profit_bars(
+        mut self,
+        bar_length: Duration,
+        sender: UnboundedSender<ProfitBar>,
+    ) -> Self {
+        self.profit_bars = Some((bar_length, sender));
+        self
+    }
+
+    /*
+        Sends a no-op event for evaluated ticks of each pair without an opportunity, at most one per interval
+    */
+    pub fn with_noops(mut self, interval: Duration, sender: UnboundedSender<NoopEvent>) -> Self {
+        self.noops = Some((interval, sender));
+        self
+    }
+
+    /*
+        Sends an event whenever an open opportunity of a pair closes, with the reason it closed for
+    */
+    pub fn with_closes(mut self, sender: UnboundedSender<CloseEvent>) -> Self {
+        self.closes = Some(sender);
+        self
+    }
+
+    /*
+        Restores deduplication state of the pairs saved by the previous run, dropping parts of it older than ttl
+    */
+    pub fn with_dedup_state(mut self, dedup_state: DedupState, ttl: Duration) -> Self {
+        self.restored_dedup_state = Some((dedup_state, ttl));
+        self
+    }
+
+    /*
+        Returns deduplication state of all the evaluated pairs
+    */
+    pub async fn dedup_state(&self) -> DedupState {
+        let mut dedup_state = DedupState::default();
+        for (binance_ticker, arbitrage_finder) in self.arbitrage_finders() {
+            dedup_state
+                .pairs
+                .insert(binance_ticker, arbitrage_finder.lock().await.dedup_state());
+        }
+        dedup_state
+    }
+
+    /*
+        Returns the current spreads of all the evaluated pairs at the timestamp, in milliseconds since Unix epoch
+    */
+    pub async fn spread_snapshot(&self, timestamp: u64) -> SpreadSnapshot {
+        SpreadSnapshot::gather(timestamp, self.arbitrage_finders()).await
+    }
+
+    fn arbitrage_finders(&self) -> Vec<(String, Arc<Mutex<ArbitrageFinder>>)> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(binance_ticker, (_, arbitrage_finder))| {
+                (binance_ticker.clone(), arbitrage_finder.clone())
+            })
+            .collect()
+    }
+
+    /*
+        Spawns the task evaluating the pair with a fresh arbitrage finder, replacing the previous one if any
+        The task waits while the pair is disabled
+    */
+    pub fn start(&self, pair: Arc<PairState>) {
+        let mut arbitrage_finder = ArbitrageFinder::with_settings(self.settings)
+            .with_last_reconnect(pair.get_last_binance_reconnect());
+        if pair.has_dex_pool() {
+            arbitrage_finder =
+                arbitrage_finder.with_dex_pool_price(pair.get_latest_dex_pool_price());
+        }
+        if let Some(stats) = &self.stats {
+            stats
+                .lock()
+                .unwrap()
+                .record_pair_enabled(&pair.binance_ticker, pair.is_enabled());
+            arbitrage_finder = arbitrage_finder.with_stats(stats.clone());
+        }
+        if let Some(decimals) = self
+            .symbol_decimals
+            .and_then(|symbol_decimals| symbol_decimals.get(&pair.binance_ticker))
+        {
+            arbitrage_finder = arbitrage_finder.with_tick_size(decimals.tick_size);
+        }
+        if let Some((bar_length, sender)) = &self.profit_bars {
+            arbitrage_finder = arbitrage_finder.with_profit_bars(ProfitBars::new(
+                &pair.binance_ticker,
+                *bar_length,
+                sender.clone(),
+            ));
+        }
+        if let Some((interval, sender)) = &self.noops {
+            arbitrage_finder = arbitrage_finder.with_noops(NoopReporter::new(
+                &pair.binance_ticker,
+                *interval,
+                sender.clone(),
+            ));
+        }
+        if let Some(sender) = &self.closes {
+            arbitrage_finder = arbitrage_finder
+                .with_closes(CloseReporter::new(&pair.binance_ticker, sender.clone()));
+        }
+        if let Some((dedup_state, ttl)) = &self.restored_dedup_state {
+            if let Some(finder_dedup_state) = dedup_state.pairs.get(&pair.binance_ticker) {
+                arbitrage_finder.restore_dedup_state(finder_dedup_state, *ttl);
+            }
+        }
+        let arbitrage_finder = Arc::new(Mutex::new(arbitrage_finder));
+        let pair_task_finder = arbitrage_finder.clone();
+        let worker_pool = self.worker_pool.clone();
+        let binance_ticker = pair.binance_ticker.clone();
+        let eval_interval = self.eval_interval;
+
+        let task = tokio::spawn(async move {
+            let mut eval_interval = eval_interval.map(|eval_interval| {
+                let mut eval_interval = tokio::time::interval(eval_interval);
+                eval_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                eval_interval
+            });
+            loop {
+                pair.wait_until_enabled().await;
+                if let Some(eval_interval) = &mut eval_interval {
+                    eval_interval.tick().await;
+                }
+                worker_pool
+                    .submit(PairEvaluation {
+                        binance_ticker: pair.binance_ticker.clone(),
+                        arbitrage_finder: arbitrage_finder.clone(),
+                        latest_pyth_price: pair.get_latest_pyth_price(),
+                        latest_binance_ticker_data: pair.get_latest_binance_ticker_data(),
+                        binance_fee: pair.binance_taker_fee,
+                    })
+                    .await;
+                tokio::task::yield_now().await; // lets the other pairs and tasks run if the queue never fills up
+            }
+        });
+
+        if let Some((previous_task, _)) = self
+            .tasks
+            .lock()
+            .unwrap()
+            .insert(binance_ticker, (task, pair_task_finder))
+        {
+            previous_task.abort();
+        }
+    }
+
+    /*
+        Enables or disables the pair, recording its state in the stats
+        A disabled pair keeps being monitored, but is not evaluated, so none of its opportunities is emitted
+    */
+    pub fn set_enabled(&self, pair: &PairState, enabled: bool) {
+        pair.set_enabled(enabled);
+        if let Some(stats) = &self.stats {
+            stats
+                .lock()
+                .unwrap()
+                .record_pair_enabled(&pair.binance_ticker, enabled);
+        }
+    }
+
+    /*
+        Aborts the task evaluating the pair, returns whether the pair was evaluated
+    */
+    pub fn stop(&self, binance_ticker: &str) -> bool {
+        match self.tasks.lock().unwrap().remove(binance_ticker) {
+            Some((task, _)) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /*
+        Stops evaluating the pairs whose symbols are not among the traded lowercase tickers, and starts again the ones stopped earlier which are traded again
+    */
+    pub fn apply_listings(&self, pairs: Vec<Arc<PairState>>, traded: &[String]) -> ListingChanges {
+        let mut delisted = self.delisted.lock().unwrap();
+        // Unsubscribed pairs are not started again
+        delisted.retain(|binance_ticker| {
+            pairs
+                .iter()
+                .any(|pair| &pair.binance_ticker == binance_ticker)
+        });
+
+        let mut changes = ListingChanges::default();
+        for pair in pairs {
+            let is_traded = traded.contains(&pair.binance_ticker);
+            if !is_traded && delisted.insert(pair.binance_ticker.clone()) {
+                self.stop(&pair.binance_ticker);
+                changes.delisted.push(pair.binance_ticker.clone());
+            } else if is_traded && delisted.remove(&pair.binance_ticker) {
+                changes.relisted.push(pair.binance_ticker.clone());
+                self.start(pair);
+            }
+        }
+        changes
+    }
+
+    /*
+        Returns whether the pair is evaluated
+    */
+    pub fn is_running(&self, binance_ticker: &str) -> bool {
+        self.tasks
+            .lock()
+            .unwrap()
+            .get(binance_ticker)
+            .is_some_and(|(task, _)| !task.is_finished())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex as StdMutex},
+        time::{Duration, Instant},
+    };
+
+    use pyth_sdk_solana::Price;
+    use tokio::sync::mpsc;
+
+    use crate::{
+        config::PairConfig,
+        structs::{
+            arbitrage_finder::{ArbitrageDirection, FinderSettings},
+            cex::binance::BookTickerData,
+            state::PairState,
+            stats::Stats,
+            worker_pool::WorkerPool,
+        },
+    };
+
+    use super::{ListingChanges, PairSupervisor};
+
+    #[tokio::test]
+    async fn test_start_and_stop() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let worker_pool = WorkerPool::new(1, move |binance_ticker: &str, opportunity, _| {
+            let _ = sender.send((binance_ticker.to_string(), opportunity));
+        });
+        let supervisor = PairSupervisor::new(FinderSettings::default(), worker_pool);
+
+        let pair = Arc::new(PairState::new(&PairConfig {
+            binance_ticker: "solusdt".to_string(),
+            pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+            pyth_quote_price_id: None,
+            enabled: true,
+        }));
+        // l: 68.43263012 h: 71.27225988
+        *pair.get_latest_pyth_price().write().await = Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        });
+        *pair.get_latest_binance_ticker_data().write().await = Some(BookTickerData {
+            b: "71.3833".to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        });
+
+        supervisor.start(pair);
+        assert!(supervisor.is_running("solusdt"));
+        let (binance_ticker, opportunity) =
+            tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(binance_ticker, "solusdt");
+        assert_eq!(opportunity.direction, ArbitrageDirection::SellBinanceBuyDex);
+
+        assert!(supervisor.stop("solusdt"));
+        assert!(!supervisor.is_running("solusdt"));
+        assert!(!supervisor.stop("solusdt"));
+    }
+
+    #[tokio::test]
+    async fn test_eval_interval() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let worker_pool = WorkerPool::new(1, move |_: &str, opportunity, _| {
+            let _ = sender.send(opportunity);
+        });
+        let supervisor = PairSupervisor::new(FinderSettings::default(), worker_pool)
+            .with_eval_interval(Duration::from_millis(100));
+
+        let pair = Arc::new(PairState::new(&PairConfig {
+            binance_ticker: "solusdt".to_string(),
+            pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+            pyth_quote_price_id: None,
+            enabled: true,
+        }));
+        // l: 68.43263012 h: 71.27225988
+        *pair.get_latest_pyth_price().write().await = Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        });
+        supervisor.start(pair.clone());
+        let started = Instant::now();
+
+        // Every update is an opportunity different from the previous one, so none of them is deduplicated
+        for update in 0..350 {
+            *pair.get_latest_binance_ticker_data().write().await = Some(BookTickerData {
+                b: "71.3833".to_string(),
+                B: format!("0.{}", 1000 + update),
+                a: "72.0012".to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            });
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert!(supervisor.stop("solusdt"));
+        let evaluations_due = started.elapsed().as_millis() / 100 + 1;
+
+        let mut opportunities = Vec::new();
+        while let Ok(opportunity) = receiver.try_recv() {
+            opportunities.push(opportunity);
+        }
+        // 350 updates, evaluated at most every 100 ms
+        assert!(!opportunities.is_empty());
+        assert!(opportunities.len() as u128 <= evaluations_due);
+        assert!(opportunities.len() < 350);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_pair() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let worker_pool = WorkerPool::new(1, move |_: &str, opportunity, _| {
+            let _ = sender.send(opportunity);
+        });
+        let stats = Arc::new(StdMutex::new(Stats::default()));
+        let supervisor =
+            PairSupervisor::new(FinderSettings::default(), worker_pool).with_stats(stats.clone());
+
+        let pair = Arc::new(PairState::new(
+            &"solusdt:H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG:disabled"
+                .parse::<PairConfig>()
+                .unwrap(),
+        ));
+        // l: 68.43263012 h: 71.27225988
+        *pair.get_latest_pyth_price().write().await = Some(Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            ..Default::default()
+        });
+        *pair.get_latest_binance_ticker_data().write().await = Some(BookTickerData {
+            b: "71.3833".to_string(),
+            B: "0.8574".to_string(),
+            a: "72.0012".to_string(),
+            A: "0.9245".to_string(),
+            ..Default::default()
+        });
+
+        // Still configured and supervised, but none of its opportunities is found
+        supervisor.start(pair.clone());
+        assert!(supervisor.is_running("solusdt"));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), receiver.recv())
+                .await
+                .is_err()
+        );
+        assert!(stats.lock().unwrap().disabled_pairs.contains("solusdt"));
+
+        supervisor.set_enabled(&pair, true);
+        let opportunity = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(opportunity.direction, ArbitrageDirection::SellBinanceBuyDex);
+        assert!(stats.lock().unwrap().disabled_pairs.is_empty());
+        assert!(supervisor.stop("solusdt"));
+    }
+
+    #[tokio::test]
+    async fn test_apply_listings() {
+        let worker_pool = WorkerPool::new(1, |_: &str, _, _| {});
+        let supervisor = PairSupervisor::new(FinderSettings::default(), worker_pool)
+            .with_eval_interval(Duration::from_millis(100));
+        let pairs = ["solusdt", "ethusdt"].map(|binance_ticker| {
+            Arc::new(PairState::new(&PairConfig {
+                binance_ticker: binance_ticker.to_string(),
+                pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+                pyth_quote_price_id: None,
+                enabled: true,
+            }))
+        });
+        for pair in &pairs {
+            supervisor.start(pair.clone());
+        }
+        let traded = |tickers: &[&str]| {
+            tickers
+                .iter()
+                .map(|ticker| ticker.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // ethusdt disappears from the refreshed listings
+        assert_eq!(
+            supervisor.apply_listings(pairs.to_vec(), &traded(&["btcusdt", "solusdt"])),
+            ListingChanges {
+                delisted: vec!["ethusdt".to_string()],
+                relisted: vec![],
+            }
+        );
+        assert!(supervisor.is_running("solusdt"));
+        assert!(!supervisor.is_running("ethusdt"));
+        // Reported only once
+        assert_eq!(
+            supervisor.apply_listings(pairs.to_vec(), &traded(&["solusdt"])),
+            ListingChanges::default()
+        );
+
+        assert_eq!(
+            supervisor.apply_listings(pairs.to_vec(), &traded(&["ethusdt", "solusdt"])),
+            ListingChanges {
+                delisted: vec![],
+                relisted: vec!["ethusdt".to_string()],
+            }
+        );
+        assert!(supervisor.is_running("ethusdt"));
+    }
+}
@@ -0,0 +1,196 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{arbitrage_finder::RawInputs, cex::binance::parse_decimal};
+
+/*
+    Struct holding open, high, low and close of the signed edge (Binance mid minus Pyth price) of a pair within a time bar
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProfitBar {
+    pub pair: String, // Binance ticker of the pair
+    pub start: u64,   // milliseconds since Unix epoch, inclusive
+    pub end: u64,     // milliseconds since Unix epoch, exclusive
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub ticks: u64,         // evaluated ticks within the bar
+    pub opportunities: u64, // ticks at which an opportunity was found
+}
+
+/*
+    Struct aggregating evaluated ticks of a pair into bars of fixed length, aligned to the Unix epoch
+    A bar is sent once the first tick past its end is recorded, bars without ticks are skipped
+    Ticks are updates of the feeds, re-evaluating unchanged snapshots of them does not count again
+*/
+pub struct ProfitBars {
+    binance_ticker: String,
+    bar_length: u64, // milliseconds
+    sender: UnboundedSender<ProfitBar>,
+    current: Option<ProfitBar>,
+    last_recorded: Option<(RawInputs, bool)>, // snapshots of the last recorded tick and whether an opportunity was found at it
+}
+
+impl ProfitBars {
+    pub fn new(
+        binance_ticker: &str,
+        bar_length: Duration,
+        sender: UnboundedSender<ProfitBar>,
+    ) -> Self {
+        Self {
+            binance_ticker: binance_ticker.to_string(),
+            bar_length: (bar_length.as_millis() as u64).max(1), // doesn't overflow
+            sender,
+            current: None,
+            last_recorded: None,
+        }
+    }
+
+    /*
+        Records the tick evaluated now, see record_at
+    */
+    pub fn record(&mut self, raw_inputs: &RawInputs, found: bool) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64; // doesn't overflow
+        self.record_at(timestamp, raw_inputs, found);
+    }
+
+    /*
+        Adds edge of the tick evaluated at the timestamp to its bar, sending the previous bar if the tick is past its end
+        Ticks with malformed prices are ignored, as are ones with the same snapshots as the last recorded tick,
+        except for counting an opportunity found at them once if none was found before
+    */
+    pub fn record_at(&mut self, timestamp: u64, raw_inputs: &RawInputs, found: bool) {
+        let Some(edge) = signed_edge(raw_inputs) else {
+            return;
+        };
+        if let Some((last_inputs, last_found)) = &mut self.last_recorded {
+            if last_inputs == raw_inputs {
+                if let (Some(current), true, false) = (&mut self.current, found, *last_found) {
+                    current.opportunities += 1;
+                    *last_found = true;
+                }
+                return;
+            }
+        }
+        self.last_recorded = Some((raw_inputs.clone(), found));
+
+        if let Some(current) = &self.current {
+            if timestamp >= current.end {
+                let _ = self.sender.send(self.current.take().unwrap()); // fails only if bars are not consumed
+            }
+        }
+
+        let start = timestamp - timestamp % self.bar_length;
+        let bar = self.current.get_or_insert_with(|| ProfitBar {
+            pair: self.binance_ticker.clone(),
+            start,
+            end: start + self.bar_length,
+            open: edge,
+            high: edge,
+            low: edge,
+            close: edge,
+            ticks: 0,
+            opportunities: 0,
+        });
+        bar.high = bar.high.max(edge);
+        bar.low = bar.low.min(edge);
+        bar.close = edge;
+        bar.ticks += 1;
+        if found {
+            bar.opportunities += 1;
+        }
+    }
+}
+
+/*
+    Returns the middle of the Binance spread minus the Pyth price, None if any of them is malformed
+*/
+fn signed_edge(raw_inputs: &RawInputs) -> Option<Decimal> {
+    let bid = parse_decimal(&raw_inputs.binance.b)?;
+    let ask = parse_decimal(&raw_inputs.binance.a)?;
+    let binance_mid = bid.checked_add(ask)?.checked_div(Decimal::TWO)?;
+    let pyth_price =
+        Decimal::try_new(raw_inputs.pyth.price, raw_inputs.pyth.expo.unsigned_abs()).ok()?;
+
+    Some(binance_mid.checked_sub(pyth_price)?.normalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, time::Duration};
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+    use tokio::sync::mpsc;
+
+    use crate::structs::{arbitrage_finder::RawInputs, cex::binance::BookTickerData};
+
+    use super::{ProfitBar, ProfitBars};
+
+    // Pyth price is 70, so the edge is the middle of the spread minus 70
+    fn inputs(bid: &str, ask: &str) -> RawInputs {
+        RawInputs {
+            pyth: Price {
+                price: 70000000,
+                conf: 669724,
+                expo: -6,
+                ..Default::default()
+            },
+            binance: BookTickerData {
+                b: bid.to_string(),
+                B: "0.8574".to_string(),
+                a: ask.to_string(),
+                A: "0.9245".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_record_at() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut profit_bars = ProfitBars::new("solusdt", Duration::from_secs(1), sender);
+
+        profit_bars.record_at(1_000_200, &inputs("70.1", "70.3"), false); // 0.2
+        profit_bars.record_at(1_000_400, &inputs("71.5", "71.7"), false); // 1.6
+                                                                          // Re-evaluating the same snapshots is not another tick, the opportunity found at it is counted once
+        for _ in 0..10 {
+            profit_bars.record_at(1_000_500, &inputs("71.5", "71.7"), true);
+        }
+        profit_bars.record_at(1_000_600, &inputs("68.3", "68.5"), true); // -1.6
+        profit_bars.record_at(1_000_999, &inputs("69.9", "70.1"), false); // 0
+        profit_bars.record_at(1_001_000, &inputs("malformed", "70.1"), false);
+        assert!(receiver.try_recv().is_err());
+
+        // The first tick of the next bar closes the previous one
+        profit_bars.record_at(1_003_500, &inputs("70", "70.2"), false); // 0.1
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            ProfitBar {
+                pair: "solusdt".to_string(),
+                start: 1_000_000,
+                end: 1_001_000,
+                open: Decimal::from_str("0.2").unwrap(),
+                high: Decimal::from_str("1.6").unwrap(),
+                low: Decimal::from_str("-1.6").unwrap(),
+                close: Decimal::ZERO,
+                ticks: 4,
+                opportunities: 2,
+            }
+        );
+        assert!(receiver.try_recv().is_err());
+
+        profit_bars.record_at(1_004_000, &inputs("70.1", "70.3"), false);
+        let bar = receiver.try_recv().unwrap();
+        assert_eq!((bar.start, bar.end, bar.ticks), (1_003_000, 1_004_000, 1));
+        assert_eq!(bar.open, Decimal::from_str("0.1").unwrap());
+        assert_eq!(bar.open, bar.close);
+    }
+}
@@ -0,0 +1,103 @@
+use schemars::schema_for;
+
+use super::arbitrage_finder::ArbitrageOpportunity;
+
+/*
+    Returns JSON Schema of the serialized ArbitrageOpportunity, derived from its serde attributes
+*/
+pub fn opportunity_schema() -> String {
+    serde_json::to_string_pretty(&schema_for!(ArbitrageOpportunity)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use crate::structs::arbitrage_finder::{
+        ArbitrageDirection, ArbitrageOpportunity, ProfitBreakdown, RequiredCapital,
+    };
+
+    use super::opportunity_schema;
+
+    // Checks the keywords schemars emits for the opportunity: $ref, type, required, properties and enum
+    fn validate(schema: &Value, root: &Value, value: &Value) -> Result<(), String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/definitions/");
+            return validate(&root["definitions"][name], root, value);
+        }
+        if let Some(all_of) = schema["allOf"].as_array() {
+            return all_of
+                .iter()
+                .try_for_each(|schema| validate(schema, root, value));
+        }
+        if let Some(variants) = schema["enum"].as_array() {
+            if !variants.contains(value) {
+                return Err(format!("{} is not one of {:?}", value, variants));
+            }
+        }
+        let type_matches = match schema["type"].as_str() {
+            Some("object") => value.is_object(),
+            Some("string") => value.is_string(),
+            Some("number") => value.is_number(),
+            Some(_) | None => true,
+        };
+        if !type_matches {
+            return Err(format!("{} is not of type {}", value, schema["type"]));
+        }
+        for required in schema["required"].as_array().into_iter().flatten() {
+            if value.get(required.as_str().unwrap()).is_none() {
+                return Err(format!("{} is missing", required));
+            }
+        }
+        if let Some(properties) = schema["properties"].as_object() {
+            for (name, property) in properties {
+                if let Some(field) = value.get(name) {
+                    validate(property, root, field)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_opportunity_schema() {
+        let schema = serde_json::from_str::<Value>(&opportunity_schema()).unwrap();
+        assert_eq!(schema["title"], "ArbitrageOpportunity");
+
+        let opportunity = ArbitrageOpportunity {
+            id: Uuid::nil(),
+            direction: ArbitrageDirection::SellBinanceBuyDex,
+            quantity: Decimal::from_str("0.8574").unwrap(),
+            estimated_profit: Decimal::from_str("0.03400176").unwrap(),
+            profit_low: Decimal::from_str("0.03400176").unwrap(),
+            profit_high: Decimal::from_str("2.46918952").unwrap(),
+            binance_price: Decimal::from_str("71.3833").unwrap(),
+            pyth_price: Decimal::from_str("71.27225988").unwrap(),
+            profit_breakdown: ProfitBreakdown {
+                gross_edge: Decimal::from_str("0.095205798888").unwrap(),
+                binance_fee: Decimal::from_str("0.06120404142").unwrap(),
+                dex_fee: Decimal::ZERO,
+            },
+            required_capital: RequiredCapital {
+                cex: Decimal::from_str("61.20404142").unwrap(),
+                dex: Decimal::from_str("61.108835621112").unwrap(),
+            },
+        };
+        let serialized = serde_json::to_value(opportunity).unwrap();
+        assert_eq!(validate(&schema, &schema, &serialized), Ok(()));
+
+        let mut broken = serialized.clone();
+        broken["direction"] = Value::from("SellDexBuyBinance");
+        assert!(validate(&schema, &schema, &broken).is_err());
+        broken = serialized;
+        broken["profit_breakdown"]
+            .as_object_mut()
+            .unwrap()
+            .remove("gross_edge");
+        assert!(validate(&schema, &schema, &broken).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::structs::{
+    arbitrage_finder::{
+        ArbitrageDirection, ArbitrageOpportunity, ProfitBreakdown, RequiredCapital,
+    },
+    emitter::EmittedOpportunity,
+};
+
+// Bytes opening every frame after its length, telling frames apart from other data
+pub const MAGIC: [u8; 2] = *b"KT";
+// Version of the layout, the byte after the magic
+pub const VERSION: u8 = 1;
+// Bytes of an encoded decimal, its mantissa and scale
+const DECIMAL_LEN: usize = 17;
+// Bit of the flags byte set for marginal opportunities
+const MARGINAL_FLAG: u8 = 1;
+
+/*
+    Encodes the opportunity as a frame of the compact binary layout, all integers being little-endian:
+        u32      length of the rest of the frame
+        [u8; 2]  magic, "KT"
+        u8       layout version, 1
+        u64      seq
+        u64      timestamp, milliseconds since Unix epoch
+        u8       length of the pair, followed by the Binance ticker in UTF-8
+        [u8; 16] id, UUID bytes in RFC 4122 order
+        u8       direction, 0 for SellBinanceBuyDex and 1 for BuyBinanceSellDex
+        u8       flags, bit 0 set if marginal
+        11 decimals: quantity, estimated_profit, profit_low, profit_high, binance_price, pyth_price,
+                     gross_edge, binance_fee, dex_fee, cex and dex required capital
+    Every decimal is an i128 mantissa followed by a u8 scale, i.e. mantissa / 10^scale, so it keeps its exact value and scale
+    Raw inputs of the feeds and the funding rate are not carried
+    Later versions only append fields, so a decoder reads the fields it knows of a newer frame and skips the rest by its length,
+    while a change of any existing field requires another magic
+*/
+pub fn encode(emitted: &EmittedOpportunity) -> Vec<u8> {
+    let opportunity = &emitted.opportunity;
+    let pair = &emitted.pair.as_bytes()[..emitted.pair.len().min(u8::MAX as usize)]; // tickers are far shorter
+    let mut frame =
+        Vec::with_capacity(4 + 2 + 1 + 8 + 8 + 1 + pair.len() + 16 + 2 + 11 * DECIMAL_LEN);
+    frame.extend_from_slice(&[0; 4]); // length, known once the rest is encoded
+    frame.extend_from_slice(&MAGIC);
+    frame.push(VERSION);
+    frame.extend_from_slice(&emitted.seq.to_le_bytes());
+    frame.extend_from_slice(&emitted.timestamp.to_le_bytes());
+    frame.push(pair.len() as u8);
+    frame.extend_from_slice(pair);
+    frame.extend_from_slice(opportunity.id.as_bytes());
+    frame.push(match opportunity.direction {
+        ArbitrageDirection::SellBinanceBuyDex => 0,
+        ArbitrageDirection::BuyBinanceSellDex => 1,
+    });
+    frame.push(if emitted.marginal { MARGINAL_FLAG } else { 0 });
+    for decimal in [
+        opportunity.quantity,
+        opportunity.estimated_profit,
+        opportunity.profit_low,
+        opportunity.profit_high,
+        opportunity.binance_price,
+        opportunity.pyth_price,
+        opportunity.profit_breakdown.gross_edge,
+        opportunity.profit_breakdown.binance_fee,
+        opportunity.profit_breakdown.dex_fee,
+        opportunity.required_capital.cex,
+        opportunity.required_capital.dex,
+    ] {
+        frame.extend_from_slice(&decimal.mantissa().to_le_bytes());
+        frame.push(decimal.scale() as u8); // at most 28
+    }
+    let len = (frame.len() - 4) as u32; // a few hundred bytes
+    frame[..4].copy_from_slice(&len.to_le_bytes());
+
+    frame
+}
+
+/*
+    Decodes a frame encoded by encode, failing if it is truncated, lacks the magic or holds invalid values
+    Fields appended by newer versions are skipped
+*/
+pub fn decode(frame: &[u8]) -> Result<EmittedOpportunity> {
+    let mut reader = Reader { bytes: frame };
+    let len = u32::from_le_bytes(reader.take()?) as usize;
+    if reader.bytes.len() != len {
+        return Err(anyhow!(
+            "Frame has {} bytes after its length of {}",
+            reader.bytes.len(),
+            len
+        ));
+    }
+    if reader.take()? != MAGIC {
+        return Err(anyhow!("Frame does not start with the magic"));
+    }
+    let [version] = reader.take()?;
+    if version == 0 {
+        return Err(anyhow!("Frame layout version {} is not supported", version));
+    }
+    let seq = u64::from_le_bytes(reader.take()?);
+    let timestamp = u64::from_le_bytes(reader.take()?);
+    let [pair_len] = reader.take()?;
+    let pair = String::from_utf8(reader.take_slice(pair_len as usize)?.to_vec())?;
+    let id = Uuid::from_bytes(reader.take()?);
+    let direction = match reader.take()? {
+        [0] => ArbitrageDirection::SellBinanceBuyDex,
+        [1] => ArbitrageDirection::BuyBinanceSellDex,
+        [direction] => return Err(anyhow!("Direction {} is unknown", direction)),
+    };
+    let [flags] = reader.take()?;
+    let mut decimals = [Decimal::ZERO; 11];
+    for decimal in &mut decimals {
+        let mantissa = i128::from_le_bytes(reader.take()?);
+        let [scale] = reader.take()?;
+        *decimal = Decimal::try_from_i128_with_scale(mantissa, scale as u32)?;
+    }
+    let [quantity, estimated_profit, profit_low, profit_high, binance_price, pyth_price, gross_edge, binance_fee, dex_fee, cex, dex] =
+        decimals;
+    if version == VERSION && !reader.bytes.is_empty() {
+        return Err(anyhow!(
+            "Frame has {} bytes after its fields",
+            reader.bytes.len()
+        ));
+    }
+
+    Ok(EmittedOpportunity {
+        seq,
+        pair,
+        timestamp,
+        opportunity: ArbitrageOpportunity {
+            id,
+            direction,
+            quantity,
+            estimated_profit,
+            profit_low,
+            profit_high,
+            binance_price,
+            pyth_price,
+            profit_breakdown: ProfitBreakdown {
+                gross_edge,
+                binance_fee,
+                dex_fee,
+            },
+            required_capital: RequiredCapital { cex, dex },
+        },
+        marginal: flags & MARGINAL_FLAG != 0,
+        raw: None,
+        funding_rate: None,
+    })
+}
+
+// Cursor over the bytes of a frame
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(anyhow!("Frame is truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take_slice(N)?.try_into().unwrap()) // of length N
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    use crate::structs::{
+        arbitrage_finder::{
+            ArbitrageDirection, ArbitrageOpportunity, ProfitBreakdown, RequiredCapital,
+        },
+        emitter::EmittedOpportunity,
+    };
+
+    use super::{decode, encode};
+
+    #[test]
+    fn test_round_trip() {
+        let mut emitted = EmittedOpportunity::new(
+            7,
+            "solusdt",
+            ArbitrageOpportunity {
+                id: Uuid::from_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap(),
+                direction: ArbitrageDirection::BuyBinanceSellDex,
+                quantity: Decimal::from_str("0.8574").unwrap(),
+                estimated_profit: Decimal::from_str("0.03400176").unwrap(),
+                profit_low: Decimal::from_str("-0.0000000000000000000000000001").unwrap(),
+                profit_high: Decimal::MAX,
+                binance_price: Decimal::from_str("71.38330").unwrap(),
+                pyth_price: Decimal::from_str("71.27225988").unwrap(),
+                profit_breakdown: ProfitBreakdown {
+                    gross_edge: Decimal::from_str("0.095205798888").unwrap(),
+                    binance_fee: Decimal::from_str("0.06120404142").unwrap(),
+                    dex_fee: Decimal::ZERO,
+                },
+                required_capital: RequiredCapital {
+                    cex: Decimal::MIN,
+                    dex: Decimal::from_str("61.10843562").unwrap(),
+                },
+            },
+        );
+        emitted.marginal = true;
+
+        let frame = encode(&emitted);
+        assert_eq!(frame.len(), 4 + 2 + 1 + 8 + 8 + 1 + 7 + 16 + 2 + 11 * 17);
+        assert_eq!(&frame[..4], &(frame.len() as u32 - 4).to_le_bytes());
+        let decoded = decode(&frame).unwrap();
+        assert_eq!(decoded, emitted);
+        assert_eq!(decoded.opportunity.id, emitted.opportunity.id);
+        // Scales are kept along with the values
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&emitted).unwrap()
+        );
+        assert_eq!(decoded.opportunity.binance_price.to_string(), "71.38330");
+
+        assert!(decode(&frame[..frame.len() - 1]).is_err());
+        let mut other_magic = frame.clone();
+        other_magic[4] = b'J';
+        assert!(decode(&other_magic).is_err());
+        let mut invalid_version = frame.clone();
+        invalid_version[6] = 0;
+        assert!(decode(&invalid_version).is_err());
+
+        // A newer version with an appended field decodes the known ones, while the current one has none after them
+        let mut newer_version = frame.clone();
+        newer_version[6] = 2;
+        newer_version.extend_from_slice(&[1, 2, 3]);
+        let len = newer_version.len() as u32 - 4;
+        newer_version[..4].copy_from_slice(&len.to_le_bytes());
+        assert_eq!(decode(&newer_version).unwrap(), emitted);
+        newer_version[6] = 1;
+        assert!(decode(&newer_version).is_err());
+    }
+}
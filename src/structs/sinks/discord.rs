@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use reqwest::{header::RETRY_AFTER, Client, StatusCode};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::structs::{arbitrage_finder::ArbitrageDirection, emitter::EmittedOpportunity};
+
+const MAX_ATTEMPTS: usize = 3;
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+const SELL_BINANCE_BUY_DEX_COLOR: u32 = 0xe74c3c; // red
+const BUY_BINANCE_SELL_DEX_COLOR: u32 = 0x2ecc71; // green
+
+/*
+    Struct posting found opportunities to a Discord webhook as embeds
+    Opportunities are posted one by one, waiting as long as Discord requests when rate limited
+*/
+pub struct DiscordSink {
+    client: Client,
+    url: String,
+    receiver: broadcast::Receiver<EmittedOpportunity>,
+}
+
+impl DiscordSink {
+    pub fn new(url: &str, sender: &broadcast::Sender<EmittedOpportunity>) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.to_string(),
+            receiver: sender.subscribe(),
+        }
+    }
+
+    /*
+        Posts received opportunities until the channel is closed
+    */
+    pub async fn serve(mut self) {
+        loop {
+            match self.receiver.recv().await {
+                Ok(emitted) => {
+                    if let Err(err) = self.post(&Self::format_message(&emitted)).await {
+                        eprintln!("Could not post opportunity to Discord: {}", err);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!(
+                        "Discord sink lagged behind, skipped {} opportunities",
+                        skipped
+                    );
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /*
+        Posts the message, retrying after the time given by Discord if rate limited
+    */
+    async fn post(&self, message: &Value) -> Result<()> {
+        for _ in 0..MAX_ATTEMPTS {
+            let response = self.client.post(&self.url).json(message).send().await?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                response.error_for_status()?;
+                return Ok(());
+            }
+
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok());
+            tokio::time::sleep(Self::retry_after(retry_after)).await;
+        }
+
+        Err(anyhow!("Rate limited {} times in a row", MAX_ATTEMPTS))
+    }
+
+    /*
+        Returns time to wait given the Retry-After header in seconds, at most MAX_RETRY_AFTER
+        A missing header or one which is not a valid duration, e.g. negative, NaN or infinite, waits DEFAULT_RETRY_AFTER
+    */
+    fn retry_after(header: Option<&str>) -> Duration {
+        header
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|seconds| seconds.is_finite())
+            .and_then(|seconds| {
+                Duration::try_from_secs_f64(seconds.min(MAX_RETRY_AFTER.as_secs_f64())).ok()
+            })
+            .unwrap_or(DEFAULT_RETRY_AFTER)
+    }
+
+    /*
+        Formats the opportunity as a webhook message with a single embed colored by direction
+    */
+    pub fn format_message(emitted: &EmittedOpportunity) -> Value {
+        let opportunity = &emitted.opportunity;
+        let color = match opportunity.direction {
+            ArbitrageDirection::SellBinanceBuyDex => SELL_BINANCE_BUY_DEX_COLOR,
+            ArbitrageDirection::BuyBinanceSellDex => BUY_BINANCE_SELL_DEX_COLOR,
+        };
+        let field =
+            |name: &str, value: String| json!({"name": name, "value": value, "inline": true});
+
+        json!({
+            "embeds": [{
+                "title": format!(
+                    "{:?} opportunity for {}",
+                    opportunity.direction,
+                    emitted.pair.to_uppercase()
+                ),
+                "color": color,
+                "fields": [
+                    field("Estimated profit", opportunity.estimated_profit.to_string()),
+                    field(
+                        "Profit range",
+                        format!("{} - {}", opportunity.profit_low, opportunity.profit_high)
+                    ),
+                    field("Quantity", opportunity.quantity.to_string()),
+                    field("Binance price", opportunity.binance_price.to_string()),
+                    field("Pyth price", opportunity.pyth_price.to_string()),
+                ],
+                "footer": {"text": format!("seq {} | id {}", emitted.seq, opportunity.id)},
+            }]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::broadcast,
+    };
+
+    use crate::structs::{arbitrage_finder::ArbitrageOpportunity, emitter::EmittedOpportunity};
+
+    use super::{DiscordSink, DEFAULT_RETRY_AFTER, MAX_RETRY_AFTER};
+
+    fn emitted() -> EmittedOpportunity {
+        EmittedOpportunity::new(7, "solusdt", ArbitrageOpportunity::sample())
+    }
+
+    #[test]
+    fn test_format_message() {
+        let message = DiscordSink::format_message(&emitted());
+        assert_eq!(
+            message,
+            json!({
+                "embeds": [{
+                    "title": "SellBinanceBuyDex opportunity for SOLUSDT",
+                    "color": 0xe74c3c,
+                    "fields": [
+                        {"name": "Estimated profit", "value": "0.03400176", "inline": true},
+                        {"name": "Profit range", "value": "0.03400176 - 2.46870031", "inline": true},
+                        {"name": "Quantity", "value": "0.8574", "inline": true},
+                        {"name": "Binance price", "value": "71.3833", "inline": true},
+                        {"name": "Pyth price", "value": "71.27225988", "inline": true},
+                    ],
+                    "footer": {"text": "seq 7 | id 00000000-0000-0000-0000-000000000000"},
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_retry_after() {
+        assert_eq!(
+            DiscordSink::retry_after(Some("0.05")),
+            Duration::from_millis(50)
+        );
+        assert_eq!(DiscordSink::retry_after(Some("1e30")), MAX_RETRY_AFTER);
+        for header in [None, Some("-1"), Some("inf"), Some("NaN"), Some("soon")] {
+            assert_eq!(DiscordSink::retry_after(header), DEFAULT_RETRY_AFTER);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_retries_after_rate_limit() {
+        for retry_after in ["0.05", "-1", "inf"] {
+            assert_eq!(serve_rate_limited_once(retry_after).await, 2);
+        }
+    }
+
+    // Serves a single opportunity to a webhook rate limiting the first request, returns the number of requests
+    async fn serve_rate_limited_once(retry_after: &'static str) -> usize {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let requests = requests.clone();
+            async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let mut buffer = [0; 4096];
+                    let _ = stream.read(&mut buffer).await;
+                    // The first request is rate limited
+                    let response = if requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                        format!("HTTP/1.1 429 Too Many Requests\r\nRetry-After: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", retry_after)
+                    } else {
+                        "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n".to_string()
+                    };
+                    let _ = stream.write_all(response.as_bytes()).await;
+                }
+            }
+        });
+
+        let (sender, _) = broadcast::channel(16);
+        let sink = DiscordSink::new(&url, &sender);
+        sender.send(emitted()).unwrap();
+        drop(sender);
+        tokio::time::timeout(Duration::from_secs(5), sink.serve())
+            .await
+            .unwrap();
+        requests.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    net::{SocketAddr, TcpListener},
+    pin::Pin,
+};
+
+use anyhow::Result;
+use futures_util::{stream, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::structs::{arbitrage_finder::ArbitrageDirection, emitter::EmittedOpportunity};
+
+use self::proto::{
+    opportunities_server::{Opportunities, OpportunitiesServer},
+    Direction, Opportunity, ProfitBreakdown, RequiredCapital, SubscribeRequest,
+};
+
+/*
+    Messages and service generated from proto/opportunities.proto by the build script
+*/
+pub mod proto {
+    tonic::include_proto!("keyrock");
+}
+
+/*
+    Struct streaming found opportunities to gRPC subscribers of the Opportunities service, see proto/opportunities.proto
+    Each subscriber has its own receiver of the broadcast channel, so a slow one skips opportunities instead of holding up the others
+*/
+pub struct GrpcSink {
+    listener: TcpListener,
+    sender: broadcast::Sender<EmittedOpportunity>,
+}
+
+impl GrpcSink {
+    pub fn bind(addr: SocketAddr, sender: broadcast::Sender<EmittedOpportunity>) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self { listener, sender })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /*
+        Serves subscriptions until the server fails
+    */
+    pub async fn serve(self) {
+        let listener = match tokio::net::TcpListener::from_std(self.listener) {
+            Ok(listener) => listener,
+            Err(err) => return eprintln!("Could not start gRPC server: {}", err),
+        };
+        let service = OpportunitiesServer::new(OpportunitiesService {
+            sender: self.sender,
+        });
+        if let Err(err) = Server::builder()
+            .add_service(service)
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+        {
+            eprintln!("gRPC server failed: {}", err);
+        }
+    }
+}
+
+struct OpportunitiesService {
+    sender: broadcast::Sender<EmittedOpportunity>,
+}
+
+#[tonic::async_trait]
+impl Opportunities for OpportunitiesService {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<Opportunity, Status>> + Send>>;
+
+    /*
+        Streams every received opportunity to the subscriber until it disconnects
+    */
+    async fn subscribe(
+        &self,
+        _request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let opportunities = stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(emitted) => return Some((Ok(Opportunity::from(&emitted)), receiver)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!(
+                            "gRPC subscriber lagged behind, skipped {} opportunities",
+                            skipped
+                        );
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(opportunities)))
+    }
+}
+
+impl From<&EmittedOpportunity> for Opportunity {
+    fn from(emitted: &EmittedOpportunity) -> Self {
+        let opportunity = &emitted.opportunity;
+        let direction = match opportunity.direction {
+            ArbitrageDirection::SellBinanceBuyDex => Direction::SellBinanceBuyDex,
+            ArbitrageDirection::BuyBinanceSellDex => Direction::BuyBinanceSellDex,
+        };
+        Self {
+            seq: emitted.seq,
+            pair: emitted.pair.clone(),
+            timestamp: emitted.timestamp,
+            direction: direction.into(),
+            quantity: opportunity.quantity.to_string(),
+            estimated_profit: opportunity.estimated_profit.to_string(),
+            profit_low: opportunity.profit_low.to_string(),
+            profit_high: opportunity.profit_high.to_string(),
+            binance_price: opportunity.binance_price.to_string(),
+            pyth_price: opportunity.pyth_price.to_string(),
+            profit_breakdown: Some(ProfitBreakdown {
+                gross_edge: opportunity.profit_breakdown.gross_edge.to_string(),
+                binance_fee: opportunity.profit_breakdown.binance_fee.to_string(),
+                dex_fee: opportunity.profit_breakdown.dex_fee.to_string(),
+            }),
+            required_capital: Some(RequiredCapital {
+                cex: opportunity.required_capital.cex.to_string(),
+                dex: opportunity.required_capital.dex.to_string(),
+            }),
+            marginal: emitted.marginal,
+            id: opportunity.id.to_string(),
+            funding_rate: emitted
+                .funding_rate
+                .map(|funding_rate| funding_rate.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::broadcast;
+
+    use crate::structs::{
+        arbitrage_finder::{ArbitrageDirection, ArbitrageOpportunity},
+        emitter::EmittedOpportunity,
+    };
+
+    use super::{
+        proto::{opportunities_client::OpportunitiesClient, SubscribeRequest},
+        Direction, GrpcSink, Opportunity, ProfitBreakdown, RequiredCapital,
+    };
+
+    fn opportunity() -> EmittedOpportunity {
+        EmittedOpportunity::new(7, "solusdt", ArbitrageOpportunity::sample())
+    }
+
+    #[test]
+    fn test_opportunity() {
+        let mut emitted = opportunity();
+        emitted.marginal = true;
+        assert_eq!(
+            Opportunity::from(&emitted),
+            Opportunity {
+                seq: 7,
+                pair: "solusdt".to_string(),
+                timestamp: emitted.timestamp,
+                direction: Direction::SellBinanceBuyDex.into(),
+                quantity: "0.8574".to_string(),
+                estimated_profit: "0.03400176".to_string(),
+                profit_low: "0.03400176".to_string(),
+                profit_high: "2.46870031".to_string(),
+                binance_price: "71.3833".to_string(),
+                pyth_price: "71.27225988".to_string(),
+                profit_breakdown: Some(ProfitBreakdown {
+                    gross_edge: "0.095205798888".to_string(),
+                    binance_fee: "0.06120404142".to_string(),
+                    dex_fee: "0".to_string(),
+                }),
+                required_capital: Some(RequiredCapital {
+                    cex: "61.20404142".to_string(),
+                    dex: "61.10843562".to_string(),
+                }),
+                marginal: true,
+                id: "00000000-0000-0000-0000-000000000000".to_string(),
+                funding_rate: None,
+            }
+        );
+
+        emitted.opportunity.direction = ArbitrageDirection::BuyBinanceSellDex;
+        assert_eq!(
+            Opportunity::from(&emitted).direction(),
+            Direction::BuyBinanceSellDex
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let (sender, _) = broadcast::channel(16);
+        let sink = GrpcSink::bind("127.0.0.1:0".parse().unwrap(), sender.clone()).unwrap();
+        let addr = sink.local_addr().unwrap();
+        tokio::spawn(sink.serve());
+
+        let mut client = OpportunitiesClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+        let mut stream = client
+            .subscribe(SubscribeRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        while sender.receiver_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let emitted = opportunity();
+        sender.send(emitted.clone()).unwrap();
+        let message = tokio::time::timeout(Duration::from_secs(5), stream.message())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(message, Opportunity::from(&emitted));
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    fs::OpenOptions,
+    future::Future,
+    io::{self, Write},
+    time::Duration,
+};
+
+use anyhow::Result;
+use reqwest::Client;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    config::{SinkConfig, SinkTarget},
+    structs::emitter::EmittedOpportunity,
+};
+
+use super::binary;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+const CSV_HEADER: &str =
+    "seq,timestamp,pair,direction,quantity,estimated_profit,binance_price,pyth_price,id";
+
+/*
+    Trait of outputs handling every emitted opportunity, so any number of them can be stacked
+*/
+pub trait OpportunityHandler: Send {
+    // Writes or sends the opportunity, an error is reported and the following ones are handled anyway
+    fn handle(&mut self, emitted: &EmittedOpportunity) -> impl Future<Output = Result<()>> + Send;
+}
+
+/*
+    Struct feeding emitted opportunities to a handler
+    Every sink has its own receiver of the broadcast channel, so a failing or slow sink does not hold up the others
+*/
+pub struct HandlerSink<H> {
+    name: String, // shown in the reported errors
+    handler: H,
+    receiver: broadcast::Receiver<EmittedOpportunity>,
+}
+
+impl<H: OpportunityHandler> HandlerSink<H> {
+    pub fn new(name: &str, handler: H, sender: &broadcast::Sender<EmittedOpportunity>) -> Self {
+        Self {
+            name: name.to_string(),
+            handler,
+            receiver: sender.subscribe(),
+        }
+    }
+
+    /*
+        Handles received opportunities until the channel is closed
+    */
+    pub async fn serve(mut self) {
+        loop {
+            match self.receiver.recv().await {
+                Ok(emitted) => {
+                    if let Err(err) = self.handler.handle(&emitted).await {
+                        eprintln!("Sink {} could not handle opportunity: {}", self.name, err);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!(
+                        "Sink {} lagged behind, skipped {} opportunities",
+                        self.name, skipped
+                    );
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+/*
+    Supported formats of opportunities written as lines
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineFormat {
+    Json,
+    Csv,
+}
+
+/*
+    Struct writing opportunities to stdout or a file, one per line
+    CSV header is written before the first opportunity unless the file already has contents
+*/
+pub struct WriterHandler {
+    writer: Box<dyn Write + Send>,
+    format: LineFormat,
+    header_pending: bool,
+}
+
+impl WriterHandler {
+    pub fn new(writer: Box<dyn Write + Send>, format: LineFormat, header_pending: bool) -> Self {
+        Self {
+            writer,
+            format,
+            header_pending: header_pending && format == LineFormat::Csv,
+        }
+    }
+
+    /*
+        Opens the target, appending to a file so restarts keep the earlier opportunities
+    */
+    pub fn open(target: &SinkTarget, format: LineFormat) -> Result<Self> {
+        match target {
+            SinkTarget::Stdout => Ok(Self::new(Box::new(io::stdout()), format, true)),
+            SinkTarget::File(path) => {
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                let is_empty = file.metadata()?.len() == 0;
+                Ok(Self::new(Box::new(file), format, is_empty))
+            }
+        }
+    }
+
+    /*
+        Formats the opportunity as a line without the line break
+    */
+    pub fn format_line(&self, emitted: &EmittedOpportunity) -> String {
+        match self.format {
+            LineFormat::Json => serde_json::to_string(emitted).unwrap(),
+            LineFormat::Csv => {
+                let opportunity = &emitted.opportunity;
+                format!(
+                    "{},{},{},{:?},{},{},{},{},{}",
+                    emitted.seq,
+                    emitted.timestamp,
+                    emitted.pair,
+                    opportunity.direction,
+                    opportunity.quantity,
+                    opportunity.estimated_profit,
+                    opportunity.binance_price,
+                    opportunity.pyth_price,
+                    opportunity.id
+                )
+            }
+        }
+    }
+}
+
+impl OpportunityHandler for WriterHandler {
+    async fn handle(&mut self, emitted: &EmittedOpportunity) -> Result<()> {
+        if self.header_pending {
+            writeln!(self.writer, "{}", CSV_HEADER)?;
+            self.header_pending = false;
+        }
+        let line = self.format_line(emitted);
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/*
+    Struct writing opportunities to stdout or a file as frames of the compact binary layout, see binary::encode
+*/
+pub struct BinaryHandler {
+    writer: Box<dyn Write + Send>,
+}
+
+impl BinaryHandler {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self { writer }
+    }
+
+    /*
+        Opens the target, appending to a file so restarts keep the earlier opportunities
+    */
+    pub fn open(target: &SinkTarget) -> Result<Self> {
+        match target {
+            SinkTarget::Stdout => Ok(Self::new(Box::new(io::stdout()))),
+            SinkTarget::File(path) => Ok(Self::new(Box::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            ))),
+        }
+    }
+}
+
+impl OpportunityHandler for BinaryHandler {
+    async fn handle(&mut self, emitted: &EmittedOpportunity) -> Result<()> {
+        self.writer.write_all(&binary::encode(emitted))?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/*
+    Struct posting opportunities as JSON to a webhook
+*/
+pub struct WebhookHandler {
+    client: Client,
+    url: String,
+}
+
+impl WebhookHandler {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.to_string(),
+        }
+    }
+}
+
+impl OpportunityHandler for WebhookHandler {
+    async fn handle(&mut self, emitted: &EmittedOpportunity) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(emitted)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/*
+    Starts serving the configured sink, failing if its file cannot be opened
+*/
+pub fn spawn_sink(
+    sink_config: &SinkConfig,
+    sender: &broadcast::Sender<EmittedOpportunity>,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let name = sink_config.to_string();
+    Ok(match sink_config {
+        SinkConfig::Json(target) => tokio::spawn(
+            HandlerSink::new(
+                &name,
+                WriterHandler::open(target, LineFormat::Json)?,
+                sender,
+            )
+            .serve(),
+        ),
+        SinkConfig::Csv(target) => tokio::spawn(
+            HandlerSink::new(&name, WriterHandler::open(target, LineFormat::Csv)?, sender).serve(),
+        ),
+        SinkConfig::Binary(target) => {
+            tokio::spawn(HandlerSink::new(&name, BinaryHandler::open(target)?, sender).serve())
+        }
+        SinkConfig::Webhook(url) => {
+            tokio::spawn(HandlerSink::new(&name, WebhookHandler::new(url), sender).serve())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        str::FromStr,
+        sync::{Arc, Mutex},
+    };
+
+    use anyhow::{anyhow, Result};
+    use rust_decimal::Decimal;
+    use tokio::sync::broadcast;
+    use uuid::Uuid;
+
+    use crate::{
+        config::{SinkConfig, SinkTarget},
+        structs::{
+            arbitrage_finder::{
+                ArbitrageDirection, ArbitrageOpportunity, ProfitBreakdown, RequiredCapital,
+            },
+            emitter::EmittedOpportunity,
+        },
+    };
+
+    use super::{HandlerSink, LineFormat, OpportunityHandler, WriterHandler};
+
+    // Records handled opportunities, failing on every one if configured to
+    struct MockHandler {
+        handled: Arc<Mutex<Vec<u64>>>,
+        fail: bool,
+    }
+
+    impl OpportunityHandler for MockHandler {
+        async fn handle(&mut self, emitted: &EmittedOpportunity) -> Result<()> {
+            self.handled.lock().unwrap().push(emitted.seq);
+            if self.fail {
+                return Err(anyhow!("Mock failure"));
+            }
+            Ok(())
+        }
+    }
+
+    fn emitted(seq: u64) -> EmittedOpportunity {
+        EmittedOpportunity::new(
+            seq,
+            "solusdt",
+            ArbitrageOpportunity {
+                id: Uuid::nil(),
+                direction: ArbitrageDirection::SellBinanceBuyDex,
+                quantity: Decimal::from_str("0.8574").unwrap(),
+                estimated_profit: Decimal::from_str("0.03400176").unwrap(),
+                profit_low: Decimal::from_str("0.03400176").unwrap(),
+                profit_high: Decimal::from_str("2.46918952").unwrap(),
+                binance_price: Decimal::from_str("71.3833").unwrap(),
+                pyth_price: Decimal::from_str("71.27225988").unwrap(),
+                profit_breakdown: ProfitBreakdown {
+                    gross_edge: Decimal::from_str("0.095205798888").unwrap(),
+                    binance_fee: Decimal::from_str("0.06120404142").unwrap(),
+                    dex_fee: Decimal::ZERO,
+                },
+                required_capital: RequiredCapital {
+                    cex: Decimal::from_str("0.8574").unwrap(),
+                    dex: Decimal::from_str("61.10843562").unwrap(),
+                },
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_stacked_sinks() {
+        let (sender, _) = broadcast::channel(16);
+        let failing = Arc::new(Mutex::new(vec![]));
+        let working = Arc::new(Mutex::new(vec![]));
+        let tasks = [
+            tokio::spawn(
+                HandlerSink::new(
+                    "failing",
+                    MockHandler {
+                        handled: failing.clone(),
+                        fail: true,
+                    },
+                    &sender,
+                )
+                .serve(),
+            ),
+            tokio::spawn(
+                HandlerSink::new(
+                    "working",
+                    MockHandler {
+                        handled: working.clone(),
+                        fail: false,
+                    },
+                    &sender,
+                )
+                .serve(),
+            ),
+        ];
+
+        // Both sinks receive every opportunity even though one of them fails on each
+        sender.send(emitted(0)).unwrap();
+        sender.send(emitted(1)).unwrap();
+        drop(sender);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(*failing.lock().unwrap(), vec![0, 1]);
+        assert_eq!(*working.lock().unwrap(), vec![0, 1]);
+
+        let handler = WriterHandler::new(Box::new(std::io::sink()), LineFormat::Csv, true);
+        let line = handler.format_line(&emitted(2));
+        assert!(line.starts_with("2,"));
+        assert!(line.ends_with(
+            ",solusdt,SellBinanceBuyDex,0.8574,0.03400176,71.3833,71.27225988,00000000-0000-0000-0000-000000000000"
+        ));
+
+        assert_eq!(
+            SinkConfig::from_str("csv:/tmp/opportunities.csv").unwrap(),
+            SinkConfig::Csv(SinkTarget::File(PathBuf::from("/tmp/opportunities.csv")))
+        );
+        assert_eq!(
+            SinkConfig::from_str("json:stdout").unwrap(),
+            SinkConfig::Json(SinkTarget::Stdout)
+        );
+        assert_eq!(
+            SinkConfig::from_str("webhook:https://example.com/hook")
+                .unwrap()
+                .to_string(),
+            "webhook:https://example.com/hook"
+        );
+        assert_eq!(
+            SinkConfig::from_str("binary:stdout").unwrap().to_string(),
+            "binary:stdout"
+        );
+        assert!(SinkConfig::from_str("xml:stdout").is_err());
+        assert!(SinkConfig::from_str("csv:").is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use rdkafka::{
+    producer::{FutureProducer, FutureRecord},
+    util::Timeout,
+    ClientConfig,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::structs::emitter::EmittedOpportunity;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/*
+    Trait of clients producing records to Kafka, so the sink can be tested without a broker
+*/
+pub trait KafkaProducer: Send {
+    // Produces the record to the partition of the topic its key belongs to
+    fn send(
+        &mut self,
+        topic: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+/*
+    Struct producing found opportunities to a Kafka topic as JSON values keyed by their pair
+    Opportunities which could not be produced in time are dropped and counted, so a slow or unavailable broker never holds up detection
+*/
+pub struct KafkaSink<P> {
+    producer: P,
+    topic: String,
+    receiver: broadcast::Receiver<EmittedOpportunity>,
+    dropped: Arc<AtomicU64>, // opportunities which were not produced
+}
+
+impl<P: KafkaProducer> KafkaSink<P> {
+    pub fn new(producer: P, topic: &str, sender: &broadcast::Sender<EmittedOpportunity>) -> Self {
+        Self {
+            producer,
+            topic: topic.to_string(),
+            receiver: sender.subscribe(),
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /*
+        Returns counter of the dropped opportunities, which keeps counting while the sink is served
+    */
+    pub fn dropped(&self) -> Arc<AtomicU64> {
+        self.dropped.clone()
+    }
+
+    /*
+        Produces received opportunities until the channel is closed
+    */
+    pub async fn serve(mut self) {
+        loop {
+            match self.receiver.recv().await {
+                Ok(emitted) => {
+                    let (key, value) = Self::format_message(&emitted);
+                    let result = tokio::time::timeout(
+                        SEND_TIMEOUT,
+                        self.producer
+                            .send(&self.topic, key.as_bytes(), value.as_bytes()),
+                    )
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow!("Timed out after {:?}", SEND_TIMEOUT)));
+                    if let Err(err) = result {
+                        let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                        eprintln!(
+                            "Could not produce opportunity to Kafka, {} dropped so far: {}",
+                            dropped, err
+                        );
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    self.dropped.fetch_add(skipped, Ordering::Relaxed);
+                    eprintln!(
+                        "Kafka sink lagged behind, skipped {} opportunities",
+                        skipped
+                    );
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /*
+        Formats the opportunity as a record keyed by its pair with the JSON-serialized opportunity as its value
+    */
+    pub fn format_message(emitted: &EmittedOpportunity) -> (String, String) {
+        (
+            emitted.pair.clone(),
+            serde_json::to_string(emitted).unwrap(),
+        )
+    }
+}
+
+/*
+    Struct producing records to Kafka brokers through librdkafka, which discovers the cluster and partitions records by their key
+    A record not delivered within the send timeout is expired by librdkafka as well, so a dropped opportunity is never produced later
+*/
+pub struct RdKafkaProducer {
+    producer: FutureProducer,
+}
+
+impl RdKafkaProducer {
+    /*
+        Creates the producer bootstrapping from the host:port addresses, it connects to them lazily on the first record
+    */
+    pub fn new(bootstrap_brokers: &[String]) -> Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", bootstrap_brokers.join(","))
+            .set("message.timeout.ms", SEND_TIMEOUT.as_millis().to_string())
+            .create()?;
+        Ok(Self { producer })
+    }
+}
+
+impl KafkaProducer for RdKafkaProducer {
+    async fn send(&mut self, topic: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        let record = FutureRecord::to(topic).key(key).payload(value);
+        self.producer
+            .send(record, Timeout::After(SEND_TIMEOUT))
+            .await
+            .map(|_| ())
+            .map_err(|(err, _)| anyhow!(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::Ordering, Arc, Mutex};
+
+    use anyhow::{anyhow, Result};
+    use tokio::sync::broadcast;
+
+    use crate::structs::{arbitrage_finder::ArbitrageOpportunity, emitter::EmittedOpportunity};
+
+    use super::{KafkaProducer, KafkaSink, RdKafkaProducer};
+
+    #[derive(Default)]
+    struct MockProducer {
+        failures: usize, // number of the first records which fail
+        records: Arc<Mutex<Vec<(String, String, String)>>>, // topic, key and value
+    }
+
+    impl KafkaProducer for MockProducer {
+        async fn send(&mut self, topic: &str, key: &[u8], value: &[u8]) -> Result<()> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(anyhow!("Broker is unavailable"));
+            }
+            self.records.lock().unwrap().push((
+                topic.to_string(),
+                String::from_utf8(key.to_vec()).unwrap(),
+                String::from_utf8(value.to_vec()).unwrap(),
+            ));
+            Ok(())
+        }
+    }
+
+    fn emitted(seq: u64) -> EmittedOpportunity {
+        EmittedOpportunity::new(seq, "solusdt", ArbitrageOpportunity::sample())
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let (sender, _) = broadcast::channel(16);
+        let producer = MockProducer {
+            failures: 1,
+            ..Default::default()
+        };
+        let records = producer.records.clone();
+        let sink = KafkaSink::new(producer, "opportunities", &sender);
+        let dropped = sink.dropped();
+        let serving = tokio::spawn(sink.serve());
+
+        let second = emitted(2);
+        sender.send(emitted(1)).unwrap();
+        sender.send(second.clone()).unwrap();
+        drop(sender);
+        serving.await.unwrap();
+
+        // The first record failed and was dropped without holding up the second one
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        let (topic, key, value) = &records[0];
+        assert_eq!(topic, "opportunities");
+        assert_eq!(key, "solusdt");
+        assert_eq!(value, &serde_json::to_string(&second).unwrap());
+        let json: serde_json::Value = serde_json::from_str(value).unwrap();
+        assert_eq!(json["seq"], 2);
+        assert_eq!(json["estimated_profit"], "0.03400176");
+    }
+
+    #[tokio::test]
+    async fn test_rdkafka_producer_unavailable_broker() {
+        // Nothing listens on the port, so the record expires instead of blocking the sink
+        let mut producer = RdKafkaProducer::new(&["127.0.0.1:1".to_string()]).unwrap();
+        assert!(producer
+            .send("opportunities", b"solusdt", b"{}")
+            .await
+            .is_err());
+    }
+}
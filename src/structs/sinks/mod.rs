@@ -0,0 +1,10 @@
+pub mod binary;
+pub mod discord;
+pub mod grpc;
+pub mod handler;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod sqlite;
+pub mod uds;
@@ -0,0 +1,201 @@
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use tokio::sync::broadcast::{
+    self,
+    error::{RecvError, TryRecvError},
+};
+
+use crate::structs::emitter::EmittedOpportunity;
+
+const MAX_BATCH_SIZE: usize = 256;
+
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS opportunities (
+    id INTEGER PRIMARY KEY,
+    seq INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    pair TEXT NOT NULL,
+    direction TEXT NOT NULL,
+    quantity TEXT NOT NULL,
+    estimated_profit TEXT NOT NULL,
+    binance_price TEXT NOT NULL,
+    pyth_price TEXT NOT NULL,
+    opportunity_id TEXT
+)";
+
+// Databases created before opportunity ids were stored lack the column
+const ADD_OPPORTUNITY_ID: &str = "ALTER TABLE opportunities ADD COLUMN opportunity_id TEXT";
+
+const INSERT_OPPORTUNITY: &str = "INSERT INTO opportunities
+    (seq, timestamp, pair, direction, quantity, estimated_profit, binance_price, pyth_price, opportunity_id)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)";
+
+/*
+    Struct persisting found opportunities in a SQLite database
+    Decimal values are stored as text, so they keep their exact precision
+*/
+pub struct SqliteSink {
+    connection: Connection,
+    receiver: broadcast::Receiver<EmittedOpportunity>,
+}
+
+impl SqliteSink {
+    /*
+        Opens the database under the provided path, creating the schema if it is absent
+    The column of opportunity ids is added to databases created without it
+    */
+    pub fn open(path: &Path, sender: &broadcast::Sender<EmittedOpportunity>) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute(CREATE_TABLE, [])?;
+        if connection
+            .prepare("SELECT opportunity_id FROM opportunities LIMIT 0")
+            .is_err()
+        {
+            connection.execute(ADD_OPPORTUNITY_ID, [])?;
+        }
+
+        Ok(Self {
+            connection,
+            receiver: sender.subscribe(),
+        })
+    }
+
+    /*
+        Inserts received opportunities until the channel is closed
+        Runs on a blocking thread, as SQLite calls block
+    */
+    pub async fn serve(self) {
+        tokio::task::spawn_blocking(move || self.insert_received())
+            .await
+            .unwrap();
+    }
+
+    /*
+        Waits for an opportunity and inserts it along with all the already queued ones in a single transaction
+    */
+    fn insert_received(mut self) {
+        loop {
+            let mut batch = match self.receiver.blocking_recv() {
+                Ok(emitted) => vec![emitted],
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!(
+                        "SQLite sink lagged behind, skipped {} opportunities",
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            while batch.len() < MAX_BATCH_SIZE {
+                match self.receiver.try_recv() {
+                    Ok(emitted) => batch.push(emitted),
+                    Err(TryRecvError::Lagged(skipped)) => {
+                        eprintln!(
+                            "SQLite sink lagged behind, skipped {} opportunities",
+                            skipped
+                        );
+                    }
+                    Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                }
+            }
+
+            if let Err(err) = self.insert_batch(&batch) {
+                eprintln!("Could not insert opportunities into SQLite: {}", err);
+            }
+        }
+    }
+
+    /*
+        Inserts the opportunities in a single transaction using a prepared statement
+    */
+    pub fn insert_batch(&mut self, batch: &[EmittedOpportunity]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement = transaction.prepare_cached(INSERT_OPPORTUNITY)?;
+            for emitted in batch {
+                let opportunity = &emitted.opportunity;
+                statement.execute(params![
+                    emitted.seq,
+                    emitted.timestamp,
+                    emitted.pair,
+                    format!("{:?}", opportunity.direction),
+                    opportunity.quantity.to_string(),
+                    opportunity.estimated_profit.to_string(),
+                    opportunity.binance_price.to_string(),
+                    opportunity.pyth_price.to_string(),
+                    opportunity.id.to_string(),
+                ])?;
+            }
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rusqlite::Connection;
+    use tokio::sync::broadcast;
+
+    use crate::structs::{arbitrage_finder::ArbitrageOpportunity, emitter::EmittedOpportunity};
+
+    use super::SqliteSink;
+
+    #[tokio::test]
+    async fn test_serve_inserts_opportunities() {
+        let path = std::env::temp_dir().join(format!("keyrock-task-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let (sender, _) = broadcast::channel(16);
+        let sink = SqliteSink::open(&path, &sender).unwrap();
+
+        let opportunity = ArbitrageOpportunity::sample();
+        for (seq, pair) in ["solusdt", "btcusdt", "bnbusdt"].into_iter().enumerate() {
+            sender
+                .send(EmittedOpportunity::new(seq as u64, pair, opportunity))
+                .unwrap();
+        }
+        drop(sender); // the sink stops once it inserts everything
+        tokio::time::timeout(Duration::from_secs(5), sink.serve())
+            .await
+            .unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let rows = connection
+            .prepare("SELECT seq, pair, direction, quantity, estimated_profit, binance_price, pyth_price FROM opportunities ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    (1..7)
+                        .map(|i| row.get::<_, String>(i))
+                        .collect::<Result<Vec<_>, _>>()?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows.iter()
+                .map(|(seq, row)| (*seq, row[0].as_str()))
+                .collect::<Vec<_>>(),
+            vec![(0, "solusdt"), (1, "btcusdt"), (2, "bnbusdt")]
+        );
+        assert_eq!(
+            rows[0].1[1..],
+            [
+                "SellBinanceBuyDex",
+                "0.8574",
+                "0.03400176",
+                "71.3833",
+                "71.27225988"
+            ]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
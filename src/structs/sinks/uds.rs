@@ -0,0 +1,195 @@
+use std::{
+    io::ErrorKind,
+    os::unix::{fs::FileTypeExt, net},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{UnixListener, UnixStream},
+    sync::broadcast::{self, error::RecvError},
+};
+
+use crate::structs::emitter::EmittedOpportunity;
+
+use super::binary;
+
+/*
+    Struct serving found opportunities as newline-delimited JSON to clients of a Unix domain socket,
+    or as frames of the compact binary layout, see binary::encode
+*/
+pub struct UdsSink {
+    listener: UnixListener,
+    sender: broadcast::Sender<EmittedOpportunity>,
+    binary: bool, // whether binary frames are written instead of JSON lines
+}
+
+impl UdsSink {
+    /*
+        Binds a listener to the provided path, removing a socket left over by a previous run, see remove_stale_socket
+    */
+    pub fn bind(path: &Path, sender: broadcast::Sender<EmittedOpportunity>) -> Result<Self> {
+        remove_stale_socket(path)?;
+
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            sender,
+            binary: false,
+        })
+    }
+
+    /*
+        Writes opportunities as binary frames instead of JSON lines
+    */
+    pub fn with_binary_frames(mut self) -> Self {
+        self.binary = true;
+        self
+    }
+
+    /*
+        Accepts clients and spawns a writer for each of them, so a slow or disconnected client does not affect the others
+    */
+    pub async fn serve(self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(Self::write_to_client(
+                        stream,
+                        self.sender.subscribe(),
+                        self.binary,
+                    ));
+                }
+                Err(err) => eprintln!("Could not accept UDS client: {}", err),
+            }
+        }
+    }
+
+    /*
+        Writes every received opportunity to the client until it disconnects
+    */
+    async fn write_to_client(
+        mut stream: UnixStream,
+        mut receiver: broadcast::Receiver<EmittedOpportunity>,
+        binary: bool,
+    ) {
+        loop {
+            match receiver.recv().await {
+                Ok(opportunity) => {
+                    let message = if binary {
+                        binary::encode(&opportunity)
+                    } else {
+                        let mut line = serde_json::to_string(&opportunity).unwrap();
+                        line.push('\n');
+                        line.into_bytes()
+                    };
+                    if stream.write_all(&message).await.is_err() {
+                        return; // client disconnected
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!(
+                        "UDS client lagged behind, skipped {} opportunities",
+                        skipped
+                    );
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+/*
+    Removes a socket at the path no process accepts connections on any more, e.g. left over by a previous run
+    Fails without removing anything if the path is not a socket or another process is still serving it
+*/
+pub(crate) fn remove_stale_socket(path: &Path) -> Result<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(anyhow!("{} exists and is not a socket", path.display()));
+    }
+
+    match net::UnixStream::connect(path) {
+        Ok(_) => Err(anyhow!("{} is in use by another process", path.display())),
+        Err(err) if err.kind() == ErrorKind::ConnectionRefused => Ok(std::fs::remove_file(path)?),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{
+        io::{AsyncBufReadExt, BufReader},
+        net::UnixStream,
+        sync::broadcast,
+    };
+
+    use crate::structs::arbitrage_finder::ArbitrageOpportunity;
+
+    use crate::structs::emitter::EmittedOpportunity;
+
+    use super::{remove_stale_socket, UdsSink};
+
+    #[tokio::test]
+    async fn test_serve_writes_opportunity_to_client() {
+        let path = std::env::temp_dir().join(format!("keyrock-task-{}.sock", std::process::id()));
+        let (sender, _) = broadcast::channel(16);
+        let sink = UdsSink::bind(&path, sender.clone()).unwrap();
+        tokio::spawn(sink.serve());
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        while sender.receiver_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let opportunity = ArbitrageOpportunity::sample();
+        let emitted = EmittedOpportunity::new(0, "solusdt", opportunity);
+        sender.send(emitted.clone()).unwrap();
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).await.unwrap();
+        assert!(line.ends_with('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+            serde_json::to_value(&emitted).unwrap()
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line).unwrap()["estimated_profit"],
+            "0.03400176"
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line).unwrap()["pair"],
+            "solusdt"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remove_stale_socket() {
+        let path = std::env::temp_dir().join(format!("keyrock-task-stale-{}", std::process::id()));
+        assert!(remove_stale_socket(&path).is_ok());
+
+        // Regular files are kept
+        std::fs::write(&path, "opportunities").unwrap();
+        assert!(remove_stale_socket(&path).is_err());
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+
+        // A socket served by another listener is kept, it is removed once the listener is gone
+        let (sender, _) = broadcast::channel(16);
+        let sink = UdsSink::bind(&path, sender.clone()).unwrap();
+        assert!(UdsSink::bind(&path, sender.clone()).is_err());
+        assert!(path.exists());
+        drop(sink);
+        assert!(UdsSink::bind(&path, sender).is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use super::arbitrage_finder::{ArbitrageFinder, CurrentSpread};
+
+/*
+    Struct holding the current spread of a single pair
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PairSpread {
+    pub pair: String, // Binance ticker of the pair
+    #[serde(flatten)]
+    pub spread: CurrentSpread,
+}
+
+/*
+    Struct holding the current spreads of all the evaluated pairs at a single time, emitted as one JSON document
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SpreadSnapshot {
+    pub timestamp: u64, // milliseconds since Unix epoch
+    pub pairs: Vec<PairSpread>,
+}
+
+impl SpreadSnapshot {
+    /*
+        Gathers the current spread of every arbitrage finder, sorted by Binance ticker
+        Pairs whose last evaluation had no valid prices are left out
+    */
+    pub async fn gather(
+        timestamp: u64,
+        arbitrage_finders: Vec<(String, Arc<Mutex<ArbitrageFinder>>)>,
+    ) -> Self {
+        let mut pairs = Vec::with_capacity(arbitrage_finders.len());
+        for (binance_ticker, arbitrage_finder) in arbitrage_finders {
+            if let Some(spread) = arbitrage_finder.lock().await.current_spread() {
+                pairs.push(PairSpread {
+                    pair: binance_ticker,
+                    spread,
+                });
+            }
+        }
+        pairs.sort_by(|a, b| a.pair.cmp(&b.pair));
+
+        Self { timestamp, pairs }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+    use serde_json::json;
+    use tokio::sync::{Mutex, RwLock};
+
+    use crate::structs::{arbitrage_finder::ArbitrageFinder, cex::binance::BookTickerData};
+
+    use super::SpreadSnapshot;
+
+    #[tokio::test]
+    async fn test_gather() {
+        async fn evaluated_finder(bid: &str, ask: &str) -> Arc<Mutex<ArbitrageFinder>> {
+            let mut arbitrage_finder = ArbitrageFinder::new();
+            // l: 68.43263012 h: 71.27225988
+            arbitrage_finder
+                .find_opportunity(
+                    Arc::new(RwLock::new(Some(Price {
+                        price: 69852445,
+                        conf: 669724,
+                        expo: -6,
+                        ..Default::default()
+                    }))),
+                    Arc::new(RwLock::new(Some(BookTickerData {
+                        b: bid.to_string(),
+                        B: "0.8574".to_string(),
+                        a: ask.to_string(),
+                        A: "0.9245".to_string(),
+                        ..Default::default()
+                    }))),
+                    Decimal::new(1, 3),
+                )
+                .await;
+            Arc::new(Mutex::new(arbitrage_finder))
+        }
+
+        let snapshot = SpreadSnapshot::gather(
+            1_000_000,
+            vec![
+                (
+                    "solusdt".to_string(),
+                    evaluated_finder("71.3833", "72.0012").await,
+                ),
+                (
+                    "bnbusdt".to_string(),
+                    evaluated_finder("69.8", "69.9").await,
+                ),
+                // Never evaluated
+                (
+                    "ethusdt".to_string(),
+                    Arc::new(Mutex::new(ArbitrageFinder::new())),
+                ),
+            ],
+        )
+        .await;
+
+        assert_eq!(snapshot.pairs.len(), 2);
+        assert_eq!(snapshot.pairs[0].pair, "bnbusdt");
+        assert_eq!(
+            snapshot.pairs[0].spread.signed_edge,
+            Decimal::from_str("-0.002445").unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&snapshot).unwrap()["pairs"][1],
+            json!({
+                "pair": "solusdt",
+                "binance_mid": "71.69225",
+                "pyth_low": "68.43263012",
+                "pyth_high": "71.27225988",
+                "signed_edge": "1.839805",
+            })
+        );
+    }
+}
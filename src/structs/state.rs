@@ -1,113 +1,734 @@
-use std::{str::FromStr, sync::Arc};
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
 
+use metrics::histogram;
 use pyth_sdk_solana::Price;
 use rust_decimal::Decimal;
+use serde_json::json;
 use solana_program::pubkey::Pubkey;
-use tokio::sync::{OnceCell, RwLock};
+use tokio::sync::{watch, OnceCell, RwLock};
 
-use crate::config::{Config, CONFIG};
+use crate::config::{CexSource, Config, PairConfig, CONFIG};
 
 use super::{
-    cex::binance::{Binance, BookTickerData},
-    on_chain::pyth::Pyth,
+    cex::{
+        aggregator::{TickerAggregator, Weighting},
+        binance::{parse_decimal, Binance, BookTickerData},
+        coinbase::Coinbase,
+        TickerSource,
+    },
+    on_chain::{
+        pool::{PoolPriceSource, Whirlpool},
+        pyth::{self, FixedPrice, PriceSource, Pyth},
+    },
+    telemetry,
+    tick_buffer::TickBuffer,
 };
 
 pub static STATE: OnceCell<State> = OnceCell::const_new();
 
+const DEFAULT_TICK_BUFFER_CAPACITY: usize = 100;
+
 /*
     Struct managing runtime state of the application
 */
 pub struct State {
-    pyth: Pyth,
+    pyth: Box<dyn PriceSource>,
+    dex_pools: Option<Box<dyn PoolPriceSource>>, // source of prices of the pools of the pairs, None if no pool is configured
     binance: Binance,
+    coinbase: Option<Coinbase>, // combined with Binance, if configured
+    cex_weighting: Option<Weighting>, // weighting of the combined tops of the book, None if Binance is the only CEX source
+    pairs: std::sync::RwLock<Vec<Arc<PairState>>>,
+    subscription_id: AtomicI64,
+    binance_max_connection_age: Duration,
+    binance_idle_timeout: Duration, // time without Binance WS messages after which the connection is replaced
+    tick_buffer_capacity: usize,
+}
+
+/*
+    Struct holding the latest data of a single monitored pair
+*/
+pub struct PairState {
+    pub binance_ticker: String,
     pyth_price_id: Pubkey,
+    pyth_quote_price_id: Option<Pubkey>,
     latest_pyth_price: Arc<RwLock<Option<Price>>>,
-    latest_binance_ticker_data: Arc<RwLock<Option<BookTickerData>>>,
+    latest_binance_ticker_data: Arc<RwLock<Option<BookTickerData>>>, // combined top of the book of the CEX sources if several are
+    ticker_aggregator: Option<std::sync::Mutex<TickerAggregator>>, // combining the CEX sources, None if Binance is the only one
+    dex_pool: Option<Pubkey>, // on-chain pool whose price is blended into the reference DEX price
+    latest_dex_pool_price: Arc<RwLock<Option<Decimal>>>,
     pub binance_taker_fee: Decimal,
-    pub subscription_id: i64,
+    recent_pyth_prices: std::sync::Mutex<TickBuffer<Price>>,
+    recent_binance_ticker_data: std::sync::Mutex<TickBuffer<BookTickerData>>,
+    last_pyth_update: AtomicU64, // milliseconds since Unix epoch when the Pyth price last changed, 0 if never
+    last_binance_update: AtomicU64, // milliseconds since Unix epoch of the last Binance message, 0 if never
+    last_binance_reconnect: Arc<AtomicU64>, // milliseconds since Unix epoch of the last reconnection of Binance WS, 0 if never
+    enabled: watch::Sender<bool>, // whether the pair is evaluated, watched by the task evaluating it
 }
 
 impl State {
     /*
-        Establishes connections and creates instance of State
+        Establishes connections and creates instance of State, configured by the CLI arguments unless CONFIG is already set
     */
     pub async fn new() -> Self {
         let config = CONFIG.get_or_init(|| async { Config::new() }).await;
-        let (binance, _) = Binance::connect()
+        Self::from_config(config)
             .await
-            .expect("Could not connect to Binance WS");
+            .expect("Could not connect to Binance WS")
+    }
+
+    /*
+        Establishes connections and creates instance of State configured by the given Config, without touching CONFIG
+    */
+    pub async fn from_config(config: &Config) -> Result<Self> {
+        let cex_weighting = (!config.combine_with.is_empty()).then(|| config.cex_weighting.clone());
+        let pairs = config
+            .pairs()
+            .iter()
+            .map(|pair_config| {
+                let mut pair = PairState::new(pair_config)
+                    .with_tick_buffer_capacity(config.tick_buffer_capacity);
+                if let Some(cex_weighting) = &cex_weighting {
+                    pair = pair.with_ticker_aggregator(cex_weighting.clone());
+                }
+                Arc::new(
+                    match config
+                        .dex_pools
+                        .iter()
+                        .find(|dex_pool| dex_pool.binance_ticker == pair_config.binance_ticker)
+                    {
+                        Some(dex_pool) => pair.with_dex_pool(
+                            Pubkey::from_str(&dex_pool.pool).expect("Invalid DEX pool address"),
+                        ),
+                        None => pair,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let (binance, _) = Binance::connect_to_market(
+            config.market,
+            config.binance_endpoint,
+            Duration::from_secs(config.connect_timeout),
+        )
+        .await?;
         let subscription_id = binance
-            .subscribe_to_ticker(&config.binance_ticker)
+            .subscribe_to_tickers(&Self::binance_tickers(&pairs))
+            .await?;
+        let coinbase = match config.combine_with.contains(&CexSource::Coinbase) {
+            true => {
+                let coinbase = Coinbase::connect(Duration::from_secs(config.connect_timeout))
+                    .await
+                    .context("Could not connect to Coinbase WS")?;
+                coinbase.subscribe(&Self::binance_tickers(&pairs)).await?;
+                Some(coinbase)
+            }
+            false => None,
+        };
+
+        Ok(Self {
+            pyth: match config.fixed_dex_price {
+                Some(fixed_dex_price) => Box::new(FixedPrice::new(fixed_dex_price)),
+                None => Box::new(
+                    Pyth::with_publisher_trim(config.pyth_publisher_trim)
+                        .with_field(config.pyth_field)
+                        .with_timeout(Duration::from_secs(config.pyth_timeout)),
+                ),
+            },
+            dex_pools: (!config.dex_pools.is_empty()).then(|| {
+                Box::new(Whirlpool::new(&config.solana_rpc_url)) as Box<dyn PoolPriceSource>
+            }),
+            binance,
+            coinbase,
+            cex_weighting,
+            pairs: std::sync::RwLock::new(pairs),
+            subscription_id: AtomicI64::new(subscription_id),
+            binance_max_connection_age: Duration::from_secs(config.binance_max_connection_age),
+            binance_idle_timeout: Duration::from_secs(config.idle_timeout),
+            tick_buffer_capacity: config.tick_buffer_capacity,
+        })
+    }
+
+    /*
+        Acquires write locks and updates values of latest_pyth_price fields of all pairs
+        Pairs whose price could not be loaded keep the last known one, the first of such errors is returned
+    */
+    pub async fn update_latest_pyth_price(&self) -> Result<()> {
+        let mut result = Ok(());
+        for pair in self.pairs() {
+            if let Err(err) = pair.refresh_pyth_price(self.pyth.as_ref()).await {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+
+    /*
+        Updates the latest pool prices of the pairs with a configured pool
+        Pairs whose price could not be loaded keep the last known one, the first of such errors is returned
+    */
+    pub async fn update_latest_dex_pool_prices(&self) -> Result<()> {
+        let Some(dex_pools) = &self.dex_pools else {
+            return Ok(());
+        };
+        let mut result = Ok(());
+        for pair in self.pairs() {
+            if let Err(err) = pair.refresh_dex_pool_price(dex_pools.as_ref()).await {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+
+    /*
+        Acquires write lock and updates value of latest_binance_ticker_data field of the pair the message refers to
+        Cycles the Binance WS connection once it gets too old or idle, waiting for the next message no longer than that
+    */
+    pub async fn update_latest_binance_ticker_data(&self) {
+        let pairs = self.pairs();
+        let tickers = Self::binance_tickers(&pairs);
+        let maybe_subscription_id = self
+            .binance
+            .cycle_connection_if_expired(self.binance_max_connection_age, &tickers)
+            .await
+            .expect("Could not reconnect to Binance WS");
+        if let Some(subscription_id) = maybe_subscription_id {
+            println!("Cycled Binance WS connection");
+            self.subscription_id
+                .store(subscription_id, Ordering::Relaxed);
+            self.record_binance_reconnect(&pairs);
+        }
+        let maybe_subscription_id = self
+            .binance
+            .reconnect_if_idle(self.binance_idle_timeout, &tickers)
+            .await
+            .expect("Could not reconnect to Binance WS");
+        if let Some(subscription_id) = maybe_subscription_id {
+            println!(
+                "Reconnected to Binance WS, no message was received for {:?}",
+                self.binance_idle_timeout
+            );
+            self.subscription_id
+                .store(subscription_id, Ordering::Relaxed);
+            self.record_binance_reconnect(&pairs);
+        }
+
+        let time_left = self
+            .binance_max_connection_age
+            .saturating_sub(self.binance.connection_age().await)
+            .min(
+                self.binance_idle_timeout
+                    .saturating_sub(self.binance.idle_time().await),
+            );
+        if let Ok(Some(binance_response)) =
+            tokio::time::timeout(time_left, self.binance.read_next_message()).await
+        {
+            let maybe_pair = self.pairs().into_iter().find(|pair| {
+                pair.binance_ticker
+                    .eq_ignore_ascii_case(&binance_response.data.s)
+            });
+            if let Some(pair) = maybe_pair {
+                pair.update_latest_cex_ticker_data(self.binance.name(), binance_response.data)
+                    .await;
+            }
+        }
+    }
+
+    /*
+        Combines the next Coinbase top of the book into the pair it refers to, does nothing unless Coinbase is combined
+        Replaces the Coinbase WS connection once it gets idle or is closed, waiting for the next message no longer than the idle timeout
+    */
+    pub async fn update_latest_coinbase_ticker_data(&self) -> Result<()> {
+        let Some(coinbase) = &self.coinbase else {
+            return Ok(());
+        };
+        if coinbase
+            .reconnect_if_idle(self.binance_idle_timeout)
+            .await?
+        {
+            println!(
+                "Reconnected to Coinbase WS, no message was received for {:?}",
+                self.binance_idle_timeout
+            );
+        }
+
+        let time_left = self
+            .binance_idle_timeout
+            .saturating_sub(coinbase.idle_time().await);
+        match tokio::time::timeout(time_left, coinbase.read_next_message()).await {
+            Ok(Ok(Some(ticker_data))) => {
+                if let Some(pair) = self.pair(&ticker_data.s) {
+                    pair.update_latest_cex_ticker_data(coinbase.name(), ticker_data)
+                        .await;
+                }
+            }
+            Ok(Ok(None)) | Err(_) => {}
+            Ok(Err(err)) => {
+                coinbase.reconnect().await?;
+                println!("Reconnected to Coinbase WS: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    /*
+        Unsubscribes from the Binance WS ticker streams
+    */
+    pub async fn terminate(&self) {
+        self.binance
+            .unsubscribe_from_tickers(
+                &Self::binance_tickers(&self.pairs()),
+                self.subscription_id.load(Ordering::Relaxed),
+            )
             .await
             .unwrap();
+    }
+
+    /*
+        Returns the latest funding rate of the pair if Binance perp market is monitored
+    */
+    pub fn get_binance_funding_rate(&self, binance_ticker: &str) -> Option<Decimal> {
+        self.binance.funding_rate(binance_ticker)
+    }
+
+    /*
+        Returns all the currently monitored pairs
+    */
+    pub fn pairs(&self) -> Vec<Arc<PairState>> {
+        self.pairs.read().unwrap().clone()
+    }
+
+    /*
+        Returns the monitored pair with the Binance ticker, if any
+    */
+    pub fn pair(&self, binance_ticker: &str) -> Option<Arc<PairState>> {
+        let binance_ticker = binance_ticker.to_lowercase();
+        self.pairs
+            .read()
+            .unwrap()
+            .iter()
+            .find(|pair| pair.binance_ticker == binance_ticker)
+            .cloned()
+    }
 
+    /*
+        Starts monitoring the pair, subscribing to its Binance stream and Coinbase product if Coinbase is combined
+    */
+    pub async fn add_pair(&self, pair_config: &PairConfig) -> Result<Arc<PairState>> {
+        for pyth_price_id in std::iter::once(&pair_config.pyth_price_id)
+            .chain(pair_config.pyth_quote_price_id.as_ref())
+        {
+            Pubkey::from_str(pyth_price_id)
+                .map_err(|_| anyhow!("Invalid Pyth price id {}", pyth_price_id))?;
+        }
+
+        let mut pair =
+            PairState::new(pair_config).with_tick_buffer_capacity(self.tick_buffer_capacity);
+        if let Some(cex_weighting) = &self.cex_weighting {
+            pair = pair.with_ticker_aggregator(cex_weighting.clone());
+        }
+        let pair = Arc::new(pair);
+        {
+            let mut pairs = self.pairs.write().unwrap();
+            if pairs
+                .iter()
+                .any(|monitored| monitored.binance_ticker == pair.binance_ticker)
+            {
+                return Err(anyhow!("Pair {} is already monitored", pair.binance_ticker));
+            }
+            pairs.push(pair.clone());
+        }
+
+        self.binance
+            .request_subscription(&[&pair.binance_ticker])
+            .await?;
+        if let Some(coinbase) = &self.coinbase {
+            coinbase.subscribe(&[&pair.binance_ticker]).await?;
+        }
+        Ok(pair)
+    }
+
+    /*
+        Stops monitoring the pair, unsubscribing from its Binance stream and Coinbase product
+    */
+    pub async fn remove_pair(&self, binance_ticker: &str) -> Result<()> {
+        let binance_ticker = binance_ticker.to_lowercase();
+        {
+            let mut pairs = self.pairs.write().unwrap();
+            let pairs_count = pairs.len();
+            pairs.retain(|pair| pair.binance_ticker != binance_ticker);
+            if pairs.len() == pairs_count {
+                return Err(anyhow!("Pair {} is not monitored", binance_ticker));
+            }
+        }
+
+        self.binance
+            .request_unsubscription(&[&binance_ticker])
+            .await?;
+        if let Some(coinbase) = &self.coinbase {
+            coinbase.unsubscribe(&[&binance_ticker]).await?;
+        }
+        Ok(())
+    }
+
+    fn record_binance_reconnect(&self, pairs: &[Arc<PairState>]) {
+        let now = now();
+        for pair in pairs {
+            pair.last_binance_reconnect.store(now, Ordering::Relaxed);
+        }
+    }
+
+    fn binance_tickers(pairs: &[Arc<PairState>]) -> Vec<&str> {
+        pairs
+            .iter()
+            .map(|pair| pair.binance_ticker.as_str())
+            .collect()
+    }
+}
+
+/*
+    Returns milliseconds since Unix epoch
+*/
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64 // doesn't overflow
+}
+
+impl PairState {
+    pub fn new(pair_config: &PairConfig) -> Self {
         Self {
-            pyth: Pyth::new(),
-            binance,
-            pyth_price_id: Pubkey::from_str(&config.pyth_price_id).unwrap(),
+            binance_ticker: pair_config.binance_ticker.clone(),
+            pyth_price_id: Pubkey::from_str(&pair_config.pyth_price_id).unwrap(),
+            pyth_quote_price_id: pair_config
+                .pyth_quote_price_id
+                .as_ref()
+                .map(|pyth_quote_price_id| Pubkey::from_str(pyth_quote_price_id).unwrap()),
             latest_pyth_price: Arc::new(RwLock::new(None)),
             latest_binance_ticker_data: Arc::new(RwLock::new(None)),
-            binance_taker_fee: if config.binance_ticker.contains("bnb") {
-                Decimal::new(75, 5)
-            } else {
-                Decimal::new(1, 3)
-            },
-            subscription_id,
+            ticker_aggregator: None,
+            dex_pool: None,
+            latest_dex_pool_price: Arc::new(RwLock::new(None)),
+            binance_taker_fee: Binance::taker_fee(&pair_config.binance_ticker),
+            recent_pyth_prices: std::sync::Mutex::new(TickBuffer::new(
+                DEFAULT_TICK_BUFFER_CAPACITY,
+            )),
+            recent_binance_ticker_data: std::sync::Mutex::new(TickBuffer::new(
+                DEFAULT_TICK_BUFFER_CAPACITY,
+            )),
+            last_pyth_update: AtomicU64::new(0),
+            last_binance_update: AtomicU64::new(0),
+            last_binance_reconnect: Arc::new(AtomicU64::new(0)),
+            enabled: watch::Sender::new(pair_config.enabled),
         }
     }
 
     /*
-        Returns a pointer to latest_pyth_price field
+        Returns whether opportunities of the pair are emitted
     */
-    pub fn get_latest_pyth_price(&self) -> Arc<RwLock<Option<Price>>> {
-        self.latest_pyth_price.clone()
+    pub fn is_enabled(&self) -> bool {
+        *self.enabled.borrow()
     }
 
     /*
-        Returns a pointer to latest_binance_ticker_data field
+        Enables or disables the pair, its feeds keep being updated either way
+        Returns whether the state changed
     */
-    pub fn get_latest_binance_ticker_data(&self) -> Arc<RwLock<Option<BookTickerData>>> {
-        self.latest_binance_ticker_data.clone()
+    pub fn set_enabled(&self, enabled: bool) -> bool {
+        self.enabled.send_replace(enabled) != enabled
     }
 
     /*
-        Acquires write lock and updates value of latest_pyth_price field
+        Waits until the pair is enabled, returning immediately if it already is
     */
-    pub async fn update_latest_pyth_price(&self) {
-        let maybe_price = self
-            .pyth
-            .get_price(&self.pyth_price_id)
-            .expect("Could not load price feed from account");
-        *self.latest_pyth_price.write().await = maybe_price;
+    pub async fn wait_until_enabled(&self) {
+        let _ = self.enabled.subscribe().wait_for(|enabled| *enabled).await; // the sender lives as long as self
     }
 
     /*
-        Acquires write lock and updates value of latest_binance_ticker_data field
+        Loads the Pyth price of the pair from the source and stores it, keeping the last known price on error
     */
-    pub async fn update_latest_binance_ticker_data(&self) {
-        if let Some(binance_response) = self.binance.read_next_message().await {
-            *self.latest_binance_ticker_data.write().await = Some(binance_response.data);
+    pub async fn refresh_pyth_price(&self, pyth: &dyn PriceSource) -> Result<()> {
+        let context = || format!("Could not load Pyth price of {}", self.binance_ticker);
+        let mut maybe_price = pyth.get_price(&self.pyth_price_id).with_context(context)?;
+        if let Some(pyth_quote_price_id) = &self.pyth_quote_price_id {
+            let maybe_quote_price = pyth.get_price(pyth_quote_price_id).with_context(context)?;
+            maybe_price = maybe_price
+                .zip(maybe_quote_price)
+                .and_then(|(price, quote_price)| pyth::derive_cross_price(&price, &quote_price));
         }
+        self.update_latest_pyth_price(maybe_price).await;
+        Ok(())
     }
 
     /*
-        Unsubscribes from the Binance WS ticker stream
+        Loads the price of the pool of the pair from the source and stores it, keeping the last known price on error
+        Does nothing if the pair has no pool
     */
-    pub async fn terminate(&self) {
-        let config = CONFIG.get().unwrap();
-        self.binance
-            .unsubscribe(&config.binance_ticker, self.subscription_id)
-            .await
-            .unwrap();
+    pub async fn refresh_dex_pool_price(&self, dex_pools: &dyn PoolPriceSource) -> Result<()> {
+        let Some(dex_pool) = &self.dex_pool else {
+            return Ok(());
+        };
+        let price = dex_pools
+            .get_price(dex_pool)
+            .with_context(|| format!("Could not load DEX pool price of {}", self.binance_ticker))?;
+        *self.latest_dex_pool_price.write().await = Some(price);
+        Ok(())
+    }
+
+    /*
+        Stores the Pyth price, recording the update if the price changed
+    */
+    pub async fn update_latest_pyth_price(&self, maybe_price: Option<Price>) {
+        let mut latest_pyth_price_write = self.latest_pyth_price.write().await;
+        if let Some(price) = maybe_price.filter(|price| *latest_pyth_price_write != Some(*price)) {
+            self.recent_pyth_prices.lock().unwrap().push(price);
+            let now = now();
+            self.last_pyth_update.store(now, Ordering::Relaxed);
+            histogram!(telemetry::PYTH_PRICE_AGE, "pair" => self.binance_ticker.clone())
+                .record(now as f64 / 1000.0 - price.publish_time as f64);
+        }
+        *latest_pyth_price_write = maybe_price;
+    }
+
+    /*
+        Returns time of the last reconnection of Binance WS, shared with the arbitrage finder of the pair
+    */
+    pub fn get_last_binance_reconnect(&self) -> Arc<AtomicU64> {
+        self.last_binance_reconnect.clone()
+    }
+
+    /*
+        Stores the top of the book received from the CEX source, or the combined one if the pair combines several sources
+        The previous top of the book is kept until the combined one can be computed, e.g. before a weighted source is received
+    */
+    pub async fn update_latest_cex_ticker_data(
+        &self,
+        source_name: &str,
+        ticker_data: BookTickerData,
+    ) {
+        let ticker_data = match &self.ticker_aggregator {
+            Some(ticker_aggregator) => {
+                let mut ticker_aggregator = ticker_aggregator.lock().unwrap();
+                ticker_aggregator.update(source_name, ticker_data);
+                match ticker_aggregator.combined() {
+                    Some(combined) => combined,
+                    None => return,
+                }
+            }
+            None => ticker_data,
+        };
+        self.update_latest_binance_ticker_data(ticker_data).await;
+    }
+
+    /*
+        Stores the Binance ticker data, recording the update
+    */
+    pub async fn update_latest_binance_ticker_data(&self, binance_ticker_data: BookTickerData) {
+        self.recent_binance_ticker_data
+            .lock()
+            .unwrap()
+            .push(binance_ticker_data.clone());
+        self.last_binance_update.store(now(), Ordering::Relaxed);
+        *self.latest_binance_ticker_data.write().await = Some(binance_ticker_data);
+    }
+
+    /*
+        Formats the current Binance spread and Pyth price along with the times of the last updates as a single line, noting if the pair is disabled
+    */
+    pub async fn heartbeat(&self) -> String {
+        let now = now();
+        let ago = |last_update: &AtomicU64| match last_update.load(Ordering::Relaxed) {
+            0 => "never updated".to_string(),
+            last_update => format!("updated {} ms ago", now.saturating_sub(last_update)),
+        };
+
+        let binance = match &*self.latest_binance_ticker_data.read().await {
+            Some(binance_ticker_data) => {
+                match (
+                    parse_decimal(&binance_ticker_data.b),
+                    parse_decimal(&binance_ticker_data.a),
+                ) {
+                    (Some(bid), Some(ask)) => format!(
+                        "bid {} ask {} spread {}",
+                        bid.normalize(),
+                        ask.normalize(),
+                        (ask - bid).normalize()
+                    ),
+                    _ => "malformed ticker data".to_string(),
+                }
+            }
+            None => "no ticker data".to_string(),
+        };
+        let pyth = match &*self.latest_pyth_price.read().await {
+            Some(price) => {
+                let exponential = price.expo.unsigned_abs();
+                match (
+                    Decimal::try_new(price.price, exponential),
+                    i64::try_from(price.conf)
+                        .ok()
+                        .and_then(|conf| Decimal::try_new(conf, exponential).ok()),
+                ) {
+                    (Ok(price), Some(conf)) => {
+                        format!("price {} conf {}", price.normalize(), conf.normalize())
+                    }
+                    _ => "unrepresentable price".to_string(),
+                }
+            }
+            None => "no price".to_string(),
+        };
+
+        format!(
+            "Heartbeat {}{}: Binance {} ({}), Pyth {} ({})",
+            self.binance_ticker,
+            if self.is_enabled() { "" } else { " (disabled)" },
+            binance,
+            ago(&self.last_binance_update),
+            pyth,
+            ago(&self.last_pyth_update)
+        )
+    }
+
+    /*
+        Sets the on-chain pool whose price is blended into the reference DEX price
+    */
+    pub fn with_dex_pool(mut self, dex_pool: Pubkey) -> Self {
+        self.dex_pool = Some(dex_pool);
+        self
+    }
+
+    /*
+        Combines tops of the book of the CEX sources by the weighting into the one compared with Pyth prices
+    */
+    pub fn with_ticker_aggregator(mut self, weighting: Weighting) -> Self {
+        self.ticker_aggregator = Some(std::sync::Mutex::new(TickerAggregator::new(weighting)));
+        self
+    }
+
+    /*
+        Returns whether the pair has an on-chain pool
+    */
+    pub fn has_dex_pool(&self) -> bool {
+        self.dex_pool.is_some()
+    }
+
+    /*
+        Sets number of the most recent updates of each feed kept for debugging
+    */
+    pub fn with_tick_buffer_capacity(self, capacity: usize) -> Self {
+        Self {
+            recent_pyth_prices: std::sync::Mutex::new(TickBuffer::new(capacity)),
+            recent_binance_ticker_data: std::sync::Mutex::new(TickBuffer::new(capacity)),
+            ..self
+        }
+    }
+
+    /*
+        Formats the most recent updates of both feeds as lines of JSON, from the oldest
+    */
+    pub fn dump_recent_ticks(&self) -> String {
+        let pyth_lines = self
+            .recent_pyth_prices
+            .lock()
+            .unwrap()
+            .ticks()
+            .map(|(timestamp, price)| {
+                json!({"pair": self.binance_ticker, "feed": "pyth", "timestamp": timestamp, "tick": price})
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        let binance_lines = self
+            .recent_binance_ticker_data
+            .lock()
+            .unwrap()
+            .ticks()
+            .map(|(timestamp, data)| {
+                json!({"pair": self.binance_ticker, "feed": "binance", "timestamp": timestamp, "tick": data})
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+
+        pyth_lines
+            .into_iter()
+            .chain(binance_lines)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /*
+        Returns a pointer to latest_pyth_price field
+    */
+    pub fn get_latest_pyth_price(&self) -> Arc<RwLock<Option<Price>>> {
+        self.latest_pyth_price.clone()
+    }
+
+    /*
+        Returns a pointer to latest_binance_ticker_data field
+    */
+    pub fn get_latest_binance_ticker_data(&self) -> Arc<RwLock<Option<BookTickerData>>> {
+        self.latest_binance_ticker_data.clone()
+    }
+
+    /*
+        Returns a pointer to latest_dex_pool_price field, shared with the arbitrage finder of the pair
+    */
+    pub fn get_latest_dex_pool_price(&self) -> Arc<RwLock<Option<Decimal>>> {
+        self.latest_dex_pool_price.clone()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use anyhow::{anyhow, Result};
+    use pyth_sdk_solana::Price;
     use rust_decimal::Decimal;
+    use solana_program::pubkey::Pubkey;
 
-    use super::State;
-    use crate::config::{Config, CONFIG};
+    use super::{PairState, State};
+    use crate::{
+        config::{Config, PairConfig, CONFIG},
+        structs::{
+            cex::{aggregator::Weighting, binance::BookTickerData},
+            on_chain::pyth::PriceSource,
+        },
+    };
+
+    /*
+        Source failing the given number of times before returning the price
+    */
+    struct FlakySource {
+        failures_left: AtomicUsize,
+        price: Price,
+    }
+
+    impl PriceSource for FlakySource {
+        fn get_price(&self, _price_id: &Pubkey) -> Result<Option<Price>> {
+            if self
+                .failures_left
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                    left.checked_sub(1)
+                })
+                .is_ok()
+            {
+                return Err(anyhow!("RPC unavailable"));
+            }
+            Ok(Some(self.price))
+        }
+    }
 
     #[tokio::test]
     #[ignore = "Uses global static, hence has to be ran manually"]
@@ -115,13 +736,14 @@ mod tests {
         CONFIG
             .get_or_init(|| async {
                 Config {
-                    binance_ticker: "bnbusdt".to_string(),
-                    pyth_price_id: "4CkQJBxhU8EZ2UjhigbtdaPbpTe6mqf811fipYBFbSYN".to_string(),
+                    binance_ticker: Some("bnbusdt".to_string()),
+                    pyth_price_id: Some("4CkQJBxhU8EZ2UjhigbtdaPbpTe6mqf811fipYBFbSYN".to_string()),
+                    ..Default::default()
                 }
             })
             .await;
         let state = State::new().await;
-        assert_eq!(state.binance_taker_fee, Decimal::new(75, 5));
+        assert_eq!(state.pairs()[0].binance_taker_fee, Decimal::new(75, 5));
     }
 
     #[tokio::test]
@@ -130,12 +752,115 @@ mod tests {
         CONFIG
             .get_or_init(|| async {
                 Config {
-                    binance_ticker: "solusdt".to_string(),
-                    pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+                    binance_ticker: Some("solusdt".to_string()),
+                    pyth_price_id: Some("H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string()),
+                    ..Default::default()
                 }
             })
             .await;
         let state = State::new().await;
-        assert_eq!(state.binance_taker_fee, Decimal::new(1, 3));
+        assert_eq!(state.pairs()[0].binance_taker_fee, Decimal::new(1, 3));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_pyth_price_recovers() {
+        let pair = PairState::new(&PairConfig {
+            binance_ticker: "solusdt".to_string(),
+            pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+            pyth_quote_price_id: None,
+            enabled: true,
+        });
+        let last_known_price = Price {
+            price: 69852445,
+            conf: 669724,
+            expo: -6,
+            publish_time: 1,
+        };
+        pair.update_latest_pyth_price(Some(last_known_price)).await;
+
+        let source = FlakySource {
+            failures_left: AtomicUsize::new(2),
+            price: Price {
+                price: 70000000,
+                publish_time: 2,
+                ..last_known_price
+            },
+        };
+        for _ in 0..2 {
+            let err = pair.refresh_pyth_price(&source).await.unwrap_err();
+            assert_eq!(err.to_string(), "Could not load Pyth price of solusdt");
+            assert_eq!(
+                *pair.get_latest_pyth_price().read().await,
+                Some(last_known_price)
+            );
+        }
+
+        pair.refresh_pyth_price(&source).await.unwrap();
+        assert_eq!(
+            *pair.get_latest_pyth_price().read().await,
+            Some(source.price)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_latest_cex_ticker_data() {
+        let book = |b: &str, bid_quantity: &str, a: &str, ask_quantity: &str| BookTickerData {
+            s: "SOLUSDT".to_string(),
+            b: b.to_string(),
+            B: bid_quantity.to_string(),
+            a: a.to_string(),
+            A: ask_quantity.to_string(),
+            ..Default::default()
+        };
+        let pair_config = PairConfig {
+            binance_ticker: "solusdt".to_string(),
+            pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+            pyth_quote_price_id: None,
+            enabled: true,
+        };
+
+        // Only Binance, stored as received
+        let pair = PairState::new(&pair_config);
+        pair.update_latest_cex_ticker_data("binance", book("100", "1", "101", "2"))
+            .await;
+        assert_eq!(
+            *pair.get_latest_binance_ticker_data().read().await,
+            Some(book("100", "1", "101", "2"))
+        );
+
+        let weighting = Weighting::Fixed(HashMap::from([("coinbase".to_string(), Decimal::ONE)]));
+        let pair = PairState::new(&pair_config).with_ticker_aggregator(weighting);
+        // Nothing to combine until a weighted source is received, unweighted ones are ignored
+        pair.update_latest_cex_ticker_data("binance", book("100", "1", "101", "2"))
+            .await;
+        assert!(pair.get_latest_binance_ticker_data().read().await.is_none());
+        pair.update_latest_cex_ticker_data("coinbase", book("100.5", "3", "100.8", "2"))
+            .await;
+        assert_eq!(
+            *pair.get_latest_binance_ticker_data().read().await,
+            Some(BookTickerData {
+                u: 2,
+                ..book("100.5", "3", "100.8", "2")
+            })
+        );
+    }
+
+    #[test]
+    fn test_pair_state_new() {
+        let pair = PairState::new(&PairConfig {
+            binance_ticker: "bnbusdt".to_string(),
+            pyth_price_id: "4CkQJBxhU8EZ2UjhigbtdaPbpTe6mqf811fipYBFbSYN".to_string(),
+            pyth_quote_price_id: None,
+            enabled: true,
+        });
+        assert_eq!(pair.binance_taker_fee, Decimal::new(75, 5));
+
+        let pair = PairState::new(&PairConfig {
+            binance_ticker: "solusdt".to_string(),
+            pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+            pyth_quote_price_id: None,
+            enabled: true,
+        });
+        assert_eq!(pair.binance_taker_fee, Decimal::new(1, 3));
     }
 }
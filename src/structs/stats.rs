@@ -0,0 +1,502 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use rust_decimal::Decimal;
+
+use super::arbitrage_finder::{ArbitrageDirection, ArbitrageOpportunity};
+
+/*
+    Struct accumulating statistics of the session
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub sell_binance_buy_dex: u64, // emitted opportunities per direction
+    pub buy_binance_sell_dex: u64,
+    pub total_estimated_profit: Decimal,
+    pub suppressed_by_persistence: u64, // found opportunities suppressed per reason
+    pub suppressed_by_cooldown: u64,
+    pub suppressed_as_duplicate: u64,
+    pub suppressed_as_initial: u64,
+    pub suppressed_by_congestion: u64,
+    pub suppressed_as_not_net_positive: u64,
+    pub suppressed_while_paused: u64,
+    pub suppressed_as_anomalous: u64,
+    pub suppressed_by_skew: u64,
+    pub suppressed_as_zero_profit: u64,
+    pub suppressed_as_not_improved: u64,
+    pub suppressed_after_reconnect: u64,
+    pub suppressed_as_same_direction: u64,
+    pub invalidated_stale: u64, // open opportunities invalidated by the Pyth price going stale
+    pub disabled_pairs: BTreeSet<String>, // Binance tickers of the monitored pairs whose opportunities are not emitted
+    pub profit_histogram: ProfitHistogram,
+    pub lifetime_histogram: LifetimeHistogram,
+    pub window_start: Option<u64>, // milliseconds since Unix epoch at which the counting started, if known
+}
+
+/*
+    Reasons for which a found opportunity is not emitted
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suppression {
+    Persistence,    // not present for the minimum persistence yet, or already reported
+    Cooldown,       // another one in the same direction was reported within the cooldown
+    Duplicate,      // the same as the previous one, or in the same price bucket
+    Initial,        // the first one found after startup
+    Congestion,     // Solana is congested
+    NotNetPositive, // estimated profit is not positive
+    Paused,         // emission is paused
+    Anomalous,      // estimated profit is implausibly high, likely because of bad data
+    Skew,           // Binance and Pyth prices are too far apart in time
+    ZeroProfit,     // estimated profit is positive but rounds to zero
+    NotImproved,    // an open one was already reported with estimated profit not much lower
+    Reconnect,      // the Binance feed reconnected within the post-reconnect grace
+    SameDirection, // in the direction of the last reported one, while only direction changes are reported
+}
+
+/*
+    Struct counting emitted opportunities in buckets of estimated profit
+    n edges define n + 1 buckets, each including its lower edge, the first and the last one being unbounded
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfitHistogram {
+    edges: Vec<Decimal>,
+    counts: Vec<u64>,
+}
+
+/*
+    Struct counting closed opportunities in buckets of the time they stayed open, laid out like ProfitHistogram
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LifetimeHistogram {
+    edges: Vec<Duration>,
+    counts: Vec<u64>,
+}
+
+impl Stats {
+    /*
+        Records an emitted opportunity
+    */
+    pub fn record_opportunity(&mut self, opportunity: &ArbitrageOpportunity) {
+        match opportunity.direction {
+            ArbitrageDirection::SellBinanceBuyDex => self.sell_binance_buy_dex += 1,
+            ArbitrageDirection::BuyBinanceSellDex => self.buy_binance_sell_dex += 1,
+        }
+        self.total_estimated_profit += opportunity.estimated_profit;
+        self.profit_histogram.record(opportunity.estimated_profit);
+    }
+
+    /*
+        Records the time a closed opportunity stayed open
+    */
+    pub fn record_lifetime(&mut self, lifetime: Duration) {
+        self.lifetime_histogram.record(lifetime);
+    }
+
+    /*
+        Records an opportunity suppressed for the reason
+    */
+    pub fn record_suppression(&mut self, suppression: Suppression) {
+        match suppression {
+            Suppression::Persistence => self.suppressed_by_persistence += 1,
+            Suppression::Cooldown => self.suppressed_by_cooldown += 1,
+            Suppression::Duplicate => self.suppressed_as_duplicate += 1,
+            Suppression::Initial => self.suppressed_as_initial += 1,
+            Suppression::Congestion => self.suppressed_by_congestion += 1,
+            Suppression::NotNetPositive => self.suppressed_as_not_net_positive += 1,
+            Suppression::Paused => self.suppressed_while_paused += 1,
+            Suppression::Anomalous => self.suppressed_as_anomalous += 1,
+            Suppression::Skew => self.suppressed_by_skew += 1,
+            Suppression::ZeroProfit => self.suppressed_as_zero_profit += 1,
+            Suppression::NotImproved => self.suppressed_as_not_improved += 1,
+            Suppression::Reconnect => self.suppressed_after_reconnect += 1,
+            Suppression::SameDirection => self.suppressed_as_same_direction += 1,
+        }
+    }
+
+    /*
+        Records whether the pair is enabled
+    */
+    pub fn record_pair_enabled(&mut self, binance_ticker: &str, enabled: bool) {
+        if enabled {
+            self.disabled_pairs.remove(binance_ticker);
+        } else {
+            self.disabled_pairs.insert(binance_ticker.to_string());
+        }
+    }
+
+    /*
+        Zeroes every counter, keeping the histogram buckets and disabled pairs, and starts counting anew at the timestamp
+    */
+    pub fn reset(&mut self, timestamp: u64) {
+        *self = Self {
+            disabled_pairs: std::mem::take(&mut self.disabled_pairs),
+            profit_histogram: ProfitHistogram::new(self.profit_histogram.edges.clone()),
+            lifetime_histogram: LifetimeHistogram::new(self.lifetime_histogram.edges.clone()),
+            window_start: Some(timestamp),
+            ..Default::default()
+        };
+    }
+
+    /*
+        Returns number of emitted opportunities in both directions
+    */
+    pub fn opportunities(&self) -> u64 {
+        self.sell_binance_buy_dex + self.buy_binance_sell_dex
+    }
+
+    /*
+        Formats the statistics as a human-readable summary
+    */
+    pub fn summary(&self) -> String {
+        let summary = format!(
+            "Opportunities: {} (SellBinanceBuyDex: {}, BuyBinanceSellDex: {})\nTotal estimated profit: {}\nInvalidated by stale Pyth price: {}\nDisabled pairs: {}\nSuppressed opportunities:\n  not persisted: {}\n  cooldown: {}\n  duplicate: {}\n  initial: {}\n  Solana congestion: {}\n  not net positive: {}\n  paused: {}\n  anomalous: {}\n  feed skew: {}\n  zero profit: {}\n  not improved: {}\n  after reconnect: {}\n  same direction: {}",
+            self.opportunities(),
+            self.sell_binance_buy_dex,
+            self.buy_binance_sell_dex,
+            self.total_estimated_profit.normalize(),
+            self.invalidated_stale,
+            if self.disabled_pairs.is_empty() {
+                "none".to_string()
+            } else {
+                Vec::from_iter(self.disabled_pairs.iter().cloned()).join(", ")
+            },
+            self.suppressed_by_persistence,
+            self.suppressed_by_cooldown,
+            self.suppressed_as_duplicate,
+            self.suppressed_as_initial,
+            self.suppressed_by_congestion,
+            self.suppressed_as_not_net_positive,
+            self.suppressed_while_paused,
+            self.suppressed_as_anomalous,
+            self.suppressed_by_skew,
+            self.suppressed_as_zero_profit,
+            self.suppressed_as_not_improved,
+            self.suppressed_after_reconnect,
+            self.suppressed_as_same_direction
+        );
+        let summary = match self.window_start {
+            Some(window_start) => format!(
+                "Stats window start: {} ms since Unix epoch\n{}",
+                window_start, summary
+            ),
+            None => summary,
+        };
+        let summary = if self.profit_histogram.edges.is_empty() {
+            summary
+        } else {
+            format!(
+                "{}\nEstimated profit histogram:\n{}",
+                summary,
+                self.profit_histogram.format()
+            )
+        };
+        if self.lifetime_histogram.edges.is_empty() {
+            return summary;
+        }
+
+        format!(
+            "{}\nOpportunity lifetime histogram:\n{}",
+            summary,
+            self.lifetime_histogram.format()
+        )
+    }
+}
+
+impl ProfitHistogram {
+    /*
+        Creates empty histogram with the provided bucket edges, which get sorted
+    */
+    pub fn new(mut edges: Vec<Decimal>) -> Self {
+        edges.sort();
+        edges.dedup();
+        let counts = if edges.is_empty() {
+            Vec::new()
+        } else {
+            vec![0; edges.len() + 1]
+        };
+
+        Self { edges, counts }
+    }
+
+    /*
+        Counts the profit in its bucket, does nothing if the histogram has no edges
+    */
+    pub fn record(&mut self, estimated_profit: Decimal) {
+        if self.counts.is_empty() {
+            return;
+        }
+
+        let bucket = self.edges.partition_point(|edge| *edge <= estimated_profit);
+        self.counts[bucket] += 1;
+    }
+
+    /*
+        Returns lower edge, upper edge and count of every bucket, None meaning an unbounded side
+    */
+    pub fn buckets(&self) -> Vec<(Option<Decimal>, Option<Decimal>, u64)> {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                (
+                    i.checked_sub(1).map(|lower| self.edges[lower]),
+                    self.edges.get(i).copied(),
+                    *count,
+                )
+            })
+            .collect()
+    }
+
+    fn format(&self) -> String {
+        self.buckets()
+            .into_iter()
+            .map(|(lower, upper, count)| {
+                let range = match (lower, upper) {
+                    (None, Some(upper)) => format!("< {}", upper.normalize()),
+                    (Some(lower), Some(upper)) => {
+                        format!("{} - {}", lower.normalize(), upper.normalize())
+                    }
+                    (Some(lower), None) => format!(">= {}", lower.normalize()),
+                    (None, None) => "all".to_string(),
+                };
+                format!("  {}: {}", range, count)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl LifetimeHistogram {
+    /*
+        Creates empty histogram with the provided bucket edges, which get sorted
+    */
+    pub fn new(mut edges: Vec<Duration>) -> Self {
+        edges.sort();
+        edges.dedup();
+        let counts = if edges.is_empty() {
+            Vec::new()
+        } else {
+            vec![0; edges.len() + 1]
+        };
+
+        Self { edges, counts }
+    }
+
+    /*
+        Counts the lifetime in its bucket, does nothing if the histogram has no edges
+    */
+    pub fn record(&mut self, lifetime: Duration) {
+        if self.counts.is_empty() {
+            return;
+        }
+
+        let bucket = self.edges.partition_point(|edge| *edge <= lifetime);
+        self.counts[bucket] += 1;
+    }
+
+    /*
+        Returns lower edge, upper edge and count of every bucket, None meaning an unbounded side
+    */
+    pub fn buckets(&self) -> Vec<(Option<Duration>, Option<Duration>, u64)> {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                (
+                    i.checked_sub(1).map(|lower| self.edges[lower]),
+                    self.edges.get(i).copied(),
+                    *count,
+                )
+            })
+            .collect()
+    }
+
+    fn format(&self) -> String {
+        self.buckets()
+            .into_iter()
+            .map(|(lower, upper, count)| {
+                let range = match (lower, upper) {
+                    (None, Some(upper)) => format!("< {:?}", upper),
+                    (Some(lower), Some(upper)) => format!("{:?} - {:?}", lower, upper),
+                    (Some(lower), None) => format!(">= {:?}", lower),
+                    (None, None) => "all".to_string(),
+                };
+                format!("  {}: {}", range, count)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    use crate::structs::arbitrage_finder::{
+        ArbitrageDirection, ArbitrageOpportunity, ProfitBreakdown, RequiredCapital,
+    };
+
+    use super::{ProfitHistogram, Stats, Suppression};
+
+    #[test]
+    fn test_record_opportunity() {
+        let mut stats = Stats::default();
+        for (direction, estimated_profit) in [
+            (ArbitrageDirection::SellBinanceBuyDex, "0.5"),
+            (ArbitrageDirection::BuyBinanceSellDex, "1.25"),
+            (ArbitrageDirection::SellBinanceBuyDex, "0.25"),
+        ] {
+            stats.record_opportunity(&ArbitrageOpportunity {
+                id: Uuid::nil(),
+                direction,
+                quantity: Decimal::ONE,
+                estimated_profit: Decimal::from_str(estimated_profit).unwrap(),
+                profit_low: Decimal::from_str(estimated_profit).unwrap(),
+                profit_high: Decimal::from_str(estimated_profit).unwrap(),
+                binance_price: Decimal::ONE,
+                pyth_price: Decimal::ONE,
+                profit_breakdown: ProfitBreakdown {
+                    gross_edge: Decimal::ZERO,
+                    binance_fee: Decimal::ZERO,
+                    dex_fee: Decimal::ZERO,
+                },
+                required_capital: RequiredCapital::default(),
+            });
+        }
+
+        assert_eq!(stats.sell_binance_buy_dex, 2);
+        assert_eq!(stats.buy_binance_sell_dex, 1);
+        assert_eq!(stats.opportunities(), 3);
+        assert_eq!(
+            stats.total_estimated_profit,
+            Decimal::from_str("2").unwrap()
+        );
+        assert!(stats.summary().starts_with("Opportunities: 3 "));
+    }
+
+    #[test]
+    fn test_record_suppression() {
+        let mut stats = Stats::default();
+        for suppression in [
+            Suppression::Persistence,
+            Suppression::Cooldown,
+            Suppression::Duplicate,
+            Suppression::Duplicate,
+            Suppression::Initial,
+            Suppression::Congestion,
+            Suppression::NotNetPositive,
+            Suppression::Paused,
+            Suppression::Anomalous,
+            Suppression::Skew,
+            Suppression::ZeroProfit,
+            Suppression::NotImproved,
+            Suppression::Reconnect,
+            Suppression::SameDirection,
+        ] {
+            stats.record_suppression(suppression);
+        }
+
+        assert_eq!(stats.opportunities(), 0);
+        assert!(stats.summary().ends_with(
+            "Suppressed opportunities:\n  not persisted: 1\n  cooldown: 1\n  duplicate: 2\n  initial: 1\n  Solana congestion: 1\n  not net positive: 1\n  paused: 1\n  anomalous: 1\n  feed skew: 1\n  zero profit: 1\n  not improved: 1\n  after reconnect: 1\n  same direction: 1"
+        ));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut stats = Stats {
+            profit_histogram: ProfitHistogram::new(vec![Decimal::ONE]),
+            window_start: Some(1_000_000),
+            ..Default::default()
+        };
+        stats.record_opportunity(&ArbitrageOpportunity {
+            id: Uuid::nil(),
+            direction: ArbitrageDirection::BuyBinanceSellDex,
+            quantity: Decimal::ONE,
+            estimated_profit: Decimal::new(5, 1),
+            profit_low: Decimal::new(5, 1),
+            profit_high: Decimal::new(5, 1),
+            binance_price: Decimal::ONE,
+            pyth_price: Decimal::ONE,
+            profit_breakdown: ProfitBreakdown {
+                gross_edge: Decimal::ZERO,
+                binance_fee: Decimal::ZERO,
+                dex_fee: Decimal::ZERO,
+            },
+            required_capital: RequiredCapital::default(),
+        });
+        stats.record_suppression(Suppression::Cooldown);
+        stats.record_lifetime(std::time::Duration::from_secs(1));
+        stats.record_pair_enabled("bnbusdt", false);
+        assert!(stats
+            .summary()
+            .starts_with("Stats window start: 1000000 ms since Unix epoch\nOpportunities: 1 "));
+
+        stats.reset(2_000_000);
+        assert_eq!(
+            stats,
+            Stats {
+                profit_histogram: ProfitHistogram::new(vec![Decimal::ONE]),
+                window_start: Some(2_000_000),
+                disabled_pairs: ["bnbusdt".to_string()].into(),
+                ..Default::default()
+            }
+        );
+        assert!(stats.summary().contains("\nDisabled pairs: bnbusdt\n"));
+        assert!(stats
+            .summary()
+            .starts_with("Stats window start: 2000000 ms since Unix epoch\nOpportunities: 0 "));
+        assert!(stats
+            .summary()
+            .ends_with("Estimated profit histogram:\n  < 1: 0\n  >= 1: 0"));
+    }
+
+    #[test]
+    fn test_profit_histogram() {
+        let mut stats = Stats {
+            profit_histogram: ProfitHistogram::new(vec![
+                Decimal::ONE,
+                Decimal::new(1, 2),
+                Decimal::new(1, 1),
+            ]),
+            ..Default::default()
+        };
+        for estimated_profit in ["0.001", "0.005", "0.01", "0.05", "0.5", "0.99", "1", "25"] {
+            stats.record_opportunity(&ArbitrageOpportunity {
+                id: Uuid::nil(),
+                direction: ArbitrageDirection::SellBinanceBuyDex,
+                quantity: Decimal::ONE,
+                estimated_profit: Decimal::from_str(estimated_profit).unwrap(),
+                profit_low: Decimal::from_str(estimated_profit).unwrap(),
+                profit_high: Decimal::from_str(estimated_profit).unwrap(),
+                binance_price: Decimal::ONE,
+                pyth_price: Decimal::ONE,
+                profit_breakdown: ProfitBreakdown {
+                    gross_edge: Decimal::ZERO,
+                    binance_fee: Decimal::ZERO,
+                    dex_fee: Decimal::ZERO,
+                },
+                required_capital: RequiredCapital::default(),
+            });
+        }
+
+        assert_eq!(
+            stats
+                .profit_histogram
+                .buckets()
+                .iter()
+                .map(|(_, _, count)| *count)
+                .collect::<Vec<_>>(),
+            vec![2, 2, 2, 2]
+        );
+        assert_eq!(
+            stats.profit_histogram.buckets()[1],
+            (Some(Decimal::new(1, 2)), Some(Decimal::new(1, 1)), 2)
+        );
+        assert!(stats.summary().ends_with(
+            "Estimated profit histogram:\n  < 0.01: 2\n  0.01 - 0.1: 2\n  0.1 - 1: 2\n  >= 1: 2"
+        ));
+    }
+}
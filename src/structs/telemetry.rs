@@ -0,0 +1,104 @@
+/*
+    Names of the metrics recorded through the metrics crate
+    They are discarded until the application installs a recorder, e.g. the one of metrics-exporter-prometheus
+*/
+pub const OPPORTUNITIES: &str = "opportunities_total"; // counter of emitted opportunities per pair and direction
+pub const ESTIMATED_PROFIT: &str = "opportunity_estimated_profit"; // gauge of the last emitted opportunity per pair
+pub const PYTH_PRICE_AGE: &str = "pyth_price_age_seconds"; // histogram of age of stored Pyth prices per pair
+pub const BINANCE_RECONNECTS: &str = "binance_reconnects_total"; // counter of replaced Binance connections
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use metrics::{Key, Label};
+    use metrics_util::{
+        debugging::{DebugValue, DebuggingRecorder},
+        CompositeKey, MetricKind,
+    };
+    use pyth_sdk_solana::Price;
+    use tokio::sync::broadcast;
+
+    use crate::{
+        config::PairConfig,
+        structs::{
+            arbitrage_finder::ArbitrageOpportunity, emitter::OpportunityEmitter, state::PairState,
+            stats::Stats,
+        },
+    };
+
+    use super::{ESTIMATED_PROFIT, OPPORTUNITIES, PYTH_PRICE_AGE};
+
+    #[test]
+    fn test_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        // The recorder is local to the thread, so metrics of tests running in parallel are not captured
+        metrics::with_local_recorder(&recorder, || {
+            let (sender, _receiver) = broadcast::channel(16);
+            let emitter = OpportunityEmitter::new(sender, Arc::new(Mutex::new(Stats::default())));
+            assert!(emitter.emit("solusdt", ArbitrageOpportunity::sample()));
+
+            let pair = PairState::new(&PairConfig {
+                binance_ticker: "solusdt".to_string(),
+                pyth_price_id: "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG".to_string(),
+                pyth_quote_price_id: None,
+                enabled: true,
+            });
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(pair.update_latest_pyth_price(Some(Price {
+                    price: 69852445,
+                    conf: 669724,
+                    expo: -6,
+                    publish_time: 1,
+                })));
+        });
+
+        let key = |kind, name, labels: &[(&'static str, &'static str)]| {
+            let labels = labels
+                .iter()
+                .map(|(key, value)| Label::new(*key, *value))
+                .collect::<Vec<_>>();
+            CompositeKey::new(kind, Key::from_parts(name, labels))
+        };
+        let mut recorded = snapshotter
+            .snapshot()
+            .into_hashmap()
+            .into_iter()
+            .map(|(key, (_, _, value))| (key, value))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(recorded.len(), 3);
+        assert_eq!(
+            recorded.remove(&key(
+                MetricKind::Counter,
+                OPPORTUNITIES,
+                &[("pair", "solusdt"), ("direction", "SellBinanceBuyDex")]
+            )),
+            Some(DebugValue::Counter(1))
+        );
+        assert_eq!(
+            recorded.remove(&key(
+                MetricKind::Gauge,
+                ESTIMATED_PROFIT,
+                &[("pair", "solusdt")]
+            )),
+            Some(DebugValue::Gauge(0.03400176.into()))
+        );
+        let Some(DebugValue::Histogram(ages)) = recorded.remove(&key(
+            MetricKind::Histogram,
+            PYTH_PRICE_AGE,
+            &[("pair", "solusdt")],
+        )) else {
+            panic!("Pyth price age is not recorded");
+        };
+        // Published a second after Unix epoch
+        assert_eq!(ages.len(), 1);
+        assert!(ages[0].into_inner() > 1_700_000_000.0);
+    }
+}
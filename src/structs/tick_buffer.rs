@@ -0,0 +1,75 @@
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/*
+    Struct keeping the most recent updates of a feed along with the times they were received, for debugging
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickBuffer<T> {
+    capacity: usize,
+    ticks: VecDeque<(u64, T)>, // milliseconds since UNIX epoch and the update, from the oldest
+}
+
+impl<T> TickBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ticks: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /*
+        Stores the update received now, dropping the oldest one if the buffer is full
+    */
+    pub fn push(&mut self, tick: T) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64; // doesn't overflow
+        self.push_at(timestamp, tick);
+    }
+
+    /*
+        Stores the update received at the timestamp, dropping the oldest one if the buffer is full
+    */
+    pub fn push_at(&mut self, timestamp: u64, tick: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.ticks.len() == self.capacity {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back((timestamp, tick));
+    }
+
+    /*
+        Returns the stored updates from the oldest
+    */
+    pub fn ticks(&self) -> impl Iterator<Item = &(u64, T)> {
+        self.ticks.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TickBuffer;
+
+    #[test]
+    fn test_push_beyond_capacity() {
+        let mut tick_buffer = TickBuffer::new(3);
+        for i in 0..5 {
+            tick_buffer.push_at(i * 1000, i);
+        }
+
+        assert_eq!(
+            tick_buffer.ticks().copied().collect::<Vec<_>>(),
+            vec![(2000, 2), (3000, 3), (4000, 4)]
+        );
+
+        let mut tick_buffer = TickBuffer::new(0);
+        tick_buffer.push(1);
+        assert_eq!(tick_buffer.ticks().count(), 0);
+    }
+}
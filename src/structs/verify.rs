@@ -0,0 +1,240 @@
+use std::{str::FromStr, sync::Arc};
+
+use pyth_sdk_solana::Price;
+use rust_decimal::Decimal;
+use tokio::sync::RwLock;
+
+use super::{
+    arbitrage_finder::{calculate_pyth_confidence_bounds, ArbitrageDirection, ArbitrageFinder},
+    cex::binance::BookTickerData,
+};
+
+const SOL_USD: Price = Price {
+    price: 69852445,
+    conf: 669724,
+    expo: -6,
+    publish_time: 0,
+};
+const BTC_USD: Price = Price {
+    price: 4856126854,
+    conf: 612455,
+    expo: -5,
+    publish_time: 0,
+};
+
+/*
+    Struct describing documented bounds of the probable price range of a Pyth price
+*/
+#[derive(Debug, Clone)]
+pub struct BoundsVector {
+    pub name: &'static str,
+    pub pyth_price: Price,
+    pub confidence_factor: Decimal,
+    pub lower: &'static str,
+    pub higher: &'static str,
+}
+
+/*
+    Struct describing the opportunity documented for the Binance top of the book and Pyth price, if any
+*/
+#[derive(Debug, Clone)]
+pub struct OpportunityVector {
+    pub name: &'static str,
+    pub pyth_price: Price,
+    pub binance_ticker_data: BookTickerData,
+    pub binance_fee: Decimal,
+    pub expected: Option<(ArbitrageDirection, &'static str, &'static str)>, // direction, quantity and estimated profit
+}
+
+/*
+    Struct representing the outcome of checking a single vector
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyResult {
+    pub name: &'static str,
+    pub failure: Option<String>, // expected and computed values, None if they are equal
+}
+
+pub fn bounds_vectors() -> Vec<BoundsVector> {
+    vec![
+        BoundsVector {
+            name: "SOL/USD bounds at 95%",
+            pyth_price: SOL_USD,
+            confidence_factor: Decimal::new(212, 2),
+            lower: "68.43263012",
+            higher: "71.27225988",
+        },
+        BoundsVector {
+            name: "BTC/USD bounds at 95%",
+            pyth_price: BTC_USD,
+            confidence_factor: Decimal::new(212, 2),
+            lower: "48548.284494",
+            higher: "48574.252586",
+        },
+    ]
+}
+
+pub fn opportunity_vectors() -> Vec<OpportunityVector> {
+    let book = |b: &str, bid_quantity: &str, a: &str, ask_quantity: &str| BookTickerData {
+        b: b.to_string(),
+        B: bid_quantity.to_string(),
+        a: a.to_string(),
+        A: ask_quantity.to_string(),
+        ..Default::default()
+    };
+
+    vec![
+        OpportunityVector {
+            name: "SOL/USD bid above the range",
+            pyth_price: SOL_USD,
+            binance_ticker_data: book("71.3833", "0.8574", "72.0012", "0.9245"),
+            binance_fee: Decimal::new(1, 3),
+            expected: Some((
+                ArbitrageDirection::SellBinanceBuyDex,
+                "0.8574",
+                "0.03400176",
+            )),
+        },
+        OpportunityVector {
+            name: "SOL/USD bid above the range, fee exceeding the edge",
+            pyth_price: SOL_USD,
+            binance_ticker_data: book("71.3833", "0.8574", "72.0012", "0.9245"),
+            binance_fee: Decimal::new(5, 3),
+            expected: None,
+        },
+        OpportunityVector {
+            name: "SOL/USD ask below the range",
+            pyth_price: SOL_USD,
+            binance_ticker_data: book("67.5421", "1.1258", "67.8423", "2.5569"),
+            binance_fee: Decimal::new(1, 3),
+            expected: Some((
+                ArbitrageDirection::BuyBinanceSellDex,
+                "2.5569",
+                "1.33594911",
+            )),
+        },
+        OpportunityVector {
+            name: "SOL/USD book within the range",
+            pyth_price: SOL_USD,
+            binance_ticker_data: book("69.8", "0.8574", "69.9", "0.9245"),
+            binance_fee: Decimal::new(1, 3),
+            expected: None,
+        },
+        OpportunityVector {
+            name: "BTC/USD bid above the range",
+            pyth_price: BTC_USD,
+            binance_ticker_data: book("48620.5", "0.25", "48621", "0.4"),
+            binance_fee: Decimal::new(1, 4),
+            expected: Some((ArbitrageDirection::SellBinanceBuyDex, "0.25", "10.346341")),
+        },
+        OpportunityVector {
+            name: "BTC/USD ask below the range",
+            pyth_price: BTC_USD,
+            binance_ticker_data: book("48501", "1.5", "48502.3", "0.75"),
+            binance_fee: Decimal::new(1, 4),
+            expected: Some((ArbitrageDirection::BuyBinanceSellDex, "0.75", "30.850698")),
+        },
+    ]
+}
+
+/*
+    Checks every vector against the code of this binary with default settings
+*/
+pub async fn verify() -> Vec<VerifyResult> {
+    let mut results = bounds_vectors()
+        .into_iter()
+        .map(|vector| {
+            let computed =
+                calculate_pyth_confidence_bounds(vector.pyth_price, vector.confidence_factor)
+                    .map(|(lower, higher)| (lower.normalize(), higher.normalize()));
+            let expected = Some((parse(vector.lower), parse(vector.higher)));
+            VerifyResult {
+                name: vector.name,
+                failure: describe_failure(expected, computed),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for vector in opportunity_vectors() {
+        let computed = ArbitrageFinder::new()
+            .find_opportunity(
+                Arc::new(RwLock::new(Some(vector.pyth_price))),
+                Arc::new(RwLock::new(Some(vector.binance_ticker_data))),
+                vector.binance_fee,
+            )
+            .await
+            .map(|opportunity| {
+                (
+                    opportunity.direction,
+                    opportunity.quantity,
+                    opportunity.estimated_profit.normalize(),
+                )
+            });
+        let expected = vector
+            .expected
+            .map(|(direction, quantity, estimated_profit)| {
+                (direction, parse(quantity), parse(estimated_profit))
+            });
+        results.push(VerifyResult {
+            name: vector.name,
+            failure: describe_failure(expected, computed),
+        });
+    }
+
+    results
+}
+
+/*
+    Formats the results as a line per vector followed by the number of passed ones
+*/
+pub fn format_results(results: &[VerifyResult]) -> String {
+    let mut formatted = String::new();
+    for result in results {
+        match &result.failure {
+            None => formatted.push_str(&format!("PASS {}\n", result.name)),
+            Some(failure) => formatted.push_str(&format!("FAIL {}: {}\n", result.name, failure)),
+        }
+    }
+    let passed = results
+        .iter()
+        .filter(|result| result.failure.is_none())
+        .count();
+    formatted.push_str(&format!("{}/{} passed\n", passed, results.len()));
+
+    formatted
+}
+
+// Vectors hold valid decimals
+fn parse(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap().normalize()
+}
+
+fn describe_failure<T: PartialEq + std::fmt::Debug>(expected: T, computed: T) -> Option<String> {
+    (expected != computed).then(|| format!("expected {:?}, computed {:?}", expected, computed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_results, verify, VerifyResult};
+
+    #[tokio::test]
+    async fn test_verify() {
+        let results = verify().await;
+        assert_eq!(results.len(), 8);
+        for result in &results {
+            assert_eq!(result.failure, None, "{}", result.name);
+        }
+        assert!(
+            format_results(&results).ends_with("PASS BTC/USD ask below the range\n8/8 passed\n")
+        );
+
+        let failed = VerifyResult {
+            name: "broken",
+            failure: Some("expected 1, computed 2".to_string()),
+        };
+        assert_eq!(
+            format_results(&[results[0].clone(), failed]),
+            "PASS SOL/USD bounds at 95%\nFAIL broken: expected 1, computed 2\n1/2 passed\n"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use pyth_sdk_solana::Price;
+use rust_decimal::Decimal;
+use tokio::{
+    sync::{mpsc, Mutex, RwLock},
+    task::JoinHandle,
+};
+
+use super::{
+    arbitrage_finder::{ArbitrageFinder, ArbitrageOpportunity, RawInputs},
+    cex::binance::BookTickerData,
+};
+
+const QUEUE_CAPACITY_PER_WORKER: usize = 4;
+
+/*
+    Struct describing a single evaluation of a pair, carrying everything a worker needs to process it
+*/
+pub struct PairEvaluation {
+    pub binance_ticker: String,
+    pub arbitrage_finder: Arc<Mutex<ArbitrageFinder>>,
+    pub latest_pyth_price: Arc<RwLock<Option<Price>>>,
+    pub latest_binance_ticker_data: Arc<RwLock<Option<BookTickerData>>>,
+    pub binance_fee: Decimal,
+}
+
+/*
+    Struct managing a fixed number of worker tasks processing pair evaluations from a shared, bounded queue
+*/
+pub struct WorkerPool {
+    sender: mpsc::Sender<PairEvaluation>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    /*
+        Spawns the workers, each of them calling on_opportunity for every opportunity it finds, along with its inputs
+    */
+    pub fn new<F>(workers: usize, on_opportunity: F) -> Self
+    where
+        F: Fn(&str, ArbitrageOpportunity, Option<RawInputs>) + Send + Sync + 'static,
+    {
+        let (sender, receiver) =
+            mpsc::channel::<PairEvaluation>(workers * QUEUE_CAPACITY_PER_WORKER);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let on_opportunity = Arc::new(on_opportunity);
+
+        let workers = (0..workers)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let on_opportunity = on_opportunity.clone();
+
+                tokio::spawn(async move {
+                    loop {
+                        let maybe_evaluation = receiver.lock().await.recv().await;
+                        let Some(evaluation) = maybe_evaluation else {
+                            return; // queue closed and drained
+                        };
+
+                        let mut arbitrage_finder = evaluation.arbitrage_finder.lock().await;
+                        let maybe_opportunity = arbitrage_finder
+                            .find_opportunity(
+                                evaluation.latest_pyth_price,
+                                evaluation.latest_binance_ticker_data,
+                                evaluation.binance_fee,
+                            )
+                            .await;
+                        if let Some(opportunity) = maybe_opportunity {
+                            on_opportunity(
+                                &evaluation.binance_ticker,
+                                opportunity,
+                                arbitrage_finder.last_inputs().cloned(),
+                            );
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Self { sender, workers }
+    }
+
+    /*
+        Enqueues the evaluation, waiting for free space if all workers are busy
+    */
+    pub async fn submit(&self, evaluation: PairEvaluation) {
+        // The receiver lives as long as the workers, which stop only after the sender is dropped
+        let _ = self.sender.send(evaluation).await;
+    }
+
+    /*
+        Closes the queue and waits until the workers process all enqueued evaluations
+    */
+    pub async fn shutdown(self) {
+        drop(self.sender);
+        for worker in self.workers {
+            worker.await.unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex as StdMutex},
+    };
+
+    use pyth_sdk_solana::Price;
+    use rust_decimal::Decimal;
+    use tokio::sync::{Mutex, RwLock};
+
+    use crate::structs::{arbitrage_finder::ArbitrageFinder, cex::binance::BookTickerData};
+
+    use super::{PairEvaluation, WorkerPool};
+
+    #[tokio::test]
+    async fn test_many_pairs_few_workers() {
+        let found = Arc::new(StdMutex::new(Vec::new()));
+        let worker_pool = WorkerPool::new(2, {
+            let found = found.clone();
+            move |binance_ticker: &str, _, _| found.lock().unwrap().push(binance_ticker.to_string())
+        });
+
+        for i in 0..50 {
+            worker_pool
+                .submit(PairEvaluation {
+                    binance_ticker: format!("pair{}", i),
+                    arbitrage_finder: Arc::new(Mutex::new(ArbitrageFinder::new())),
+                    // l: 68.43263012 h: 71.27225988
+                    latest_pyth_price: Arc::new(RwLock::new(Some(Price {
+                        price: 69852445,
+                        conf: 669724,
+                        expo: -6,
+                        ..Default::default()
+                    }))),
+                    latest_binance_ticker_data: Arc::new(RwLock::new(Some(BookTickerData {
+                        b: "71.3833".to_string(),
+                        B: "0.8574".to_string(),
+                        a: "72.0012".to_string(),
+                        A: "0.9245".to_string(),
+                        ..Default::default()
+                    }))),
+                    binance_fee: Decimal::new(1, 3),
+                })
+                .await;
+        }
+        worker_pool.shutdown().await;
+
+        let found = found.lock().unwrap();
+        assert_eq!(found.len(), 50);
+        assert_eq!(found.iter().collect::<HashSet<_>>().len(), 50);
+    }
+}